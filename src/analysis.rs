@@ -0,0 +1,468 @@
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use color_eyre::eyre::{OptionExt, WrapErr};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+use crate::engine::{self, SearchLimits, SearchResult};
+use crate::event::{AppEvent, Event};
+use crate::game::{Game, Move};
+use crate::tablebase::Tablebase;
+use crate::uci;
+use crate::variant::VariantKind;
+
+/// Most engine lines the analysis board shows.
+pub const MAX_LINES: usize = 5;
+
+/// Numbers searches across analysers, so an update from one that was dropped is never taken
+/// for one of its replacement's.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// A move in the analysis tree. The first child continues the line and the others are
+/// variations.
+#[derive(Debug, Clone)]
+struct Node {
+    mv: Option<Move>,
+    parent: usize,
+    children: Vec<usize>,
+}
+
+/// A position explored freely, with every line tried kept as a tree of variations, and what
+/// the engine says about the shown position.
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    start: Game,
+    /// The starting position first, with no move.
+    nodes: Vec<Node>,
+    current: usize,
+    /// Position after the moves leading to `current`.
+    game: Game,
+    /// The engine's best lines for the shown position, best first.
+    pub lines: Vec<SearchResult>,
+    /// The search whose lines are wanted; updates from older ones are dropped.
+    generation: u64,
+    pub engine_on: bool,
+    /// Lines the engine is asked for, from 1 to [`MAX_LINES`].
+    pub multipv: usize,
+    /// Why the engine could not start or stopped.
+    pub message: Option<String>,
+}
+
+impl Analysis {
+    pub fn new(start: Game) -> Self {
+        Self {
+            game: start.clone(),
+            start,
+            nodes: vec![Node {
+                mv: None,
+                parent: 0,
+                children: Vec::new(),
+            }],
+            current: 0,
+            lines: Vec::new(),
+            generation: 0,
+            engine_on: true,
+            multipv: 1,
+            message: None,
+        }
+    }
+
+    /// The position being shown.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// The position the tree starts from.
+    pub fn start(&self) -> &Game {
+        &self.start
+    }
+
+    /// Plays `mv` from the shown position, following it if it was tried before and adding a
+    /// variation otherwise.
+    pub fn play(&mut self, mv: Move) {
+        let existing = self.nodes[self.current]
+            .children
+            .iter()
+            .copied()
+            .find(|&child| self.nodes[child].mv == Some(mv));
+        self.current = existing.unwrap_or_else(|| {
+            self.nodes.push(Node {
+                mv: Some(mv),
+                parent: self.current,
+                children: Vec::new(),
+            });
+            let child = self.nodes.len() - 1;
+            self.nodes[self.current].children.push(child);
+            child
+        });
+        self.game.make_move(mv);
+    }
+
+    /// Takes back the last move. Returns `false` at the start.
+    pub fn back(&mut self) -> bool {
+        if self.current == 0 {
+            return false;
+        }
+        self.current = self.nodes[self.current].parent;
+        self.rebuild();
+        true
+    }
+
+    /// Plays the move the current line continues with. Returns `false` at its end.
+    pub fn forward(&mut self) -> bool {
+        let Some(&child) = self.nodes[self.current].children.first() else {
+            return false;
+        };
+        self.current = child;
+        self.rebuild();
+        true
+    }
+
+    pub fn to_start(&mut self) {
+        self.current = 0;
+        self.rebuild();
+    }
+
+    /// Plays the current line to its end.
+    pub fn to_end(&mut self) {
+        while self.forward() {}
+    }
+
+    /// Replaces the last move with the next move tried in its place, going round to the first.
+    /// Returns `false` when there is no other.
+    pub fn next_variation(&mut self) -> bool {
+        if self.current == 0 {
+            return false;
+        }
+        let siblings = &self.nodes[self.nodes[self.current].parent].children;
+        if siblings.len() < 2 {
+            return false;
+        }
+        let index = siblings
+            .iter()
+            .position(|&sibling| sibling == self.current)
+            .expect("a node is among its parent's children");
+        self.current = siblings[(index + 1) % siblings.len()];
+        self.rebuild();
+        true
+    }
+
+    /// The moves from the start to the shown position.
+    pub fn moves_played(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        let mut node = self.current;
+        while let Some(mv) = self.nodes[node].mv {
+            moves.push(mv);
+            node = self.nodes[node].parent;
+        }
+        moves.reverse();
+        moves
+    }
+
+    /// The moves tried from the shown position, the continuation first.
+    pub fn next_moves(&self) -> Vec<Move> {
+        self.nodes[self.current]
+            .children
+            .iter()
+            .filter_map(|&child| self.nodes[child].mv)
+            .collect()
+    }
+
+    /// Waits for the lines of search `generation`, forgetting the ones shown.
+    pub fn expect(&mut self, generation: u64) {
+        self.generation = generation;
+        self.lines.clear();
+    }
+
+    /// Shows the lines of search `generation` if it is the one expected.
+    pub fn update(&mut self, generation: u64, lines: Vec<SearchResult>) {
+        if generation == self.generation {
+            self.lines = lines;
+        }
+    }
+
+    fn rebuild(&mut self) {
+        let mut game = self.start.clone();
+        for mv in self.moves_played() {
+            game.make_move(mv);
+        }
+        self.game = game;
+    }
+}
+
+/// A request to an external engine: analyse a position for a number of lines.
+#[derive(Debug)]
+struct Request {
+    generation: u64,
+    game: Game,
+    lines: usize,
+}
+
+/// Where positions are analysed.
+#[derive(Debug)]
+enum Backend {
+    /// The built-in engine on a blocking thread, stopped through the flag.
+    BuiltIn {
+        tablebase: Option<Arc<Tablebase>>,
+        stop: Option<Arc<AtomicBool>>,
+    },
+    /// A UCI engine process driven by a task, which stops searching on `None` and quits the
+    /// engine when the sender is dropped.
+    External(mpsc::UnboundedSender<Option<Request>>),
+}
+
+/// Analyses positions off the render thread without a depth limit, sending
+/// [`AppEvent::Analysis`] with the lines found so far until the next position is asked for.
+#[derive(Debug)]
+pub struct Analyser {
+    events: mpsc::UnboundedSender<Event>,
+    backend: Backend,
+    name: String,
+}
+
+impl Analyser {
+    pub fn built_in(
+        events: mpsc::UnboundedSender<Event>,
+        tablebase: Option<Arc<Tablebase>>,
+    ) -> Self {
+        Self {
+            events,
+            backend: Backend::BuiltIn {
+                tablebase,
+                stop: None,
+            },
+            name: "Built-in engine".to_string(),
+        }
+    }
+
+    /// Starts the UCI engine at `path`, which must be run inside the tokio runtime.
+    pub fn external(events: mpsc::UnboundedSender<Event>, path: &Path) -> color_eyre::Result<Self> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .wrap_err_with(|| format!("cannot start {}", path.display()))?;
+        let stdin = child.stdin.take().ok_or_eyre("no engine input")?;
+        let stdout = child.stdout.take().ok_or_eyre("no engine output")?;
+        let (requests, receiver) = mpsc::unbounded_channel();
+        let task_events = events.clone();
+        tokio::spawn(async move {
+            let result = drive(stdin, BufReader::new(stdout), receiver, &task_events).await;
+            if let Err(err) = result {
+                let _ = task_events.send(Event::App(AppEvent::AnalysisFailed(err.to_string())));
+            }
+            let _ = child.kill().await;
+        });
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        Ok(Self {
+            events,
+            backend: Backend::External(requests),
+            name,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Stops the current search and starts on `game`, returning the generation its updates
+    /// will carry.
+    pub fn analyse(&mut self, game: &Game, lines: usize) -> u64 {
+        self.stop();
+        let generation = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+        match &mut self.backend {
+            Backend::BuiltIn { tablebase, stop } => {
+                let flag = Arc::new(AtomicBool::new(false));
+                *stop = Some(flag.clone());
+                let limits = SearchLimits {
+                    stop: Some(flag),
+                    tablebase: tablebase.clone(),
+                    ..SearchLimits::default()
+                };
+                let game = game.clone();
+                let events = self.events.clone();
+                tokio::task::spawn_blocking(move || {
+                    engine::search_lines(&game, &limits, lines, |results| {
+                        let update = AppEvent::Analysis {
+                            generation,
+                            lines: results.to_vec(),
+                        };
+                        let _ = events.send(Event::App(update));
+                    });
+                });
+            }
+            Backend::External(requests) => {
+                let _ = requests.send(Some(Request {
+                    generation,
+                    game: game.clone(),
+                    lines,
+                }));
+            }
+        }
+        generation
+    }
+
+    /// Stops searching.
+    pub fn stop(&mut self) {
+        match &mut self.backend {
+            Backend::BuiltIn { stop, .. } => {
+                if let Some(flag) = stop.take() {
+                    flag.store(true, Ordering::Relaxed);
+                }
+            }
+            Backend::External(requests) => {
+                let _ = requests.send(None);
+            }
+        }
+    }
+}
+
+impl Drop for Analyser {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Talks to an external engine: each request stops the running search, and once the engine
+/// is ready the new position is searched without limit. Its `info` lines are sent on as
+/// [`AppEvent::Analysis`] until the next request, which is `None` to only stop.
+async fn drive(
+    mut stdin: tokio::process::ChildStdin,
+    stdout: BufReader<tokio::process::ChildStdout>,
+    mut requests: mpsc::UnboundedReceiver<Option<Request>>,
+    events: &mpsc::UnboundedSender<Event>,
+) -> color_eyre::Result<()> {
+    stdin.write_all(b"uci\n").await?;
+    let mut output = stdout.lines();
+    // Requested but not yet searched, then being searched with the lines found so far
+    let mut pending: Option<Request> = None;
+    let mut searching: Option<(Request, Vec<Option<SearchResult>>)> = None;
+    loop {
+        tokio::select! {
+            request = requests.recv() => {
+                let Some(request) = request else {
+                    stdin.write_all(b"stop\nquit\n").await?;
+                    return Ok(());
+                };
+                searching = None;
+                pending = None;
+                let Some(request) = request else {
+                    stdin.write_all(b"stop\n").await?;
+                    continue;
+                };
+                let variant = request.game.variant();
+                let mut commands = String::from("stop\n");
+                if variant != VariantKind::Standard {
+                    let name = variant.name().to_ascii_lowercase().replace([' ', '-'], "");
+                    commands.push_str(&format!("setoption name UCI_Variant value {name}\n"));
+                }
+                commands.push_str(&format!(
+                    "setoption name MultiPV value {}\nposition fen {}\nisready\n",
+                    request.lines,
+                    request.game.to_fen()
+                ));
+                stdin.write_all(commands.as_bytes()).await?;
+                pending = Some(request);
+            }
+            line = output.next_line() => {
+                let Some(line) = line? else {
+                    return Err(color_eyre::eyre::eyre!("the engine quit"));
+                };
+                let line = line.trim();
+                if line == "readyok" {
+                    if let Some(request) = pending.take() {
+                        stdin.write_all(b"go infinite\n").await?;
+                        let slots = vec![None; request.lines];
+                        searching = Some((request, slots));
+                    }
+                } else if let Some((request, slots)) = &mut searching
+                    && let Some((multipv, result)) = uci::parse_info(&request.game, line)
+                    && let Some(slot) = slots.get_mut(multipv.wrapping_sub(1))
+                {
+                    *slot = Some(result);
+                    let update = AppEvent::Analysis {
+                        generation: request.generation,
+                        lines: slots.iter().flatten().cloned().collect(),
+                    };
+                    let _ = events.send(Event::App(update));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+
+    fn play(analysis: &mut Analysis, san: &str) {
+        let mv = notation::parse_move(analysis.game(), san).unwrap();
+        analysis.play(mv);
+    }
+
+    fn line(analysis: &Analysis) -> String {
+        notation::numbered(analysis.start(), &analysis.moves_played())
+    }
+
+    #[test]
+    fn keeps_every_line_tried() {
+        let mut analysis = Analysis::new(Game::new());
+        for san in ["e4", "e5", "Nf3"] {
+            play(&mut analysis, san);
+        }
+        analysis.back();
+        play(&mut analysis, "Bc4");
+        assert_eq!(line(&analysis), "1. e4 e5 2. Bc4");
+        assert!(analysis.next_variation());
+        assert_eq!(line(&analysis), "1. e4 e5 2. Nf3");
+
+        analysis.to_start();
+        analysis.to_end();
+        assert_eq!(line(&analysis), "1. e4 e5 2. Nf3");
+        analysis.back();
+        let sans: Vec<String> = analysis
+            .next_moves()
+            .iter()
+            .map(|mv| notation::san(analysis.game(), mv))
+            .collect();
+        assert_eq!(sans, ["Nf3", "Bc4"]);
+        // Playing a move again follows it rather than adding another variation
+        play(&mut analysis, "Bc4");
+        analysis.back();
+        assert_eq!(analysis.next_moves().len(), 2);
+        assert!(!analysis.next_variation());
+    }
+
+    #[tokio::test]
+    async fn built_in_engine_reports_lines_until_replaced() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut analyser = Analyser::built_in(sender, None);
+        let game =
+            Game::from_fen("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1", VariantKind::Standard).unwrap();
+        let first = analyser.analyse(&game, 2);
+        let second = analyser.analyse(&game, 2);
+        assert_ne!(first, second);
+        loop {
+            let Some(Event::App(AppEvent::Analysis { generation, lines })) = receiver.recv().await
+            else {
+                continue;
+            };
+            if generation == second {
+                assert_eq!(lines.len(), 2);
+                assert_eq!(notation::san(&game, &lines[0].pv[0]), "Ra8#");
+                assert_eq!(lines[0].score, engine::Score::Mate(1));
+                break;
+            }
+        }
+    }
+}
@@ -1,19 +1,167 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
 use crate::event::{AppEvent, Event, EventHandler};
+use color_eyre::eyre::eyre;
 use ratatui::{
     DefaultTerminal,
-    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    crossterm::event::{KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind},
+    layout::{Position, Rect},
 };
 
-use crate::game::Game;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use crate::analysis::{self, Analyser, Analysis};
+use crate::book::Book;
+use crate::clock::{Clock, TimeControl};
+use crate::config::{Config, EngineConfig};
+use crate::database::{Browser, Database};
+use crate::eco::{self, Opening};
+use crate::editor::Editor;
+use crate::endgame::{Drill, ENDGAMES, Endgame};
+use crate::engine::{self, Score, SearchLimits};
+use crate::game::{Game, Move};
+use crate::keymap::{self, Action, KeyBinding, Keymap, KeymapPreset, Keys};
+use crate::notation::{self, Completions};
+use crate::pgn::PgnGame;
+use crate::pieces::{Color, Piece};
+use crate::profile::{Pairing, Profiles};
+use crate::puzzle::{Attempt, Progress, Status, Trainer};
+use crate::repertoire::{self, Repertoire, Session, Stats, Verdict};
+use crate::replay::Replay;
+use crate::report::Report;
+use crate::search::PositionSearch;
+use crate::tablebase::{Probe, Tablebase};
+use crate::theme::{GlyphSet, PieceStyle, Theme};
+use crate::ui::BoardLayout;
+use crate::variant::VariantKind;
+use crate::widgets::EvalGraph;
+
+/// Depth and time limit of the searches behind the eval bar and graph.
+const EVAL_DEPTH: u32 = 6;
+const EVAL_TIME: Duration = Duration::from_millis(300);
+/// Depth of the searches behind the post-game report.
+const REPORT_DEPTH: u32 = 5;
+
+/// Shortest and longest pause between autoplayed replay moves, and the step between them.
+const AUTOPLAY_DELAY_MS: (u64, u64, u64) = (200, 5000, 200);
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AppState {
     MainMenu,
+    NewGame,
     Game,
+    /// Exploring a position freely with the engine's evaluation.
+    Analysis,
+    /// Setting up a position piece by piece.
+    Editor,
+    /// Solving tactics puzzles.
+    Puzzle,
+    /// Drilling the lines of an opening repertoire.
+    Repertoire,
+    /// Converting or holding an ending against the engine.
+    Endgame,
+    /// Ratings and results of the recorded games.
+    Stats,
+    /// Stepping through a recorded game.
+    Replay,
+    /// Browsing the games in a PGN file.
+    Database,
+    /// Looking for games in the open PGN file that reach a position.
+    Search,
+    Settings,
     Help,
     Credits,
 }
 
+/// What the side panels say about the position on screen, worked out when the position changes
+/// rather than on every frame.
+#[derive(Debug, Clone)]
+pub struct PositionNotes {
+    /// The start position and moves of the game the notes are for.
+    start_fen: String,
+    moves: Vec<Move>,
+    pub opening: Option<Opening>,
+    /// The book moves with their share of the weight, like `Book: e4 60%, d4 40%`.
+    pub book: Option<String>,
+    pub probe: Option<Probe>,
+}
+
+impl PositionNotes {
+    fn new(game: &Game, book: Option<&Book>, tablebase: Option<&Tablebase>) -> Self {
+        Self {
+            start_fen: game.start_fen().to_string(),
+            moves: game.get_move_history().clone(),
+            opening: eco::classify(game),
+            book: book.map(|book| book_line(book, game)),
+            probe: tablebase.and_then(|tablebase| tablebase.probe(game)),
+        }
+    }
+
+    fn is_for(&self, game: &Game) -> bool {
+        self.moves == *game.get_move_history() && self.start_fen == game.start_fen()
+    }
+}
+
+/// The book moves for the position with their share of the weight, like `Book: e4 60%, d4 40%`.
+fn book_line(book: &Book, game: &Game) -> String {
+    let moves = book.moves(game);
+    let total: u32 = moves
+        .iter()
+        .map(|book_move| u32::from(book_move.weight))
+        .sum();
+    if moves.is_empty() {
+        return "Out of book".to_string();
+    }
+    let shares: Vec<String> = moves
+        .iter()
+        .take(5)
+        .map(|book_move| {
+            let san = notation::san(game, &book_move.mv);
+            let share = u32::from(book_move.weight) * 100 / total.max(1);
+            format!("{san} {share}%")
+        })
+        .collect();
+    format!("Book: {}", shares.join(", "))
+}
+
+/// A pawn move waiting for the player to pick the piece it becomes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromotionChoice {
+    /// One legal move per piece on offer, in the variant's order. Grand chess also offers
+    /// staying a pawn.
+    pub moves: Vec<Move>,
+    /// The move played on `Select`.
+    pub index: usize,
+}
+
+impl PromotionChoice {
+    /// The piece the `index`th move leaves on the destination square.
+    pub fn piece(&self, index: usize) -> Piece {
+        let mv = &self.moves[index];
+        mv.promotion.map_or(mv.piece, |piece_type| {
+            Piece::new(mv.piece.color, piece_type)
+        })
+    }
+
+    /// The square the `index`th choice is shown on: the destination, then the squares behind it
+    /// along its file.
+    pub fn square(&self, index: usize) -> (usize, usize) {
+        let mv = &self.moves[0];
+        let back = -mv.piece.color.pawn_direction() * index as isize;
+        (mv.to.0.saturating_add_signed(back), mv.to.1)
+    }
+
+    /// The choice shown on `square`, if any.
+    pub fn index_at(&self, square: (usize, usize)) -> Option<usize> {
+        (0..self.moves.len()).find(|&index| self.square(index) == square)
+    }
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct MenuSelector {
     pub selected_index: usize,
@@ -23,14 +171,25 @@ pub struct MenuSelector {
 
 impl MenuSelector {
     pub fn new() -> Self {
+        Self::from_items(vec![
+            "Normal game".to_string(),
+            "Analysis".to_string(),
+            "Board editor".to_string(),
+            "Puzzles".to_string(),
+            "Repertoire".to_string(),
+            "Endgames".to_string(),
+            "Stats".to_string(),
+            "Settings".to_string(),
+            "Help section".to_string(),
+            "Credits".to_string(),
+        ])
+    }
+
+    pub fn from_items(items: Vec<String>) -> Self {
         Self {
             selected_index: 0,
-            num_items: 3,
-            items: vec![
-                "Normal game".to_string(),
-                "Help section".to_string(),
-                "Credits".to_string(),
-            ],
+            num_items: items.len(),
+            items,
         }
     }
 
@@ -67,8 +226,108 @@ pub struct App {
     pub counter: u8,
     pub events: EventHandler,
     pub menu_selector: MenuSelector,
+    pub variant_selector: MenuSelector,
     pub state: Vec<AppState>,
     pub game: Option<Game>,
+    /// Geometry of the board as last rendered, used for mouse hit-testing.
+    pub board_layout: Option<BoardLayout>,
+    pub board_flipped: bool,
+    /// Square whose piece is picked up, waiting for a destination.
+    pub selected_square: Option<(usize, usize)>,
+    /// Square highlighted for keyboard play.
+    pub cursor: (usize, usize),
+    /// Whether the left mouse button went down on the selected piece and is still held.
+    pub dragging: bool,
+    /// Promotion waiting for a piece to be picked, shown over the board.
+    pub promotion: Option<PromotionChoice>,
+    /// Themes offered in the settings screen: the built-ins, plus user themes when started
+    /// through [`App::new`].
+    pub themes: Vec<Theme>,
+    pub theme: Theme,
+    /// Preferences edited in the settings screen and saved to the config file.
+    pub config: Config,
+    pub keymap: Keymap,
+    pub settings_selector: MenuSelector,
+    /// Problem reading or writing the config file, shown in the settings screen.
+    pub settings_message: Option<String>,
+    /// Path or profile name being typed in the settings screen, for the selected row.
+    pub path_input: Option<String>,
+    /// Which of [`keymap::bindable`] the settings screen offers to rebind.
+    pub rebind_index: usize,
+    /// Whether the next key pressed is the new binding for that action.
+    pub rebinding: bool,
+    /// Where settings are saved; `None`, as in tests, keeps them in memory.
+    pub config_path: Option<PathBuf>,
+    /// Clock of the current game, if it is timed.
+    pub clock: Option<Clock>,
+    /// Move being typed in the game screen's command bar, while it is open.
+    pub command_input: Option<String>,
+    /// Why the last typed move was rejected.
+    pub command_error: Option<String>,
+    /// The moves the command bar completes to, for the position it was last opened on.
+    pub completions: Option<Completions>,
+    /// The side the built-in engine plays, if any.
+    pub engine_color: Option<Color>,
+    /// Whether the engine is searching for its next move.
+    pub engine_thinking: bool,
+    /// The game shown in the replay screen.
+    pub replay: Option<Replay>,
+    /// FEN of every main-line position of that game, for its evaluation graph.
+    pub replay_positions: Vec<String>,
+    /// Where the evaluation graph was last drawn, inside its border, for mouse hit-testing.
+    pub graph_area: Option<Rect>,
+    /// Engine evaluations from White's point of view, by FEN, for the eval bar and graph.
+    pub evaluations: HashMap<String, Score>,
+    /// Raised to cancel the evaluations still being worked through.
+    pub evaluation_stop: Option<Arc<AtomicBool>>,
+    /// The engine's judgement of every move of the finished game.
+    pub report: Option<Report>,
+    /// Positions of the finished game searched so far and in all, while its report runs.
+    pub report_progress: Option<(usize, usize)>,
+    /// Raised to cancel the report being worked on.
+    pub report_stop: Option<Arc<AtomicBool>>,
+    /// Where the annotated game was saved, or why it could not be.
+    pub report_message: Option<String>,
+    /// The opening book the built-in engine plays from.
+    pub book: Option<Book>,
+    /// The endgame tables the built-in engine probes.
+    pub tablebase: Option<Arc<Tablebase>>,
+    /// The opening, book moves and tablebase result of the position on screen.
+    pub notes: Option<PositionNotes>,
+    /// The PGN file shown in the database screen.
+    pub database: Option<Browser>,
+    /// The last position search of that file.
+    pub position_search: Option<PositionSearch>,
+    /// The tree of moves explored in the analysis screen.
+    pub analysis: Option<Analysis>,
+    /// The engine analysing it, started when the screen opens.
+    pub analyser: Option<Analyser>,
+    /// The position being set up in the board editor.
+    pub editor: Option<Editor>,
+    /// Why the edited position cannot be played or analysed yet.
+    pub editor_message: Option<String>,
+    /// The puzzles being solved.
+    pub trainer: Option<Trainer>,
+    /// Why the puzzle file could not be opened.
+    pub puzzle_message: Option<String>,
+    /// The repertoire being drilled.
+    pub session: Option<Session>,
+    /// Why the repertoire could not be opened.
+    pub repertoire_message: Option<String>,
+    /// The ending being played out in the endgame screen, on the board of `game`.
+    pub endgame: Option<Drill>,
+    /// Why no position of the chosen ending could be set up.
+    pub endgame_message: Option<String>,
+    /// Where finished games are recorded; `None`, as in tests, records nothing.
+    pub profiles_path: Option<PathBuf>,
+    /// Who plays the current game, until it is recorded.
+    pub pairing: Option<Pairing>,
+    /// The players and games shown in the stats screen.
+    pub profiles: Option<Profiles>,
+    /// Index into those players of the one whose results are shown.
+    pub stats_player: usize,
+    /// Why the last game could not be recorded, or the profiles read.
+    pub stats_message: Option<String>,
 }
 
 impl Default for App {
@@ -78,15 +337,106 @@ impl Default for App {
             counter: 0,
             events: EventHandler::new(),
             menu_selector: MenuSelector::new(),
+            variant_selector: MenuSelector::from_items(
+                VariantKind::iter()
+                    .map(|variant| variant.name().to_string())
+                    .collect(),
+            ),
             state: vec![AppState::MainMenu],
             game: None, // Game instance will be created when entering the game state
+            board_layout: None,
+            board_flipped: false,
+            selected_square: None,
+            cursor: (6, 4),
+            dragging: false,
+            promotion: None,
+            themes: Theme::builtin(),
+            theme: Theme::default(),
+            config: Config::default(),
+            keymap: Keymap::default(),
+            settings_selector: MenuSelector::from_items(
+                SettingsRow::iter()
+                    .map(|row| row.label().to_string())
+                    .collect(),
+            ),
+            settings_message: None,
+            path_input: None,
+            rebind_index: 0,
+            rebinding: false,
+            config_path: None,
+            clock: None,
+            command_input: None,
+            command_error: None,
+            completions: None,
+            engine_color: None,
+            engine_thinking: false,
+            replay: None,
+            replay_positions: Vec::new(),
+            graph_area: None,
+            evaluations: HashMap::new(),
+            evaluation_stop: None,
+            report: None,
+            report_progress: None,
+            report_stop: None,
+            report_message: None,
+            book: None,
+            tablebase: None,
+            notes: None,
+            database: None,
+            position_search: None,
+            analysis: None,
+            analyser: None,
+            editor: None,
+            editor_message: None,
+            trainer: None,
+            puzzle_message: None,
+            session: None,
+            repertoire_message: None,
+            endgame: None,
+            endgame_message: None,
+            profiles_path: None,
+            pairing: None,
+            profiles: None,
+            stats_player: 0,
+            stats_message: None,
         }
     }
 }
 
 impl App {
+    /// Creates the app with the preferences saved in the config file.
     pub fn new() -> Self {
-        Self::default()
+        let mut app = Self {
+            themes: Theme::available(Theme::user_dir().as_deref()),
+            profiles_path: Profiles::path(),
+            config_path: Config::path(),
+            ..Self::default()
+        };
+        match Config::load() {
+            Ok(config) => app.apply_config(config),
+            Err(err) => app.settings_message = Some(format!("Could not read settings: {err}")),
+        }
+        app
+    }
+
+    /// Adopts `config`, looking its theme up by name. Key bindings that do not build into a
+    /// keymap are reported and the current keymap is kept.
+    pub fn apply_config(&mut self, config: Config) {
+        if let Some(theme) = self.themes.iter().find(|theme| theme.name == config.theme) {
+            self.theme = theme.clone();
+        }
+        self.board_flipped = config.flip_board;
+        self.config = config;
+        self.rebuild_keymap();
+        self.load_book();
+        self.load_tablebase();
+    }
+
+    fn rebuild_keymap(&mut self) {
+        match Keymap::new(self.config.keymap, &self.config.key_bindings) {
+            Ok(keymap) => self.keymap = keymap,
+            Err(err) => self.settings_message = Some(format!("Key bindings not applied: {err}")),
+        }
     }
 
     pub async fn run(mut self, mut terminal: DefaultTerminal) -> color_eyre::Result<()> {
@@ -94,56 +444,1721 @@ impl App {
             terminal.draw(|frame| frame.render_widget(&mut self, frame.area()))?;
             match self.events.next().await? {
                 Event::Tick => self.tick(),
-                Event::Crossterm(event) => {
-                    if let crossterm::event::Event::Key(key_event) = event {
-                        self.handle_key_events(key_event)?;
+                Event::Crossterm(event) => match event {
+                    crossterm::event::Event::Key(key_event) => self.handle_key_events(key_event)?,
+                    crossterm::event::Event::Mouse(mouse_event) => {
+                        self.handle_mouse_events(mouse_event)
                     }
-                }
+                    _ => {}
+                },
                 Event::App(app_event) => match app_event {
                     AppEvent::Increment => self.increment_counter(),
                     AppEvent::Decrement => self.decrement_counter(),
                     AppEvent::Quit => self.quit(),
+                    AppEvent::EngineMove(mv) => self.play_engine_move(mv),
+                    AppEvent::ReportProgress { done, total } => {
+                        if self.report_stop.is_some() {
+                            self.report_progress = Some((done, total));
+                        }
+                    }
+                    AppEvent::Report(report) => {
+                        if self.report_stop.take().is_some() {
+                            self.report = Some(*report);
+                            self.report_progress = None;
+                        }
+                    }
+                    AppEvent::Evaluation { fen, score } => {
+                        self.evaluations.insert(fen, score);
+                    }
+                    AppEvent::Analysis { generation, lines } => {
+                        if let Some(analysis) = &mut self.analysis {
+                            analysis.update(generation, lines);
+                        }
+                    }
+                    AppEvent::AnalysisFailed(err) => {
+                        self.analyser = None;
+                        if let Some(analysis) = &mut self.analysis {
+                            analysis.engine_on = false;
+                            analysis.message = Some(format!("Engine stopped: {err}"));
+                        }
+                    }
                 },
             }
         }
         Ok(())
     }
 
-    fn handle_main_menu_keys(&mut self, key_event: KeyEvent) -> color_eyre::Result<()> {
-        match key_event.code {
-            KeyCode::Right => self.events.send(AppEvent::Increment),
-            KeyCode::Left => self.events.send(AppEvent::Decrement),
-            KeyCode::Up => self.menu_selector.previous(),
-            KeyCode::Down => self.menu_selector.next(),
-            KeyCode::Enter => self.select_app_state(),
+    fn handle_main_menu_action(&mut self, action: Action) {
+        match action {
+            Action::Increment => self.events.send(AppEvent::Increment),
+            Action::Decrement => self.events.send(AppEvent::Decrement),
+            Action::Up => self.menu_selector.previous(),
+            Action::Down => self.menu_selector.next(),
+            Action::Select => self.select_app_state(),
             _ => {}
         }
-        Ok(())
     }
 
     fn select_app_state(&mut self) {
         match self.menu_selector.selected_index {
-            0 => self.state.push(AppState::Game),
-            1 => self.state.push(AppState::Help),
-            2 => self.state.push(AppState::Credits),
+            0 => self.state.push(AppState::NewGame),
+            1 => self.start_analysis(Game::new()),
+            2 => self.start_editor(&Game::new()),
+            3 => {
+                let result = match self.config.puzzles.clone() {
+                    Some(file) => self.start_puzzles(&file, ""),
+                    None => Err(eyre!("choose a puzzle file under Settings first")),
+                };
+                if let Err(err) = result {
+                    self.puzzle_message = Some(format!("{err:#}"));
+                    self.state.push(AppState::Puzzle);
+                }
+            }
+            4 => {
+                let result = match self.config.repertoire.clone() {
+                    Some(file) => self.start_repertoire(&file, None),
+                    None => Err(eyre!("choose a repertoire PGN under Settings first")),
+                };
+                if let Err(err) = result {
+                    self.repertoire_message = Some(format!("{err:#}"));
+                    self.state.push(AppState::Repertoire);
+                }
+            }
+            5 => {
+                let endgame = ENDGAMES[0].parse().expect("built-in endings parse");
+                self.start_endgame(endgame);
+            }
+            6 => self.show_stats(),
+            7 => self.state.push(AppState::Settings),
+            8 => self.state.push(AppState::Help),
+            9 => self.state.push(AppState::Credits),
+            _ => {}
+        }
+    }
+
+    fn handle_new_game_action(&mut self, action: Action) {
+        match action {
+            Action::Up => self.variant_selector.previous(),
+            Action::Down => self.variant_selector.next(),
+            Action::Select => {
+                if let Some(variant) = VariantKind::iter().nth(self.variant_selector.selected_index)
+                {
+                    self.start_game(Game::with_variant(variant));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_game_action(&mut self, action: Action) {
+        let (rows, cols) = match self.board_game() {
+            Some(game) => game.get_board().dimensions(),
+            None => return,
+        };
+        // Arrow keys follow the screen, so they are mirrored when the board is flipped
+        let step = if self.board_flipped { -1 } else { 1 };
+        let (row, col) = self.cursor;
+        match action {
+            Action::Up => self.cursor.0 = offset_clamped(row, -step, rows),
+            Action::Down => self.cursor.0 = offset_clamped(row, step, rows),
+            Action::Left => self.cursor.1 = offset_clamped(col, -step, cols),
+            Action::Right => self.cursor.1 = offset_clamped(col, step, cols),
+            Action::Select => self.select_square(self.cursor),
+            Action::Flip => self.board_flipped = !self.board_flipped,
+            Action::Command => {
+                self.command_input = Some(String::new());
+                self.command_error = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Selects or drops pieces with the left mouse button: click a piece then a destination, or
+    /// drag the piece onto it.
+    pub fn handle_mouse_events(&mut self, mouse_event: MouseEvent) {
+        if self.state.last() == Some(&AppState::Replay) {
+            self.handle_graph_click(mouse_event);
+            return;
+        }
+        if self.state.last() == Some(&AppState::Editor) {
+            self.handle_editor_click(mouse_event);
+            return;
+        }
+        if !matches!(
+            self.state.last(),
+            Some(
+                AppState::Game
+                    | AppState::Analysis
+                    | AppState::Puzzle
+                    | AppState::Repertoire
+                    | AppState::Endgame
+            )
+        ) {
+            return;
+        }
+        let square = self
+            .board_layout
+            .and_then(|layout| layout.square_at(mouse_event.column, mouse_event.row));
+        if let Some(choice) = self.promotion.take() {
+            // Clicking a piece on offer promotes to it, clicking anywhere else cancels
+            if mouse_event.kind == MouseEventKind::Down(MouseButton::Left)
+                && let Some(index) = square.and_then(|square| choice.index_at(square))
+            {
+                self.play_board_move(choice.moves[index]);
+            } else if mouse_event.kind != MouseEventKind::Down(MouseButton::Left) {
+                self.promotion = Some(choice);
+            }
+            return;
+        }
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) => match square {
+                Some(square) => {
+                    self.cursor = square;
+                    self.select_square(square);
+                    self.dragging = self.selected_square == Some(square);
+                }
+                None => self.selected_square = None,
+            },
+            MouseEventKind::Up(MouseButton::Left) => {
+                if self.dragging
+                    && let (Some(from), Some(to)) = (self.selected_square, square)
+                    && from != to
+                {
+                    self.cursor = to;
+                    self.try_move(from, to);
+                }
+                self.dragging = false;
+            }
+            _ => {}
+        }
+    }
+
+    /// Jumps to the main-line position clicked on in the replay's evaluation graph.
+    fn handle_graph_click(&mut self, mouse_event: MouseEvent) {
+        if mouse_event.kind != MouseEventKind::Down(MouseButton::Left) {
+            return;
+        }
+        if let (Some(area), Some(replay)) = (self.graph_area, &mut self.replay)
+            && area.contains(Position::new(mouse_event.column, mouse_event.row))
+            && let Some(ply) =
+                EvalGraph::point_at(self.replay_positions.len(), area, mouse_event.column)
+        {
+            replay.go_to(ply);
+        }
+    }
+
+    /// Places the selected piece with the left mouse button and empties squares with the right.
+    fn handle_editor_click(&mut self, mouse_event: MouseEvent) {
+        let (Some(square), Some(editor)) = (
+            self.board_layout
+                .and_then(|layout| layout.square_at(mouse_event.column, mouse_event.row)),
+            &mut self.editor,
+        ) else {
+            return;
+        };
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) => editor.place(square),
+            MouseEventKind::Down(MouseButton::Right) => editor.remove(square),
+            _ => return,
+        }
+        self.cursor = square;
+        self.editor_message = None;
+    }
+
+    /// Picks up a piece of the side to move, or moves the picked-up piece to `square`.
+    fn select_square(&mut self, square: (usize, usize)) {
+        let playing = matches!(self.state.last(), Some(AppState::Game | AppState::Endgame));
+        let Some(game) = self.board_game() else {
+            return;
+        };
+        if game.is_game_over() || (playing && self.is_engine_turn()) {
+            self.selected_square = None;
+            return;
+        }
+        let own_piece = game
+            .get_board()
+            .get_piece_at(square)
+            .is_some_and(|piece| piece.color == game.get_current_turn());
+        match self.selected_square {
+            Some(from) if from != square && !own_piece => self.try_move(from, square),
+            _ if own_piece => self.selected_square = Some(square),
+            _ => self.selected_square = None,
+        }
+    }
+
+    /// Plays the legal move from `from` to `to` if there is one. A pawn with a choice of
+    /// pieces to promote to waits for the player to pick one.
+    fn try_move(&mut self, from: (usize, usize), to: (usize, usize)) {
+        let moves: Vec<Move> = self
+            .board_game()
+            .map(|game| game.legal_moves_from(from))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|mv| mv.to == to && !mv.is_drop())
+            .collect();
+        match moves.as_slice() {
+            [] => {}
+            [mv] => self.play_board_move(*mv),
+            _ => self.promotion = Some(PromotionChoice { moves, index: 0 }),
+        }
+        self.selected_square = None;
+    }
+
+    /// Plays a legal move on the board of the current screen.
+    fn play_board_move(&mut self, mv: Move) {
+        match self.state.last() {
+            Some(AppState::Analysis) => self.play_analysis_move(mv),
+            Some(AppState::Puzzle) => self.play_puzzle_move(mv),
+            Some(AppState::Repertoire) => self.play_repertoire_move(mv),
+            _ => self.play_move(mv),
+        }
+    }
+
+    /// Steps through the pieces on offer for a promotion, plays the highlighted one on
+    /// `Select` and takes the pawn back on `Back`.
+    fn handle_promotion_action(&mut self, action: Action) {
+        let Some(choice) = &mut self.promotion else {
+            return;
+        };
+        let len = choice.moves.len();
+        match action {
+            Action::Up | Action::Left => choice.index = (choice.index + len - 1) % len,
+            Action::Down | Action::Right => choice.index = (choice.index + 1) % len,
+            Action::Select => {
+                let mv = choice.moves[choice.index];
+                self.promotion = None;
+                self.play_board_move(mv);
+            }
+            Action::Back => self.promotion = None,
+            _ => {}
+        }
+    }
+
+    /// The game on the board of the current screen, which may be an analysis.
+    fn board_game(&self) -> Option<&Game> {
+        match self.state.last() {
+            Some(AppState::Analysis) => self.analysis.as_ref().map(Analysis::game),
+            Some(AppState::Editor) => self.editor.as_ref().map(Editor::game),
+            Some(AppState::Puzzle) => self
+                .trainer
+                .as_ref()
+                .and_then(|trainer| trainer.attempt.as_ref())
+                .map(Attempt::game),
+            Some(AppState::Repertoire) => self.session.as_ref().map(|session| session.drill.game()),
+            _ => self.game.as_ref(),
+        }
+    }
+
+    /// Lists the moves of the position on the board for the command bar while it is open,
+    /// unless they are listed already.
+    pub fn refresh_completions(&mut self) {
+        if self.command_input.is_none() {
+            return;
+        }
+        let completions = match self.board_game() {
+            Some(game)
+                if !self
+                    .completions
+                    .as_ref()
+                    .is_some_and(|completions| completions.is_for(game)) =>
+            {
+                Completions::new(game)
+            }
+            _ => return,
+        };
+        self.completions = Some(completions);
+    }
+
+    /// Plays a legal move of the current game and runs the clock and bell. In the endgame
+    /// screen the drill judges every move, and no more are played once it is settled.
+    fn play_move(&mut self, mv: Move) {
+        let drilling = self.state.last() == Some(&AppState::Endgame);
+        if drilling && self.endgame_settled() {
+            return;
+        }
+        let Some(game) = &mut self.game else {
+            return;
+        };
+        let mover = game.get_current_turn();
+        game.make_move(mv);
+        if let Some(clock) = &mut self.clock {
+            if game.is_game_over() {
+                clock.stop();
+            } else {
+                clock.press(mover);
+            }
+        }
+        if self.config.bell {
+            ring_bell();
+        }
+        let game = game.clone();
+        if drilling && let Some(drill) = &mut self.endgame {
+            drill.judge(&game, self.tablebase.as_deref());
+        }
+        self.request_evaluations(vec![game]);
+        if drilling {
+            if self.endgame_settled() {
+                return;
+            }
+        } else {
+            self.record_game();
+            self.request_report();
+        }
+        self.request_engine_move();
+    }
+
+    /// Starts judging every move of the current game on a blocking thread once it is over.
+    /// Progress and the report come back as [`AppEvent::ReportProgress`] and
+    /// [`AppEvent::Report`].
+    fn request_report(&mut self) {
+        let Some(game) = &self.game else {
+            return;
+        };
+        if !game.is_game_over() || self.report.is_some() || self.report_stop.is_some() {
+            return;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        self.report_stop = Some(stop.clone());
+        self.report_progress = Some((0, game.get_move_history().len() + 1));
+        let limits = SearchLimits {
+            depth: Some(REPORT_DEPTH),
+            time: Some(EVAL_TIME),
+            stop: Some(stop.clone()),
+            tablebase: self.tablebase.clone(),
+            ..SearchLimits::default()
+        };
+        let game = game.clone();
+        let sender = self.events.sender();
+        tokio::task::spawn_blocking(move || {
+            let report = Report::new(&game, &limits, |done, total| {
+                let progress = AppEvent::ReportProgress { done, total };
+                let _ = sender.send(Event::App(progress));
+            });
+            if let Ok(report) = report
+                && !stop.load(Ordering::Relaxed)
+            {
+                let _ = sender.send(Event::App(AppEvent::Report(Box::new(report))));
+            }
+        });
+    }
+
+    /// Drops the report of the previous game, stopping it if it is still running.
+    fn cancel_report(&mut self) {
+        if let Some(stop) = self.report_stop.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+        self.report = None;
+        self.report_progress = None;
+        self.report_message = None;
+    }
+
+    /// Writes the finished game, annotated from its report, as PGN to the path typed in the
+    /// command bar.
+    fn save_report(&mut self) {
+        let (Some(input), Some(game)) = (&self.command_input, &self.game) else {
+            return;
+        };
+        let Some(report) = &self.report else {
+            self.command_error = Some("the report is not ready yet".to_string());
+            return;
+        };
+        let path = input.trim();
+        if path.is_empty() {
+            self.command_error = Some("type a file name".to_string());
+            return;
+        }
+        let mut pgn = PgnGame::from_game(game);
+        report.annotate(&mut pgn);
+        match std::fs::write(path, pgn.to_string()) {
+            Ok(()) => {
+                self.report_message = Some(format!("Saved to {path}"));
+                self.command_input = None;
+                self.command_error = None;
+            }
+            Err(err) => self.command_error = Some(format!("cannot write {path}: {err}")),
+        }
+    }
+
+    /// Evaluates `games` one after another on a blocking thread, skipping those already known
+    /// and cancelling any earlier batch. Scores come back as [`AppEvent::Evaluation`].
+    fn request_evaluations(&mut self, games: Vec<Game>) {
+        if let Some(stop) = self.evaluation_stop.take() {
+            stop.store(true, Ordering::Relaxed);
+        }
+        let games: Vec<Game> = games
+            .into_iter()
+            .filter(|game| !self.evaluations.contains_key(&game.to_fen()))
+            .collect();
+        if games.is_empty() {
+            return;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        self.evaluation_stop = Some(stop.clone());
+        let limits = SearchLimits {
+            depth: Some(EVAL_DEPTH),
+            time: Some(EVAL_TIME),
+            stop: Some(stop.clone()),
+            tablebase: self.tablebase.clone(),
+            ..SearchLimits::default()
+        };
+        let sender = self.events.sender();
+        tokio::task::spawn_blocking(move || {
+            for game in games {
+                let score = engine::search(&game, &limits).score;
+                // A cancelled search ends early, so its score is not kept
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                let evaluation = AppEvent::Evaluation {
+                    fen: game.to_fen(),
+                    score: score.white_view(game.get_current_turn()),
+                };
+                let _ = sender.send(Event::App(evaluation));
+            }
+        });
+    }
+
+    /// Has the built-in engine play `color` in the current game, moving now if it is its turn.
+    pub fn play_against_engine(&mut self, color: Color) {
+        self.engine_color = Some(color);
+        self.board_flipped = color == Color::White;
+        if self.pairing.is_some() {
+            self.pairing = Some(self.pairing());
+        }
+        self.request_engine_move();
+    }
+
+    fn is_engine_turn(&self) -> bool {
+        self.game
+            .as_ref()
+            .is_some_and(|game| Some(game.get_current_turn()) == self.engine_color)
+    }
+
+    /// Starts the engine searching on a blocking thread when it is to move. Its choice comes
+    /// back as [`AppEvent::EngineMove`].
+    fn request_engine_move(&mut self) {
+        let Some(game) = &self.game else {
+            return;
+        };
+        if !self.is_engine_turn() || game.is_game_over() || self.engine_thinking {
+            return;
+        }
+        self.engine_thinking = true;
+        let sender = self.events.sender();
+        if let Some(mv) = self
+            .book
+            .as_ref()
+            .and_then(|book| book.choose(game, &mut rand::rng()))
+        {
+            let _ = sender.send(Event::App(AppEvent::EngineMove(mv)));
+            return;
+        }
+        let game = game.clone();
+        let limits = SearchLimits {
+            tablebase: self.tablebase.clone(),
+            ..SearchLimits::for_strength(self.config.engine.strength)
+        };
+        tokio::task::spawn_blocking(move || {
+            if let Some(mv) = engine::search(&game, &limits).best_move {
+                let _ = sender.send(Event::App(AppEvent::EngineMove(mv)));
+            }
+        });
+    }
+
+    /// Plays the engine's move if it still fits the game on the board.
+    fn play_engine_move(&mut self, mv: Move) {
+        self.engine_thinking = false;
+        let legal = self
+            .game
+            .as_ref()
+            .is_some_and(|game| !game.is_game_over() && game.legal_moves().contains(&mv));
+        if legal && self.is_engine_turn() {
+            self.play_move(mv);
+        }
+    }
+
+    /// Edits the command bar. Tab completes the typed move as far as the legal moves agree,
+    /// Enter plays it and Esc closes the bar. In the analysis screen Enter also sets up a
+    /// typed FEN, and in the board editor it only takes one.
+    fn handle_command_keys(&mut self, key_event: KeyEvent) {
+        if key_event.code == KeyCode::Enter && self.state.last() == Some(&AppState::Analysis) {
+            self.enter_analysis_command();
+            return;
+        }
+        if key_event.code == KeyCode::Enter && self.state.last() == Some(&AppState::Editor) {
+            self.enter_editor_command();
+            return;
+        }
+        if key_event.code == KeyCode::Enter && self.state.last() == Some(&AppState::Puzzle) {
+            self.enter_puzzle_command();
+            return;
+        }
+        if key_event.code == KeyCode::Enter && self.state.last() == Some(&AppState::Repertoire) {
+            self.enter_repertoire_command();
+            return;
+        }
+        if key_event.code == KeyCode::Enter
+            && self.state.last() == Some(&AppState::Endgame)
+            && self.enter_endgame_command()
+        {
+            return;
+        }
+        if key_event.code == KeyCode::Enter
+            && self.state.last() == Some(&AppState::Game)
+            && self.game.as_ref().is_some_and(Game::is_game_over)
+        {
+            self.save_report();
+            return;
+        }
+        self.refresh_completions();
+        let game = match self.state.last() {
+            Some(AppState::Analysis) => self.analysis.as_ref().map(Analysis::game),
+            Some(AppState::Editor) => self.editor.as_ref().map(Editor::game),
+            Some(AppState::Puzzle) => self
+                .trainer
+                .as_ref()
+                .and_then(|trainer| trainer.attempt.as_ref())
+                .map(Attempt::game),
+            Some(AppState::Repertoire) => self.session.as_ref().map(|session| session.drill.game()),
+            _ => self.game.as_ref(),
+        };
+        let (Some(input), Some(game)) = (&mut self.command_input, game) else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Char(c) => {
+                input.push(c);
+                self.command_error = None;
+            }
+            KeyCode::Backspace => {
+                input.pop();
+                self.command_error = None;
+            }
+            KeyCode::Tab => {
+                let completions = self
+                    .completions
+                    .as_ref()
+                    .map(|completions| completions.matching(input))
+                    .unwrap_or_default();
+                if let Some(prefix) = common_prefix(&completions)
+                    && prefix.len() > input.len()
+                {
+                    *input = prefix;
+                }
+            }
+            KeyCode::Enter => match notation::parse_move(game, input) {
+                Ok(_) if Some(game.get_current_turn()) == self.engine_color => {
+                    self.command_error = Some("wait for the engine to move".to_string())
+                }
+                Ok(mv) if !game.is_game_over() => {
+                    self.command_input = None;
+                    self.command_error = None;
+                    self.selected_square = None;
+                    self.play_move(mv);
+                }
+                Ok(_) => self.command_error = Some("the game is over".to_string()),
+                Err(err) => self.command_error = Some(err.to_string()),
+            },
+            KeyCode::Esc => {
+                self.command_input = None;
+                self.command_error = None;
+            }
             _ => {}
         }
     }
 
-    fn handle_window_keys(&mut self, key_event: KeyEvent) -> color_eyre::Result<()> {
+    /// Shows `game` on the board in place of the current screen, or over the main menu, and
+    /// starts its clock. Both sides are played by hand until [`App::play_against_engine`]
+    /// gives the engine one.
+    pub fn start_game(&mut self, game: Game) {
+        let (rows, cols) = game.get_board().dimensions();
+        self.cursor = (rows - 2, cols / 2);
+        self.selected_square = None;
+        self.board_flipped = self.config.flip_board;
+        self.command_input = None;
+        self.command_error = None;
+        self.clock = self.config.time_control.map(|control| {
+            let mut clock = Clock::new(control);
+            clock.start(game.get_current_turn());
+            clock
+        });
+        self.request_evaluations(vec![game.clone()]);
+        self.cancel_report();
+        self.engine_color = None;
+        self.engine_thinking = false;
+        self.pairing = (!game.is_game_over()).then(|| self.pairing());
+        self.game = Some(game);
+        if self.state.last() != Some(&AppState::MainMenu) {
+            self.state.pop();
+        }
+        self.state.push(AppState::Game);
+        self.request_report();
+        self.request_engine_move();
+    }
+
+    /// Opens `pgn` in the replay screen on top of the current one.
+    pub fn start_replay(&mut self, pgn: PgnGame) -> color_eyre::Result<()> {
+        let replay = Replay::new(pgn)?;
+        let positions = replay.main_line();
+        self.replay_positions = positions.iter().map(Game::to_fen).collect();
+        self.request_evaluations(positions);
+        self.replay = Some(replay);
+        self.board_flipped = self.config.flip_board;
+        self.selected_square = None;
+        self.state.push(AppState::Replay);
+        Ok(())
+    }
+
+    /// Opens the analysis screen on `game` and starts the engine on it.
+    pub fn start_analysis(&mut self, game: Game) {
+        let (rows, cols) = game.get_board().dimensions();
+        self.cursor = (rows - 2, cols / 2);
+        self.selected_square = None;
+        self.board_flipped = self.config.flip_board;
+        self.command_input = None;
+        self.command_error = None;
+        self.analysis = Some(Analysis::new(game));
+        self.state.push(AppState::Analysis);
+        self.analyse();
+    }
+
+    fn handle_analysis_action(&mut self, action: Action) {
+        let Some(analysis) = &mut self.analysis else {
+            return;
+        };
+        let changed = match action {
+            Action::Previous => analysis.back(),
+            Action::Next => analysis.forward(),
+            Action::First => {
+                analysis.to_start();
+                true
+            }
+            Action::Last => {
+                analysis.to_end();
+                true
+            }
+            Action::Variation => analysis.next_variation(),
+            Action::Engine => {
+                analysis.engine_on = !analysis.engine_on;
+                analysis.message = None;
+                true
+            }
+            Action::Increment => {
+                analysis.multipv = (analysis.multipv + 1).min(analysis::MAX_LINES);
+                true
+            }
+            Action::Decrement => {
+                analysis.multipv = (analysis.multipv - 1).max(1);
+                true
+            }
+            _ => {
+                self.handle_game_action(action);
+                return;
+            }
+        };
+        if changed {
+            self.selected_square = None;
+            self.analyse();
+        }
+    }
+
+    fn play_analysis_move(&mut self, mv: Move) {
+        if let Some(analysis) = &mut self.analysis {
+            analysis.play(mv);
+            self.analyse();
+        }
+    }
+
+    /// Plays the move typed in the analysis screen's command bar, or sets up the position of
+    /// a typed FEN in a fresh tree.
+    fn enter_analysis_command(&mut self) {
+        let (Some(input), Some(analysis)) = (&self.command_input, &mut self.analysis) else {
+            return;
+        };
+        let input = input.trim();
+        let result = if input.contains('/') {
+            Game::from_fen(input, analysis.game().variant()).map(|game| {
+                let mut fresh = Analysis::new(game);
+                fresh.engine_on = analysis.engine_on;
+                fresh.multipv = analysis.multipv;
+                *analysis = fresh;
+            })
+        } else {
+            notation::parse_move(analysis.game(), input).map(|mv| analysis.play(mv))
+        };
+        if let Err(err) = result {
+            self.command_error = Some(err.to_string());
+            return;
+        }
+        self.command_input = None;
+        self.command_error = None;
+        self.selected_square = None;
+        self.analyse();
+    }
+
+    /// Has the engine analyse the shown position, or stops it when it is switched off or the
+    /// game is over. The engine is started on first use; an external one that cannot start
+    /// gives way to the built-in engine.
+    fn analyse(&mut self) {
+        let Some(analysis) = &mut self.analysis else {
+            return;
+        };
+        if !analysis.engine_on || analysis.game().is_game_over() {
+            if let Some(analyser) = &mut self.analyser {
+                analyser.stop();
+            }
+            // No search is numbered 0, so nothing more is shown
+            analysis.expect(0);
+            return;
+        }
+        let analyser = self.analyser.get_or_insert_with(|| {
+            let events = self.events.sender();
+            match &self.config.engine.path {
+                Some(path) => Analyser::external(events.clone(), path).unwrap_or_else(|err| {
+                    analysis.message = Some(format!("{err:#}; using the built-in engine"));
+                    Analyser::built_in(events, self.tablebase.clone())
+                }),
+                None => Analyser::built_in(events, self.tablebase.clone()),
+            }
+        });
+        analysis.expect(analyser.analyse(analysis.game(), analysis.multipv));
+    }
+
+    /// Opens the board editor on the position of `game`.
+    pub fn start_editor(&mut self, game: &Game) {
+        let (rows, cols) = game.get_board().dimensions();
+        self.cursor = (rows - 1, cols / 2);
+        self.selected_square = None;
+        self.board_flipped = self.config.flip_board;
+        self.command_input = None;
+        self.command_error = None;
+        self.editor = Some(Editor::new(game));
+        self.editor_message = None;
+        self.state.push(AppState::Editor);
+    }
+
+    fn handle_editor_action(&mut self, action: Action) {
+        let Some(editor) = &mut self.editor else {
+            return;
+        };
+        self.editor_message = None;
+        match action {
+            Action::Select => editor.place(self.cursor),
+            Action::Remove => editor.remove(self.cursor),
+            Action::Clear => editor.clear(),
+            Action::Previous => editor.cycle_piece(-1),
+            Action::Next => editor.cycle_piece(1),
+            Action::SideToMove => editor.toggle_turn(),
+            Action::Castling => {
+                if let Err(err) = editor.toggle_castling(self.cursor) {
+                    self.editor_message = Some(err.to_string());
+                }
+            }
+            Action::EnPassant => editor.toggle_en_passant(self.cursor),
+            Action::Increment | Action::Decrement => {
+                let delta = if action == Action::Increment { 1 } else { -1 };
+                let variant = cycle(VariantKind::iter(), editor.variant(), delta);
+                *editor = Editor::new(&Game::with_variant(variant));
+                let (rows, cols) = editor.board().dimensions();
+                self.cursor = (rows - 1, cols / 2);
+            }
+            Action::Play | Action::Analyse => match editor.validate() {
+                // The game or analysis takes the editor's place
+                Ok(game) if action == Action::Play => self.start_game(game),
+                Ok(game) => {
+                    self.state.pop();
+                    self.start_analysis(game);
+                }
+                Err(err) => self.editor_message = Some(err.to_string()),
+            },
+            _ => self.handle_game_action(action),
+        }
+    }
+
+    /// Sets up the position of the FEN typed in the board editor's command bar.
+    fn enter_editor_command(&mut self) {
+        let (Some(input), Some(editor)) = (&self.command_input, &mut self.editor) else {
+            return;
+        };
+        match Game::from_fen(input.trim(), editor.variant()) {
+            Ok(game) => {
+                *editor = Editor::new(&game);
+                self.command_input = None;
+                self.command_error = None;
+            }
+            Err(err) => self.command_error = Some(err.to_string()),
+        }
+    }
+
+    /// Opens the puzzle screen on the puzzles of `file` that `query` keeps, with the progress
+    /// saved in the data directory.
+    pub fn start_puzzles(&mut self, file: &Path, query: &str) -> color_eyre::Result<()> {
+        let trainer = Trainer::open(file, query, Progress::path())?;
+        self.show_puzzles(trainer);
+        Ok(())
+    }
+
+    /// Shows `trainer`'s current puzzle on top of the current screen.
+    pub fn show_puzzles(&mut self, trainer: Trainer) {
+        self.trainer = Some(trainer);
+        self.puzzle_message = None;
+        self.command_input = None;
+        self.command_error = None;
+        self.state.push(AppState::Puzzle);
+        self.show_puzzle();
+    }
+
+    /// Turns the board to the side solving the current puzzle.
+    fn show_puzzle(&mut self) {
+        let Some(attempt) = self
+            .trainer
+            .as_ref()
+            .and_then(|trainer| trainer.attempt.as_ref())
+        else {
+            return;
+        };
+        let (rows, cols) = attempt.game().get_board().dimensions();
+        self.board_flipped = attempt.player() == Color::Black;
+        self.cursor = match attempt.player() {
+            Color::White => (rows - 2, cols / 2),
+            Color::Black => (1, cols / 2),
+        };
+        self.selected_square = None;
+    }
+
+    fn handle_puzzle_action(&mut self, action: Action) {
+        let Some(trainer) = &mut self.trainer else {
+            return;
+        };
+        match action {
+            Action::Hint => {
+                trainer.hint();
+                // The first hint picks up the piece to move
+                if let Some(attempt) = &trainer.attempt
+                    && attempt.hint == 1
+                {
+                    self.selected_square = attempt.expected().map(|mv| mv.from);
+                }
+            }
+            Action::Next => {
+                trainer.message = trainer.next_puzzle().err().map(|err| err.to_string());
+                self.show_puzzle();
+            }
+            Action::Filter => {
+                trainer.filter_input = Some(trainer.query.clone());
+                trainer.message = None;
+            }
+            _ => self.handle_game_action(action),
+        }
+    }
+
+    fn play_puzzle_move(&mut self, mv: Move) {
+        if let Some(trainer) = &mut self.trainer
+            && trainer.play(mv) != Status::Failed
+            && self.config.bell
+        {
+            ring_bell();
+        }
+    }
+
+    /// Plays the move typed in the puzzle screen's command bar.
+    fn enter_puzzle_command(&mut self) {
+        let Some(input) = &self.command_input else {
+            return;
+        };
+        let Some(game) = self
+            .trainer
+            .as_ref()
+            .and_then(|trainer| trainer.attempt.as_ref())
+            .map(Attempt::game)
+        else {
+            return;
+        };
+        match notation::parse_move(game, input) {
+            Ok(mv) => {
+                self.command_input = None;
+                self.command_error = None;
+                self.selected_square = None;
+                self.play_puzzle_move(mv);
+            }
+            Err(err) => self.command_error = Some(err.to_string()),
+        }
+    }
+
+    /// Edits the puzzle filter; Enter reloads the puzzles it keeps and Esc closes the bar.
+    fn handle_puzzle_filter_keys(&mut self, key_event: KeyEvent) {
+        let Some(trainer) = &mut self.trainer else {
+            return;
+        };
+        let Some(input) = &mut trainer.filter_input else {
+            return;
+        };
         match key_event.code {
-            KeyCode::Esc | KeyCode::Char('q') => self.quit(),
-            KeyCode::Char('c' | 'C') if key_event.modifiers == KeyModifiers::CONTROL => self.quit(),
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Enter => {
+                let query = input.clone();
+                match trainer.set_filter(&query) {
+                    Ok(()) => {
+                        trainer.filter_input = None;
+                        trainer.message = None;
+                        self.show_puzzle();
+                    }
+                    Err(err) => trainer.message = Some(err.to_string()),
+                }
+            }
+            KeyCode::Esc => {
+                trainer.filter_input = None;
+                trainer.message = None;
+            }
             _ => {}
         }
+    }
+
+    /// Opens the repertoire screen on the lines of the PGN `file`, drilled from `color` or the
+    /// side the file suggests, with statistics saved in the data directory.
+    pub fn start_repertoire(
+        &mut self,
+        file: &Path,
+        color: Option<Color>,
+    ) -> color_eyre::Result<()> {
+        let repertoire = Repertoire::open(file, color)?;
+        let session = Session::new(repertoire, Stats::path(), repertoire::today())?;
+        self.show_repertoire(session);
         Ok(())
     }
 
+    /// Shows `session`'s current line on top of the current screen.
+    pub fn show_repertoire(&mut self, session: Session) {
+        self.session = Some(session);
+        self.repertoire_message = None;
+        self.command_input = None;
+        self.command_error = None;
+        self.state.push(AppState::Repertoire);
+        self.show_drill();
+    }
+
+    /// Turns the board to the side the repertoire is drilled from.
+    fn show_drill(&mut self) {
+        let Some(session) = &self.session else {
+            return;
+        };
+        let (rows, cols) = session.drill.game().get_board().dimensions();
+        let color = session.repertoire.color;
+        self.board_flipped = color == Color::Black;
+        self.cursor = match color {
+            Color::White => (rows - 2, cols / 2),
+            Color::Black => (1, cols / 2),
+        };
+        self.selected_square = None;
+    }
+
+    fn handle_repertoire_action(&mut self, action: Action) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        match action {
+            Action::Hint => {
+                session.hint();
+                self.selected_square = session.drill.expected().map(|mv| mv.from);
+            }
+            Action::Next => {
+                session.next_line();
+                self.show_drill();
+            }
+            _ => self.handle_game_action(action),
+        }
+    }
+
+    fn play_repertoire_move(&mut self, mv: Move) {
+        if let Some(session) = &mut self.session
+            && session.play(mv) != Verdict::Wrong
+            && self.config.bell
+        {
+            ring_bell();
+        }
+    }
+
+    /// Plays the move typed in the repertoire screen's command bar.
+    fn enter_repertoire_command(&mut self) {
+        let (Some(input), Some(session)) = (&self.command_input, &self.session) else {
+            return;
+        };
+        match notation::parse_move(session.drill.game(), input) {
+            Ok(mv) => {
+                self.command_input = None;
+                self.command_error = None;
+                self.selected_square = None;
+                self.play_repertoire_move(mv);
+            }
+            Err(err) => self.command_error = Some(err.to_string()),
+        }
+    }
+
+    /// Opens the endgame screen on a random position of `endgame`, or on why none could be
+    /// set up.
+    pub fn start_endgame(&mut self, endgame: Endgame) {
+        match Drill::new(endgame, &mut rand::rng(), self.tablebase.as_deref()) {
+            Ok(drill) => self.show_endgame(drill),
+            Err(err) => {
+                self.endgame_message = Some(err.to_string());
+                if self.state.last() != Some(&AppState::Endgame) {
+                    self.state.push(AppState::Endgame);
+                }
+            }
+        }
+    }
+
+    /// Sets up `drill`'s position with the engine defending or attacking against the user,
+    /// untimed.
+    pub fn show_endgame(&mut self, drill: Drill) {
+        let game = drill.start.clone();
+        let (rows, cols) = game.get_board().dimensions();
+        self.cursor = (rows - 2, cols / 2);
+        self.selected_square = None;
+        self.command_input = None;
+        self.command_error = None;
+        self.clock = None;
+        self.cancel_report();
+        self.pairing = None;
+        self.request_evaluations(vec![game.clone()]);
+        self.game = Some(game);
+        self.engine_thinking = false;
+        self.endgame_message = None;
+        let player = drill.player;
+        self.endgame = Some(drill);
+        if self.state.last() != Some(&AppState::Endgame) {
+            self.state.push(AppState::Endgame);
+        }
+        self.play_against_engine(player.opposite());
+    }
+
+    /// Whether the drill has been won, held or missed.
+    fn endgame_settled(&self) -> bool {
+        self.endgame
+            .as_ref()
+            .is_some_and(|drill| drill.verdict.is_some())
+    }
+
+    fn handle_endgame_action(&mut self, action: Action) {
+        let Some(drill) = &self.endgame else {
+            return;
+        };
+        match action {
+            Action::Next => self.start_endgame(drill.endgame.clone()),
+            Action::Retry => self.show_endgame(drill.retry(self.tablebase.as_deref())),
+            Action::Increment | Action::Decrement => {
+                let delta = if action == Action::Increment { 1 } else { -1 };
+                let name = drill.endgame.to_string();
+                let index = ENDGAMES.iter().position(|&ending| ending == name);
+                let next = match index {
+                    Some(index) => (index as isize + delta).rem_euclid(ENDGAMES.len() as isize),
+                    None => 0,
+                };
+                let endgame = ENDGAMES[next as usize]
+                    .parse()
+                    .expect("built-in endings parse");
+                self.start_endgame(endgame);
+            }
+            _ => self.handle_game_action(action),
+        }
+    }
+
+    /// Starts drilling an ending typed in the endgame screen's command bar, such as KRvK.
+    /// Anything else is taken as a move.
+    fn enter_endgame_command(&mut self) -> bool {
+        let Some(Ok(endgame)) = self
+            .command_input
+            .as_ref()
+            .filter(|input| !input.trim().is_empty())
+            .map(|input| input.parse::<Endgame>())
+        else {
+            return false;
+        };
+        self.start_endgame(endgame);
+        true
+    }
+
+    /// Who plays a game starting now: the player has the side at the bottom of the board and
+    /// faces the engine if it has a side, or else the opponent.
+    fn pairing(&self) -> Pairing {
+        let (player_color, opponent) = match self.engine_color {
+            Some(color) => (color.opposite(), self.engine_name()),
+            None if self.board_flipped => (Color::Black, self.config.opponent.clone()),
+            None => (Color::White, self.config.opponent.clone()),
+        };
+        let player = self.config.player.clone();
+        match player_color {
+            Color::White => Pairing {
+                white: player,
+                black: opponent,
+            },
+            Color::Black => Pairing {
+                white: opponent,
+                black: player,
+            },
+        }
+    }
+
+    /// The profile name of the built-in engine, which plays every engine game whatever engine
+    /// the analysis board uses. It includes the strength so each level is rated apart.
+    fn engine_name(&self) -> String {
+        format!("Built-in engine level {}", self.config.engine.strength)
+    }
+
+    /// Records the current game for its players once it is over, rating them on it.
+    fn record_game(&mut self) {
+        let Some(game) = self.game.as_ref().filter(|game| game.is_game_over()) else {
+            return;
+        };
+        let (Some(path), Some(pairing)) = (&self.profiles_path, self.pairing.take()) else {
+            return;
+        };
+        let control = self.clock.as_ref().map(Clock::control);
+        let recorded = Profiles::load_from(path).and_then(|mut profiles| {
+            profiles.record(&pairing, game, control)?;
+            profiles.save_to(path)
+        });
+        self.stats_message = recorded
+            .err()
+            .map(|err| format!("Could not record the game: {err}"));
+    }
+
+    /// Opens the stats screen on the recorded games, showing the player's own results first.
+    pub fn show_stats(&mut self) {
+        match self.profiles_path.as_deref().map(Profiles::load_from) {
+            Some(Ok(profiles)) => {
+                self.stats_player = profiles
+                    .players
+                    .iter()
+                    .position(|profile| profile.name == self.config.player)
+                    .unwrap_or(0);
+                self.profiles = Some(profiles);
+            }
+            Some(Err(err)) => {
+                self.profiles = None;
+                self.stats_message = Some(format!("Could not read profiles: {err}"));
+            }
+            None => {
+                self.profiles = None;
+                self.stats_message = Some("No data directory to keep profiles in".to_string());
+            }
+        }
+        self.state.push(AppState::Stats);
+    }
+
+    fn handle_stats_action(&mut self, action: Action) {
+        let Some(profiles) = &self.profiles else {
+            return;
+        };
+        let count = profiles.players.len().max(1);
+        self.stats_player = match action {
+            Action::Up => (self.stats_player + count - 1) % count,
+            Action::Down => (self.stats_player + 1) % count,
+            _ => return,
+        };
+    }
+
+    fn handle_replay_action(&mut self, action: Action) {
+        let delay = self.autoplay_delay();
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        match action {
+            Action::Left => {
+                replay.back();
+            }
+            Action::Right => {
+                replay.forward();
+            }
+            Action::Up => {
+                replay.exit_variation();
+            }
+            Action::Down => {
+                replay.enter_variation();
+            }
+            Action::First => replay.to_start(),
+            Action::Last => replay.to_end(),
+            Action::Autoplay => replay.toggle_autoplay(delay),
+            Action::Flip => self.board_flipped = !self.board_flipped,
+            Action::Increment | Action::Decrement => {
+                // Faster means a shorter pause
+                let (min, max, step) = AUTOPLAY_DELAY_MS;
+                let delay = self.config.autoplay_delay_ms;
+                self.config.autoplay_delay_ms = match action {
+                    Action::Increment => delay.saturating_sub(step),
+                    _ => delay + step,
+                }
+                .clamp(min, max);
+                self.save_config();
+            }
+            _ => {}
+        }
+    }
+
+    /// Lists the games of `database` in the database screen on top of the current one.
+    pub fn open_database(&mut self, database: Database) {
+        self.database = Some(Browser::new(database));
+        self.state.push(AppState::Database);
+    }
+
+    fn handle_database_action(&mut self, action: Action) {
+        let Some(browser) = &mut self.database else {
+            return;
+        };
+        let page = browser.page.max(1) as isize;
+        match action {
+            Action::Up => browser.move_selection(-1),
+            Action::Down => browser.move_selection(1),
+            Action::PageUp => browser.move_selection(-page),
+            Action::PageDown => browser.move_selection(page),
+            Action::First => browser.selected = 0,
+            Action::Last => browser.move_selection(isize::MAX),
+            Action::Left => browser.cycle_column(-1),
+            Action::Right => browser.cycle_column(1),
+            Action::Reverse => browser.reverse(),
+            Action::Command => {
+                browser.filter_input = Some(browser.query.clone());
+                browser.error = None;
+            }
+            Action::Search => {
+                self.position_search
+                    .get_or_insert_with(PositionSearch::default)
+                    .edit();
+                self.state.push(AppState::Search);
+            }
+            Action::Select => {
+                let Some(header) = browser.selected_header() else {
+                    return;
+                };
+                match browser
+                    .database
+                    .load(header)
+                    .and_then(|pgn| self.start_replay(pgn))
+                {
+                    Ok(()) => {}
+                    Err(err) => {
+                        if let Some(browser) = &mut self.database {
+                            browser.error = Some(err.to_string());
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_search_action(&mut self, action: Action) {
+        let (Some(search), Some(browser)) = (&mut self.position_search, &mut self.database) else {
+            return;
+        };
+        let page = search.page.max(1) as isize;
+        match action {
+            Action::Up => search.move_selection(-1),
+            Action::Down => search.move_selection(1),
+            Action::PageUp => search.move_selection(-page),
+            Action::PageDown => search.move_selection(page),
+            Action::First => search.selected = 0,
+            Action::Last => search.move_selection(isize::MAX),
+            Action::Command => search.edit(),
+            Action::Select => {
+                let Some((index, found)) = search.selected_hit() else {
+                    return;
+                };
+                let ply = found.ply;
+                let header = &browser.database.games()[*index];
+                match browser
+                    .database
+                    .load(header)
+                    .and_then(|pgn| self.start_replay(pgn))
+                {
+                    Ok(()) => {
+                        if let Some(replay) = &mut self.replay {
+                            replay.go_to(ply);
+                        }
+                    }
+                    Err(err) => {
+                        if let Some(search) = &mut self.position_search {
+                            search.error = Some(err.to_string());
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Edits the search query; Enter runs it and Esc closes the input.
+    fn handle_search_keys(&mut self, key_event: KeyEvent) {
+        let (Some(search), Some(browser)) = (&mut self.position_search, &self.database) else {
+            return;
+        };
+        let Some(input) = &mut search.input else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Enter => search.run(&browser.database),
+            KeyCode::Esc => {
+                search.input = None;
+                search.error = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Edits the database filter; Enter applies it and Esc keeps the current one.
+    fn handle_filter_keys(&mut self, key_event: KeyEvent) {
+        let Some(browser) = &mut self.database else {
+            return;
+        };
+        let Some(input) = &mut browser.filter_input else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Enter => {
+                let query = input.clone();
+                match browser.set_query(&query) {
+                    Ok(()) => {
+                        browser.filter_input = None;
+                        browser.error = None;
+                    }
+                    Err(err) => browser.error = Some(err.to_string()),
+                }
+            }
+            KeyCode::Esc => {
+                browser.filter_input = None;
+                browser.error = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn autoplay_delay(&self) -> Duration {
+        Duration::from_millis(self.config.autoplay_delay_ms)
+    }
+
+    fn handle_settings_action(&mut self, action: Action) {
+        let Some(row) = SettingsRow::nth(self.settings_selector.selected_index) else {
+            return;
+        };
+        let delta = match action {
+            Action::Up => {
+                self.settings_selector.previous();
+                return;
+            }
+            Action::Down => {
+                self.settings_selector.next();
+                return;
+            }
+            Action::Select if row.is_path() => {
+                let path = match row {
+                    SettingsRow::Engine => self.config.engine.path.as_ref(),
+                    SettingsRow::OpeningBook => self.config.engine.book.as_ref(),
+                    SettingsRow::Tablebases => self.config.engine.tablebases.as_ref(),
+                    SettingsRow::Puzzles => self.config.puzzles.as_ref(),
+                    _ => self.config.repertoire.as_ref(),
+                };
+                self.path_input = Some(
+                    path.map(|path| path.display().to_string())
+                        .unwrap_or_default(),
+                );
+                return;
+            }
+            Action::Select if row == SettingsRow::Rebind => {
+                self.rebinding = true;
+                return;
+            }
+            Action::Select if row.is_name() => {
+                self.path_input = Some(if row == SettingsRow::Player {
+                    self.config.player.clone()
+                } else {
+                    self.config.opponent.clone()
+                });
+                return;
+            }
+            Action::Left => -1,
+            Action::Right | Action::Select => 1,
+            _ => return,
+        };
+        match row {
+            SettingsRow::Theme => self.cycle_theme(delta),
+            SettingsRow::Glyphs => {
+                self.config.glyph_set = cycle(GlyphSet::iter(), self.config.glyph_set, delta)
+            }
+            SettingsRow::Pieces => {
+                self.config.piece_style = cycle(PieceStyle::iter(), self.config.piece_style, delta)
+            }
+            SettingsRow::Orientation => {
+                self.config.flip_board = !self.config.flip_board;
+                self.board_flipped = self.config.flip_board;
+            }
+            SettingsRow::Coordinates => {
+                self.config.show_coordinates = !self.config.show_coordinates
+            }
+            SettingsRow::Bell => self.config.bell = !self.config.bell,
+            SettingsRow::TimeControl => {
+                let choices = std::iter::once(None).chain(TimeControl::PRESETS.map(Some));
+                self.config.time_control = cycle(choices, self.config.time_control, delta);
+            }
+            SettingsRow::EngineStrength => {
+                let strength = self.config.engine.strength as isize + delta;
+                self.config.engine.strength =
+                    strength.clamp(1, EngineConfig::MAX_STRENGTH as isize) as u8;
+            }
+            SettingsRow::KeyBindings => {
+                self.config.keymap = cycle(KeymapPreset::iter(), self.config.keymap, delta);
+                self.rebuild_keymap();
+            }
+            SettingsRow::Rebind => {
+                let count = keymap::bindable().len() as isize;
+                self.rebind_index = (self.rebind_index as isize + delta).rem_euclid(count) as usize;
+                return;
+            }
+            SettingsRow::Engine
+            | SettingsRow::OpeningBook
+            | SettingsRow::Tablebases
+            | SettingsRow::Puzzles
+            | SettingsRow::Repertoire
+            | SettingsRow::Player
+            | SettingsRow::Opponent => return,
+        }
+        self.save_config();
+    }
+
+    /// Edits the engine, book, tablebase, puzzle or repertoire path, or a profile name; Enter
+    /// keeps it, with an empty path meaning the built-in engine or none, and Esc discards it.
+    /// An empty name is not kept.
+    fn handle_path_keys(&mut self, key_event: KeyEvent) {
+        let Some(input) = &mut self.path_input else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Enter => {
+                let name = input.trim().to_string();
+                let path = (!name.is_empty()).then(|| name.clone().into());
+                self.path_input = None;
+                let Some(row) = SettingsRow::nth(self.settings_selector.selected_index) else {
+                    return;
+                };
+                match row {
+                    SettingsRow::Player | SettingsRow::Opponent if name.is_empty() => return,
+                    SettingsRow::Player => self.config.player = name,
+                    SettingsRow::Opponent => self.config.opponent = name,
+                    SettingsRow::Engine => self.config.engine.path = path,
+                    SettingsRow::OpeningBook => self.config.engine.book = path,
+                    SettingsRow::Tablebases => self.config.engine.tablebases = path,
+                    SettingsRow::Puzzles => self.config.puzzles = path,
+                    SettingsRow::Repertoire => self.config.repertoire = path,
+                    _ => return,
+                }
+                self.save_config();
+                match row {
+                    SettingsRow::OpeningBook => self.load_book(),
+                    SettingsRow::Tablebases => self.load_tablebase(),
+                    _ => {}
+                }
+            }
+            KeyCode::Esc => self.path_input = None,
+            _ => {}
+        }
+    }
+
+    /// Binds the key pressed to the action being rebound in the settings screen, in place of
+    /// its keys on that screen. Delete brings the preset's keys back and Esc keeps the current
+    /// ones. A key that would clash with another binding is refused.
+    fn handle_rebind_key(&mut self, key_event: KeyEvent) {
+        self.rebinding = false;
+        let Some(&(scope, action)) = keymap::bindable().get(self.rebind_index) else {
+            return;
+        };
+        let mut overrides = self.config.key_bindings.clone();
+        match key_event.code {
+            KeyCode::Esc => return,
+            KeyCode::Delete => {
+                if let Some(actions) = overrides.get_mut(&scope) {
+                    actions.remove(&action);
+                    if actions.is_empty() {
+                        overrides.remove(&scope);
+                    }
+                }
+            }
+            _ => {
+                let key = KeyBinding::from_event(key_event).to_string();
+                overrides
+                    .entry(scope)
+                    .or_default()
+                    .insert(action, Keys::One(key));
+            }
+        }
+        match Keymap::new(self.config.keymap, &overrides) {
+            Ok(keymap) => {
+                self.keymap = keymap;
+                self.config.key_bindings = overrides;
+                self.save_config();
+            }
+            Err(err) => self.settings_message = Some(format!("Key not bound: {err}")),
+        }
+    }
+
+    /// Reads the opening book the settings name, reporting failures in the settings screen.
+    fn load_book(&mut self) {
+        self.book = match &self.config.engine.book {
+            Some(path) => match Book::open(path) {
+                Ok(book) => Some(book),
+                Err(err) => {
+                    self.settings_message = Some(format!("Could not read opening book: {err}"));
+                    None
+                }
+            },
+            None => None,
+        };
+        self.notes = None;
+    }
+
+    /// Finds the endgame tables the settings name, reporting failures in the settings screen.
+    fn load_tablebase(&mut self) {
+        self.tablebase = match &self.config.engine.tablebases {
+            Some(dir) => match Tablebase::open(dir) {
+                Ok(tablebase) => Some(Arc::new(tablebase)),
+                Err(err) => {
+                    self.settings_message = Some(format!("Could not read tablebases: {err}"));
+                    None
+                }
+            },
+            None => None,
+        };
+        self.notes = None;
+    }
+
+    /// Works out the notes on the position on screen, unless they are already for it.
+    pub fn refresh_notes(&mut self) {
+        let game = match self.state.last() {
+            Some(AppState::Game | AppState::Endgame) => self.game.as_ref(),
+            Some(AppState::Analysis) => self.analysis.as_ref().map(Analysis::game),
+            Some(AppState::Replay) => self.replay.as_ref().map(Replay::game),
+            _ => None,
+        };
+        let Some(game) = game else {
+            return;
+        };
+        if self.notes.as_ref().is_some_and(|notes| notes.is_for(game)) {
+            return;
+        }
+        self.notes = Some(PositionNotes::new(
+            game,
+            self.book.as_ref(),
+            self.tablebase.as_deref(),
+        ));
+    }
+
+    /// Writes the settings to the config file, reporting failures in the settings screen.
+    fn save_config(&mut self) {
+        let Some(path) = &self.config_path else {
+            return;
+        };
+        self.settings_message = self
+            .config
+            .save_to(path)
+            .err()
+            .map(|err| format!("Could not save settings: {err}"));
+    }
+
+    /// Switches to the next or previous theme in [`App::themes`].
+    pub fn cycle_theme(&mut self, delta: isize) {
+        let current = self
+            .themes
+            .iter()
+            .position(|theme| theme.name == self.theme.name)
+            .unwrap_or(0);
+        let next = (current as isize + delta).rem_euclid(self.themes.len() as isize) as usize;
+        self.theme = self.themes[next].clone();
+        self.config.theme = self.theme.name.clone();
+    }
+
     pub fn handle_key_events(&mut self, key_event: KeyEvent) -> color_eyre::Result<()> {
-        if self.state.last() == Some(&AppState::MainMenu) {
-            self.handle_main_menu_keys(key_event)?;
+        // Typed text must not trigger shortcuts
+        if self.rebinding {
+            self.handle_rebind_key(key_event);
+            return Ok(());
+        }
+        if self.path_input.is_some() {
+            self.handle_path_keys(key_event);
+            return Ok(());
+        }
+        if self.command_input.is_some() {
+            self.handle_command_keys(key_event);
+            return Ok(());
+        }
+        if self.state.last() == Some(&AppState::Database)
+            && self
+                .database
+                .as_ref()
+                .is_some_and(|browser| browser.filter_input.is_some())
+        {
+            self.handle_filter_keys(key_event);
+            return Ok(());
+        }
+        if self.state.last() == Some(&AppState::Puzzle)
+            && self
+                .trainer
+                .as_ref()
+                .is_some_and(|trainer| trainer.filter_input.is_some())
+        {
+            self.handle_puzzle_filter_keys(key_event);
+            return Ok(());
+        }
+        if self.state.last() == Some(&AppState::Search)
+            && self
+                .position_search
+                .as_ref()
+                .is_some_and(|search| search.input.is_some())
+        {
+            self.handle_search_keys(key_event);
+            return Ok(());
+        }
+        let Some(&state) = self.state.last() else {
+            return Ok(());
+        };
+        let Some(action) = self.keymap.action(&state, key_event) else {
+            return Ok(());
+        };
+        if self.promotion.is_some() {
+            self.handle_promotion_action(action);
+            return Ok(());
+        }
+        match state {
+            AppState::MainMenu => self.handle_main_menu_action(action),
+            AppState::NewGame => self.handle_new_game_action(action),
+            AppState::Game => self.handle_game_action(action),
+            AppState::Analysis => self.handle_analysis_action(action),
+            AppState::Editor => self.handle_editor_action(action),
+            AppState::Puzzle => self.handle_puzzle_action(action),
+            AppState::Repertoire => self.handle_repertoire_action(action),
+            AppState::Endgame => self.handle_endgame_action(action),
+            AppState::Replay => self.handle_replay_action(action),
+            AppState::Database => self.handle_database_action(action),
+            AppState::Search => self.handle_search_action(action),
+            AppState::Stats => self.handle_stats_action(action),
+            AppState::Settings => self.handle_settings_action(action),
+            _ => {}
+        }
+        if action == Action::Back {
+            self.quit();
         }
-        self.handle_window_keys(key_event)?;
 
         Ok(())
     }
@@ -152,10 +2167,41 @@ impl App {
     ///
     /// The tick event is where you can update the state of your application with any logic that
     /// needs to be updated at a fixed frame rate. E.g. polling a server, updating an animation.
-    pub fn tick(&self) {}
+    pub fn tick(&mut self) {
+        let delay = self.autoplay_delay();
+        if self.state.last() == Some(&AppState::Replay)
+            && let Some(replay) = &mut self.replay
+        {
+            replay.tick(delay);
+        }
+        if self.state.last() == Some(&AppState::Game)
+            && let (Some(game), Some(clock)) = (&mut self.game, &mut self.clock)
+            && !game.is_game_over()
+            && let Some(color) = clock.flagged()
+        {
+            game.flag(color);
+            clock.stop();
+            self.record_game();
+            self.request_report();
+        }
+    }
 
     /// Set running to false to quit the application.
     pub fn quit(&mut self) {
+        if self.state.last() == Some(&AppState::Analysis) {
+            self.analyser = None;
+        }
+        if self.state.last() == Some(&AppState::Endgame) {
+            self.engine_color = None;
+            self.endgame = None;
+        }
+        // A game left unfinished is abandoned: its clock cannot flag, the engine stops playing
+        // and it is not recorded
+        if self.state.last() == Some(&AppState::Game) {
+            self.clock = None;
+            self.pairing = None;
+            self.engine_color = None;
+        }
         if self.state.len() > 1 {
             self.state.pop();
         } else {
@@ -174,3 +2220,110 @@ impl App {
         self.counter = self.counter.saturating_sub(1);
     }
 }
+
+/// The rows of the settings screen, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum SettingsRow {
+    Theme,
+    Glyphs,
+    Pieces,
+    Orientation,
+    Coordinates,
+    Bell,
+    TimeControl,
+    /// The engine path, which is typed rather than cycled like the rows above.
+    Engine,
+    EngineStrength,
+    OpeningBook,
+    Tablebases,
+    KeyBindings,
+    /// One action's keys on one screen, rebound by pressing the new key.
+    Rebind,
+    Puzzles,
+    Repertoire,
+    /// The player's profile name, typed like the paths.
+    Player,
+    Opponent,
+}
+
+impl SettingsRow {
+    /// The row at `index` from the top.
+    pub fn nth(index: usize) -> Option<Self> {
+        Self::iter().nth(index)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SettingsRow::Theme => "Theme",
+            SettingsRow::Glyphs => "Glyphs",
+            SettingsRow::Pieces => "Pieces",
+            SettingsRow::Orientation => "Orientation",
+            SettingsRow::Coordinates => "Coordinates",
+            SettingsRow::Bell => "Bell",
+            SettingsRow::TimeControl => "Time control",
+            SettingsRow::Engine => "Engine",
+            SettingsRow::EngineStrength => "Engine strength",
+            SettingsRow::OpeningBook => "Opening book",
+            SettingsRow::Tablebases => "Tablebases",
+            SettingsRow::KeyBindings => "Key bindings",
+            SettingsRow::Rebind => "Rebind",
+            SettingsRow::Puzzles => "Puzzles",
+            SettingsRow::Repertoire => "Repertoire",
+            SettingsRow::Player => "Player",
+            SettingsRow::Opponent => "Opponent",
+        }
+    }
+
+    /// Whether the row holds a path, typed in full rather than cycled.
+    pub fn is_path(&self) -> bool {
+        matches!(
+            self,
+            SettingsRow::Engine
+                | SettingsRow::OpeningBook
+                | SettingsRow::Tablebases
+                | SettingsRow::Puzzles
+                | SettingsRow::Repertoire
+        )
+    }
+
+    /// Whether the row holds a profile name, also typed.
+    pub fn is_name(&self) -> bool {
+        matches!(self, SettingsRow::Player | SettingsRow::Opponent)
+    }
+}
+
+/// Rings the terminal bell.
+fn ring_bell() {
+    use std::io::Write;
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\x07").and_then(|()| stdout.flush());
+}
+
+/// The longest string every entry of `words` starts with.
+fn common_prefix(words: &[String]) -> Option<String> {
+    let first = words.first()?;
+    let len = words.iter().fold(first.len(), |len, word| {
+        first
+            .bytes()
+            .zip(word.bytes())
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count()
+    });
+    Some(first[..len].to_string())
+}
+
+/// Moves an index by `delta`, staying within `0..len`.
+fn offset_clamped(index: usize, delta: isize, len: usize) -> usize {
+    (index as isize + delta).clamp(0, len as isize - 1) as usize
+}
+
+/// The value `delta` steps away from `current` in `values`, wrapping around at either end.
+fn cycle<T: PartialEq + Copy>(values: impl Iterator<Item = T>, current: T, delta: isize) -> T {
+    let values: Vec<T> = values.collect();
+    let index = values
+        .iter()
+        .position(|&value| value == current)
+        .unwrap_or(0);
+    values[(index as isize + delta).rem_euclid(values.len() as isize) as usize]
+}
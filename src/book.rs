@@ -0,0 +1,203 @@
+use std::cmp::Reverse;
+use std::path::Path;
+
+use color_eyre::eyre::{WrapErr, bail};
+use rand::Rng;
+
+use crate::game::{Game, Move, MoveKind};
+use crate::pieces::PieceType;
+use crate::zobrist;
+
+/// One move of a Polyglot book: the position's key, the move as Polyglot packs it, and how
+/// often to play it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Entry {
+    key: u64,
+    mv: u16,
+    weight: u16,
+}
+
+/// A Polyglot opening book, read whole from a `.bin` file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Book {
+    /// Sorted by key, as in the file.
+    entries: Vec<Entry>,
+}
+
+/// A move the book has for a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookMove {
+    pub mv: Move,
+    pub weight: u16,
+}
+
+impl Book {
+    pub fn open(path: &Path) -> color_eyre::Result<Self> {
+        let bytes =
+            std::fs::read(path).wrap_err_with(|| format!("cannot read {}", path.display()))?;
+        Self::from_bytes(&bytes)
+            .wrap_err_with(|| format!("{} is not a Polyglot book", path.display()))
+    }
+
+    /// Reads the 16-byte big-endian records of a book: key, move, weight and a learning value
+    /// that is ignored.
+    pub fn from_bytes(bytes: &[u8]) -> color_eyre::Result<Self> {
+        if !bytes.len().is_multiple_of(16) {
+            bail!("the size is not a multiple of 16 bytes");
+        }
+        let mut entries: Vec<Entry> = bytes
+            .chunks_exact(16)
+            .map(|record| Entry {
+                key: u64::from_be_bytes(record[..8].try_into().expect("8 bytes")),
+                mv: u16::from_be_bytes([record[8], record[9]]),
+                weight: u16::from_be_bytes([record[10], record[11]]),
+            })
+            .collect();
+        // Books are written sorted, but a stable sort keeps the order of equal keys if not
+        entries.sort_by_key(|entry| entry.key);
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The legal book moves for `game`, most weighted first. Positions without a Polyglot key
+    /// have none.
+    pub fn moves(&self, game: &Game) -> Vec<BookMove> {
+        let Some(key) = zobrist::polyglot(game) else {
+            return Vec::new();
+        };
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let mut moves: Vec<BookMove> = self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .filter_map(|entry| {
+                decode(game, entry.mv).map(|mv| BookMove {
+                    mv,
+                    weight: entry.weight,
+                })
+            })
+            .collect();
+        moves.sort_by_key(|book_move| Reverse(book_move.weight));
+        moves
+    }
+
+    /// Picks a book move at random in proportion to the weights. Moves weighted 0 are never
+    /// played.
+    pub fn choose(&self, game: &Game, rng: &mut impl Rng) -> Option<Move> {
+        let moves = self.moves(game);
+        let total: u32 = moves
+            .iter()
+            .map(|book_move| u32::from(book_move.weight))
+            .sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = rng.random_range(0..total);
+        moves.into_iter().find_map(|book_move| {
+            let weight = u32::from(book_move.weight);
+            if roll < weight {
+                Some(book_move.mv)
+            } else {
+                roll -= weight;
+                None
+            }
+        })
+    }
+}
+
+/// Finds the legal move Polyglot packs as to-square, from-square and promotion in 6, 6 and 3
+/// bits, with squares counted from a1. Castling is written as the king taking its own rook.
+fn decode(game: &Game, packed: u16) -> Option<Move> {
+    let square = |bits: u16| (7 - usize::from((bits >> 3) & 7), usize::from(bits & 7));
+    let to = square(packed & 0x3f);
+    let from = square((packed >> 6) & 0x3f);
+    let promotion = match (packed >> 12) & 7 {
+        0 => None,
+        1 => Some(PieceType::Knight),
+        2 => Some(PieceType::Bishop),
+        3 => Some(PieceType::Rook),
+        4 => Some(PieceType::Queen),
+        _ => return None,
+    };
+    game.legal_moves().into_iter().find(|mv| {
+        mv.from == from
+            && mv.promotion == promotion
+            && match mv.kind {
+                MoveKind::Castle { rook_from, .. } => rook_from == to,
+                _ => mv.to == to,
+            }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::notation;
+    use crate::variant::VariantKind;
+
+    /// A Polyglot record; squares are numbered from a1 = 0.
+    fn record(key: u64, from: u16, to: u16, weight: u16) -> Vec<u8> {
+        let mut bytes = key.to_be_bytes().to_vec();
+        bytes.extend((to | from << 6).to_be_bytes());
+        bytes.extend(weight.to_be_bytes());
+        bytes.extend([0; 4]);
+        bytes
+    }
+
+    #[test]
+    fn reads_and_weighs_book_moves() {
+        let start = 0x463b96181691fc9c;
+        // e2e4, d2d4 and a never-played a2a3
+        let bytes = [
+            record(start, 12, 28, 3),
+            record(start, 11, 27, 1),
+            record(start, 8, 16, 0),
+        ]
+        .concat();
+        let book = Book::from_bytes(&bytes).unwrap();
+        let game = Game::new();
+        let sans: Vec<String> = book
+            .moves(&game)
+            .iter()
+            .map(|book_move| notation::san(&game, &book_move.mv))
+            .collect();
+        assert_eq!(sans, ["e4", "d4", "a3"]);
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut e4 = 0;
+        for _ in 0..400 {
+            let mv = book.choose(&game, &mut rng).unwrap();
+            assert_ne!(notation::san(&game, &mv), "a3");
+            if notation::san(&game, &mv) == "e4" {
+                e4 += 1;
+            }
+        }
+        assert!((250..350).contains(&e4), "{e4}");
+        let mut after_e4 = game.clone();
+        after_e4.make_move(notation::parse_move(&game, "e4").unwrap());
+        assert!(book.moves(&after_e4).is_empty());
+        assert!(Book::from_bytes(&[0; 15]).is_err());
+    }
+
+    #[test]
+    fn decodes_castling_as_king_takes_rook() {
+        let game = Game::from_fen(
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+            VariantKind::Standard,
+        )
+        .unwrap();
+        let short = decode(&game, 7 | 4 << 6).unwrap();
+        assert_eq!(notation::san(&game, &short), "O-O");
+        let long = decode(&game, 4 << 6).unwrap();
+        assert_eq!(notation::san(&game, &long), "O-O-O");
+    }
+}
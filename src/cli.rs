@@ -0,0 +1,481 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use color_eyre::eyre::{WrapErr, bail};
+
+use crate::clock::TimeControl;
+use crate::database::Database;
+use crate::endgame::Endgame;
+use crate::engine::SearchLimits;
+use crate::game::Game;
+use crate::pgn::{self, PgnGame};
+use crate::pieces::Color;
+use crate::record::GameRecord;
+use crate::replay::Replay;
+use crate::report::Report;
+use crate::search::Query;
+use crate::variant::VariantKind;
+
+/// Chess and chess variants in the terminal.
+///
+/// Without a subcommand the game opens at the main menu. Exit status is 0 on success, 1 when a
+/// command fails and 2 for invalid arguments.
+#[derive(Debug, Parser)]
+#[command(name = "chess-tui", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Open the game straight at the board
+    Play(PlayArgs),
+    /// Judge every move of a PGN game with the built-in engine and report each player's accuracy
+    Analyze {
+        /// PGN file, or `-` for standard input
+        pgn: PathBuf,
+        /// Which game of the file to analyse, counting from 1
+        #[arg(long, default_value_t = 1)]
+        game: usize,
+        /// Search depth per position
+        #[arg(long, default_value_t = 4)]
+        depth: u32,
+        /// Also write the game with judgements and `[%eval]` comments as PGN to this file, or
+        /// `-` to print only the PGN
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Step through a PGN or JSON game with its comments and variations
+    Replay {
+        /// PGN or JSON game file, or `-` for standard input
+        file: PathBuf,
+        /// Which game of a PGN file to open, counting from 1
+        #[arg(long, default_value_t = 1)]
+        game: usize,
+        /// Print every position of the main line instead of opening the viewer
+        #[arg(long)]
+        print: bool,
+    },
+    /// List the games of a PGN file to sort, filter and open them
+    Browse {
+        /// PGN file
+        pgn: PathBuf,
+    },
+    /// Solve tactics puzzles from a CSV file in the Lichess puzzle database format
+    Puzzles {
+        /// Puzzle CSV file; the one chosen in Settings when left out
+        csv: Option<PathBuf>,
+        /// Themes and a rating range to train on, such as "fork rating:1200-1800"
+        #[arg(long, default_value = "")]
+        filter: String,
+    },
+    /// Drill the lines of an opening repertoire kept as PGN with variations
+    Repertoire {
+        /// Repertoire PGN file; the one chosen in Settings when left out
+        pgn: Option<PathBuf>,
+        /// Side to train; by default the one whose moves never branch
+        #[arg(long, value_enum)]
+        color: Option<Side>,
+    },
+    /// Convert or hold random positions of an ending against the engine
+    Endgame {
+        /// Material with the side to win first, such as KRvK or KBNvK, or Lucena or Philidor
+        #[arg(default_value = "KQvK")]
+        ending: Endgame,
+    },
+    /// List the games of a PGN file that reach a position or material balance
+    Search {
+        /// PGN file
+        pgn: PathBuf,
+        /// A FEN, a Polyglot key such as 0x463b96181691fc9c, or material such as KRvKB or "R vs B"
+        query: String,
+    },
+    /// Count the move paths from a position, to check move generation
+    Perft {
+        depth: u32,
+        /// Position to start from; the variant's starting position by default
+        #[arg(long)]
+        fen: Option<String>,
+        #[arg(long, default_value = "standard")]
+        variant: VariantKind,
+        /// Also print the count below each legal move
+        #[arg(long)]
+        divide: bool,
+    },
+    /// Run the built-in engine as a UCI engine on standard input and output
+    Uci,
+    /// Host a game over TCP that clients play with one command per line
+    Serve {
+        #[arg(long, default_value_t = 7878)]
+        port: u16,
+        #[arg(long, default_value = "standard")]
+        variant: VariantKind,
+        #[arg(long)]
+        fen: Option<String>,
+    },
+    /// Convert a game or position between FEN, PGN and JSON
+    Convert {
+        /// Input file, or `-` for standard input
+        input: PathBuf,
+        /// Output format
+        #[arg(long)]
+        to: Format,
+        /// Input format; guessed from the file extension or contents when omitted
+        #[arg(long)]
+        from: Option<Format>,
+        /// Variant of a FEN input
+        #[arg(long, default_value = "standard")]
+        variant: VariantKind,
+    },
+}
+
+#[derive(Debug, Default, Args)]
+pub struct PlayArgs {
+    /// Start from this position
+    #[arg(long, conflicts_with = "pgn")]
+    pub fen: Option<String>,
+    /// Continue from the end of the first game in this PGN file
+    #[arg(long)]
+    pub pgn: Option<PathBuf>,
+    #[arg(long)]
+    pub variant: Option<VariantKind>,
+    /// Clock such as `5+3` (minutes + seconds of increment), replacing the saved setting
+    #[arg(long)]
+    pub time: Option<TimeControl>,
+    /// Play the built-in engine, which takes the given side
+    #[arg(long, value_name = "SIDE", num_args = 0..=1, default_missing_value = "black")]
+    pub vs_engine: Option<Side>,
+}
+
+impl PlayArgs {
+    /// The game these options ask for, or `None` to start at the main menu.
+    pub fn game(&self) -> color_eyre::Result<Option<Game>> {
+        let variant = self.variant.unwrap_or_default();
+        if let Some(fen) = &self.fen {
+            return Ok(Some(Game::from_fen(fen, variant)?));
+        }
+        if let Some(path) = &self.pgn {
+            let mut game = PgnGame::parse(&read_input(path)?)?;
+            if let Some(variant) = self.variant {
+                game.set_tag("Variant", variant.name());
+            }
+            return Ok(Some(game.mainline()?));
+        }
+        let wants_board = self.variant.is_some() || self.vs_engine.is_some() || self.time.is_some();
+        Ok(wants_board.then(|| Game::with_variant(variant)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Side {
+    White,
+    Black,
+}
+
+impl From<Side> for Color {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::White => Color::White,
+            Side::Black => Color::Black,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Fen,
+    Pgn,
+    Json,
+}
+
+impl Format {
+    /// Guesses the format of `text` read from `path`.
+    fn detect(path: &Path, text: &str) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("pgn") => return Format::Pgn,
+            Some("json") => return Format::Json,
+            Some("fen") => return Format::Fen,
+            _ => {}
+        }
+        let text = text.trim_start();
+        if text.starts_with(['{', '[']) && serde_json::from_str::<serde_json::Value>(text).is_ok() {
+            Format::Json
+        } else if text.starts_with('[') || text.starts_with("1.") {
+            Format::Pgn
+        } else {
+            Format::Fen
+        }
+    }
+}
+
+/// Reads a file, or standard input for `-`.
+pub fn read_input(path: &Path) -> color_eyre::Result<String> {
+    if path == Path::new("-") {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        return Ok(text);
+    }
+    std::fs::read_to_string(path).wrap_err_with(|| format!("cannot read {}", path.display()))
+}
+
+/// Reads game `number`, counting from 1, of a PGN file or the game in a JSON record.
+pub fn load_game(path: &Path, number: usize) -> color_eyre::Result<PgnGame> {
+    let text = read_input(path)?;
+    match Format::detect(path, &text) {
+        Format::Json => serde_json::from_str::<GameRecord>(&text)?.to_pgn(),
+        Format::Pgn => match pgn::PgnReader::new(text.as_bytes()).nth(number.saturating_sub(1)) {
+            Some(game) => game,
+            None => bail!("{} has no game {number}", path.display()),
+        },
+        Format::Fen => bail!("{} holds a position, not a game", path.display()),
+    }
+}
+
+/// Prints each position of the main line with the move that led to it and its comments.
+pub fn print_replay(pgn_game: PgnGame) -> color_eyre::Result<()> {
+    let mut replay = Replay::new(pgn_game)?;
+    loop {
+        let heading = replay
+            .current_move_label()
+            .unwrap_or_else(|| "Starting position".to_string());
+        let comments = match replay.current_move() {
+            Some(pgn_move) => pgn_move.comments.clone(),
+            None => replay.pgn().comments.clone(),
+        };
+        println!("{heading}");
+        for comment in comments {
+            println!("  {comment}");
+        }
+        replay.game().get_board().pretty_print();
+        println!();
+        if !replay.forward() {
+            break;
+        }
+    }
+    if let Some(error) = replay.error() {
+        bail!("{error}");
+    }
+    println!("{}", replay.pgn().result);
+    Ok(())
+}
+
+/// Prints each game of `pgn` that reaches a position `query` matches, with the ply it gets
+/// there, and how many games matched.
+pub fn search(pgn: &Path, query: &str) -> color_eyre::Result<()> {
+    let query: Query = query.parse()?;
+    let database = Database::open(pgn)?;
+    let hits = database.search(&query)?;
+    for (index, found) in &hits {
+        let header = &database.games()[*index];
+        let reached = match &found.label {
+            Some(label) => format!("ply {} after {label}", found.ply),
+            None => "starting position".to_string(),
+        };
+        println!(
+            "{:>5}  {} - {}  {} {}  {} ({reached})",
+            header.number, header.white, header.black, header.event, header.date, header.result
+        );
+    }
+    println!(
+        "{} of {} games reach {query}",
+        hits.len(),
+        database.games().len()
+    );
+    Ok(())
+}
+
+/// Prints every move of the main line with the engine's evaluation after it from White's point
+/// of view, its judgement and the move preferred, then each player's accuracy. The annotated
+/// game goes to `output` as PGN, replacing the table when it is `-`.
+pub fn analyze(
+    pgn: &Path,
+    game_number: usize,
+    depth: u32,
+    output: Option<&Path>,
+) -> color_eyre::Result<()> {
+    let mut pgn_game = load_game(pgn, game_number)?;
+    let game = pgn_game.mainline()?;
+    let to_stdout = output == Some(Path::new("-"));
+    let report = Report::new(&game, &SearchLimits::depth(depth), |done, total| {
+        if to_stdout {
+            eprint!("\rAnalysing position {done} of {total}");
+        }
+    })?;
+    if !to_stdout {
+        for report in &report.moves {
+            let glyph = report
+                .judgement
+                .nag()
+                .and_then(pgn::nag_symbol)
+                .unwrap_or("");
+            let note = report
+                .best
+                .as_ref()
+                .map(|best| format!("best {best}"))
+                .unwrap_or_default();
+            println!(
+                "{:<14} {:>7} {glyph:<2} {note}",
+                report.label,
+                report.eval.to_string()
+            );
+        }
+        println!("{}", pgn_game.result);
+        println!("White: {}", report.white);
+        println!("Black: {}", report.black);
+    }
+    if let Some(output) = output {
+        report.annotate(&mut pgn_game);
+        if to_stdout {
+            eprintln!();
+            print!("{pgn_game}");
+        } else {
+            std::fs::write(output, pgn_game.to_string())
+                .wrap_err_with(|| format!("cannot write {}", output.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints the perft count, optionally split by first move.
+pub fn perft(
+    fen: Option<&str>,
+    variant: VariantKind,
+    depth: u32,
+    divide: bool,
+) -> color_eyre::Result<()> {
+    let game = match fen {
+        Some(fen) => Game::from_fen(fen, variant)?,
+        None => Game::with_variant(variant),
+    };
+    if divide && depth > 0 {
+        let mut total = 0;
+        for mv in game.legal_moves() {
+            let mut child = game.clone();
+            child.make_move(mv);
+            let nodes = child.perft(depth - 1);
+            println!("{}: {nodes}", mv.to_uci(game.get_board()));
+            total += nodes;
+        }
+        println!();
+        println!("{total}");
+    } else {
+        println!("{}", game.perft(depth));
+    }
+    Ok(())
+}
+
+/// Converts `text` between formats. PGN input may hold several games, which become one FEN per
+/// line, a PGN database or a JSON array.
+pub fn convert(
+    text: &str,
+    from: Format,
+    to: Format,
+    variant: VariantKind,
+) -> color_eyre::Result<String> {
+    let records: Vec<GameRecord> = match from {
+        Format::Fen => vec![GameRecord::from_game(&Game::from_fen(
+            text.trim(),
+            variant,
+        )?)],
+        Format::Pgn => pgn::parse_all(text)?
+            .iter()
+            .map(GameRecord::from_pgn)
+            .collect::<color_eyre::Result<_>>()?,
+        Format::Json => match serde_json::from_str::<Vec<GameRecord>>(text) {
+            Ok(records) => records,
+            Err(_) => vec![serde_json::from_str(text)?],
+        },
+    };
+    if records.is_empty() {
+        bail!("no game found in the input");
+    }
+
+    Ok(match to {
+        Format::Fen => records
+            .iter()
+            .map(|record| record.fen.clone() + "\n")
+            .collect(),
+        Format::Pgn => records
+            .iter()
+            .map(|record| Ok(record.to_pgn()?.to_string()))
+            .collect::<color_eyre::Result<Vec<String>>>()?
+            .join("\n"),
+        Format::Json if records.len() == 1 => serde_json::to_string_pretty(&records[0])? + "\n",
+        Format::Json => serde_json::to_string_pretty(&records)? + "\n",
+    })
+}
+
+/// Runs `chess-tui convert`, printing the result.
+pub fn run_convert(
+    input: &Path,
+    from: Option<Format>,
+    to: Format,
+    variant: VariantKind,
+) -> color_eyre::Result<()> {
+    let text = read_input(input)?;
+    let from = from.unwrap_or_else(|| Format::detect(input, &text));
+    print!("{}", convert(&text, from, to, variant)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_subcommands() {
+        let cli =
+            Cli::try_parse_from(["chess-tui", "play", "--variant", "koth", "--vs-engine"]).unwrap();
+        let Some(Command::Play(args)) = cli.command else {
+            panic!("expected play");
+        };
+        assert_eq!(args.variant, Some(VariantKind::KingOfTheHill));
+        assert_eq!(args.vs_engine, Some(Side::Black));
+        assert!(args.game().unwrap().is_some());
+
+        let cli = Cli::try_parse_from(["chess-tui", "play", "--time", "3+2"]).unwrap();
+        let Some(Command::Play(args)) = cli.command else {
+            panic!("expected play");
+        };
+        assert_eq!(args.time, Some(TimeControl::new(3, 2)));
+
+        let err = Cli::try_parse_from(["chess-tui", "perft", "--variant", "nope", "3"]);
+        assert_eq!(err.unwrap_err().exit_code(), 2);
+        assert!(
+            Cli::try_parse_from(["chess-tui"])
+                .unwrap()
+                .command
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn converts_between_formats() {
+        let pgn = "[Event \"Test\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n";
+        let fen = convert(pgn, Format::Pgn, Format::Fen, VariantKind::Standard).unwrap();
+        assert_eq!(
+            fen.trim(),
+            "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3"
+        );
+
+        let json = convert(pgn, Format::Pgn, Format::Json, VariantKind::Standard).unwrap();
+        assert_eq!(Format::detect(Path::new("-"), &json), Format::Json);
+        let back = convert(&json, Format::Json, Format::Pgn, VariantKind::Standard).unwrap();
+        assert!(back.contains("[Event \"Test\"]"), "{back}");
+        assert!(back.contains("2. g4 Qh4#"), "{back}");
+
+        let from_fen = convert(&fen, Format::Fen, Format::Pgn, VariantKind::Standard).unwrap();
+        assert!(from_fen.contains("[SetUp \"1\"]"), "{from_fen}");
+        assert!(
+            convert(
+                "not a fen",
+                Format::Fen,
+                Format::Json,
+                VariantKind::Standard
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::{OptionExt, eyre};
+
+use crate::pieces::Color;
+
+/// Starting time per side and the increment added after each move, written `minutes+seconds`
+/// as in `5+3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+impl TimeControl {
+    /// Controls offered in the settings screen, fastest first.
+    pub const PRESETS: [TimeControl; 9] = [
+        Self::new(1, 0),
+        Self::new(2, 1),
+        Self::new(3, 0),
+        Self::new(3, 2),
+        Self::new(5, 0),
+        Self::new(5, 3),
+        Self::new(10, 0),
+        Self::new(15, 10),
+        Self::new(30, 0),
+    ];
+
+    pub const fn new(minutes: u64, increment_seconds: u64) -> Self {
+        Self {
+            base: Duration::from_secs(minutes * 60),
+            increment: Duration::from_secs(increment_seconds),
+        }
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.base.as_secs();
+        if seconds.is_multiple_of(60) {
+            write!(f, "{}", seconds / 60)?;
+        } else {
+            write!(f, "{}", seconds as f64 / 60.0)?;
+        }
+        write!(f, "+{}", self.increment.as_secs())
+    }
+}
+
+impl FromStr for TimeControl {
+    type Err = color_eyre::Report;
+
+    /// Parses `minutes+seconds`; the minutes may be fractional (`0.5+0`) and a missing increment
+    /// means none.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (minutes, increment) = s.trim().split_once('+').unwrap_or((s.trim(), "0"));
+        let minutes: f64 = minutes
+            .parse()
+            .map_err(|_| eyre!("invalid time control minutes: {minutes}"))?;
+        let increment: u64 = increment
+            .parse()
+            .map_err(|_| eyre!("invalid time control increment: {increment}"))?;
+        let base = Duration::try_from_secs_f64(minutes * 60.0)
+            .ok()
+            .filter(|base| !base.is_zero())
+            .ok_or_eyre("time control needs a positive base time")?;
+        Ok(Self {
+            base,
+            increment: Duration::from_secs(increment),
+        })
+    }
+}
+
+impl TryFrom<String> for TimeControl {
+    type Error = color_eyre::Report;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<TimeControl> for String {
+    fn from(control: TimeControl) -> Self {
+        control.to_string()
+    }
+}
+
+/// A two-sided chess clock. Only the side to move loses time.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    control: TimeControl,
+    /// Time left per colour as of the last press.
+    remaining: [Duration; 2],
+    /// The side whose time is running and when it last started.
+    running: Option<(Color, Instant)>,
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        Self {
+            control,
+            remaining: [control.base; 2],
+            running: None,
+        }
+    }
+
+    pub fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    /// Time left for `color`, including the time spent on the current move.
+    pub fn remaining(&self, color: Color) -> Duration {
+        let left = self.remaining[color as usize];
+        match self.running {
+            Some((side, since)) if side == color => left.saturating_sub(since.elapsed()),
+            _ => left,
+        }
+    }
+
+    /// Starts counting down for `color`.
+    pub fn start(&mut self, color: Color) {
+        self.stop();
+        self.running = Some((color, Instant::now()));
+    }
+
+    /// Stops the running side's time without adding an increment.
+    pub fn stop(&mut self) {
+        if let Some((side, _)) = self.running {
+            self.remaining[side as usize] = self.remaining(side);
+            self.running = None;
+        }
+    }
+
+    /// Ends `mover`'s turn: their time stops, the increment is added and the opponent's time
+    /// starts.
+    pub fn press(&mut self, mover: Color) {
+        self.stop();
+        self.remaining[mover as usize] += self.control.increment;
+        self.start(mover.opposite());
+    }
+
+    /// The side that has run out of time, if any.
+    pub fn flagged(&self) -> Option<Color> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .find(|&color| self.remaining(color).is_zero())
+    }
+}
+
+/// Formats a clock reading as `m:ss`, with tenths once under ten seconds.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds < 10 {
+        format!("0:{seconds:02}.{}", duration.subsec_millis() / 100)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
@@ -0,0 +1,185 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::clock::TimeControl;
+use crate::keymap::{KeyOverrides, KeymapPreset};
+use crate::theme::{GlyphSet, PieceStyle, Theme};
+
+/// Where progress such as the puzzle rating and repertoire statistics is saved:
+/// `$XDG_DATA_HOME/chess-tui` on Linux.
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("chess-tui"))
+}
+
+/// User preferences, kept as TOML in `$XDG_CONFIG_HOME/chess-tui/config.toml` on Linux.
+///
+/// Missing fields take their default, so older files keep loading as settings are added.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Name of a built-in or user theme.
+    pub theme: String,
+    pub glyph_set: GlyphSet,
+    pub piece_style: PieceStyle,
+    /// Start games with black at the bottom.
+    pub flip_board: bool,
+    /// Draw file and rank labels around the board.
+    pub show_coordinates: bool,
+    /// Ring the terminal bell after every move.
+    pub bell: bool,
+    /// Clock for new games; `None` plays untimed.
+    pub time_control: Option<TimeControl>,
+    /// Milliseconds between moves while a replay plays itself.
+    pub autoplay_delay_ms: u64,
+    pub engine: EngineConfig,
+    /// A CSV file of puzzles in the Lichess puzzle database format.
+    pub puzzles: Option<PathBuf>,
+    /// A PGN file of opening lines, with variations, to drill.
+    pub repertoire: Option<PathBuf>,
+    /// Profile of whoever sits at the bottom of the board.
+    pub player: String,
+    /// Profile of the other side in games between two people.
+    pub opponent: String,
+    /// Extra keys layered over the default bindings.
+    pub keymap: KeymapPreset,
+    /// Keys per screen and action, replacing the default keys of each action listed, e.g.
+    /// `[key_bindings.game]` with `flip = ["F", "ctrl-r"]`.
+    pub key_bindings: KeyOverrides,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            theme: Theme::default().name,
+            glyph_set: GlyphSet::default(),
+            piece_style: PieceStyle::default(),
+            flip_board: false,
+            show_coordinates: true,
+            bell: false,
+            time_control: None,
+            autoplay_delay_ms: 1000,
+            engine: EngineConfig::default(),
+            puzzles: None,
+            repertoire: None,
+            player: "Player".to_string(),
+            opponent: "Guest".to_string(),
+            keymap: KeymapPreset::default(),
+            key_bindings: KeyOverrides::new(),
+        }
+    }
+}
+
+/// The computer opponent.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct EngineConfig {
+    /// A UCI engine executable; the built-in engine plays when unset.
+    pub path: Option<PathBuf>,
+    /// Playing strength from 1 to [`EngineConfig::MAX_STRENGTH`].
+    pub strength: u8,
+    /// A Polyglot `.bin` book the built-in engine plays its opening moves from.
+    pub book: Option<PathBuf>,
+    /// A directory of Syzygy `.rtbw` and `.rtbz` tables the built-in engine probes.
+    pub tablebases: Option<PathBuf>,
+}
+
+impl EngineConfig {
+    pub const MAX_STRENGTH: u8 = 20;
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            strength: 10,
+            book: None,
+            tablebases: None,
+        }
+    }
+}
+
+impl Config {
+    /// Where the config file lives, if the platform has a config directory.
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("chess-tui").join("config.toml"))
+    }
+
+    /// Reads the user's config file, or the defaults when there is none yet.
+    pub fn load() -> color_eyre::Result<Self> {
+        match Self::path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> color_eyre::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(toml::from_str(&text)?),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Writes the config to `path`, creating its directory as needed.
+    pub fn save_to(&self, path: &Path) -> color_eyre::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::keymap::{Action, Keys, Scope};
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let path = std::env::temp_dir()
+            .join(format!("chess-tui-config-{}", std::process::id()))
+            .join("config.toml");
+        let config = Config {
+            theme: "monochrome".to_string(),
+            glyph_set: GlyphSet::Ascii,
+            piece_style: PieceStyle::Outlined,
+            flip_board: true,
+            show_coordinates: false,
+            bell: true,
+            time_control: Some(TimeControl::new(5, 3)),
+            autoplay_delay_ms: 400,
+            engine: EngineConfig {
+                path: Some(PathBuf::from("/usr/bin/stockfish")),
+                strength: 3,
+                book: Some(PathBuf::from("/usr/share/books/performance.bin")),
+                tablebases: Some(PathBuf::from("/usr/share/syzygy")),
+            },
+            puzzles: Some(PathBuf::from("/usr/share/lichess_db_puzzle.csv")),
+            repertoire: Some(PathBuf::from("/home/user/repertoire.pgn")),
+            player: "Ada".to_string(),
+            opponent: "Bob".to_string(),
+            keymap: KeymapPreset::Vim,
+            key_bindings: BTreeMap::from([(
+                Scope::Game,
+                BTreeMap::from([(Action::Flip, Keys::Many(vec!["F".into(), "ctrl-r".into()]))]),
+            )]),
+        };
+        config.save_to(&path).unwrap();
+        assert_eq!(Config::load_from(&path).unwrap(), config);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn missing_file_and_fields_use_defaults() {
+        let missing = std::env::temp_dir().join("chess-tui-no-such-dir/config.toml");
+        assert_eq!(Config::load_from(&missing).unwrap(), Config::default());
+
+        let partial: Config = toml::from_str("bell = true\ntime_control = \"3+2\"").unwrap();
+        assert!(partial.bell);
+        assert_eq!(partial.time_control, Some(TimeControl::new(3, 2)));
+        assert_eq!(partial.engine, EngineConfig::default());
+    }
+}
@@ -0,0 +1,456 @@
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{WrapErr, bail};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use crate::pgn::{self, PgnGame, PgnReader};
+use crate::search::{self, Match, Query};
+
+/// The tags of one game in a PGN file and where the game starts, so the file can be browsed
+/// without keeping any movetext in memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameHeader {
+    /// Position of the game in the file, counting from 1.
+    pub number: usize,
+    /// Byte offset of the game's first line.
+    pub offset: u64,
+    /// Length of the game's text in bytes.
+    pub len: u64,
+    pub white: String,
+    pub black: String,
+    pub event: String,
+    pub date: String,
+    pub result: String,
+    pub eco: String,
+    pub opening: String,
+    /// Moves in the main line.
+    pub plies: usize,
+}
+
+impl GameHeader {
+    fn from_game(game: &PgnGame, number: usize, offset: u64, len: u64) -> Self {
+        let tag = |name| game.tag(name).unwrap_or("?").to_string();
+        Self {
+            number,
+            offset,
+            len,
+            white: tag("White"),
+            black: tag("Black"),
+            event: tag("Event"),
+            date: tag("Date"),
+            result: game.result.clone(),
+            eco: game.tag("ECO").unwrap_or_default().to_string(),
+            opening: game.tag("Opening").unwrap_or_default().to_string(),
+            plies: game.moves.len(),
+        }
+    }
+
+    /// The value shown in `column`.
+    pub fn field(&self, column: Column) -> String {
+        match column {
+            Column::White => self.white.clone(),
+            Column::Black => self.black.clone(),
+            Column::Event => self.event.clone(),
+            Column::Date => self.date.clone(),
+            Column::Result => self.result.clone(),
+            Column::Eco => self.eco.clone(),
+            Column::Plies => self.plies.to_string(),
+        }
+    }
+
+    fn compare(&self, other: &Self, column: Column) -> Ordering {
+        match column {
+            Column::Plies => self.plies.cmp(&other.plies),
+            column => self.field(column).cmp(&other.field(column)),
+        }
+    }
+}
+
+/// A column of the game table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter)]
+pub enum Column {
+    White,
+    Black,
+    Event,
+    #[default]
+    Date,
+    Result,
+    Eco,
+    Plies,
+}
+
+impl Column {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::White => "White",
+            Column::Black => "Black",
+            Column::Event => "Event",
+            Column::Date => "Date",
+            Column::Result => "Result",
+            Column::Eco => "ECO",
+            Column::Plies => "Plies",
+        }
+    }
+}
+
+/// Which games to list, parsed from terms such as `player:carlsen result:1-0 eco:C6`. Words
+/// without a field match players, the event or the opening. Matching ignores case.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    pub player: Vec<String>,
+    pub result: Option<String>,
+    /// An ECO code prefix or part of the opening name.
+    pub opening: Vec<String>,
+    pub text: Vec<String>,
+}
+
+impl Filter {
+    pub fn parse(query: &str) -> color_eyre::Result<Self> {
+        let mut filter = Self::default();
+        for term in query.split_whitespace() {
+            let lower = term.to_lowercase();
+            match lower.split_once(':') {
+                Some(("player" | "p" | "white" | "black", value)) => {
+                    filter.player.push(value.to_string())
+                }
+                Some(("result" | "r", value)) => {
+                    filter.result = Some(
+                        match value {
+                            "1-0" | "white" | "w" => "1-0",
+                            "0-1" | "black" | "b" => "0-1",
+                            "1/2-1/2" | "1/2" | "draw" | "d" => "1/2-1/2",
+                            "*" | "unfinished" => "*",
+                            _ => bail!("unknown result `{value}`: use 1-0, 0-1, draw or *"),
+                        }
+                        .to_string(),
+                    )
+                }
+                Some(("eco" | "opening" | "o", value)) => filter.opening.push(value.to_string()),
+                Some((field, _)) => {
+                    bail!("unknown filter `{field}`: use player, result, eco or opening")
+                }
+                None => filter.text.push(lower),
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn matches(&self, header: &GameHeader) -> bool {
+        let contains = |field: &str, value: &str| field.to_lowercase().contains(value);
+        let player =
+            |value: &String| contains(&header.white, value) || contains(&header.black, value);
+        let opening = |value: &String| {
+            header.eco.to_lowercase().starts_with(value.as_str())
+                || contains(&header.opening, value)
+        };
+        self.player.iter().all(player)
+            && self
+                .result
+                .as_ref()
+                .is_none_or(|result| header.result == *result)
+            && self.opening.iter().all(opening)
+            && self
+                .text
+                .iter()
+                .all(|word| player(word) || opening(word) || contains(&header.event, word))
+    }
+}
+
+/// A PGN file indexed by game, reading each game from disk when it is opened.
+#[derive(Debug, Clone)]
+pub struct Database {
+    path: PathBuf,
+    games: Vec<GameHeader>,
+    /// Games that could not be read, with the reason.
+    errors: Vec<String>,
+}
+
+impl Database {
+    /// Scans `path` once, keeping only the tags and position of each game.
+    pub fn open(path: &Path) -> color_eyre::Result<Self> {
+        let file = File::open(path).wrap_err_with(|| format!("cannot open {}", path.display()))?;
+        let mut reader = PgnReader::new(BufReader::new(file));
+        let mut games = Vec::new();
+        let mut errors = Vec::new();
+        while let Some((offset, len, text)) = reader.next_chunk()? {
+            let number = games.len() + errors.len() + 1;
+            match pgn::parse_game(&text) {
+                Ok(game) => games.push(GameHeader::from_game(&game, number, offset, len)),
+                Err(err) => errors.push(format!("game {number}: {err}")),
+            }
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            games,
+            errors,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn games(&self) -> &[GameHeader] {
+        &self.games
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// Reads the full game behind `header` from the file.
+    pub fn load(&self, header: &GameHeader) -> color_eyre::Result<PgnGame> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(header.offset))?;
+        let mut bytes = Vec::new();
+        file.take(header.len).read_to_end(&mut bytes)?;
+        pgn::parse_game(&String::from_utf8_lossy(&bytes))
+    }
+
+    /// Reads every game again and returns the index of each one that reaches a position
+    /// `query` matches, with where it does. Games with illegal moves only match before them.
+    pub fn search(&self, query: &Query) -> color_eyre::Result<Vec<(usize, Match)>> {
+        let file = File::open(&self.path)?;
+        let mut reader = PgnReader::new(BufReader::new(file));
+        let mut hits = Vec::new();
+        while let Some((offset, _, text)) = reader.next_chunk()? {
+            // Games that failed to index have no header to report
+            let Ok(index) = self.games.binary_search_by_key(&offset, |game| game.offset) else {
+                continue;
+            };
+            let Ok(game) = pgn::parse_game(&text) else {
+                continue;
+            };
+            if let Ok(Some(found)) = search::find(&game, query) {
+                hits.push((index, found));
+            }
+        }
+        Ok(hits)
+    }
+
+    /// Indices into [`Database::games`] of the games `filter` keeps, ordered by `column`.
+    pub fn query(&self, filter: &Filter, column: Column, descending: bool) -> Vec<usize> {
+        let mut rows: Vec<usize> = (0..self.games.len())
+            .filter(|&i| filter.matches(&self.games[i]))
+            .collect();
+        rows.sort_by(|&a, &b| {
+            let order = self.games[a].compare(&self.games[b], column);
+            if descending { order.reverse() } else { order }
+        });
+        rows
+    }
+}
+
+/// The game table: a database with the filter, ordering and selection being shown.
+#[derive(Debug, Clone)]
+pub struct Browser {
+    pub database: Database,
+    /// The filter as typed.
+    pub query: String,
+    filter: Filter,
+    pub column: Column,
+    pub descending: bool,
+    /// Indices into the database's games, in display order.
+    rows: Vec<usize>,
+    /// Position of the highlighted row in `rows`.
+    pub selected: usize,
+    /// Filter being edited, while the filter bar is open.
+    pub filter_input: Option<String>,
+    /// Why the last filter was rejected or the last game could not be opened.
+    pub error: Option<String>,
+    /// Rows that fit on screen when the table was last drawn.
+    pub page: usize,
+}
+
+impl Browser {
+    pub fn new(database: Database) -> Self {
+        let mut browser = Self {
+            database,
+            query: String::new(),
+            filter: Filter::default(),
+            column: Column::default(),
+            descending: false,
+            rows: Vec::new(),
+            selected: 0,
+            filter_input: None,
+            error: None,
+            page: 10,
+        };
+        browser.refresh();
+        browser
+    }
+
+    /// The listed games in display order.
+    pub fn rows(&self) -> impl Iterator<Item = &GameHeader> {
+        self.rows.iter().map(|&i| &self.database.games()[i])
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn selected_header(&self) -> Option<&GameHeader> {
+        self.rows
+            .get(self.selected)
+            .map(|&i| &self.database.games()[i])
+    }
+
+    /// Moves the highlight by `delta` rows, stopping at either end.
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.rows.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+    }
+
+    /// Sorts by the next or previous column.
+    pub fn cycle_column(&mut self, delta: isize) {
+        let columns: Vec<Column> = Column::iter().collect();
+        let index = columns.iter().position(|&c| c == self.column).unwrap_or(0) as isize;
+        self.column = columns[(index + delta).rem_euclid(columns.len() as isize) as usize];
+        self.refresh();
+    }
+
+    pub fn reverse(&mut self) {
+        self.descending = !self.descending;
+        self.refresh();
+    }
+
+    /// Applies a typed filter, keeping the old one if it does not parse.
+    pub fn set_query(&mut self, query: &str) -> color_eyre::Result<()> {
+        self.filter = Filter::parse(query)?;
+        self.query = query.trim().to_string();
+        self.refresh();
+        Ok(())
+    }
+
+    /// Recomputes the rows, keeping the highlighted game when it is still listed.
+    fn refresh(&mut self) {
+        let selected = self.rows.get(self.selected).copied();
+        self.rows = self
+            .database
+            .query(&self.filter, self.column, self.descending);
+        self.selected = selected
+            .and_then(|game| self.rows.iter().position(|&row| row == game))
+            .unwrap_or(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAMES: &str = r#"[Event "Rapid"]
+[White "Carlsen, Magnus"]
+[Black "Nakamura, Hikaru"]
+[Date "2023.01.02"]
+[Result "1-0"]
+[ECO "C65"]
+[Opening "Ruy Lopez: Berlin Defense"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 Nf6 1-0
+
+[Event "Blitz"]
+[White "Nakamura, Hikaru"]
+[Black "Caruana, Fabiano"]
+[Date "2022.05.06"]
+[Result "1/2-1/2"]
+[ECO "D37"]
+
+1. d4 d5 1/2-1/2
+"#;
+
+    /// Indexes [`GAMES`] from a file named after the test, so tests can run in parallel.
+    fn database(test: &str) -> Database {
+        let path =
+            std::env::temp_dir().join(format!("chess-tui-{test}-{}.pgn", std::process::id()));
+        std::fs::write(&path, GAMES).unwrap();
+        let database = Database::open(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        database
+    }
+
+    #[test]
+    fn indexes_and_loads_games() {
+        let path = std::env::temp_dir().join(format!("chess-tui-load-{}.pgn", std::process::id()));
+        std::fs::write(&path, GAMES).unwrap();
+        let database = Database::open(&path).unwrap();
+        assert_eq!(database.games().len(), 2);
+        let second = &database.games()[1];
+        assert_eq!(second.white, "Nakamura, Hikaru");
+        assert_eq!(second.plies, 2);
+        let game = database.load(second).unwrap();
+        assert_eq!(game.tag("Event"), Some("Blitz"));
+        assert_eq!(game.moves[1].san, "d5");
+
+        let query = "rnbqkbnr/ppp1pppp/8/3p4/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 2";
+        let hits = database.search(&query.parse().unwrap()).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].0, hits[0].1.ply), (1, 2));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn filters_and_sorts() {
+        let database = database("filters");
+        let all = Filter::default();
+        assert_eq!(database.query(&all, Column::Date, false), [1, 0]);
+        assert_eq!(database.query(&all, Column::Plies, true), [0, 1]);
+
+        let query = |text| database.query(&Filter::parse(text).unwrap(), Column::Date, false);
+        assert_eq!(query("player:nakamura"), [1, 0]);
+        assert_eq!(query("player:carlsen result:draw"), Vec::<usize>::new());
+        assert_eq!(query("eco:c6"), [0]);
+        assert_eq!(query("berlin"), [0]);
+        assert_eq!(query("caruana"), [1]);
+        assert!(Filter::parse("colour:white").is_err());
+    }
+
+    #[test]
+    fn browser_keeps_the_selection_across_sorting() {
+        let mut browser = Browser::new(database("browser"));
+        assert_eq!(browser.selected_header().unwrap().event, "Blitz");
+        browser.move_selection(5);
+        assert_eq!(browser.selected_header().unwrap().event, "Rapid");
+        browser.reverse();
+        assert_eq!(browser.selected, 0);
+        assert_eq!(browser.selected_header().unwrap().event, "Rapid");
+        browser.set_query("result:draw").unwrap();
+        assert_eq!(browser.len(), 1);
+        assert!(browser.set_query("result:maybe").is_err());
+        assert_eq!(browser.query, "result:draw");
+    }
+
+    #[test]
+    fn latin1_games_are_indexed_by_their_bytes_on_disk() {
+        // "Jos\xe9" in Latin-1 becomes a three-byte replacement character once read as UTF-8
+        let mut bytes = b"[Event \"One\"]\n[White \"Jos".to_vec();
+        bytes.extend([0xE9; 24]);
+        bytes.extend(b"\"]\n\n1. e4 e5 *\n\n[Event \"Two\"]\n[White \"B\"]\n\n1. d4 d5 *\n");
+        let path =
+            std::env::temp_dir().join(format!("chess-tui-latin1-{}.pgn", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let database = Database::open(&path).unwrap();
+        let [first, second] = database.games() else {
+            panic!("expected two games, got {:?}", database.games());
+        };
+        assert_eq!(first.offset + first.len, second.offset);
+        let sans = |header| {
+            let game = database.load(header).unwrap();
+            game.moves
+                .iter()
+                .map(|mv| mv.san.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sans(first), ["e4", "e5"]);
+        assert_eq!(sans(second), ["d4", "d5"]);
+        assert_eq!(database.load(first).unwrap().tag("Event"), Some("One"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::game::Game;
+use crate::notation;
+use crate::variant::VariantKind;
+use crate::zobrist;
+
+/// A named opening with its Encyclopaedia of Chess Openings code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Opening {
+    pub eco: &'static str,
+    pub name: &'static str,
+}
+
+impl fmt::Display for Opening {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.eco, self.name)
+    }
+}
+
+/// Openings as `eco`, `name` and the numbered SAN moves that define them, one per line after
+/// a header.
+const TABLE: &str = include_str!("eco.tsv");
+
+/// Plays the lines of [`TABLE`] and keys each opening by the Polyglot key of the position it
+/// ends in, so a game that reaches it by another move order is still named. When two lines
+/// end in the same position, the first one listed wins.
+fn index() -> &'static HashMap<u64, Opening> {
+    static INDEX: OnceLock<HashMap<u64, Opening>> = OnceLock::new();
+    INDEX.get_or_init(|| {
+        let mut index = HashMap::new();
+        for line in TABLE.lines().skip(1).filter(|line| !line.is_empty()) {
+            let mut fields = line.split('\t');
+            let (Some(eco), Some(name), Some(moves)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                panic!("malformed ECO line `{line}`");
+            };
+            let mut game = Game::new();
+            for san in moves
+                .split_whitespace()
+                .filter(|token| !token.ends_with('.'))
+            {
+                let mv = notation::parse_move(&game, san)
+                    .unwrap_or_else(|err| panic!("bad move `{san}` in ECO line `{line}`: {err}"));
+                game.make_move(mv);
+            }
+            let key = zobrist::polyglot(&game).expect("standard positions have a key");
+            index.entry(key).or_insert(Opening { eco, name });
+        }
+        index
+    })
+}
+
+/// The opening of the last position in `game` that the table names, whatever the move order
+/// that reached it. Only standard chess is classified.
+pub fn classify(game: &Game) -> Option<Opening> {
+    if game.variant() != VariantKind::Standard {
+        return None;
+    }
+    let index = index();
+    let mut replay = Game::from_fen(game.start_fen(), VariantKind::Standard).ok()?;
+    let mut opening = None;
+    for mv in game.get_move_history() {
+        replay.make_move(*mv);
+        if let Some(found) = zobrist::polyglot(&replay).and_then(|key| index.get(&key)) {
+            opening = Some(*found);
+        }
+    }
+    opening
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn play(sans: &[&str]) -> Game {
+        let mut game = Game::new();
+        for san in sans {
+            game.make_move(notation::parse_move(&game, san).unwrap());
+        }
+        game
+    }
+
+    #[test]
+    fn names_the_last_known_position() {
+        assert_eq!(classify(&Game::new()), None);
+        let berlin = play(&["e4", "e5", "Nf3", "Nc6", "Bb5", "Nf6", "O-O"]);
+        assert_eq!(
+            classify(&berlin).unwrap().to_string(),
+            "C65 Ruy Lopez: Berlin Defense"
+        );
+    }
+
+    #[test]
+    fn follows_transpositions() {
+        let qgd = play(&["c4", "e6", "Nc3", "d5", "d4"]);
+        assert_eq!(
+            classify(&qgd).unwrap().to_string(),
+            "D31 Queen's Gambit Declined: Queen's Knight Variation"
+        );
+        // Every line in the table plays out, and every code from A00 to E99 is named
+        let codes: HashSet<_> = index().values().map(|opening| opening.eco).collect();
+        assert_eq!(codes.len(), 500);
+    }
+}
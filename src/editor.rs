@@ -0,0 +1,314 @@
+use color_eyre::eyre::bail;
+
+use crate::game::{Board, CastlingRights, Game};
+use crate::pieces::{Color, Piece, PieceType};
+use crate::variant::VariantKind;
+
+/// A position being set up square by square, before play or analysis starts from it.
+#[derive(Debug, Clone)]
+pub struct Editor {
+    /// Holds the board being edited and its variant; its own turn and rights are not used.
+    game: Game,
+    pub turn: Color,
+    pub castling: CastlingRights,
+    pub en_passant: Option<(usize, usize)>,
+    /// Pieces that can be placed, White's then Black's.
+    palette: Vec<Piece>,
+    /// Index of the piece placed next.
+    pub selected: usize,
+}
+
+impl Editor {
+    /// Starts from the position of `game`, with the first white piece selected.
+    pub fn new(game: &Game) -> Self {
+        let variant = game.variant();
+        let types = variant.rules().piece_types();
+        let palette = [Color::White, Color::Black]
+            .into_iter()
+            .flat_map(|color| {
+                types
+                    .iter()
+                    .map(move |&piece_type| Piece::new(color, piece_type))
+            })
+            .collect();
+        let mut editor_game = Game::with_variant(variant);
+        *editor_game.board_mut() = game.get_board().clone();
+        Self {
+            game: editor_game,
+            turn: game.get_current_turn(),
+            castling: game.castling_rights(),
+            en_passant: game.en_passant(),
+            palette,
+            selected: 0,
+        }
+    }
+
+    /// A game holding the edited board, for drawing it.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn board(&self) -> &Board {
+        self.game.get_board()
+    }
+
+    pub fn variant(&self) -> VariantKind {
+        self.game.variant()
+    }
+
+    pub fn palette(&self) -> &[Piece] {
+        &self.palette
+    }
+
+    /// The piece placed next.
+    pub fn piece(&self) -> Piece {
+        self.palette[self.selected]
+    }
+
+    /// Selects the piece `delta` places along the palette, wrapping around.
+    pub fn cycle_piece(&mut self, delta: isize) {
+        let len = self.palette.len() as isize;
+        self.selected = (self.selected as isize + delta).rem_euclid(len) as usize;
+    }
+
+    /// Puts the selected piece on `square`, or empties it if that piece is already there.
+    pub fn place(&mut self, square: (usize, usize)) {
+        let piece = self.piece();
+        let board = self.game.board_mut();
+        if board.get_piece_at(square) == Some(&piece) {
+            board.set_piece_at(square, None);
+        } else {
+            board.set_piece_at(square, Some(piece));
+        }
+    }
+
+    pub fn remove(&mut self, square: (usize, usize)) {
+        self.game.board_mut().set_piece_at(square, None);
+    }
+
+    /// Empties the board, dropping every castling right and the en passant square.
+    pub fn clear(&mut self) {
+        let (rows, cols) = self.board().dimensions();
+        *self.game.board_mut() = Board::empty(rows, cols);
+        self.castling = CastlingRights::default();
+        self.en_passant = None;
+    }
+
+    pub fn toggle_turn(&mut self) {
+        self.turn = self.turn.opposite();
+    }
+
+    /// Toggles the castling right of the rook whose home square is `square`, or both of a
+    /// side's rights from its king's home square.
+    pub fn toggle_castling(&mut self, square: (usize, usize)) -> color_eyre::Result<()> {
+        let board = self.board();
+        for color in [Color::White, Color::Black] {
+            let king_side = board.castling_squares(color, true);
+            let queen_side = board.castling_squares(color, false);
+            let king_from = king_side.or(queen_side).map(|squares| squares.king_from);
+            let sides: &[bool] = if king_from == Some(square) {
+                &[true, false]
+            } else if king_side.is_some_and(|squares| squares.rook_from == square) {
+                &[true]
+            } else if queen_side.is_some_and(|squares| squares.rook_from == square) {
+                &[false]
+            } else {
+                continue;
+            };
+            let on = !sides.iter().any(|&side| self.castling.get(color, side));
+            for &side in sides {
+                self.castling.set(color, side, on);
+            }
+            return Ok(());
+        }
+        bail!("castling is set from a king's or rook's home square")
+    }
+
+    /// Sets `square` as the en passant target, or clears it if it already is.
+    pub fn toggle_en_passant(&mut self, square: (usize, usize)) {
+        if self.en_passant == Some(square) {
+            self.en_passant = None;
+        } else {
+            self.en_passant = Some(square);
+        }
+    }
+
+    /// The edited position in FEN, with the move counters reset.
+    pub fn fen(&self) -> String {
+        let board = self.board();
+        let turn = match self.turn {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+        let en_passant = self
+            .en_passant
+            .map(|square| board.square_name(square))
+            .unwrap_or_else(|| "-".to_string());
+        format!(
+            "{} {turn} {} {en_passant} 0 1",
+            board.to_fen_placement(),
+            self.castling.to_fen()
+        )
+    }
+
+    /// The edited position as a game, once it is one that could have arisen: one king per side,
+    /// no pawns on the first or last rank, the side not to move not in check, and castling
+    /// rights and the en passant square that fit the board.
+    pub fn validate(&self) -> color_eyre::Result<Game> {
+        let board = self.board();
+        let variant = self.variant();
+        let rules = variant.rules();
+        let (rows, _) = board.dimensions();
+        for color in [Color::White, Color::Black] {
+            let kings = board
+                .pieces(color)
+                .filter(|(_, piece)| piece.piece_type == PieceType::King)
+                .count();
+            let needs_king = match variant {
+                VariantKind::Antichess => false,
+                VariantKind::Horde => color == Color::Black,
+                _ => true,
+            };
+            if needs_king && kings != 1 {
+                bail!("{color:?} needs exactly one king, not {kings}");
+            }
+            for (square, piece) in board.pieces(color) {
+                // Horde's white pawns start on the first rank
+                let home_pawn = variant == VariantKind::Horde
+                    && color == Color::White
+                    && square.0 == board.back_rank(color);
+                if piece.piece_type == PieceType::Pawn
+                    && (square.0 == 0 || square.0 == rows - 1)
+                    && !home_pawn
+                {
+                    bail!(
+                        "{color:?} pawn on {}: pawns cannot stand on the first or last rank",
+                        board.square_name(square)
+                    );
+                }
+            }
+        }
+        let waiting = self.turn.opposite();
+        if rules.is_in_check(board, waiting) {
+            bail!("{waiting:?} is in check but it is {:?} to move", self.turn);
+        }
+        for color in [Color::White, Color::Black] {
+            for king_side in [true, false] {
+                if !self.castling.get(color, king_side) {
+                    continue;
+                }
+                let Some(squares) = board.castling_squares(color, king_side) else {
+                    bail!("the board is too narrow for {color:?} to castle");
+                };
+                if board.get_piece_at(squares.king_from)
+                    != Some(&Piece::new(color, PieceType::King))
+                    || board.get_piece_at(squares.rook_from)
+                        != Some(&Piece::new(color, PieceType::Rook))
+                {
+                    bail!(
+                        "{color:?} cannot castle {}: its king and rook must be on {} and {}",
+                        if king_side { "king side" } else { "queen side" },
+                        board.square_name(squares.king_from),
+                        board.square_name(squares.rook_from)
+                    );
+                }
+            }
+        }
+        if let Some(square) = self.en_passant {
+            // The waiting side's pawn has just moved two squares, passing over `square`
+            let direction = waiting.pawn_direction();
+            let passed = board.get_piece_at(square).is_none()
+                && board
+                    .offset(square, -direction, 0)
+                    .is_some_and(|from| board.get_piece_at(from).is_none())
+                && board.offset(square, direction, 0).is_some_and(|to| {
+                    board.get_piece_at(to) == Some(&Piece::new(waiting, PieceType::Pawn))
+                });
+            if !passed {
+                bail!(
+                    "no {waiting:?} pawn can just have passed {} for en passant",
+                    board.square_name(square)
+                );
+            }
+        }
+        Game::from_fen(&self.fen(), variant)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor(fen: &str) -> Editor {
+        Editor::new(&Game::from_fen(fen, VariantKind::Standard).unwrap())
+    }
+
+    #[test]
+    fn places_pieces_and_builds_the_fen() {
+        let mut editor = editor("8/8/8/8/8/8/8/8 w - - 0 1");
+        assert_eq!(editor.palette().len(), 12);
+        assert_eq!(editor.piece(), Piece::new(Color::White, PieceType::Pawn));
+        editor.cycle_piece(5);
+        editor.place((7, 4));
+        editor.cycle_piece(6);
+        editor.place((0, 4));
+        editor.cycle_piece(-8);
+        editor.place((7, 7));
+        assert_eq!(editor.piece(), Piece::new(Color::White, PieceType::Rook));
+        editor.toggle_castling((7, 7)).unwrap();
+        assert!(editor.toggle_castling((4, 4)).is_err());
+        editor.toggle_turn();
+        assert_eq!(editor.fen(), "4k3/8/8/8/8/8/8/4K2R b K - 0 1");
+        let game = editor.validate().unwrap();
+        assert_eq!(game.to_fen(), "4k3/8/8/8/8/8/8/4K2R b K - 0 1");
+
+        // Placing the same piece again takes it away
+        editor.place((7, 7));
+        assert_eq!(editor.board().get_piece_at((7, 7)), None);
+        assert!(editor.validate().is_err());
+        editor.clear();
+        assert_eq!(editor.fen(), "8/8/8/8/8/8/8/8 b - - 0 1");
+    }
+
+    #[test]
+    fn rejects_impossible_positions() {
+        let error = |fen: &str| editor(fen).validate().unwrap_err().to_string();
+        assert!(error("8/8/8/8/8/8/8/4K3 w - - 0 1").contains("Black needs exactly one king"));
+        assert!(error("4k3/8/8/8/8/8/8/3KK3 w - - 0 1").contains("not 2"));
+        assert!(error("P3k3/8/8/8/8/8/8/4K3 w - - 0 1").contains("pawn on a8"));
+        assert!(error("4k3/8/8/8/8/8/8/4K2p w - - 0 1").contains("pawn on h1"));
+        assert_eq!(
+            error("4k2R/8/8/8/8/8/8/4K3 w - - 0 1"),
+            "Black is in check but it is White to move"
+        );
+
+        let mut rook_moved = editor("4k3/8/8/8/8/8/8/4K1R1 w - - 0 1");
+        rook_moved.castling.white_king_side = true;
+        assert!(
+            rook_moved
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("cannot castle king side")
+        );
+
+        let mut pushed = editor("4k3/8/8/4p3/8/8/8/4K3 w - - 0 1");
+        pushed.toggle_en_passant((2, 4));
+        assert_eq!(pushed.validate().unwrap().en_passant(), Some((2, 4)));
+        pushed.toggle_en_passant((2, 3));
+        assert!(pushed.validate().unwrap_err().to_string().contains("d6"));
+    }
+
+    #[test]
+    fn variants_without_kings_are_allowed() {
+        let horde = Editor::new(&Game::with_variant(VariantKind::Horde));
+        assert!(horde.validate().is_ok());
+        assert!(
+            horde
+                .palette()
+                .contains(&Piece::new(Color::White, PieceType::King))
+        );
+        let antichess = Game::from_fen("8/8/8/8/8/8/8/R7 w - - 0 1", VariantKind::Antichess);
+        assert!(Editor::new(&antichess.unwrap()).validate().is_ok());
+    }
+}
@@ -0,0 +1,519 @@
+use std::fmt;
+use std::str::FromStr;
+
+use color_eyre::eyre::bail;
+use rand::Rng;
+
+use crate::game::{Board, Game};
+use crate::pieces::{Color, Piece, PieceType};
+use crate::tablebase::{Tablebase, Wdl};
+use crate::variant::VariantKind;
+
+/// Endings the endgame screen steps through, in order.
+pub const ENDGAMES: [&str; 8] = [
+    "KQvK", "KRvK", "KBBvK", "KBNvK", "KPvK", "KQvKR", "Lucena", "Philidor",
+];
+
+/// Moves the defending side must last to hold a drawn ending.
+pub const HOLD_MOVES: u32 = 25;
+
+/// Random positions tried in search of one the tablebases agree with.
+const ATTEMPTS: usize = 500;
+
+/// Most pieces, kings included, an ending may have.
+const MAX_PIECES: usize = 7;
+
+/// Order of the pieces in an ending's name, strongest first.
+const PIECE_ORDER: &str = "QRBNP";
+
+/// An ending to practise, from either side of the board.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endgame {
+    /// Random positions with the pieces of the side to win and of the defender, kings left out.
+    Material(Vec<PieceType>, Vec<PieceType>),
+    /// Rook and pawn against rook with the pawn on the seventh and the defending king cut off,
+    /// won by building a bridge.
+    Lucena,
+    /// Rook and pawn against rook with the defending king in front of the pawn and its rook on
+    /// the sixth rank, held by checking from behind once the pawn advances.
+    Philidor,
+}
+
+/// What the user has to do in an ending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    /// Checkmate the defender.
+    Win,
+    /// Draw, or last [`HOLD_MOVES`] moves without the position becoming lost.
+    Hold,
+}
+
+impl FromStr for Endgame {
+    type Err = color_eyre::Report;
+
+    /// Reads `Lucena`, `Philidor` or material such as `KRvK` or `R vs`, the side to win first.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("lucena") {
+            return Ok(Endgame::Lucena);
+        }
+        if s.eq_ignore_ascii_case("philidor") {
+            return Ok(Endgame::Philidor);
+        }
+        let compact = s.split_whitespace().collect::<String>().to_uppercase();
+        let compact = compact.replace("VS", "V");
+        let Some((first, second)) = compact.split_once('V') else {
+            bail!("expected Lucena, Philidor or material such as KRvK, got `{s}`");
+        };
+        let mut sides = [Vec::new(), Vec::new()];
+        for (side, letters) in sides.iter_mut().zip([first, second]) {
+            let letters = letters.strip_prefix('K').unwrap_or(letters);
+            for c in letters.chars() {
+                if !PIECE_ORDER.contains(c) {
+                    bail!("unknown piece `{c}` in `{s}`");
+                }
+                side.push(PieceType::from_char(c).expect("piece letter"));
+            }
+            side.sort_by_key(|piece_type| {
+                PIECE_ORDER.find(piece_type.to_char().to_ascii_uppercase())
+            });
+        }
+        let [strong, weak] = sides;
+        if strong.is_empty() {
+            bail!("the first side of `{s}` has nothing to win with");
+        }
+        if strong.len() + weak.len() + 2 > MAX_PIECES {
+            bail!("an ending has at most {MAX_PIECES} pieces, kings included");
+        }
+        Ok(Endgame::Material(strong, weak))
+    }
+}
+
+impl fmt::Display for Endgame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letters = |pieces: &[PieceType]| -> String {
+            pieces
+                .iter()
+                .map(|piece_type| piece_type.to_char().to_ascii_uppercase())
+                .collect()
+        };
+        match self {
+            Endgame::Material(strong, weak) => {
+                write!(f, "K{}vK{}", letters(strong), letters(weak))
+            }
+            Endgame::Lucena => f.write_str("Lucena"),
+            Endgame::Philidor => f.write_str("Philidor"),
+        }
+    }
+}
+
+impl Endgame {
+    pub fn goal(&self) -> Goal {
+        match self {
+            Endgame::Philidor => Goal::Hold,
+            _ => Goal::Win,
+        }
+    }
+
+    /// A random position of the ending with the user's side to move, playing White or Black
+    /// at random. Positions the tablebases cover must have the result the goal asks for.
+    pub fn position(
+        &self,
+        rng: &mut impl Rng,
+        tablebase: Option<&Tablebase>,
+    ) -> color_eyre::Result<Game> {
+        let wanted = match self.goal() {
+            Goal::Win => Wdl::Win,
+            Goal::Hold => Wdl::Draw,
+        };
+        for _ in 0..ATTEMPTS {
+            let Some(game) = self.try_position(rng) else {
+                continue;
+            };
+            match tablebase.and_then(|tablebase| tablebase.probe_wdl(&game)) {
+                Some(wdl) if wdl != wanted => {}
+                _ => return Ok(game),
+            }
+        }
+        bail!("could not set up a {self} position")
+    }
+
+    /// One random setup, or `None` if it turned out illegal or already over.
+    fn try_position(&self, rng: &mut impl Rng) -> Option<Game> {
+        let (mut board, mut turn) = match self {
+            Endgame::Material(strong, weak) => (random_board(strong, weak, rng)?, Color::White),
+            Endgame::Lucena => (lucena(rng), Color::White),
+            Endgame::Philidor => (philidor(rng), Color::Black),
+        };
+        if rng.random_bool(0.5) {
+            board = swap_colors(&board);
+            turn = turn.opposite();
+        }
+        if board.is_in_check(turn.opposite()) {
+            return None;
+        }
+        let side = match turn {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+        let fen = format!("{} {side} - - 0 1", board.to_fen_placement());
+        let game = Game::from_fen(&fen, VariantKind::Standard).ok()?;
+        (!game.is_game_over()).then_some(game)
+    }
+}
+
+/// White's pieces `strong` and Black's `weak` with their kings on random squares, keeping
+/// pawns off the back ranks, kings apart and a side's two bishops on opposite colours.
+fn random_board(strong: &[PieceType], weak: &[PieceType], rng: &mut impl Rng) -> Option<Board> {
+    let mut board = Board::empty(8, 8);
+    let mut place = |board: &mut Board, piece: Piece| -> Option<(usize, usize)> {
+        let rows = if piece.piece_type == PieceType::Pawn {
+            1..7
+        } else {
+            0..8
+        };
+        let square = (rng.random_range(rows), rng.random_range(0..8));
+        if board.get_piece_at(square).is_some() {
+            return None;
+        }
+        board.set_piece_at(square, Some(piece));
+        Some(square)
+    };
+    let white_king = place(&mut board, Piece::new(Color::White, PieceType::King))?;
+    let black_king = place(&mut board, Piece::new(Color::Black, PieceType::King))?;
+    if white_king.0.abs_diff(black_king.0) <= 1 && white_king.1.abs_diff(black_king.1) <= 1 {
+        return None;
+    }
+    for (color, pieces) in [(Color::White, strong), (Color::Black, weak)] {
+        let mut bishop_squares = Vec::new();
+        for &piece_type in pieces {
+            let square = place(&mut board, Piece::new(color, piece_type))?;
+            if piece_type == PieceType::Bishop {
+                bishop_squares.push((square.0 + square.1) % 2);
+            }
+        }
+        if bishop_squares.len() == 2 && bishop_squares[0] == bishop_squares[1] {
+            return None;
+        }
+    }
+    Some(board)
+}
+
+/// A Lucena position for White on a random b- to g-file, White to move.
+fn lucena(rng: &mut impl Rng) -> Board {
+    let file: usize = rng.random_range(1..7);
+    // The king is cut off on the long side and the rook waits on the short one
+    let toward: isize = if file <= 4 { 1 } else { -1 };
+    let at = |offset: isize| {
+        file.checked_add_signed(offset * toward)
+            .expect("on the board")
+    };
+    let mut board = Board::empty(8, 8);
+    for (square, color, piece_type) in [
+        ((0, file), Color::White, PieceType::King),
+        ((1, file), Color::White, PieceType::Pawn),
+        ((7, at(1)), Color::White, PieceType::Rook),
+        ((1, at(2)), Color::Black, PieceType::King),
+        ((6, at(-1)), Color::Black, PieceType::Rook),
+    ] {
+        board.set_piece_at(square, Some(Piece::new(color, piece_type)));
+    }
+    board
+}
+
+/// A Philidor position against a pawn on a random c- to f-file, Black to move.
+fn philidor(rng: &mut impl Rng) -> Board {
+    let file = rng.random_range(2..6);
+    let king_file = if rng.random_bool(0.5) {
+        file - 1
+    } else {
+        file + 1
+    };
+    // White's rook takes the seventh rank and Black's the sixth, both on the long side
+    let (rook_file, defender_file) = if file >= 4 { (0, 1) } else { (7, 6) };
+    let mut board = Board::empty(8, 8);
+    for (square, color, piece_type) in [
+        ((3, king_file), Color::White, PieceType::King),
+        ((3, file), Color::White, PieceType::Pawn),
+        ((1, rook_file), Color::White, PieceType::Rook),
+        ((0, file), Color::Black, PieceType::King),
+        ((2, defender_file), Color::Black, PieceType::Rook),
+    ] {
+        board.set_piece_at(square, Some(Piece::new(color, piece_type)));
+    }
+    board
+}
+
+/// The same position seen from the other side: ranks mirrored and colours swapped.
+fn swap_colors(board: &Board) -> Board {
+    let (rows, cols) = board.dimensions();
+    let mut swapped = Board::empty(rows, cols);
+    for color in [Color::White, Color::Black] {
+        for ((row, col), piece) in board.pieces(color) {
+            let piece = Piece::new(color.opposite(), piece.piece_type);
+            swapped.set_piece_at((rows - 1 - row, col), Some(piece));
+        }
+    }
+    swapped
+}
+
+/// How a drill ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Achieved(String),
+    Missed(String),
+}
+
+/// One position of an ending being played out against the engine.
+#[derive(Debug, Clone)]
+pub struct Drill {
+    pub endgame: Endgame,
+    /// The position drilled, kept to try it again.
+    pub start: Game,
+    /// The side the user plays, which moves first.
+    pub player: Color,
+    /// Moves to mate, or to the next capture or pawn move, with best play from the start as
+    /// the tablebases count them.
+    pub best: Option<u32>,
+    /// Moves the user took to do the same.
+    pub converted: Option<u32>,
+    pub verdict: Option<Verdict>,
+}
+
+impl Drill {
+    /// A random position of `endgame`.
+    pub fn new(
+        endgame: Endgame,
+        rng: &mut impl Rng,
+        tablebase: Option<&Tablebase>,
+    ) -> color_eyre::Result<Self> {
+        let start = endgame.position(rng, tablebase)?;
+        Ok(Self::from_position(endgame, start, tablebase))
+    }
+
+    /// The same position again, from the start.
+    pub fn retry(&self, tablebase: Option<&Tablebase>) -> Self {
+        Self::from_position(self.endgame.clone(), self.start.clone(), tablebase)
+    }
+
+    /// Drills `start`, with the side to move as the user's.
+    pub fn from_position(endgame: Endgame, start: Game, tablebase: Option<&Tablebase>) -> Self {
+        let best = match endgame.goal() {
+            Goal::Win => tablebase
+                .and_then(|tablebase| tablebase.probe(&start))
+                .filter(|probe| probe.wdl == Wdl::Win)
+                .map(|probe| probe.dtz.unsigned_abs().div_ceil(2)),
+            Goal::Hold => None,
+        };
+        Self {
+            endgame,
+            player: start.get_current_turn(),
+            start,
+            best,
+            converted: None,
+            verdict: None,
+        }
+    }
+
+    /// Moves the user has played in `game`.
+    pub fn moves(&self, game: &Game) -> u32 {
+        game.get_move_history()
+            .iter()
+            .filter(|mv| mv.piece.color == self.player)
+            .count() as u32
+    }
+
+    /// Judges `game` after each move, settling the verdict once the goal is reached or missed.
+    /// The tablebases, when they cover the position, catch a win thrown away or a draw lost
+    /// before the board shows it.
+    pub fn judge(&mut self, game: &Game, tablebase: Option<&Tablebase>) {
+        let Some(last) = game.get_move_history().last() else {
+            return;
+        };
+        if self.verdict.is_some() {
+            return;
+        }
+        let moves = self.moves(game);
+        let by_player = last.piece.color == self.player;
+        if by_player
+            && self.converted.is_none()
+            && (last.is_capture()
+                || last.piece.piece_type == PieceType::Pawn
+                || game.is_game_over())
+        {
+            self.converted = Some(moves);
+        }
+        // For the opponent, who is to move after the user
+        let opponent_wdl = tablebase
+            .filter(|_| by_player)
+            .and_then(|tablebase| tablebase.probe_wdl(game));
+        let bare_king = game
+            .get_board()
+            .pieces(self.player)
+            .all(|(_, piece)| piece.piece_type == PieceType::King);
+        self.verdict = match (self.endgame.goal(), game.outcome()) {
+            (_, Some(outcome)) if outcome.winner == Some(self.player) => Some(Verdict::Achieved(
+                format!("Checkmate in {}", plural(moves, "move")),
+            )),
+            (Goal::Hold, Some(outcome)) if outcome.winner.is_none() => Some(Verdict::Achieved(
+                format!("Held: drawn by {}", outcome.reason),
+            )),
+            (_, Some(outcome)) if outcome.winner.is_none() => {
+                Some(Verdict::Missed(format!("Drawn by {}", outcome.reason)))
+            }
+            (_, Some(outcome)) => Some(Verdict::Missed(format!("Lost by {}", outcome.reason))),
+            (Goal::Win, None) if opponent_wdl.is_some_and(|wdl| wdl != Wdl::Loss) => Some(
+                Verdict::Missed("The win has slipped away: best play draws now".to_string()),
+            ),
+            (Goal::Win, None) if bare_king => {
+                Some(Verdict::Missed("Nothing is left to win with".to_string()))
+            }
+            (Goal::Hold, None) if opponent_wdl == Some(Wdl::Win) => Some(Verdict::Missed(
+                "The position is lost now with best play".to_string(),
+            )),
+            (Goal::Hold, None) if by_player && moves >= HOLD_MOVES => Some(Verdict::Achieved(
+                format!("Held for {}", plural(moves, "move")),
+            )),
+            _ => None,
+        };
+    }
+
+    /// How the user's conversion compares with the tablebases', once both are known.
+    pub fn comparison(&self) -> Option<String> {
+        let (converted, best) = (self.converted?, self.best?);
+        Some(format!(
+            "{} to mate or the first capture or pawn move, against {best} with best play",
+            plural(converted, "move")
+        ))
+    }
+}
+
+fn plural(count: u32, word: &str) -> String {
+    if count == 1 {
+        format!("1 {word}")
+    } else {
+        format!("{count} {word}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::notation;
+
+    fn fixtures() -> Tablebase {
+        Tablebase::open(Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/syzygy"
+        )))
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_endings() {
+        for name in ENDGAMES {
+            assert_eq!(name.parse::<Endgame>().unwrap().to_string(), name);
+        }
+        assert_eq!("r vs".parse::<Endgame>().unwrap().to_string(), "KRvK");
+        assert_eq!("kprvkr".parse::<Endgame>().unwrap().to_string(), "KRPvKR");
+        assert_eq!("lucena".parse::<Endgame>().unwrap(), Endgame::Lucena);
+        assert_eq!(Endgame::Philidor.goal(), Goal::Hold);
+        let error = |s: &str| s.parse::<Endgame>().unwrap_err().to_string();
+        assert!(error("KvKQ").contains("nothing to win with"));
+        assert!(error("KAvK").contains("unknown piece `A`"));
+        assert!(error("KQQQQQvKQ").contains("at most 7 pieces"));
+        assert!(error("Tarrasch").contains("expected Lucena"));
+    }
+
+    #[test]
+    fn sets_up_legal_positions_for_either_side() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for name in ENDGAMES {
+            let endgame: Endgame = name.parse().unwrap();
+            let mut colors = Vec::new();
+            for _ in 0..20 {
+                let game = endgame.position(&mut rng, None).unwrap();
+                let board = game.get_board();
+                let player = game.get_current_turn();
+                colors.push(player);
+                assert!(!game.is_game_over(), "{name}: {}", game.to_fen());
+                assert!(!board.is_in_check(player.opposite()), "{}", game.to_fen());
+                for ((row, _), piece) in
+                    board.pieces(Color::White).chain(board.pieces(Color::Black))
+                {
+                    assert!(
+                        piece.piece_type != PieceType::Pawn || (1..7).contains(&row),
+                        "{}",
+                        game.to_fen()
+                    );
+                }
+                // The side to win has the material named first
+                let count = |color: Color| board.pieces(color).count();
+                let attacker = match endgame.goal() {
+                    Goal::Win => player,
+                    Goal::Hold => player.opposite(),
+                };
+                if let Endgame::Material(strong, weak) = &endgame {
+                    assert_eq!(count(attacker), strong.len() + 1);
+                    assert_eq!(count(attacker.opposite()), weak.len() + 1);
+                } else {
+                    assert_eq!((count(attacker), count(attacker.opposite())), (3, 2));
+                }
+            }
+            assert!(colors.contains(&Color::White) && colors.contains(&Color::Black));
+        }
+        // The textbook Lucena is among the b-file setups
+        assert!((0..50).any(|_| lucena(&mut rng).to_fen_placement() == "1K6/1P1k4/8/8/8/8/r7/2R5"));
+    }
+
+    #[test]
+    fn judges_drills_against_the_tablebase() {
+        let tablebase = fixtures();
+        let drill = |fen: &str| {
+            let start = Game::from_fen(fen, VariantKind::Standard).unwrap();
+            Drill::from_position("KQvK".parse().unwrap(), start, Some(&tablebase))
+        };
+        let play = |drill: &mut Drill, game: &mut Game, san: &str| {
+            let mv = notation::parse_move(game, san).unwrap();
+            game.make_move(mv);
+            drill.judge(game, Some(&tablebase));
+        };
+
+        let mut won = drill("6k1/8/6K1/8/8/8/8/Q7 w - - 0 1");
+        assert_eq!(won.best, Some(1));
+        let mut game = won.start.clone();
+        play(&mut won, &mut game, "Qa8#");
+        assert_eq!(
+            won.verdict,
+            Some(Verdict::Achieved("Checkmate in 1 move".to_string()))
+        );
+        assert_eq!(
+            won.comparison().unwrap(),
+            "1 move to mate or the first capture or pawn move, against 1 with best play"
+        );
+
+        let mut stalemated = drill("7k/8/5K2/8/8/8/8/6Q1 w - - 0 1");
+        let mut game = stalemated.start.clone();
+        play(&mut stalemated, &mut game, "Qg6");
+        assert_eq!(
+            stalemated.verdict,
+            Some(Verdict::Missed("Drawn by stalemate".to_string()))
+        );
+
+        // The queen next to the king is lost, which the tablebase sees at once
+        let mut blundered = drill("4k3/8/8/8/8/8/4Q3/4K3 w - - 0 1");
+        let mut game = blundered.start.clone();
+        play(&mut blundered, &mut game, "Qe7+");
+        assert!(
+            matches!(blundered.verdict, Some(Verdict::Missed(ref reason)) if reason.contains("slipped"))
+        );
+        let retried = blundered.retry(Some(&tablebase));
+        assert_eq!(retried.verdict, None);
+        assert_eq!(retried.start.to_fen(), blundered.start.to_fen());
+    }
+}
@@ -0,0 +1,457 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::game::{Game, Move};
+use crate::pieces::{Color, PieceType};
+use crate::tablebase::{Tablebase, Wdl};
+use crate::variant::VariantKind;
+
+/// Score of delivering mate right now; mates further away score a little less.
+const MATE: i32 = 100_000;
+
+/// Score of a position the tablebases call won, above any material but below mates.
+const TB_WIN: i32 = 20_000;
+
+/// Evaluation of a position from the point of view of the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    /// Mate in this many moves, negative when the side to move gets mated.
+    Mate(i32),
+}
+
+impl Score {
+    fn from_internal(value: i32) -> Self {
+        if value.abs() >= MATE - 1000 {
+            let plies = MATE - value.abs();
+            let moves = (plies + 1) / 2;
+            Score::Mate(if value > 0 { moves } else { -moves })
+        } else {
+            Score::Centipawns(value)
+        }
+    }
+
+    /// The same score seen by the other side.
+    pub fn flip(self) -> Self {
+        match self {
+            Score::Centipawns(cp) => Score::Centipawns(-cp),
+            Score::Mate(moves) => Score::Mate(-moves),
+        }
+    }
+
+    /// The score from White's point of view, for a position with `turn` to move.
+    pub fn white_view(self, turn: Color) -> Self {
+        match turn {
+            Color::White => self,
+            Color::Black => self.flip(),
+        }
+    }
+
+    /// A centipawn value for comparing scores, with mates far beyond any material.
+    pub fn centipawns(self) -> i32 {
+        match self {
+            Score::Centipawns(cp) => cp,
+            Score::Mate(moves) if moves > 0 => MATE - moves,
+            Score::Mate(moves) => -MATE - moves,
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    /// Pawns with a sign (`+0.35`) or a mate distance (`#3`, `#-2`).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Score::Centipawns(cp) => write!(f, "{:+.2}", *cp as f64 / 100.0),
+            Score::Mate(moves) => write!(f, "#{moves}"),
+        }
+    }
+}
+
+/// When to stop searching. The search always completes depth 1 so there is a move to play.
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub time: Option<Duration>,
+    pub nodes: Option<u64>,
+    /// Raised by another thread to end the search early.
+    pub stop: Option<Arc<AtomicBool>>,
+    /// Endgame tables to probe at the root and after captures and pawn moves.
+    pub tablebase: Option<Arc<Tablebase>>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Self::default()
+        }
+    }
+
+    /// Limits for an engine strength from 1 to 20: deeper searches for stronger play.
+    pub fn for_strength(strength: u8) -> Self {
+        Self {
+            depth: Some(1 + strength.clamp(1, 20) as u32 / 5),
+            time: Some(Duration::from_secs(5)),
+            ..Self::default()
+        }
+    }
+}
+
+/// The outcome of a search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: Score,
+    /// Deepest fully searched depth.
+    pub depth: u32,
+    /// Expected continuation, starting with the best move.
+    pub pv: Vec<Move>,
+    pub nodes: u64,
+}
+
+/// Iterative-deepening alpha-beta search over the legal moves of `game`, for any variant.
+pub fn search(game: &Game, limits: &SearchLimits) -> SearchResult {
+    search_with(game, limits, |_| {})
+}
+
+/// Like [`search`], calling `on_depth` with the result of every completed depth.
+pub fn search_with(
+    game: &Game,
+    limits: &SearchLimits,
+    mut on_depth: impl FnMut(&SearchResult),
+) -> SearchResult {
+    search_lines(game, limits, 1, |results| on_depth(&results[0])).swap_remove(0)
+}
+
+/// Like [`search_with`], finding the best `lines` moves each with its own line, best first.
+/// Each further line is searched without the moves the earlier ones start with, and
+/// `on_depth` gets all of them at every completed depth. There is always at least one result.
+pub fn search_lines(
+    game: &Game,
+    limits: &SearchLimits,
+    lines: usize,
+    mut on_depth: impl FnMut(&[SearchResult]),
+) -> Vec<SearchResult> {
+    let mut results = vec![SearchResult {
+        best_move: None,
+        score: Score::from_internal(evaluate(game)),
+        depth: 0,
+        pv: Vec::new(),
+        nodes: 0,
+    }];
+    if game.is_game_over() {
+        results[0].score = Score::from_internal(terminal_score(game, 0));
+        return results;
+    }
+
+    // With the position in the tables, only the moves that keep its result are searched
+    let root = limits
+        .tablebase
+        .as_ref()
+        .and_then(|tablebase| tablebase.best_moves(game));
+    let root_moves = match &root {
+        Some((_, moves)) => moves.clone(),
+        None => game.legal_moves(),
+    };
+    let mut searcher = Searcher {
+        limits,
+        start: Instant::now(),
+        nodes: 0,
+        aborted: false,
+        root_moves: None,
+    };
+    let max_depth = limits.depth.unwrap_or(64);
+    let mut previous_pvs: Vec<Vec<Move>> = vec![Vec::new(); lines.max(1)];
+    'deepening: for depth in 1..=max_depth {
+        let mut found: Vec<SearchResult> = Vec::new();
+        for previous_pv in &mut previous_pvs {
+            let allowed: Vec<Move> = root_moves
+                .iter()
+                .filter(|mv| !found.iter().any(|line| line.best_move == Some(**mv)))
+                .copied()
+                .collect();
+            if allowed.is_empty() {
+                break;
+            }
+            searcher.root_moves = (root.is_some() || !found.is_empty()).then_some(allowed);
+            let mut pv = Vec::new();
+            let value = searcher.negamax(game, depth, 0, -MATE - 1, MATE + 1, previous_pv, &mut pv);
+            if searcher.aborted && depth > 1 {
+                break 'deepening;
+            }
+            *previous_pv = pv.clone();
+            found.push(SearchResult {
+                best_move: pv.first().copied(),
+                score: Score::from_internal(value),
+                depth,
+                pv,
+                nodes: searcher.nodes,
+            });
+            if searcher.aborted {
+                break;
+            }
+        }
+        // A later line can come out ahead when the earlier search was cut short
+        found.sort_by_key(|line| std::cmp::Reverse(line.score.centipawns()));
+        results = found;
+        on_depth(&results);
+        let mated = |line: &SearchResult| matches!(line.score, Score::Mate(_));
+        if searcher.aborted || results.iter().all(mated) {
+            break;
+        }
+    }
+    for line in &mut results {
+        if let Some((wdl, _)) = root
+            && !matches!(line.score, Score::Mate(_))
+        {
+            line.score = Score::from_internal(tablebase_score(wdl, 0));
+        }
+        line.nodes = searcher.nodes;
+    }
+    results
+}
+
+struct Searcher<'a> {
+    limits: &'a SearchLimits,
+    start: Instant,
+    nodes: u64,
+    aborted: bool,
+    /// The moves the tablebases keep at the root, when they cover it.
+    root_moves: Option<Vec<Move>>,
+}
+
+impl Searcher<'_> {
+    fn should_stop(&mut self) -> bool {
+        if !self.aborted && self.nodes.is_multiple_of(256) {
+            let limits = self.limits;
+            self.aborted = limits.time.is_some_and(|time| self.start.elapsed() >= time)
+                || limits.nodes.is_some_and(|nodes| self.nodes >= nodes)
+                || limits
+                    .stop
+                    .as_ref()
+                    .is_some_and(|stop| stop.load(Ordering::Relaxed));
+        }
+        self.aborted
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn negamax(
+        &mut self,
+        game: &Game,
+        depth: u32,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        previous_pv: &[Move],
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        self.nodes += 1;
+        if game.is_game_over() {
+            return terminal_score(game, ply);
+        }
+        // Captures and pawn moves reset the 50-move count, so the tables' result holds
+        if ply > 0
+            && game.halfmove_clock() == 0
+            && let Some(tablebase) = &self.limits.tablebase
+            && let Some(wdl) = tablebase.probe_wdl(game)
+        {
+            return tablebase_score(wdl, ply);
+        }
+        if depth == 0 {
+            return self.quiescence(game, ply, alpha, beta, 0);
+        }
+
+        let mut moves = ordered_moves(game);
+        if ply == 0
+            && let Some(root_moves) = &self.root_moves
+        {
+            moves.retain(|mv| root_moves.contains(mv));
+        }
+        // Search the previous iteration's best line first
+        if let Some(first) = previous_pv.first()
+            && let Some(index) = moves.iter().position(|mv| mv == first)
+        {
+            let mv = moves.remove(index);
+            moves.insert(0, mv);
+        }
+
+        let mut best = -MATE - 1;
+        for (i, mv) in moves.iter().enumerate() {
+            if ply > 0 && self.should_stop() {
+                break;
+            }
+            let mut child = game.clone();
+            child.make_move(*mv);
+            let mut line = Vec::new();
+            let follow = if i == 0 && !previous_pv.is_empty() {
+                &previous_pv[1..]
+            } else {
+                &[]
+            };
+            let value = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, follow, &mut line);
+            if value > best {
+                best = value;
+                pv.clear();
+                pv.push(*mv);
+                pv.extend(line);
+            }
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
+    /// Resolves captures so positions are not judged in the middle of an exchange.
+    fn quiescence(&mut self, game: &Game, ply: i32, mut alpha: i32, beta: i32, depth: u32) -> i32 {
+        self.nodes += 1;
+        if game.is_game_over() {
+            return terminal_score(game, ply);
+        }
+        let stand_pat = evaluate(game);
+        if stand_pat >= beta || depth >= 6 || self.should_stop() {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+        for mv in ordered_moves(game)
+            .into_iter()
+            .filter(|mv| mv.is_capture() || mv.promotion.is_some())
+        {
+            let mut child = game.clone();
+            child.make_move(mv);
+            let value = -self.quiescence(&child, ply + 1, -beta, -alpha, depth + 1);
+            if value >= beta {
+                return value;
+            }
+            alpha = alpha.max(value);
+        }
+        alpha
+    }
+}
+
+/// Legal moves with captures of valuable pieces by cheap ones and promotions first.
+fn ordered_moves(game: &Game) -> Vec<Move> {
+    let mut moves = game.legal_moves();
+    moves.sort_by_key(|mv| {
+        let gain = mv
+            .captured
+            .map_or(0, |piece| piece_value(piece.piece_type) * 10)
+            + mv.promotion.map_or(0, piece_value);
+        let cost = if mv.is_capture() {
+            piece_value(mv.piece.piece_type)
+        } else {
+            0
+        };
+        -(gain - cost)
+    });
+    moves
+}
+
+/// Score of a finished game for the side to move, preferring quicker wins.
+fn terminal_score(game: &Game, ply: i32) -> i32 {
+    match game.outcome().and_then(|outcome| outcome.winner) {
+        Some(winner) if winner == game.get_current_turn() => MATE - ply,
+        Some(_) => -MATE + ply,
+        None => 0,
+    }
+}
+
+/// Score of a tablebase result for the side to move, preferring wins found sooner. Wins
+/// and losses the 50-move rule turns into draws score next to a draw.
+fn tablebase_score(wdl: Wdl, ply: i32) -> i32 {
+    match wdl {
+        Wdl::Win => TB_WIN - ply,
+        Wdl::CursedWin => 1,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -1,
+        Wdl::Loss => -TB_WIN + ply,
+    }
+}
+
+/// Material value in centipawns.
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::King => 0,
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        _ => crate::pieces::Piece::new(Color::White, piece_type).get_value() as i32 * 100,
+    }
+}
+
+/// Static evaluation in centipawns from the point of view of the side to move: material,
+/// pieces in hand, central placement and pawn advancement, with variant-specific terms.
+pub fn evaluate(game: &Game) -> i32 {
+    let board = game.get_board();
+    let (rows, cols) = board.dimensions();
+    let variant = game.variant();
+    let center = |square: (usize, usize)| {
+        let row_distance = (2 * square.0 as i32 - (rows as i32 - 1)).abs();
+        let col_distance = (2 * square.1 as i32 - (cols as i32 - 1)).abs();
+        (rows.max(cols) as i32 - row_distance.max(col_distance)) / 2
+    };
+
+    let mut score = 0;
+    for color in [Color::White, Color::Black] {
+        let sign = if color == Color::White { 1 } else { -1 };
+        let mut side = 0;
+        for (square, piece) in board.pieces(color) {
+            side += piece_value(piece.piece_type);
+            side += match piece.piece_type {
+                PieceType::Pawn => {
+                    let start = board.back_rank(color) as i32;
+                    (square.0 as i32 - start).abs() * 6 + center(square)
+                }
+                PieceType::King if variant == VariantKind::KingOfTheHill => center(square) * 40,
+                PieceType::King => 0,
+                _ => center(square) * 4,
+            };
+        }
+        for (piece_type, count) in game.pocket(color).iter() {
+            side += piece_value(piece_type) * count as i32;
+        }
+        if variant == VariantKind::ThreeCheck {
+            side += (3 - game.remaining_checks(color.opposite()) as i32) * 150;
+        }
+        score += sign * side;
+    }
+    if variant == VariantKind::Antichess {
+        // Losing material is the aim
+        score = -score;
+    }
+    match game.get_current_turn() {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+
+    fn best(fen: &str, variant: VariantKind, depth: u32) -> SearchResult {
+        let game = Game::from_fen(fen, variant).unwrap();
+        search(&game, &SearchLimits::depth(depth))
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let fen = "6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1";
+        let result = best(fen, VariantKind::Standard, 2);
+        let game = Game::from_fen(fen, VariantKind::Standard).unwrap();
+        assert_eq!(notation::san(&game, &result.best_move.unwrap()), "Ra8#");
+        assert_eq!(result.score, Score::Mate(1));
+    }
+
+    #[test]
+    fn wins_hanging_material() {
+        let fen = "4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1";
+        let result = best(fen, VariantKind::Standard, 2);
+        let game = Game::from_fen(fen, VariantKind::Standard).unwrap();
+        assert_eq!(notation::san(&game, &result.best_move.unwrap()), "Rxd5");
+    }
+}
@@ -4,6 +4,10 @@ use ratatui::crossterm::event::Event as CrosstermEvent;
 use std::time::Duration;
 use tokio::sync::mpsc;
 
+use crate::engine::{Score, SearchResult};
+use crate::game::Move;
+use crate::report::Report;
+
 /// The frequency at which tick events are emitted.
 const TICK_FPS: f64 = 30.0;
 
@@ -37,6 +41,21 @@ pub enum AppEvent {
     Decrement,
     /// Quit the application.
     Quit,
+    /// The computer opponent chose its move.
+    EngineMove(Move),
+    /// The post-game report has searched `done` of the game's `total` positions.
+    ReportProgress { done: usize, total: usize },
+    /// The post-game report is ready.
+    Report(Box<Report>),
+    /// The evaluation of the position `fen`, from White's point of view.
+    Evaluation { fen: String, score: Score },
+    /// The analysis engine's best lines so far for the position of search `generation`.
+    Analysis {
+        generation: u64,
+        lines: Vec<SearchResult>,
+    },
+    /// The analysis engine failed and stopped.
+    AnalysisFailed(String),
 }
 
 /// Terminal event handler.
@@ -88,6 +107,11 @@ impl EventHandler {
         // reference to it
         let _ = self.sender.send(Event::App(app_event));
     }
+
+    /// A sender for queuing events from other tasks.
+    pub fn sender(&self) -> mpsc::UnboundedSender<Event> {
+        self.sender.clone()
+    }
 }
 
 /// A thread that handles reading crossterm events and emitting tick events on a regular schedule.
@@ -106,7 +130,7 @@ impl EventTask {
     ///
     /// This function emits tick events at a fixed rate and polls for crossterm events in between.
     async fn run(self) -> color_eyre::Result<()> {
-        let tick_rate = Duration::from_secs_f64(TICK_FPS);
+        let tick_rate = Duration::from_secs_f64(1.0 / TICK_FPS);
         let mut reader = crossterm::event::EventStream::new();
         let mut tick = tokio::time::interval(tick_rate);
         loop {
@@ -1,27 +1,295 @@
 extern crate serde_json;
-use crate::pieces::{Color, Piece, PieceType};
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use color_eyre::eyre::{OptionExt, bail};
+
+use crate::pieces::{self, Color, Piece, PieceType};
+use crate::variant::VariantKind;
+use crate::zobrist;
+
+/// FEN of the standard starting position.
+pub const STANDARD_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Special behaviour attached to a move beyond moving a piece from one square to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum MoveKind {
+    Normal,
+    /// A pawn advancing two squares, which opens an en passant square.
+    DoublePush,
+    /// A pawn capturing a pawn that just double-pushed past it.
+    EnPassant,
+    /// The king move of a castle; the rook travels between the given squares.
+    Castle {
+        rook_from: (usize, usize),
+        rook_to: (usize, usize),
+    },
+    /// A piece placed from the mover's pocket onto `to`; `from` equals `to`.
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct Move {
+    pub piece: Piece,
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub kind: MoveKind,
+    pub captured: Option<Piece>,
+    pub promotion: Option<PieceType>,
+}
+
+impl Move {
+    pub fn new(piece: Piece, from: (usize, usize), to: (usize, usize)) -> Self {
+        Self {
+            piece,
+            from,
+            to,
+            kind: MoveKind::Normal,
+            captured: None,
+            promotion: None,
+        }
+    }
+
+    /// A Crazyhouse drop of `piece` onto `to`.
+    pub fn drop(piece: Piece, to: (usize, usize)) -> Self {
+        Self {
+            kind: MoveKind::Drop,
+            ..Self::new(piece, to, to)
+        }
+    }
+
+    pub fn is_drop(&self) -> bool {
+        self.kind == MoveKind::Drop
+    }
+
+    pub fn is_capture(&self) -> bool {
+        self.captured.is_some()
+    }
+
+    /// Square the captured piece stood on, which differs from `to` for en passant.
+    pub fn capture_square(&self) -> (usize, usize) {
+        match self.kind {
+            MoveKind::EnPassant => (self.from.0, self.to.1),
+            _ => self.to,
+        }
+    }
+
+    /// Long algebraic (UCI) notation such as `e2e4` or `e7e8q`; drops are written `P@e4`.
+    pub fn to_uci(&self, board: &Board) -> String {
+        if self.is_drop() {
+            return format!(
+                "{}@{}",
+                self.piece.piece_type.to_char().to_ascii_uppercase(),
+                board.square_name(self.to)
+            );
+        }
+        let mut uci = format!(
+            "{}{}",
+            board.square_name(self.from),
+            board.square_name(self.to)
+        );
+        if let Some(promotion) = self.promotion {
+            uci.push(promotion.to_char());
+        }
+        uci
+    }
+}
 
-pub type Move = (Piece, (usize, usize), (usize, usize));
 pub type MoveHistory = Vec<Move>;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize)]
+pub struct CastlingRights {
+    pub white_king_side: bool,
+    pub white_queen_side: bool,
+    pub black_king_side: bool,
+    pub black_queen_side: bool,
+}
+
+impl CastlingRights {
+    pub fn get(&self, color: Color, king_side: bool) -> bool {
+        match (color, king_side) {
+            (Color::White, true) => self.white_king_side,
+            (Color::White, false) => self.white_queen_side,
+            (Color::Black, true) => self.black_king_side,
+            (Color::Black, false) => self.black_queen_side,
+        }
+    }
+
+    pub fn clear(&mut self, color: Color, king_side: bool) {
+        self.set(color, king_side, false);
+    }
+
+    pub fn set(&mut self, color: Color, king_side: bool, on: bool) {
+        match (color, king_side) {
+            (Color::White, true) => self.white_king_side = on,
+            (Color::White, false) => self.white_queen_side = on,
+            (Color::Black, true) => self.black_king_side = on,
+            (Color::Black, false) => self.black_queen_side = on,
+        }
+    }
+
+    /// Drops any right whose king or rook is no longer on its home square.
+    fn refresh(&mut self, board: &Board) {
+        for color in [Color::White, Color::Black] {
+            for king_side in [true, false] {
+                let home = board
+                    .castling_squares(color, king_side)
+                    .is_some_and(|squares| {
+                        board.get_piece_at(squares.king_from)
+                            == Some(&Piece::new(color, PieceType::King))
+                            && board.get_piece_at(squares.rook_from)
+                                == Some(&Piece::new(color, PieceType::Rook))
+                    });
+                if !home {
+                    self.clear(color, king_side);
+                }
+            }
+        }
+    }
+
+    /// The FEN castling field, such as `KQk` or `-`.
+    pub fn to_fen(self) -> String {
+        let mut fen = String::new();
+        for (flag, c) in [
+            (self.white_king_side, 'K'),
+            (self.white_queen_side, 'Q'),
+            (self.black_king_side, 'k'),
+            (self.black_queen_side, 'q'),
+        ] {
+            if flag {
+                fen.push(c);
+            }
+        }
+        if fen.is_empty() {
+            fen.push('-');
+        }
+        fen
+    }
+
+    fn from_fen(field: &str) -> color_eyre::Result<Self> {
+        let mut rights = Self::default();
+        for c in field.chars() {
+            match c {
+                'K' => rights.white_king_side = true,
+                'Q' => rights.white_queen_side = true,
+                'k' => rights.black_king_side = true,
+                'q' => rights.black_queen_side = true,
+                '-' => {}
+                _ => bail!("invalid castling field `{field}`"),
+            }
+        }
+        Ok(rights)
+    }
+}
+
+/// Pieces held in hand in Crazyhouse, ready to be dropped back onto the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize)]
+pub struct Pocket {
+    counts: [u32; 9],
+}
+
+impl Pocket {
+    pub fn count(&self, piece_type: PieceType) -> u32 {
+        self.counts[piece_type as usize - 1]
+    }
+
+    pub fn add(&mut self, piece_type: PieceType) {
+        self.counts[piece_type as usize - 1] += 1;
+    }
+
+    /// Takes one piece of the given type out of the pocket, returning whether there was one.
+    pub fn remove(&mut self, piece_type: PieceType) -> bool {
+        let count = &mut self.counts[piece_type as usize - 1];
+        if *count == 0 {
+            return false;
+        }
+        *count -= 1;
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.iter().all(|&count| count == 0)
+    }
+
+    /// Piece types held, most valuable first, with their counts.
+    pub fn iter(&self) -> impl Iterator<Item = (PieceType, u32)> + '_ {
+        [
+            PieceType::Amazon,
+            PieceType::Queen,
+            PieceType::Chancellor,
+            PieceType::Archbishop,
+            PieceType::Rook,
+            PieceType::Bishop,
+            PieceType::Knight,
+            PieceType::Pawn,
+        ]
+        .into_iter()
+        .map(|piece_type| (piece_type, self.count(piece_type)))
+        .filter(|&(_, count)| count > 0)
+    }
+}
+
+/// How a finished game ended.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Outcome {
+    /// `None` for a draw.
+    pub winner: Option<Color>,
+    pub reason: String,
+}
+
+impl Outcome {
+    pub fn win(winner: Color, reason: &str) -> Self {
+        Self {
+            winner: Some(winner),
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn draw(reason: &str) -> Self {
+        Self {
+            winner: None,
+            reason: reason.to_string(),
+        }
+    }
+
+    /// PGN-style result string.
+    pub fn result(&self) -> &'static str {
+        match self.winner {
+            Some(Color::White) => "1-0",
+            Some(Color::Black) => "0-1",
+            None => "1/2-1/2",
+        }
+    }
+}
+
 // --- Game struct and impl ---
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Game {
     board: Board,
     current_turn: Color,
     game_over: bool,
     move_history: MoveHistory,
+    variant: VariantKind,
+    castling: CastlingRights,
+    en_passant: Option<(usize, usize)>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    /// Checks each side still has to give; only consulted by Three-check.
+    remaining_checks: [u32; 2],
+    /// Pieces in hand per colour; only used by Crazyhouse.
+    pockets: [Pocket; 2],
+    /// Squares holding promoted pieces, which return to the pocket as pawns when captured.
+    promoted: HashSet<(usize, usize)>,
+    outcome: Option<Outcome>,
+    /// FEN of the position before the first move in `move_history`.
+    start_fen: String,
+    /// Keys of the positions since the last capture or pawn move, the current one last, for
+    /// counting repetitions.
+    positions: Vec<u64>,
 }
 impl Default for Game {
     fn default() -> Self {
-        let mut board = Board::default();
-        board.new();
-        Self {
-            board,
-            current_turn: Color::White,
-            game_over: false,
-            move_history: Vec::new(),
-        }
+        Self::with_variant(VariantKind::Standard)
     }
 }
 impl Game {
@@ -29,10 +297,124 @@ impl Game {
         Self::default()
     }
 
+    /// Starts a new game from the variant's initial position.
+    pub fn with_variant(variant: VariantKind) -> Self {
+        Self::from_fen(variant.rules().starting_fen(), variant)
+            .expect("variant starting FEN is valid")
+    }
+
+    /// Loads a position from FEN. Three-check positions may carry a `W+B` remaining-checks field
+    /// after the en passant square, and Crazyhouse positions a pocket such as `[QRp]` after the
+    /// placement, with `~` marking promoted pieces.
+    pub fn from_fen(fen: &str, variant: VariantKind) -> color_eyre::Result<Self> {
+        let mut fields = fen.split_whitespace().peekable();
+        let (placement, pocket) = split_pocket(fields.next().ok_or_eyre("empty FEN")?, variant);
+        let (board, promoted) = Board::parse_fen_placement(placement)?;
+        let piece_types = variant.rules().piece_types();
+        for (_, piece) in board.pieces(Color::White).chain(board.pieces(Color::Black)) {
+            if !piece_types.contains(&piece.piece_type) {
+                bail!("{} has no `{}` pieces", variant.name(), piece.fen_char());
+            }
+        }
+        let mut pockets = [Pocket::default(); 2];
+        for c in pocket.chars() {
+            let piece = Piece::from_fen_char(c)
+                .filter(|piece| piece_types.contains(&piece.piece_type))
+                .ok_or_eyre(format!("invalid pocket piece `{c}`"))?;
+            pockets[piece.color as usize].add(piece.piece_type);
+        }
+        let current_turn = match fields.next().unwrap_or("w") {
+            "w" => Color::White,
+            "b" => Color::Black,
+            other => bail!("invalid side to move `{other}`"),
+        };
+        let castling = CastlingRights::from_fen(fields.next().unwrap_or("-"))?;
+        let en_passant = match fields.next().unwrap_or("-") {
+            "-" => None,
+            square => Some(
+                board
+                    .parse_square(square)
+                    .ok_or_eyre(format!("invalid en passant square `{square}`"))?,
+            ),
+        };
+        let mut remaining_checks = [3, 3];
+        if let Some(checks) = fields.next_if(|field| field.contains('+')) {
+            let (white, black) = checks.split_once('+').unwrap_or_default();
+            remaining_checks = [white.parse()?, black.parse()?];
+        }
+        let halfmove_clock = fields.next().map(str::parse).transpose()?.unwrap_or(0);
+        let fullmove_number = fields.next().map(str::parse).transpose()?.unwrap_or(1);
+
+        let mut game = Self {
+            board,
+            current_turn,
+            game_over: false,
+            move_history: Vec::new(),
+            variant,
+            castling,
+            en_passant,
+            halfmove_clock,
+            fullmove_number,
+            remaining_checks,
+            pockets,
+            promoted,
+            outcome: None,
+            start_fen: String::new(),
+            positions: Vec::new(),
+        };
+        game.castling.refresh(&game.board);
+        game.positions.push(game.position_key());
+        game.update_outcome();
+        game.start_fen = game.to_fen();
+        Ok(game)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let placement = if self.variant == VariantKind::Crazyhouse {
+            let mut pocket = String::new();
+            for color in [Color::White, Color::Black] {
+                for (piece_type, count) in self.pockets[color as usize].iter() {
+                    let c = Piece::new(color, piece_type).fen_char();
+                    pocket.extend(std::iter::repeat_n(c, count as usize));
+                }
+            }
+            format!(
+                "{}[{pocket}]",
+                self.board.fen_placement_marking(&self.promoted)
+            )
+        } else {
+            self.board.to_fen_placement()
+        };
+        let mut fields = vec![
+            placement,
+            match self.current_turn {
+                Color::White => "w".to_string(),
+                Color::Black => "b".to_string(),
+            },
+            self.castling.to_fen(),
+            self.en_passant
+                .map(|square| self.board.square_name(square))
+                .unwrap_or_else(|| "-".to_string()),
+        ];
+        if self.variant == VariantKind::ThreeCheck {
+            fields.push(format!(
+                "{}+{}",
+                self.remaining_checks[0], self.remaining_checks[1]
+            ));
+        }
+        fields.push(self.halfmove_clock.to_string());
+        fields.push(self.fullmove_number.to_string());
+        fields.join(" ")
+    }
+
     pub fn get_board(&self) -> &Board {
         &self.board
     }
 
+    pub(crate) fn board_mut(&mut self) -> &mut Board {
+        &mut self.board
+    }
+
     pub fn get_current_turn(&self) -> Color {
         self.current_turn
     }
@@ -45,30 +427,384 @@ impl Game {
         &self.move_history
     }
 
-    pub fn make_move(&mut self, piece: Piece, from: (usize, usize), to: (usize, usize)) {
+    pub fn variant(&self) -> VariantKind {
+        self.variant
+    }
+
+    /// FEN of the position the game started from.
+    pub fn start_fen(&self) -> &str {
+        &self.start_fen
+    }
+
+    pub fn castling_rights(&self) -> CastlingRights {
+        self.castling
+    }
+
+    pub fn en_passant(&self) -> Option<(usize, usize)> {
+        self.en_passant
+    }
+
+    /// Plies since the last capture or pawn move, for the 50-move rule.
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    /// How many times the current position has occurred, counting this time.
+    pub fn repetitions(&self) -> usize {
+        let current = self.positions.last();
+        self.positions
+            .iter()
+            .filter(|&key| Some(key) == current)
+            .count()
+    }
+
+    /// A key that tells positions apart for repetitions: the Polyglot key in standard chess,
+    /// and elsewhere a hash of everything a variant's position holds besides the move clocks.
+    fn position_key(&self) -> u64 {
+        if self.variant == VariantKind::Standard
+            && let Some(key) = zobrist::polyglot(self)
+        {
+            return key;
+        }
+        let mut hasher = DefaultHasher::new();
+        let fen = self.to_fen();
+        // Placement with any pocket, side to move and castling rights
+        for field in fen.split_whitespace().take(3) {
+            field.hash(&mut hasher);
+        }
+        self.capturable_en_passant().hash(&mut hasher);
+        self.remaining_checks.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// The en passant square, if a pawn of the side to move stands next to the pawn that can
+    /// be taken. Position keys only count it then, as FEN records it after every double step.
+    pub fn capturable_en_passant(&self) -> Option<(usize, usize)> {
+        let square = self.en_passant?;
+        let pawn = Piece::new(self.current_turn, PieceType::Pawn);
+        let row = -self.current_turn.pawn_direction();
+        [-1, 1]
+            .iter()
+            .any(|&dc| {
+                self.board
+                    .offset(square, row, dc)
+                    .and_then(|from| self.board.get_piece_at(from))
+                    == Some(&pawn)
+            })
+            .then_some(square)
+    }
+
+    pub fn outcome(&self) -> Option<&Outcome> {
+        self.outcome.as_ref()
+    }
+
+    /// Ends the game as a loss for `color`, whose clock has run out.
+    pub fn flag(&mut self, color: Color) {
+        if !self.game_over {
+            self.game_over = true;
+            self.outcome = Some(Outcome::win(color.opposite(), "timeout"));
+        }
+    }
+
+    pub fn remaining_checks(&self, color: Color) -> u32 {
+        self.remaining_checks[color as usize]
+    }
+
+    pub(crate) fn remaining_checks_mut(&mut self, color: Color) -> &mut u32 {
+        &mut self.remaining_checks[color as usize]
+    }
+
+    pub fn pocket(&self, color: Color) -> &Pocket {
+        &self.pockets[color as usize]
+    }
+
+    pub(crate) fn pocket_mut(&mut self, color: Color) -> &mut Pocket {
+        &mut self.pockets[color as usize]
+    }
+
+    pub fn is_promoted(&self, square: (usize, usize)) -> bool {
+        self.promoted.contains(&square)
+    }
+
+    pub(crate) fn promoted_mut(&mut self) -> &mut HashSet<(usize, usize)> {
+        &mut self.promoted
+    }
+
+    /// Whether the side to move is in check under the variant's rules.
+    pub fn is_in_check(&self) -> bool {
+        self.variant
+            .rules()
+            .is_in_check(&self.board, self.current_turn)
+    }
+
+    /// All legal moves for the side to move, empty once the variant declares the game over.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let rules = self.variant.rules();
+        if rules.variant_outcome(self).is_some() {
+            return vec![];
+        }
+        rules.legal_moves(self)
+    }
+
+    /// Legal moves of the piece standing on `from`.
+    pub fn legal_moves_from(&self, from: (usize, usize)) -> Vec<Move> {
+        self.legal_moves()
+            .into_iter()
+            .filter(|mv| mv.from == from)
+            .collect()
+    }
+
+    /// The legal move from `from` to `to`. Without an explicit promotion piece, promotions pick
+    /// the first type the variant offers (a queen in most variants).
+    pub fn find_move(
+        &self,
+        from: (usize, usize),
+        to: (usize, usize),
+        promotion: Option<PieceType>,
+    ) -> Option<Move> {
+        self.legal_moves_from(from)
+            .into_iter()
+            .filter(|mv| mv.to == to && !mv.is_drop())
+            .find(|mv| promotion.is_none() || mv.promotion == promotion)
+    }
+
+    /// Pseudo-legal moves under the standard rules: piece moves, pawn pushes, en passant,
+    /// promotions and castling. Variants filter or extend this list.
+    pub fn generate_pseudo_legal_moves(&self) -> Vec<Move> {
+        let rules = self.variant.rules();
+        let color = self.current_turn;
+        let (rows, cols) = self.board.dimensions();
+        let mut moves = vec![];
+
+        for row in 0..rows {
+            for col in 0..cols {
+                let from = (row, col);
+                let Some(&piece) = self.board.get_piece_at(from) else {
+                    continue;
+                };
+                if piece.color != color {
+                    continue;
+                }
+
+                for to in piece.get_legal_moves(&self.board, from) {
+                    let mut mv = Move::new(piece, from, to);
+                    mv.captured = self.board.get_piece_at(to).copied();
+                    if piece.piece_type == PieceType::Pawn {
+                        if from.0.abs_diff(to.0) == 2 {
+                            mv.kind = MoveKind::DoublePush;
+                        }
+                        if to.0 == self.board.back_rank(color.opposite()) {
+                            for &promotion in rules.promotion_types() {
+                                moves.push(Move {
+                                    promotion: Some(promotion),
+                                    ..mv
+                                });
+                            }
+                            continue;
+                        }
+                    }
+                    moves.push(mv);
+                }
+
+                match piece.piece_type {
+                    PieceType::Pawn => self.push_en_passant(piece, from, &mut moves),
+                    PieceType::King => self.push_castling(piece, from, &mut moves),
+                    _ => {}
+                }
+            }
+        }
+
+        moves
+    }
+
+    fn push_en_passant(&self, piece: Piece, from: (usize, usize), moves: &mut Vec<Move>) {
+        let Some(target) = self.en_passant else {
+            return;
+        };
+        let forward = from.0 as isize + piece.color.pawn_direction();
+        if target.0 as isize == forward && target.1.abs_diff(from.1) == 1 {
+            let captured = self.board.get_piece_at((from.0, target.1)).copied();
+            if captured.is_some_and(|c| c.piece_type == PieceType::Pawn && c.color != piece.color) {
+                moves.push(Move {
+                    kind: MoveKind::EnPassant,
+                    captured,
+                    ..Move::new(piece, from, target)
+                });
+            }
+        }
+    }
+
+    fn push_castling(&self, piece: Piece, from: (usize, usize), moves: &mut Vec<Move>) {
+        let rules = self.variant.rules();
+        let color = piece.color;
+        if rules.is_king_square_attacked(&self.board, from, color) {
+            return;
+        }
+
+        for king_side in [true, false] {
+            let Some(squares) = self.board.castling_squares(color, king_side) else {
+                continue;
+            };
+            if from != squares.king_from || !self.castling.get(color, king_side) {
+                continue;
+            }
+            let row = from.0;
+            let cols = [
+                from.1,
+                squares.rook_from.1,
+                squares.king_to.1,
+                squares.rook_to.1,
+            ];
+            let (low, high) = (*cols.iter().min().unwrap(), *cols.iter().max().unwrap());
+            let blocked = (low..=high)
+                .filter(|&col| col != from.1 && col != squares.rook_from.1)
+                .any(|col| self.board.get_piece_at((row, col)).is_some());
+            if blocked {
+                continue;
+            }
+            // Every square the king crosses or lands on must be safe
+            let path = if squares.king_to.1 > from.1 {
+                from.1 + 1..squares.king_to.1 + 1
+            } else {
+                squares.king_to.1..from.1
+            };
+            let path_attacked = path
+                .into_iter()
+                .any(|col| rules.is_king_square_attacked(&self.board, (row, col), color));
+            if !path_attacked {
+                moves.push(Move {
+                    kind: MoveKind::Castle {
+                        rook_from: squares.rook_from,
+                        rook_to: squares.rook_to,
+                    },
+                    ..Move::new(piece, from, squares.king_to)
+                });
+            }
+        }
+    }
+
+    /// Plays a move that came from [`Game::legal_moves`] and updates the game outcome.
+    pub fn make_move(&mut self, mv: Move) {
+        self.play(mv);
+        if self.halfmove_clock == 0 {
+            self.positions.clear();
+        }
+        self.positions.push(self.position_key());
+        self.update_outcome();
+    }
+
+    /// Applies a move and its variant side effects without re-evaluating the outcome.
+    pub(crate) fn play(&mut self, mv: Move) {
         // Update the board and move history
-        self.board.squares[to.0][to.1] = Some(piece);
-        self.board.squares[from.0][from.1] = None;
-        self.move_history.push((piece, from, to));
+        self.board.apply_move(&mv);
+
+        if mv.piece.piece_type == PieceType::Pawn || mv.is_capture() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if self.current_turn == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.en_passant = match mv.kind {
+            MoveKind::DoublePush => Some(((mv.from.0 + mv.to.0) / 2, mv.from.1)),
+            _ => None,
+        };
+
+        self.variant.rules().after_move(self, &mv);
+        self.castling.refresh(&self.board);
+        self.move_history.push(mv);
 
         // Switch turns
         self.current_turn = self.current_turn.opposite();
     }
+
+    fn update_outcome(&mut self) {
+        let rules = self.variant.rules();
+        let outcome = rules.variant_outcome(self).or_else(|| {
+            if rules.legal_moves(self).is_empty() {
+                Some(rules.no_moves_outcome(self))
+            } else if self.halfmove_clock >= 150 {
+                // The 50-move and threefold repetition draws must be claimed, so only their
+                // automatic 75-move and fivefold limits end the game by themselves
+                Some(Outcome::draw("75-move rule"))
+            } else if self.repetitions() >= 5 {
+                Some(Outcome::draw("fivefold repetition"))
+            } else {
+                None
+            }
+        });
+        self.game_over = outcome.is_some();
+        self.outcome = outcome;
+    }
+
+    /// Counts leaf nodes of the legal move tree to the given depth.
+    pub fn perft(&self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .into_iter()
+            .map(|mv| {
+                let mut child = self.clone();
+                child.play(mv);
+                child.perft(depth - 1)
+            })
+            .sum()
+    }
+}
+
+/// Where the king and rook start and end for one side's castle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CastlingSquares {
+    pub king_from: (usize, usize),
+    pub rook_from: (usize, usize),
+    pub king_to: (usize, usize),
+    pub rook_to: (usize, usize),
+}
+
+/// Separates a Crazyhouse pocket from the placement field, accepting both `...RNBQKBNR[Qp]` and
+/// the nine-rank `...RNBQKBNR/Qp` forms. The latter is only recognised for Crazyhouse, where the
+/// board is always 8x8.
+fn split_pocket(field: &str, variant: VariantKind) -> (&str, &str) {
+    if let Some((placement, pocket)) = field.split_once('[') {
+        (placement, pocket.trim_end_matches(']'))
+    } else if variant == VariantKind::Crazyhouse && field.matches('/').count() == 8 {
+        field.rsplit_once('/').unwrap_or((field, ""))
+    } else {
+        (field, "")
+    }
 }
 
 // --- Board struct and impl ---
-#[derive(Default, Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
 pub struct Board {
-    squares: [[Option<Piece>; 8]; 8],
+    squares: Vec<Vec<Option<Piece>>>,
+}
+impl Default for Board {
+    fn default() -> Self {
+        Self::empty(8, 8)
+    }
 }
 impl Board {
+    /// Largest board whose squares `square_name` can label: files `a` to `z`, two-digit ranks.
+    pub const MAX_FILES: usize = 26;
+    pub const MAX_RANKS: usize = 99;
+
+    /// An empty board with the given number of ranks (rows) and files (columns).
+    pub fn empty(rows: usize, cols: usize) -> Self {
+        Self {
+            squares: vec![vec![None; cols]; rows],
+        }
+    }
+
     pub fn new(&mut self) -> &mut Self {
         // clear the board
-        for row in self.squares.iter_mut() {
-            for square in row.iter_mut() {
-                *square = None;
-            }
-        }
+        *self = Board::empty(8, 8);
 
         // Set up the initial position of the pieces
         for (row, squares) in self.squares.iter_mut().enumerate() {
@@ -124,16 +860,263 @@ impl Board {
         self
     }
 
+    /// Parses the piece placement field of a FEN string.
+    pub fn from_fen_placement(placement: &str) -> color_eyre::Result<Self> {
+        Self::parse_fen_placement(placement).map(|(board, _)| board)
+    }
+
+    /// Parses a placement field, also returning the squares of pieces marked promoted with `~`.
+    pub(crate) fn parse_fen_placement(
+        placement: &str,
+    ) -> color_eyre::Result<(Self, HashSet<(usize, usize)>)> {
+        let mut squares = vec![];
+        let mut promoted = HashSet::new();
+        for (row, rank) in placement.split('/').enumerate() {
+            let mut squares_in_rank = vec![];
+            // Empty-square counts can span several digits on boards wider than 9 files
+            let mut skip: usize = 0;
+            for c in rank.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    skip = skip
+                        .checked_mul(10)
+                        .and_then(|skip| skip.checked_add(digit as usize))
+                        .filter(|&skip| skip <= Self::MAX_FILES)
+                        .ok_or_eyre(format!("`{rank}` has more than {} files", Self::MAX_FILES))?;
+                    continue;
+                }
+                squares_in_rank.extend(std::iter::repeat_n(None, skip));
+                skip = 0;
+                if c == '~' {
+                    if squares_in_rank.is_empty() {
+                        bail!("promotion marker without a piece in `{rank}`");
+                    }
+                    promoted.insert((row, squares_in_rank.len() - 1));
+                } else {
+                    let piece = Piece::from_fen_char(c)
+                        .ok_or_eyre(format!("invalid piece `{c}` in `{placement}`"))?;
+                    squares_in_rank.push(Some(piece));
+                }
+            }
+            squares_in_rank.extend(std::iter::repeat_n(None, skip));
+            if squares_in_rank.len() > Self::MAX_FILES {
+                bail!("`{rank}` has more than {} files", Self::MAX_FILES);
+            }
+            squares.push(squares_in_rank);
+        }
+
+        let (rows, cols) = (squares.len(), squares[0].len());
+        if squares.iter().any(|rank| rank.len() != cols) {
+            bail!("ranks of `{placement}` do not all cover the same number of files");
+        }
+        if rows > Self::MAX_RANKS {
+            bail!("`{placement}` has more than {} ranks", Self::MAX_RANKS);
+        }
+        if rows < 2 || cols < 2 {
+            bail!("`{placement}` is too small to play on: {cols}x{rows}");
+        }
+        Ok((Board { squares }, promoted))
+    }
+
+    pub fn to_fen_placement(&self) -> String {
+        self.fen_placement_marking(&HashSet::new())
+    }
+
+    /// Placement field with `~` after each piece on a `promoted` square.
+    pub(crate) fn fen_placement_marking(&self, promoted: &HashSet<(usize, usize)>) -> String {
+        let mut ranks = vec![];
+        for (row, squares) in self.squares.iter().enumerate() {
+            let mut rank = String::new();
+            let mut empty = 0;
+            for (col, square) in squares.iter().enumerate() {
+                match square {
+                    Some(piece) => {
+                        if empty > 0 {
+                            rank.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        rank.push(piece.fen_char());
+                        if promoted.contains(&(row, col)) {
+                            rank.push('~');
+                        }
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                rank.push_str(&empty.to_string());
+            }
+            ranks.push(rank);
+        }
+        ranks.join("/")
+    }
+
     pub fn get_piece_at(&self, position: (usize, usize)) -> Option<&Piece> {
-        if position.0 < 8 && position.1 < 8 {
-            self.squares[position.0][position.1].as_ref()
+        self.squares.get(position.0)?.get(position.1)?.as_ref()
+    }
+
+    pub fn set_piece_at(&mut self, position: (usize, usize), piece: Option<Piece>) {
+        self.squares[position.0][position.1] = piece;
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.squares.len(), self.squares[0].len())
+    }
+
+    /// Row holding the given side's pieces at the start of the game.
+    pub fn back_rank(&self, color: Color) -> usize {
+        match color {
+            Color::White => self.dimensions().0 - 1,
+            Color::Black => 0,
+        }
+    }
+
+    /// Castling geometry for any board width: the king starts on the middle file, the rook in
+    /// the corner, and they finish two and three files in from that corner (g1/f1 and c1/d1 on
+    /// an 8x8 board). `None` on boards too narrow to hold those squares.
+    pub fn castling_squares(&self, color: Color, king_side: bool) -> Option<CastlingSquares> {
+        let row = self.back_rank(color);
+        let cols = self.dimensions().1;
+        let (rook, king_to, rook_to) = if king_side {
+            (cols - 1, cols.checked_sub(2)?, cols.checked_sub(3)?)
+        } else {
+            (0, 2, 3)
+        };
+        if rook_to >= cols || rook == cols / 2 {
+            return None;
+        }
+        Some(CastlingSquares {
+            king_from: (row, cols / 2),
+            rook_from: (row, rook),
+            king_to: (row, king_to),
+            rook_to: (row, rook_to),
+        })
+    }
+
+    /// The square reached by stepping `(dr, dc)` from `position`, if it is on the board.
+    pub fn offset(&self, position: (usize, usize), dr: isize, dc: isize) -> Option<(usize, usize)> {
+        let (rows, cols) = self.dimensions();
+        let r = position.0 as isize + dr;
+        let c = position.1 as isize + dc;
+        if (0..rows as isize).contains(&r) && (0..cols as isize).contains(&c) {
+            Some((r as usize, c as usize))
         } else {
             None
         }
     }
 
-    pub fn dimensions(&self) -> (usize, usize) {
-        (self.squares.len(), self.squares[0].len())
+    /// Algebraic name of a square, e.g. `(7, 4)` is `e1`.
+    pub fn square_name(&self, position: (usize, usize)) -> String {
+        format!(
+            "{}{}",
+            (b'a' + position.1 as u8) as char,
+            self.dimensions().0 - position.0
+        )
+    }
+
+    /// Parses an algebraic square name such as `e4`.
+    pub fn parse_square(&self, name: &str) -> Option<(usize, usize)> {
+        let (rows, cols) = self.dimensions();
+        let mut chars = name.chars();
+        let file = chars.next()?;
+        let rank: usize = chars.as_str().parse().ok()?;
+        let col = (file as usize).checked_sub('a' as usize)?;
+        if col >= cols || rank == 0 || rank > rows {
+            return None;
+        }
+        Some((rows - rank, col))
+    }
+
+    /// Every piece of `color` with its square.
+    pub fn pieces(&self, color: Color) -> impl Iterator<Item = ((usize, usize), Piece)> + '_ {
+        self.squares
+            .iter()
+            .enumerate()
+            .flat_map(move |(row, squares)| {
+                squares.iter().enumerate().filter_map(move |(col, square)| {
+                    square
+                        .filter(|piece| piece.color == color)
+                        .map(|piece| ((row, col), piece))
+                })
+            })
+    }
+
+    pub fn king_square(&self, color: Color) -> Option<(usize, usize)> {
+        let king = Piece::new(color, PieceType::King);
+        self.squares.iter().enumerate().find_map(|(row, squares)| {
+            squares
+                .iter()
+                .position(|square| *square == Some(king))
+                .map(|col| (row, col))
+        })
+    }
+
+    /// Whether any piece of colour `by` attacks `square`.
+    pub fn is_square_attacked(&self, square: (usize, usize), by: Color) -> bool {
+        let attacker_at = |position: Option<(usize, usize)>| {
+            position
+                .and_then(|position| self.get_piece_at(position))
+                .filter(|piece| piece.color == by)
+        };
+
+        let pawn_row = -by.pawn_direction();
+        if [-1, 1].iter().any(|&dc| {
+            attacker_at(self.offset(square, pawn_row, dc))
+                .is_some_and(|piece| piece.piece_type == PieceType::Pawn)
+        }) {
+            return true;
+        }
+
+        // Movement vectors are symmetric, so a piece attacks `square` exactly when it sits at the
+        // end of one of its own vectors from `square`.
+        for &(dr, dc) in pieces::all_leaps() {
+            if attacker_at(self.offset(square, dr, dc))
+                .is_some_and(|piece| piece.piece_type.def().leaps.contains(&(dr, dc)))
+            {
+                return true;
+            }
+        }
+
+        for &(dr, dc) in pieces::all_rides() {
+            let mut current = self.offset(square, dr, dc);
+            while let Some(position) = current {
+                if self.get_piece_at(position).is_some() {
+                    if attacker_at(current)
+                        .is_some_and(|piece| piece.piece_type.def().rides.contains(&(dr, dc)))
+                    {
+                        return true;
+                    }
+                    break;
+                }
+                current = self.offset(position, dr, dc);
+            }
+        }
+        false
+    }
+
+    /// Whether the king of `color` is attacked; boards without that king are never in check.
+    pub fn is_in_check(&self, color: Color) -> bool {
+        self.king_square(color)
+            .is_some_and(|square| self.is_square_attacked(square, color.opposite()))
+    }
+
+    /// Moves pieces on the board for `mv`, including the rook of a castle, the pawn taken en
+    /// passant and promotions. Clocks and rights are the [`Game`]'s concern.
+    pub fn apply_move(&mut self, mv: &Move) {
+        self.set_piece_at(mv.from, None);
+        match mv.kind {
+            MoveKind::EnPassant => self.set_piece_at(mv.capture_square(), None),
+            MoveKind::Castle { rook_from, rook_to } => {
+                let rook = self.get_piece_at(rook_from).copied();
+                self.set_piece_at(rook_from, None);
+                self.set_piece_at(rook_to, rook);
+            }
+            _ => {}
+        }
+        let placed = match mv.promotion {
+            Some(piece_type) => Piece::new(mv.piece.color, piece_type),
+            None => mv.piece,
+        };
+        self.set_piece_at(mv.to, Some(placed));
     }
 
     pub fn to_json(&self) -> String {
@@ -145,51 +1128,7 @@ impl Board {
             for square in row.iter() {
                 match square {
                     Some(piece) => {
-                        // ♔♕♖♗♘♙  ♚♛♜♝♞♟
-                        let symbol = match piece.piece_type {
-                            PieceType::King => {
-                                if piece.color == Color::White {
-                                    '♔'
-                                } else {
-                                    '♚'
-                                }
-                            }
-                            PieceType::Queen => {
-                                if piece.color == Color::White {
-                                    '♕'
-                                } else {
-                                    '♛'
-                                }
-                            }
-                            PieceType::Rook => {
-                                if piece.color == Color::White {
-                                    '♖'
-                                } else {
-                                    '♜'
-                                }
-                            }
-                            PieceType::Bishop => {
-                                if piece.color == Color::White {
-                                    '♗'
-                                } else {
-                                    '♝'
-                                }
-                            }
-                            PieceType::Knight => {
-                                if piece.color == Color::White {
-                                    '♘'
-                                } else {
-                                    '♞'
-                                }
-                            }
-                            PieceType::Pawn => {
-                                if piece.color == Color::White {
-                                    '♙'
-                                } else {
-                                    '♟'
-                                }
-                            }
-                        };
+                        let symbol = piece.unicode_symbol();
                         print!("{} ", symbol);
                     }
                     None => print!(". "),
@@ -0,0 +1,786 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use color_eyre::eyre::{bail, eyre};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use strum_macros::EnumIter;
+
+use crate::app::AppState;
+
+/// Something a key can be bound to.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    EnumIter,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Choose the highlighted entry, or pick up and drop a piece.
+    Select,
+    /// Leave the current screen, quitting from the main menu.
+    Back,
+    Flip,
+    /// Open the command bar to type a move.
+    Command,
+    Increment,
+    Decrement,
+    /// Jump to the first position of a replay, or the first row of a list.
+    First,
+    /// Jump to the last position of a replay's current line, or the last row of a list.
+    Last,
+    /// Start or stop stepping through a replay on its own.
+    Autoplay,
+    PageUp,
+    PageDown,
+    /// Reverse the order of a sorted list.
+    Reverse,
+    /// Look for games reaching a position.
+    Search,
+    /// Take back the last move of an analysis.
+    Previous,
+    /// Replay the next move of an analysis line.
+    Next,
+    /// Switch to the next variation tried in place of the last move.
+    Variation,
+    /// Start or stop the analysis engine.
+    Engine,
+    /// Empty the square under the cursor in the board editor.
+    Remove,
+    /// Empty the whole board in the board editor.
+    Clear,
+    /// Give the move to the other side in the board editor.
+    SideToMove,
+    /// Toggle the castling right of the king or rook under the cursor.
+    Castling,
+    /// Toggle the en passant target on the square under the cursor.
+    EnPassant,
+    /// Start a game from the edited position.
+    Play,
+    /// Analyse the edited position.
+    Analyse,
+    /// Show the piece to move in a puzzle, then the move itself, or the repertoire move in a
+    /// drill.
+    Hint,
+    /// Choose puzzles by theme and rating.
+    Filter,
+    /// Play the endgame drill's position again from the start.
+    Retry,
+}
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Select => "select",
+            Action::Back => "back",
+            Action::Flip => "flip",
+            Action::Command => "command",
+            Action::Increment => "increment",
+            Action::Decrement => "decrement",
+            Action::First => "first",
+            Action::Last => "last",
+            Action::Autoplay => "autoplay",
+            Action::PageUp => "page-up",
+            Action::PageDown => "page-down",
+            Action::Reverse => "reverse",
+            Action::Search => "search",
+            Action::Previous => "previous",
+            Action::Next => "next",
+            Action::Variation => "variation",
+            Action::Engine => "engine",
+            Action::Remove => "remove",
+            Action::Clear => "clear",
+            Action::SideToMove => "side-to-move",
+            Action::Castling => "castling",
+            Action::EnPassant => "en-passant",
+            Action::Play => "play",
+            Action::Analyse => "analyse",
+            Action::Hint => "hint",
+            Action::Filter => "filter",
+            Action::Retry => "retry",
+        }
+    }
+}
+
+/// Where a binding applies. Screen bindings are looked up before global ones.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    EnumIter,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scope {
+    Global,
+    MainMenu,
+    NewGame,
+    Game,
+    Analysis,
+    Editor,
+    Puzzle,
+    Repertoire,
+    Endgame,
+    Stats,
+    Replay,
+    Database,
+    Search,
+    Settings,
+    Help,
+    Credits,
+}
+
+impl Scope {
+    pub fn of(state: &AppState) -> Self {
+        match state {
+            AppState::MainMenu => Scope::MainMenu,
+            AppState::NewGame => Scope::NewGame,
+            AppState::Game => Scope::Game,
+            AppState::Analysis => Scope::Analysis,
+            AppState::Editor => Scope::Editor,
+            AppState::Puzzle => Scope::Puzzle,
+            AppState::Repertoire => Scope::Repertoire,
+            AppState::Endgame => Scope::Endgame,
+            AppState::Stats => Scope::Stats,
+            AppState::Replay => Scope::Replay,
+            AppState::Database => Scope::Database,
+            AppState::Search => Scope::Search,
+            AppState::Settings => Scope::Settings,
+            AppState::Help => Scope::Help,
+            AppState::Credits => Scope::Credits,
+        }
+    }
+}
+
+/// Extra keys layered over the defaults.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeymapPreset {
+    #[default]
+    Default,
+    /// `h`/`j`/`k`/`l` to move.
+    Vim,
+    /// `Ctrl-p`/`n`/`b`/`f` to move and `Ctrl-g` to go back.
+    Emacs,
+}
+
+impl KeymapPreset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeymapPreset::Default => "Default",
+            KeymapPreset::Vim => "Vim",
+            KeymapPreset::Emacs => "Emacs",
+        }
+    }
+
+    /// Bindings added on top of the defaults, as `(scope, action, key)`.
+    fn bindings(&self) -> &'static [(Scope, Action, &'static str)] {
+        use Action::*;
+        match self {
+            KeymapPreset::Default => &[],
+            KeymapPreset::Vim => &[
+                (Scope::MainMenu, Up, "k"),
+                (Scope::MainMenu, Down, "j"),
+                (Scope::MainMenu, Select, "l"),
+                (Scope::NewGame, Up, "k"),
+                (Scope::NewGame, Down, "j"),
+                (Scope::NewGame, Select, "l"),
+                (Scope::Game, Up, "k"),
+                (Scope::Game, Down, "j"),
+                (Scope::Game, Left, "h"),
+                (Scope::Game, Right, "l"),
+                (Scope::Analysis, Up, "k"),
+                (Scope::Analysis, Down, "j"),
+                (Scope::Analysis, Left, "h"),
+                (Scope::Analysis, Right, "l"),
+                (Scope::Editor, Up, "k"),
+                (Scope::Editor, Down, "j"),
+                (Scope::Editor, Left, "h"),
+                (Scope::Editor, Right, "l"),
+                (Scope::Puzzle, Up, "k"),
+                (Scope::Puzzle, Down, "j"),
+                (Scope::Puzzle, Left, "h"),
+                (Scope::Puzzle, Right, "l"),
+                (Scope::Repertoire, Up, "k"),
+                (Scope::Repertoire, Down, "j"),
+                (Scope::Repertoire, Left, "h"),
+                (Scope::Repertoire, Right, "l"),
+                (Scope::Endgame, Up, "k"),
+                (Scope::Endgame, Down, "j"),
+                (Scope::Endgame, Left, "h"),
+                (Scope::Endgame, Right, "l"),
+                (Scope::Stats, Up, "k"),
+                (Scope::Stats, Down, "j"),
+                (Scope::Replay, Up, "k"),
+                (Scope::Replay, Down, "j"),
+                (Scope::Replay, Left, "h"),
+                (Scope::Replay, Right, "l"),
+                (Scope::Replay, First, "g"),
+                (Scope::Replay, Last, "G"),
+                (Scope::Database, Up, "k"),
+                (Scope::Database, Down, "j"),
+                (Scope::Database, Left, "h"),
+                (Scope::Database, Right, "l"),
+                (Scope::Database, First, "g"),
+                (Scope::Database, Last, "G"),
+                (Scope::Database, PageUp, "ctrl-b"),
+                (Scope::Database, PageDown, "ctrl-f"),
+                (Scope::Search, Up, "k"),
+                (Scope::Search, Down, "j"),
+                (Scope::Search, First, "g"),
+                (Scope::Search, Last, "G"),
+                (Scope::Settings, Up, "k"),
+                (Scope::Settings, Down, "j"),
+                (Scope::Settings, Left, "h"),
+                (Scope::Settings, Right, "l"),
+            ],
+            KeymapPreset::Emacs => &[
+                (Scope::Global, Back, "ctrl-g"),
+                (Scope::MainMenu, Up, "ctrl-p"),
+                (Scope::MainMenu, Down, "ctrl-n"),
+                (Scope::NewGame, Up, "ctrl-p"),
+                (Scope::NewGame, Down, "ctrl-n"),
+                (Scope::Game, Up, "ctrl-p"),
+                (Scope::Game, Down, "ctrl-n"),
+                (Scope::Game, Left, "ctrl-b"),
+                (Scope::Game, Right, "ctrl-f"),
+                (Scope::Analysis, Up, "ctrl-p"),
+                (Scope::Analysis, Down, "ctrl-n"),
+                (Scope::Analysis, Left, "ctrl-b"),
+                (Scope::Analysis, Right, "ctrl-f"),
+                (Scope::Editor, Up, "ctrl-p"),
+                (Scope::Editor, Down, "ctrl-n"),
+                (Scope::Editor, Left, "ctrl-b"),
+                (Scope::Editor, Right, "ctrl-f"),
+                (Scope::Puzzle, Up, "ctrl-p"),
+                (Scope::Puzzle, Down, "ctrl-n"),
+                (Scope::Puzzle, Left, "ctrl-b"),
+                (Scope::Puzzle, Right, "ctrl-f"),
+                (Scope::Repertoire, Up, "ctrl-p"),
+                (Scope::Repertoire, Down, "ctrl-n"),
+                (Scope::Repertoire, Left, "ctrl-b"),
+                (Scope::Repertoire, Right, "ctrl-f"),
+                (Scope::Endgame, Up, "ctrl-p"),
+                (Scope::Endgame, Down, "ctrl-n"),
+                (Scope::Endgame, Left, "ctrl-b"),
+                (Scope::Endgame, Right, "ctrl-f"),
+                (Scope::Stats, Up, "ctrl-p"),
+                (Scope::Stats, Down, "ctrl-n"),
+                (Scope::Replay, Up, "ctrl-p"),
+                (Scope::Replay, Down, "ctrl-n"),
+                (Scope::Replay, Left, "ctrl-b"),
+                (Scope::Replay, Right, "ctrl-f"),
+                (Scope::Replay, First, "alt-<"),
+                (Scope::Replay, Last, "alt->"),
+                (Scope::Database, Up, "ctrl-p"),
+                (Scope::Database, Down, "ctrl-n"),
+                (Scope::Database, Left, "ctrl-b"),
+                (Scope::Database, Right, "ctrl-f"),
+                (Scope::Database, First, "alt-<"),
+                (Scope::Database, Last, "alt->"),
+                (Scope::Database, PageUp, "alt-v"),
+                (Scope::Database, PageDown, "ctrl-v"),
+                (Scope::Search, Up, "ctrl-p"),
+                (Scope::Search, Down, "ctrl-n"),
+                (Scope::Search, First, "alt-<"),
+                (Scope::Search, Last, "alt->"),
+                (Scope::Settings, Up, "ctrl-p"),
+                (Scope::Settings, Down, "ctrl-n"),
+                (Scope::Settings, Left, "ctrl-b"),
+                (Scope::Settings, Right, "ctrl-f"),
+            ],
+        }
+    }
+}
+
+/// Built-in bindings, as `(scope, action, key)`.
+const DEFAULT_BINDINGS: &[(Scope, Action, &str)] = {
+    use Action::*;
+    &[
+        (Scope::Global, Back, "esc"),
+        (Scope::Global, Back, "q"),
+        (Scope::Global, Back, "ctrl-c"),
+        (Scope::MainMenu, Up, "up"),
+        (Scope::MainMenu, Down, "down"),
+        (Scope::MainMenu, Increment, "right"),
+        (Scope::MainMenu, Decrement, "left"),
+        (Scope::MainMenu, Select, "enter"),
+        (Scope::NewGame, Up, "up"),
+        (Scope::NewGame, Down, "down"),
+        (Scope::NewGame, Select, "enter"),
+        (Scope::Game, Up, "up"),
+        (Scope::Game, Down, "down"),
+        (Scope::Game, Left, "left"),
+        (Scope::Game, Right, "right"),
+        (Scope::Game, Select, "enter"),
+        (Scope::Game, Select, "space"),
+        (Scope::Game, Flip, "f"),
+        (Scope::Game, Command, ":"),
+        (Scope::Game, Command, "/"),
+        (Scope::Analysis, Up, "up"),
+        (Scope::Analysis, Down, "down"),
+        (Scope::Analysis, Left, "left"),
+        (Scope::Analysis, Right, "right"),
+        (Scope::Analysis, Select, "enter"),
+        (Scope::Analysis, Select, "space"),
+        (Scope::Analysis, Flip, "f"),
+        (Scope::Analysis, Command, ":"),
+        (Scope::Analysis, Command, "/"),
+        (Scope::Analysis, Previous, "backspace"),
+        (Scope::Analysis, Previous, "["),
+        (Scope::Analysis, Next, "]"),
+        (Scope::Analysis, First, "home"),
+        (Scope::Analysis, Last, "end"),
+        (Scope::Analysis, Variation, "v"),
+        (Scope::Analysis, Engine, "e"),
+        (Scope::Analysis, Increment, "+"),
+        (Scope::Analysis, Decrement, "-"),
+        (Scope::Editor, Up, "up"),
+        (Scope::Editor, Down, "down"),
+        (Scope::Editor, Left, "left"),
+        (Scope::Editor, Right, "right"),
+        (Scope::Editor, Select, "enter"),
+        (Scope::Editor, Select, "space"),
+        (Scope::Editor, Remove, "delete"),
+        (Scope::Editor, Remove, "backspace"),
+        (Scope::Editor, Remove, "x"),
+        (Scope::Editor, Clear, "X"),
+        (Scope::Editor, Previous, "["),
+        (Scope::Editor, Next, "]"),
+        (Scope::Editor, Increment, "+"),
+        (Scope::Editor, Decrement, "-"),
+        (Scope::Editor, SideToMove, "t"),
+        (Scope::Editor, Castling, "c"),
+        (Scope::Editor, EnPassant, "e"),
+        (Scope::Editor, Play, "p"),
+        (Scope::Editor, Analyse, "a"),
+        (Scope::Editor, Flip, "f"),
+        (Scope::Editor, Command, ":"),
+        (Scope::Editor, Command, "/"),
+        (Scope::Puzzle, Up, "up"),
+        (Scope::Puzzle, Down, "down"),
+        (Scope::Puzzle, Left, "left"),
+        (Scope::Puzzle, Right, "right"),
+        (Scope::Puzzle, Select, "enter"),
+        (Scope::Puzzle, Select, "space"),
+        (Scope::Puzzle, Flip, "f"),
+        (Scope::Puzzle, Command, ":"),
+        (Scope::Puzzle, Filter, "/"),
+        (Scope::Puzzle, Hint, "?"),
+        (Scope::Puzzle, Next, "n"),
+        (Scope::Puzzle, Next, "]"),
+        (Scope::Repertoire, Up, "up"),
+        (Scope::Repertoire, Down, "down"),
+        (Scope::Repertoire, Left, "left"),
+        (Scope::Repertoire, Right, "right"),
+        (Scope::Repertoire, Select, "enter"),
+        (Scope::Repertoire, Select, "space"),
+        (Scope::Repertoire, Flip, "f"),
+        (Scope::Repertoire, Command, ":"),
+        (Scope::Repertoire, Hint, "?"),
+        (Scope::Repertoire, Next, "n"),
+        (Scope::Repertoire, Next, "]"),
+        (Scope::Endgame, Up, "up"),
+        (Scope::Endgame, Down, "down"),
+        (Scope::Endgame, Left, "left"),
+        (Scope::Endgame, Right, "right"),
+        (Scope::Endgame, Select, "enter"),
+        (Scope::Endgame, Select, "space"),
+        (Scope::Endgame, Flip, "f"),
+        (Scope::Endgame, Command, ":"),
+        (Scope::Endgame, Command, "/"),
+        (Scope::Endgame, Next, "n"),
+        (Scope::Endgame, Next, "]"),
+        (Scope::Endgame, Retry, "r"),
+        (Scope::Endgame, Increment, "+"),
+        (Scope::Endgame, Decrement, "-"),
+        (Scope::Stats, Up, "up"),
+        (Scope::Stats, Down, "down"),
+        (Scope::Replay, Left, "left"),
+        (Scope::Replay, Right, "right"),
+        (Scope::Replay, Up, "up"),
+        (Scope::Replay, Down, "down"),
+        (Scope::Replay, First, "home"),
+        (Scope::Replay, Last, "end"),
+        (Scope::Replay, Autoplay, "space"),
+        (Scope::Replay, Autoplay, "p"),
+        (Scope::Replay, Increment, "+"),
+        (Scope::Replay, Decrement, "-"),
+        (Scope::Replay, Flip, "f"),
+        (Scope::Database, Up, "up"),
+        (Scope::Database, Down, "down"),
+        (Scope::Database, Left, "left"),
+        (Scope::Database, Right, "right"),
+        (Scope::Database, PageUp, "pageup"),
+        (Scope::Database, PageDown, "pagedown"),
+        (Scope::Database, First, "home"),
+        (Scope::Database, Last, "end"),
+        (Scope::Database, Select, "enter"),
+        (Scope::Database, Reverse, "r"),
+        (Scope::Database, Command, ":"),
+        (Scope::Database, Command, "/"),
+        (Scope::Database, Search, "s"),
+        (Scope::Search, Up, "up"),
+        (Scope::Search, Down, "down"),
+        (Scope::Search, PageUp, "pageup"),
+        (Scope::Search, PageDown, "pagedown"),
+        (Scope::Search, First, "home"),
+        (Scope::Search, Last, "end"),
+        (Scope::Search, Select, "enter"),
+        (Scope::Search, Command, ":"),
+        (Scope::Search, Command, "/"),
+        (Scope::Search, Command, "s"),
+        (Scope::Settings, Up, "up"),
+        (Scope::Settings, Down, "down"),
+        (Scope::Settings, Left, "left"),
+        (Scope::Settings, Right, "right"),
+        (Scope::Settings, Select, "enter"),
+    ]
+};
+
+/// Every screen and action with default keys, in the order they are listed, for rebinding one
+/// at a time.
+pub fn bindable() -> Vec<(Scope, Action)> {
+    let mut bindable = Vec::new();
+    for &(scope, action, _) in DEFAULT_BINDINGS {
+        if !bindable.contains(&(scope, action)) {
+            bindable.push((scope, action));
+        }
+    }
+    bindable
+}
+
+/// A key with its modifiers, written like `q`, `ctrl-c`, `shift-tab` or `f5`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// The binding a key press triggers. Shift is part of the character for printable keys, so
+    /// `Q` matches a shifted `q` whatever the terminal reports.
+    pub fn from_event(event: KeyEvent) -> Self {
+        let mut modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        if !matches!(event.code, KeyCode::Char(_)) {
+            modifiers |= event.modifiers & KeyModifiers::SHIFT;
+        }
+        Self {
+            code: event.code,
+            modifiers,
+        }
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut key = s;
+        // A trailing `-` is the minus key itself, as in `ctrl--`
+        while let Some((modifier, rest)) = key.split_once('-').filter(|(_, rest)| !rest.is_empty())
+        {
+            modifiers |= match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => bail!("unknown modifier `{modifier}` in key `{s}`"),
+            };
+            key = rest;
+        }
+        let code = match key.to_ascii_lowercase().as_str() {
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "space" => KeyCode::Char(' '),
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            lower => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => match lower.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                        Some(n @ 1..=24) => KeyCode::F(n),
+                        _ => bail!("unknown key `{s}`"),
+                    },
+                }
+            }
+        };
+        // Shifted characters are written as the character itself
+        if let KeyCode::Char(c) = code
+            && modifiers.contains(KeyModifiers::SHIFT)
+        {
+            modifiers.remove(KeyModifiers::SHIFT);
+            return Ok(Self {
+                code: KeyCode::Char(c.to_ascii_uppercase()),
+                modifiers,
+            });
+        }
+        Ok(Self { code, modifiers })
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl-"),
+            (KeyModifiers::ALT, "alt-"),
+            (KeyModifiers::SHIFT, "shift-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            KeyCode::Enter => f.write_str("enter"),
+            KeyCode::Esc => f.write_str("esc"),
+            KeyCode::Tab => f.write_str("tab"),
+            KeyCode::BackTab => f.write_str("backtab"),
+            KeyCode::Backspace => f.write_str("backspace"),
+            KeyCode::Delete => f.write_str("delete"),
+            KeyCode::Insert => f.write_str("insert"),
+            KeyCode::Home => f.write_str("home"),
+            KeyCode::End => f.write_str("end"),
+            KeyCode::PageUp => f.write_str("pageup"),
+            KeyCode::PageDown => f.write_str("pagedown"),
+            KeyCode::Up => f.write_str("up"),
+            KeyCode::Down => f.write_str("down"),
+            KeyCode::Left => f.write_str("left"),
+            KeyCode::Right => f.write_str("right"),
+            other => write!(f, "{other:?}"),
+        }
+    }
+}
+
+/// One key or a list of keys, as written in the config file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Keys {
+    fn iter(&self) -> impl Iterator<Item = &str> {
+        match self {
+            Keys::One(key) => std::slice::from_ref(key).iter(),
+            Keys::Many(keys) => keys.iter(),
+        }
+        .map(String::as_str)
+    }
+}
+
+/// User bindings by scope and action. Binding an action replaces its default keys in that scope.
+pub type KeyOverrides = BTreeMap<Scope, BTreeMap<Action, Keys>>;
+
+/// Maps key presses to actions for each screen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: HashMap<(Scope, KeyBinding), Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(KeymapPreset::Default, &KeyOverrides::new()).expect("default keymap is valid")
+    }
+}
+
+impl Keymap {
+    /// Builds the keymap from the defaults, a preset and the user's overrides, failing on
+    /// unknown keys and on keys bound to two actions where both would apply.
+    pub fn new(preset: KeymapPreset, overrides: &KeyOverrides) -> color_eyre::Result<Self> {
+        let mut entries: Vec<(Scope, Action, KeyBinding)> = Vec::new();
+        for &(scope, action, key) in DEFAULT_BINDINGS.iter().chain(preset.bindings()) {
+            if !overrides
+                .get(&scope)
+                .is_some_and(|actions| actions.contains_key(&action))
+            {
+                entries.push((scope, action, key.parse()?));
+            }
+        }
+        for (&scope, actions) in overrides {
+            for (&action, keys) in actions {
+                for key in keys.iter() {
+                    entries.push((scope, action, key.parse()?));
+                }
+            }
+        }
+
+        let mut bindings = HashMap::new();
+        let mut conflicts = Vec::new();
+        for (scope, action, key) in entries {
+            match bindings.insert((scope, key), action) {
+                Some(previous) if previous != action => conflicts.push(format!(
+                    "`{key}` is bound to both {} and {} in {scope:?}",
+                    previous.name(),
+                    action.name()
+                )),
+                _ => {}
+            }
+        }
+        // A screen binding would silently hide a global one
+        for (&(scope, key), &action) in &bindings {
+            if scope != Scope::Global
+                && let Some(global) = bindings.get(&(Scope::Global, key))
+                && *global != action
+            {
+                conflicts.push(format!(
+                    "`{key}` is bound to {} in {scope:?} but to {} everywhere",
+                    action.name(),
+                    global.name()
+                ));
+            }
+        }
+        if !conflicts.is_empty() {
+            conflicts.sort();
+            return Err(eyre!("conflicting key bindings: {}", conflicts.join("; ")));
+        }
+        Ok(Self { bindings })
+    }
+
+    /// The action a key press triggers on the given screen.
+    pub fn action(&self, state: &AppState, event: KeyEvent) -> Option<Action> {
+        let key = KeyBinding::from_event(event);
+        self.bindings
+            .get(&(Scope::of(state), key))
+            .or_else(|| self.bindings.get(&(Scope::Global, key)))
+            .copied()
+    }
+
+    /// Keys bound to `action` in `scope`, for display.
+    pub fn keys(&self, scope: Scope, action: Action) -> Vec<KeyBinding> {
+        let mut keys: Vec<KeyBinding> = self
+            .bindings
+            .iter()
+            .filter(|&(&(s, _), &a)| s == scope && a == action)
+            .map(|(&(_, key), _)| key)
+            .collect();
+        keys.sort_by_key(|key| key.to_string());
+        keys
+    }
+
+    /// Keys bound to `action` in `scope`, joined for help text.
+    pub fn describe(&self, scope: Scope, action: Action) -> String {
+        let keys: Vec<String> = self
+            .keys(scope, action)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        keys.join(" or ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn parses_and_prints_keys() {
+        for key in [
+            "q",
+            "Q",
+            "ctrl-c",
+            "alt-x",
+            "shift-tab",
+            "f5",
+            "space",
+            "ctrl--",
+        ] {
+            assert_eq!(key.parse::<KeyBinding>().unwrap().to_string(), key);
+        }
+        assert_eq!(
+            "shift-q".parse::<KeyBinding>().unwrap(),
+            "Q".parse().unwrap()
+        );
+        assert!("hyper-q".parse::<KeyBinding>().is_err());
+        assert!("banana".parse::<KeyBinding>().is_err());
+    }
+
+    #[test]
+    fn screen_bindings_come_before_global_ones() {
+        let keymap = Keymap::default();
+        let enter = press(KeyCode::Enter, KeyModifiers::NONE);
+        let q = press(KeyCode::Char('q'), KeyModifiers::NONE);
+        let ctrl_c = press(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(keymap.action(&AppState::Game, enter), Some(Action::Select));
+        assert_eq!(keymap.action(&AppState::Game, q), Some(Action::Back));
+        assert_eq!(keymap.action(&AppState::Help, ctrl_c), Some(Action::Back));
+        assert_eq!(keymap.action(&AppState::Help, enter), None);
+    }
+
+    #[test]
+    fn presets_and_overrides_apply() {
+        let vim = Keymap::new(KeymapPreset::Vim, &KeyOverrides::new()).unwrap();
+        let h = press(KeyCode::Char('h'), KeyModifiers::NONE);
+        assert_eq!(vim.action(&AppState::Game, h), Some(Action::Left));
+        assert_eq!(Keymap::default().action(&AppState::Game, h), None);
+
+        let overrides: KeyOverrides =
+            toml::from_str("[game]\nflip = [\"F\", \"ctrl-r\"]\n[global]\nback = \"esc\"").unwrap();
+        let keymap = Keymap::new(KeymapPreset::Default, &overrides).unwrap();
+        let shifted_f = press(KeyCode::Char('F'), KeyModifiers::SHIFT);
+        let f = press(KeyCode::Char('f'), KeyModifiers::NONE);
+        let q = press(KeyCode::Char('q'), KeyModifiers::NONE);
+        assert_eq!(
+            keymap.action(&AppState::Game, shifted_f),
+            Some(Action::Flip)
+        );
+        assert_eq!(keymap.action(&AppState::Game, f), None);
+        assert_eq!(keymap.action(&AppState::Game, q), None);
+    }
+
+    #[test]
+    fn conflicts_are_rejected() {
+        let same_screen: KeyOverrides = toml::from_str("[game]\nflip = \"enter\"").unwrap();
+        assert!(Keymap::new(KeymapPreset::Default, &same_screen).is_err());
+        let hides_global: KeyOverrides = toml::from_str("[game]\nflip = \"q\"").unwrap();
+        assert!(Keymap::new(KeymapPreset::Default, &hides_global).is_err());
+        for preset in KeymapPreset::iter() {
+            assert!(Keymap::new(preset, &KeyOverrides::new()).is_ok());
+        }
+    }
+}
@@ -1,16 +1,183 @@
+pub mod analysis;
 pub mod app;
+pub mod book;
+pub mod cli;
+pub mod clock;
+pub mod config;
+pub mod database;
+pub mod eco;
+pub mod editor;
+pub mod endgame;
+pub mod engine;
 pub mod event;
 pub mod game;
+pub mod keymap;
+pub mod notation;
+pub mod pgn;
 pub mod pieces;
+pub mod profile;
+pub mod puzzle;
+pub mod record;
+pub mod repertoire;
+pub mod replay;
+pub mod report;
+pub mod search;
+pub mod server;
+pub mod tablebase;
+pub mod theme;
+pub mod uci;
 pub mod ui;
+pub mod variant;
+pub mod widgets;
+pub mod zobrist;
+
+use std::process::ExitCode;
+
+use clap::Parser;
+use color_eyre::eyre::OptionExt;
+use ratatui::crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+};
 
 use crate::app::App;
+use crate::cli::{Cli, Command, PlayArgs};
+use crate::database::Database;
+use crate::game::Game;
+use crate::pgn::PgnGame;
+use crate::pieces::Color;
 
 #[tokio::main]
-async fn main() -> color_eyre::Result<()> {
-    color_eyre::install()?;
+async fn main() -> ExitCode {
+    // Invalid arguments print usage and exit with status 2
+    let cli = Cli::parse();
+    if let Err(err) = color_eyre::install() {
+        eprintln!("{err:?}");
+    }
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> color_eyre::Result<()> {
+    match cli.command.unwrap_or(Command::Play(PlayArgs::default())) {
+        Command::Play(args) => play(args).await,
+        Command::Analyze {
+            pgn,
+            game,
+            depth,
+            output,
+        } => cli::analyze(&pgn, game, depth, output.as_deref()),
+        Command::Replay { file, game, print } => {
+            let pgn = cli::load_game(&file, game)?;
+            if print {
+                cli::print_replay(pgn)
+            } else {
+                replay(pgn).await
+            }
+        }
+        Command::Browse { pgn } => {
+            let database = Database::open(&pgn)?;
+            let mut app = App::new();
+            app.open_database(database);
+            run_app(app).await
+        }
+        Command::Puzzles { csv, filter } => {
+            let mut app = App::new();
+            let csv = csv
+                .or_else(|| app.config.puzzles.clone())
+                .ok_or_eyre("give a puzzle file or choose one under Settings")?;
+            app.start_puzzles(&csv, &filter)?;
+            run_app(app).await
+        }
+        Command::Repertoire { pgn, color } => {
+            let mut app = App::new();
+            let pgn = pgn
+                .or_else(|| app.config.repertoire.clone())
+                .ok_or_eyre("give a repertoire PGN or choose one under Settings")?;
+            app.start_repertoire(&pgn, color.map(Color::from))?;
+            run_app(app).await
+        }
+        Command::Endgame { ending } => {
+            let mut app = App::new();
+            app.start_endgame(ending);
+            run_app(app).await
+        }
+        Command::Search { pgn, query } => cli::search(&pgn, &query),
+        Command::Perft {
+            depth,
+            fen,
+            variant,
+            divide,
+        } => cli::perft(fen.as_deref(), variant, depth, divide),
+        Command::Uci => tokio::task::spawn_blocking(uci::run).await?,
+        Command::Serve { port, variant, fen } => {
+            let game = match fen {
+                Some(fen) => Game::from_fen(&fen, variant)?,
+                None => Game::with_variant(variant),
+            };
+            server::run(port, game).await
+        }
+        Command::Convert {
+            input,
+            to,
+            from,
+            variant,
+        } => cli::run_convert(&input, from, to, variant),
+    }
+}
+
+/// Runs the terminal UI, at the main menu or on the board the options describe.
+async fn play(args: PlayArgs) -> color_eyre::Result<()> {
+    // Load the game before taking over the terminal so errors print normally
+    let game = args.game()?;
+    let mut app = App::new();
+    if let Some(time) = args.time {
+        app.config.time_control = Some(time);
+    }
+    if let Some(game) = game {
+        app.start_game(game);
+        if let Some(side) = args.vs_engine {
+            app.play_against_engine(side.into());
+        }
+    }
+    run_app(app).await
+}
+
+/// Runs the terminal UI on the replay screen; leaving it returns to the main menu.
+async fn replay(pgn: PgnGame) -> color_eyre::Result<()> {
+    let mut app = App::new();
+    app.start_replay(pgn)?;
+    run_app(app).await
+}
+
+async fn run_app(app: App) -> color_eyre::Result<()> {
     let terminal = ratatui::init();
-    let result = App::new().run(terminal).await;
+    let result = match MouseCapture::enable() {
+        Ok(_capture) => app.run(terminal).await,
+        Err(err) => Err(err.into()),
+    };
     ratatui::restore();
     result
 }
+
+/// Keeps the terminal reporting mouse events while it lives. Dropping it turns reporting off
+/// again, so the terminal is left as it was found even when the app panics.
+struct MouseCapture;
+
+impl MouseCapture {
+    fn enable() -> std::io::Result<Self> {
+        execute!(std::io::stdout(), EnableMouseCapture)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for MouseCapture {
+    fn drop(&mut self) {
+        let _ = execute!(std::io::stdout(), DisableMouseCapture);
+    }
+}
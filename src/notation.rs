@@ -0,0 +1,386 @@
+use color_eyre::eyre::bail;
+
+use crate::game::{Game, Move, MoveKind};
+use crate::pieces::{Color, PieceType};
+
+/// Standard algebraic notation for a legal move of `game`, such as `Nbd2`, `exd5`, `e8=Q+`,
+/// `O-O` or `N@f3`, with `+` or `#` when it gives check or mate.
+pub fn san(game: &Game, mv: &Move) -> String {
+    san_among(game, mv, &game.legal_moves())
+}
+
+/// [`san`] for one of `legal`, the legal moves of `game`, so that naming every move of a
+/// position generates them only once.
+fn san_among(game: &Game, mv: &Move, legal: &[Move]) -> String {
+    let mut san = san_without_suffix(game, mv, legal);
+    let mut after = game.clone();
+    after.make_move(*mv);
+    if after.is_in_check() {
+        let mated = after
+            .outcome()
+            .is_some_and(|outcome| outcome.winner == Some(mv.piece.color));
+        san.push(if mated { '#' } else { '+' });
+    }
+    san
+}
+
+fn san_without_suffix(game: &Game, mv: &Move, legal: &[Move]) -> String {
+    let board = game.get_board();
+    let to = board.square_name(mv.to);
+    let letter = mv.piece.piece_type.to_char().to_ascii_uppercase();
+    match mv.kind {
+        MoveKind::Castle { rook_from, .. } if rook_from.1 > mv.from.1 => return "O-O".to_string(),
+        MoveKind::Castle { .. } => return "O-O-O".to_string(),
+        MoveKind::Drop => return format!("{letter}@{to}"),
+        _ => {}
+    }
+
+    let mut san = String::new();
+    if mv.piece.piece_type == PieceType::Pawn {
+        if mv.is_capture() {
+            san.push(file_char(mv.from.1));
+        }
+    } else {
+        san.push(letter);
+        // Name the origin only as precisely as needed to tell apart pieces of the same type
+        let rivals: Vec<&Move> = legal
+            .iter()
+            .filter(|other| {
+                other.piece == mv.piece
+                    && other.to == mv.to
+                    && other.from != mv.from
+                    && !other.is_drop()
+            })
+            .collect();
+        if !rivals.is_empty() {
+            let from = board.square_name(mv.from);
+            if rivals.iter().all(|other| other.from.1 != mv.from.1) {
+                san.push(file_char(mv.from.1));
+            } else if rivals.iter().all(|other| other.from.0 != mv.from.0) {
+                san.push_str(&from[1..]);
+            } else {
+                san.push_str(&from);
+            }
+        }
+    }
+    if mv.is_capture() {
+        san.push('x');
+    }
+    san.push_str(&to);
+    if let Some(promotion) = mv.promotion {
+        san.push('=');
+        san.push(promotion.to_char().to_ascii_uppercase());
+    }
+    san
+}
+
+fn file_char(col: usize) -> char {
+    (b'a' + col as u8) as char
+}
+
+/// What a typed move says about the move it names.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct MovePattern {
+    piece_type: Option<PieceType>,
+    from_file: Option<usize>,
+    from_rank: Option<usize>,
+    to: (usize, usize),
+    promotion: Option<PieceType>,
+    drop: bool,
+}
+
+/// Resolves SAN (`Nf3`, `exd5`, `O-O`, `e8=Q`, `N@f3`) or coordinate notation (`g1f3`,
+/// `e7e8q`) against the legal moves of `game`. Check and annotation marks are ignored, captures
+/// need not be marked and `0-0` is accepted for castling.
+pub fn parse_move(game: &Game, input: &str) -> color_eyre::Result<Move> {
+    let text = input.trim().trim_end_matches(['+', '#', '!', '?']);
+    if text.is_empty() {
+        bail!("type a move such as e4, Nf3 or g1f3");
+    }
+    let legal = game.legal_moves();
+
+    if let Some(king_side) = match text {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    } {
+        return legal
+            .into_iter()
+            .find(|mv| match mv.kind {
+                MoveKind::Castle { rook_from, .. } => (rook_from.1 > mv.from.1) == king_side,
+                _ => false,
+            })
+            .ok_or_else(|| color_eyre::eyre::eyre!("castling {text} is not legal here"));
+    }
+
+    if let Some(mv) = legal
+        .iter()
+        .find(|mv| mv.to_uci(game.get_board()).eq_ignore_ascii_case(text))
+    {
+        return Ok(*mv);
+    }
+
+    let Some(pattern) = parse_pattern(game, text) else {
+        bail!("cannot read `{text}` as a move");
+    };
+    let candidates: Vec<Move> = legal
+        .into_iter()
+        .filter(|mv| {
+            mv.is_drop() == pattern.drop
+                && mv.to == pattern.to
+                && mv.piece.piece_type == pattern.piece_type.unwrap_or(PieceType::Pawn)
+                && pattern.from_file.is_none_or(|col| mv.from.1 == col)
+                && pattern.from_rank.is_none_or(|row| mv.from.0 == row)
+                && (pattern.promotion.is_none() || mv.promotion == pattern.promotion)
+        })
+        .collect();
+    match candidates.as_slice() {
+        [] => bail!("{text} is not a legal move"),
+        [mv] => Ok(*mv),
+        _ => {
+            let options: Vec<String> = candidates.iter().map(|mv| san(game, mv)).collect();
+            bail!("{text} is ambiguous: {}", options.join(", "))
+        }
+    }
+}
+
+/// Splits SAN into piece, origin hints, destination and promotion.
+fn parse_pattern(game: &Game, text: &str) -> Option<MovePattern> {
+    let board = game.get_board();
+    let mut pattern = MovePattern::default();
+    let mut rest = text.replace(['x', ':', '-'], "");
+
+    // Promotion, written `e8=Q` or `e8Q`
+    if let Some(last) = rest.chars().last()
+        && last.is_ascii_uppercase()
+        && rest.len() > 2
+    {
+        pattern.promotion = Some(PieceType::from_char(last)?);
+        rest.pop();
+        if rest.ends_with('=') {
+            rest.pop();
+        }
+    }
+
+    let mut chars = rest.as_str();
+    if let Some(first) = chars.chars().next()
+        && first.is_ascii_uppercase()
+    {
+        pattern.piece_type = Some(PieceType::from_char(first)?);
+        chars = &chars[1..];
+    }
+    if let Some(square) = chars.strip_prefix('@') {
+        pattern.drop = true;
+        pattern.to = board.parse_square(square)?;
+        return Some(pattern);
+    }
+
+    // The destination is the last file letter and the rank digits after it
+    let to_start = chars.rfind(|c: char| c.is_ascii_lowercase())?;
+    pattern.to = board.parse_square(&chars[to_start..])?;
+    let hint = &chars[..to_start];
+    let rank_start = hint
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(hint.len());
+    let (file, rank) = hint.split_at(rank_start);
+    match file.len() {
+        0 => {}
+        1 => pattern.from_file = Some(file.as_bytes()[0].checked_sub(b'a')? as usize),
+        _ => return None,
+    }
+    if !rank.is_empty() {
+        let rank: usize = rank.parse().ok()?;
+        let rows = board.dimensions().0;
+        if rank == 0 || rank > rows {
+            return None;
+        }
+        pattern.from_rank = Some(rows - rank);
+    }
+    Some(pattern)
+}
+
+/// The legal moves of a position in SAN and coordinate notation, worked out once so the
+/// command bar can narrow them down as a move is typed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completions {
+    fen: String,
+    /// Each move's SAN and coordinate notation, sorted by SAN.
+    moves: Vec<(String, String)>,
+}
+
+impl Completions {
+    pub fn new(game: &Game) -> Self {
+        let legal = game.legal_moves();
+        let mut moves: Vec<(String, String)> = legal
+            .iter()
+            .map(|mv| (san_among(game, mv, &legal), mv.to_uci(game.get_board())))
+            .collect();
+        moves.sort();
+        Self {
+            fen: game.to_fen(),
+            moves,
+        }
+    }
+
+    /// Whether these are the moves of `game`'s position.
+    pub fn is_for(&self, game: &Game) -> bool {
+        self.fen == game.to_fen()
+    }
+
+    /// Moves whose SAN or coordinate notation starts with `prefix`, in SAN, for
+    /// autocompletion.
+    pub fn matching(&self, prefix: &str) -> Vec<String> {
+        let prefix = prefix.trim();
+        let uci_prefix = prefix.to_ascii_lowercase();
+        let mut matches: Vec<String> = self
+            .moves
+            .iter()
+            .filter(|(san, uci)| {
+                san.starts_with(prefix)
+                    || san.replace('x', "").starts_with(prefix)
+                    || uci.starts_with(&uci_prefix)
+            })
+            .map(|(san, _)| san.clone())
+            .collect();
+        matches.dedup();
+        matches
+    }
+}
+
+/// `moves` played from `game` in numbered SAN, like `12. Nf3 Nc6 13. Bb5` or `12... Nc6`.
+/// Moves that are not legal in turn end the line.
+pub fn numbered(game: &Game, moves: &[Move]) -> String {
+    let mut game = game.clone();
+    let mut fullmove: usize = game
+        .to_fen()
+        .rsplit(' ')
+        .next()
+        .and_then(|field| field.parse().ok())
+        .unwrap_or(1);
+    let mut tokens = Vec::new();
+    for (i, mv) in moves.iter().enumerate() {
+        if !game.legal_moves().contains(mv) {
+            break;
+        }
+        match game.get_current_turn() {
+            Color::White => tokens.push(format!("{fullmove}.")),
+            Color::Black if i == 0 => tokens.push(format!("{fullmove}...")),
+            Color::Black => {}
+        }
+        tokens.push(san(&game, mv));
+        if game.get_current_turn() == Color::Black {
+            fullmove += 1;
+        }
+        game.make_move(*mv);
+    }
+    tokens.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variant::VariantKind;
+
+    fn game(fen: &str) -> Game {
+        Game::from_fen(fen, VariantKind::Standard).unwrap()
+    }
+
+    fn sans(game: &Game) -> Vec<String> {
+        let mut sans: Vec<String> = game.legal_moves().iter().map(|mv| san(game, mv)).collect();
+        sans.sort();
+        sans
+    }
+
+    #[test]
+    fn writes_san() {
+        let start = Game::new();
+        assert!(sans(&start).contains(&"Nf3".to_string()));
+        assert!(sans(&start).contains(&"e4".to_string()));
+
+        // Rooks on a1 and a5 both reach a3
+        let position = game("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1");
+        let all = sans(&position);
+        assert!(all.contains(&"R1a3".to_string()), "{all:?}");
+        assert!(all.contains(&"R5a3".to_string()), "{all:?}");
+
+        let promotion = game("8/4P1k1/8/8/8/8/8/4K3 w - - 0 1");
+        assert!(sans(&promotion).contains(&"e8=Q".to_string()));
+        assert!(sans(&promotion).contains(&"e8=N+".to_string()));
+
+        let castling = game("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert!(sans(&castling).contains(&"O-O".to_string()));
+        assert!(sans(&castling).contains(&"O-O-O".to_string()));
+
+        let mate = game("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1");
+        assert!(sans(&mate).contains(&"Ra8#".to_string()));
+        assert!(sans(&mate).contains(&"Ra7".to_string()));
+    }
+
+    #[test]
+    fn reads_san_and_coordinates() {
+        let start = Game::new();
+        let nf3 = parse_move(&start, "Nf3").unwrap();
+        assert_eq!(parse_move(&start, "g1f3").unwrap(), nf3);
+        assert_eq!(parse_move(&start, "Ng1f3").unwrap(), nf3);
+        assert_eq!(parse_move(&start, "Nf3!?").unwrap(), nf3);
+
+        let exd5 = game("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1");
+        assert_eq!(san(&exd5, &parse_move(&exd5, "exd5").unwrap()), "exd5");
+        assert_eq!(
+            parse_move(&exd5, "ed5").unwrap(),
+            parse_move(&exd5, "exd5").unwrap()
+        );
+
+        let castling = game("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert_eq!(
+            san(&castling, &parse_move(&castling, "0-0-0").unwrap()),
+            "O-O-O"
+        );
+
+        let promotion = game("8/4P1k1/8/8/8/8/8/4K3 w - - 0 1");
+        let knight = parse_move(&promotion, "e8=N").unwrap();
+        assert_eq!(knight.promotion, Some(PieceType::Knight));
+        assert_eq!(parse_move(&promotion, "e7e8n").unwrap(), knight);
+    }
+
+    #[test]
+    fn reports_illegal_and_ambiguous_input() {
+        let start = Game::new();
+        assert!(parse_move(&start, "Nf6").is_err());
+        assert!(parse_move(&start, "e5").is_err());
+        assert!(parse_move(&start, "hello").is_err());
+        assert!(parse_move(&start, "O-O").is_err());
+
+        let knights = game("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1");
+        let err = parse_move(&knights, "Nd2").unwrap_err().to_string();
+        assert!(err.contains("Nbd2") && err.contains("Nfd2"), "{err}");
+        assert!(parse_move(&knights, "Nbd2").is_ok());
+
+        let promotion = game("8/4P1k1/8/8/8/8/8/4K3 w - - 0 1");
+        assert!(parse_move(&promotion, "e8").is_err());
+    }
+
+    #[test]
+    fn completes_prefixes() {
+        let start = Completions::new(&Game::new());
+        assert_eq!(start.matching("N"), ["Na3", "Nc3", "Nf3", "Nh3"]);
+        assert_eq!(start.matching("g1"), ["Nf3", "Nh3"]);
+        assert_eq!(start.matching("e"), ["e3", "e4"]);
+        assert_eq!(start.matching("").len(), 20);
+        assert!(start.is_for(&Game::new()));
+        assert!(!start.is_for(&game("4k3/8/8/8/8/8/8/4K3 w - - 0 1")));
+    }
+
+    #[test]
+    fn numbers_lines() {
+        let start = Game::new();
+        let e4 = parse_move(&start, "e4").unwrap();
+        let mut after = start.clone();
+        after.make_move(e4);
+        let e5 = parse_move(&after, "e5").unwrap();
+        assert_eq!(numbered(&start, &[e4, e5]), "1. e4 e5");
+        assert_eq!(numbered(&after, &[e5]), "1... e5");
+        // The second e4 is not legal
+        assert_eq!(numbered(&start, &[e4, e4]), "1. e4");
+    }
+}
@@ -0,0 +1,582 @@
+use std::fmt;
+use std::io::BufRead;
+
+use color_eyre::eyre::{WrapErr, bail, eyre};
+
+use crate::eco;
+use crate::game::Game;
+use crate::notation;
+use crate::variant::VariantKind;
+
+/// Result token of an unfinished game.
+pub const UNKNOWN_RESULT: &str = "*";
+
+/// A move in PGN movetext with the annotations after it and the lines played instead of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PgnMove {
+    pub san: String,
+    /// Numeric annotation glyphs; the suffixes `!`, `?`, `!!`, `??`, `!?` and `?!` are stored as
+    /// NAGs 1 to 6.
+    pub nags: Vec<u8>,
+    pub comments: Vec<String>,
+    /// Alternatives to this move, each starting from the position before it.
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    pub fn new(san: impl Into<String>) -> Self {
+        Self {
+            san: san.into(),
+            ..Self::default()
+        }
+    }
+}
+
+/// One game of a PGN file: its tag pairs and annotated movetext.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnGame {
+    /// Tag pairs in file order.
+    pub tags: Vec<(String, String)>,
+    /// Comments before the first move.
+    pub comments: Vec<String>,
+    pub moves: Vec<PgnMove>,
+    /// `1-0`, `0-1`, `1/2-1/2` or `*`.
+    pub result: String,
+}
+
+impl Default for PgnGame {
+    fn default() -> Self {
+        Self {
+            tags: Vec::new(),
+            comments: Vec::new(),
+            moves: Vec::new(),
+            result: UNKNOWN_RESULT.to_string(),
+        }
+    }
+}
+
+/// Tags every exported game carries, in the order the PGN standard lists them.
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", UNKNOWN_RESULT),
+];
+
+impl PgnGame {
+    /// Reads a single game.
+    pub fn parse(text: &str) -> color_eyre::Result<Self> {
+        let mut games = parse_all(text)?;
+        match games.len() {
+            0 => bail!("no game found in PGN"),
+            _ => Ok(games.remove(0)),
+        }
+    }
+
+    /// The value of tag `name`, if present.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets tag `name`, replacing any existing value.
+    pub fn set_tag(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.tags.iter_mut().find(|(key, _)| key == name) {
+            Some((_, existing)) => *existing = value,
+            None => self.tags.push((name.to_string(), value)),
+        }
+    }
+
+    /// The variant named by the `Variant` tag; standard chess when there is none.
+    pub fn variant(&self) -> color_eyre::Result<VariantKind> {
+        match self.tag("Variant") {
+            Some(name) => name.parse(),
+            None => Ok(VariantKind::Standard),
+        }
+    }
+
+    /// The position before the first move, from the `FEN` tag or the variant's start.
+    pub fn starting_position(&self) -> color_eyre::Result<Game> {
+        let variant = self.variant()?;
+        match self.tag("FEN") {
+            Some(fen) => Game::from_fen(fen, variant),
+            None => Ok(Game::with_variant(variant)),
+        }
+    }
+
+    /// Plays the main line, failing at the first move that is not legal.
+    pub fn mainline(&self) -> color_eyre::Result<Game> {
+        let mut game = self.starting_position()?;
+        for (index, pgn_move) in self.moves.iter().enumerate() {
+            let mv = notation::parse_move(&game, &pgn_move.san)
+                .wrap_err_with(|| format!("move {} of the main line", index + 1))?;
+            game.make_move(mv);
+        }
+        Ok(game)
+    }
+
+    /// A game record of everything played in `game`, with placeholder player tags.
+    pub fn from_game(game: &Game) -> Self {
+        let mut pgn = Self::default();
+        for (name, value) in SEVEN_TAG_ROSTER {
+            pgn.set_tag(name, value);
+        }
+        let variant = game.variant();
+        if variant != VariantKind::Standard {
+            pgn.set_tag("Variant", variant.name());
+        }
+        if game.start_fen() != Game::with_variant(variant).start_fen() {
+            pgn.set_tag("SetUp", "1");
+            pgn.set_tag("FEN", game.start_fen());
+        }
+
+        let mut replay =
+            Game::from_fen(game.start_fen(), variant).expect("a game's starting FEN reloads");
+        for mv in game.get_move_history() {
+            pgn.moves.push(PgnMove::new(notation::san(&replay, mv)));
+            replay.make_move(*mv);
+        }
+        if let Some(opening) = eco::classify(game) {
+            pgn.set_tag("ECO", opening.eco);
+            pgn.set_tag("Opening", opening.name);
+        }
+        if let Some(outcome) = game.outcome() {
+            pgn.result = outcome.result().to_string();
+            pgn.moves
+                .last_mut()
+                .map(|last| &mut last.comments)
+                .unwrap_or(&mut pgn.comments)
+                .push(outcome.reason.clone());
+        }
+        pgn.set_tag("Result", pgn.result.clone());
+        pgn
+    }
+
+    /// Ply number of the first move: 0 when white starts from move 1.
+    fn first_ply(&self) -> usize {
+        let Some(fen) = self.tag("FEN") else {
+            return 0;
+        };
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let black = fields.get(1) == Some(&"b");
+        let fullmove: usize = fields
+            .last()
+            .and_then(|field| field.parse().ok())
+            .filter(|&n| n > 0)
+            .unwrap_or(1);
+        (fullmove - 1) * 2 + black as usize
+    }
+}
+
+impl fmt::Display for PgnGame {
+    /// Export format: tags, a blank line and movetext wrapped at 80 columns.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in &self.tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(f, "[{name} \"{value}\"]")?;
+        }
+        if !self.tags.is_empty() {
+            writeln!(f)?;
+        }
+
+        let mut tokens: Vec<String> = self.comments.iter().map(|c| format!("{{{c}}}")).collect();
+        write_line(&mut tokens, &self.moves, self.first_ply(), true);
+        glue_parentheses(&mut tokens);
+        tokens.push(self.result.clone());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+                writeln!(f, "{line}")?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{line}")
+    }
+}
+
+/// Appends the tokens of `moves`, starting at `ply`, with variations in parentheses. Black moves
+/// get a `12...` number when they open a line or follow a comment or variation.
+fn write_line(tokens: &mut Vec<String>, moves: &[PgnMove], mut ply: usize, mut number: bool) {
+    for pgn_move in moves {
+        let move_number = ply / 2 + 1;
+        if ply.is_multiple_of(2) {
+            tokens.push(format!("{move_number}."));
+        } else if number {
+            tokens.push(format!("{move_number}..."));
+        }
+        tokens.push(pgn_move.san.clone());
+        tokens.extend(pgn_move.nags.iter().map(|nag| format!("${nag}")));
+        tokens.extend(pgn_move.comments.iter().map(|c| format!("{{{c}}}")));
+        number = !pgn_move.comments.is_empty() || !pgn_move.variations.is_empty();
+        for variation in &pgn_move.variations {
+            tokens.push("(".to_string());
+            write_line(tokens, variation, ply, true);
+            tokens.push(")".to_string());
+        }
+        ply += 1;
+    }
+}
+
+/// Glues parentheses to their neighbours, as in `(12... Nf6)`.
+fn glue_parentheses(tokens: &mut Vec<String>) {
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i] == "(" && i + 1 < tokens.len() {
+            let next = tokens.remove(i + 1);
+            tokens[i].push_str(&next);
+        } else if tokens[i] == ")" && i > 0 {
+            tokens.remove(i);
+            tokens[i - 1].push(')');
+            continue;
+        }
+        i += 1;
+    }
+}
+
+/// Reads every game of a PGN file held in memory.
+pub fn parse_all(text: &str) -> color_eyre::Result<Vec<PgnGame>> {
+    PgnReader::new(text.as_bytes()).collect()
+}
+
+/// Streams games out of a PGN source one at a time, so large databases need not fit in memory.
+pub struct PgnReader<R> {
+    reader: R,
+    /// Byte offset of the next unread line.
+    offset: u64,
+    /// First tag line of the next game and its offset, read while finishing the previous one.
+    pending: Option<(u64, String)>,
+    /// Number of games read so far, for error messages.
+    count: usize,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            offset: 0,
+            pending: None,
+            count: 0,
+        }
+    }
+
+    /// Reads a line, replacing bytes that are not UTF-8 since old databases are often Latin-1.
+    fn read_line(&mut self) -> color_eyre::Result<Option<String>> {
+        let mut bytes = Vec::new();
+        let read = self.reader.read_until(b'\n', &mut bytes)?;
+        self.offset += read as u64;
+        Ok((read > 0).then(|| String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    /// The text of the next game with the byte offset it starts at and the number of bytes it
+    /// spans in the source, which differs from the text's length when invalid UTF-8 was
+    /// replaced. A game ends where a tag line follows movetext.
+    pub fn next_chunk(&mut self) -> color_eyre::Result<Option<(u64, u64, String)>> {
+        let (mut start, mut chunk) = self.pending.take().unwrap_or((self.offset, String::new()));
+        let mut end = self.offset;
+        let mut seen_movetext = false;
+        loop {
+            let line_start = self.offset;
+            let Some(line) = self.read_line()? else {
+                break;
+            };
+            let trimmed = line.trim_start();
+            if trimmed.starts_with('[') && seen_movetext {
+                self.pending = Some((line_start, line));
+                break;
+            }
+            if !trimmed.is_empty() && !trimmed.starts_with(['[', '%']) {
+                seen_movetext = true;
+            }
+            if chunk.trim().is_empty() && trimmed.is_empty() {
+                // Skip blank lines before the game
+                start = self.offset;
+                continue;
+            }
+            chunk.push_str(&line);
+            end = self.offset;
+        }
+        Ok((!chunk.trim().is_empty()).then_some((start, end - start, chunk)))
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = color_eyre::Result<PgnGame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, _, chunk) = match self.next_chunk() {
+            Ok(chunk) => chunk?,
+            Err(err) => return Some(Err(err)),
+        };
+        self.count += 1;
+        let count = self.count;
+        Some(parse_game(&chunk).wrap_err_with(|| format!("game {count} of the PGN")))
+    }
+}
+
+/// Parses text holding exactly one game, as returned by [`PgnReader::next_chunk`].
+pub fn parse_game(text: &str) -> color_eyre::Result<PgnGame> {
+    let mut game = PgnGame::default();
+    let mut movetext = String::new();
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('%') {
+            continue;
+        }
+        if movetext.trim().is_empty()
+            && let Some(tag) = trimmed.strip_prefix('[')
+        {
+            let tag = tag
+                .strip_suffix(']')
+                .ok_or_else(|| eyre!("unterminated tag: {trimmed}"))?;
+            let (name, value) = tag
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| eyre!("tag without a value: {trimmed}"))?;
+            let value = value
+                .trim()
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .ok_or_else(|| eyre!("tag value must be quoted: {trimmed}"))?;
+            game.tags.push((
+                name.to_string(),
+                value.replace("\\\"", "\"").replace("\\\\", "\\"),
+            ));
+            continue;
+        }
+        movetext.push_str(line);
+        movetext.push('\n');
+    }
+    parse_movetext(&movetext, &mut game)?;
+    if game.result == UNKNOWN_RESULT
+        && let Some(result) = game.tag("Result")
+    {
+        game.result = result.to_string();
+    }
+    Ok(game)
+}
+
+/// Parses movetext into `game`'s moves, comments and result.
+fn parse_movetext(text: &str, game: &mut PgnGame) -> color_eyre::Result<()> {
+    // The main line and the variations currently open inside it
+    let mut lines: Vec<Vec<PgnMove>> = vec![Vec::new()];
+    // Comments that come before the first move of a variation
+    let mut leading: Vec<String> = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '{' | ';' => {
+                let close = if c == '{' { '}' } else { '\n' };
+                chars.next();
+                let mut comment = String::new();
+                for (_, c) in chars.by_ref() {
+                    if c == close {
+                        break;
+                    }
+                    comment.push(c);
+                }
+                let comment = comment.split_whitespace().collect::<Vec<_>>().join(" ");
+                let depth = lines.len();
+                let line = lines.last_mut().expect("main line is never closed");
+                match line.last_mut() {
+                    Some(last) => last.comments.push(comment),
+                    None if depth == 1 => game.comments.push(comment),
+                    None => leading.push(comment),
+                }
+            }
+            '(' => {
+                chars.next();
+                if lines.last().is_none_or(|line| line.is_empty()) {
+                    bail!("variation before any move");
+                }
+                lines.push(Vec::new());
+            }
+            ')' => {
+                chars.next();
+                if lines.len() == 1 {
+                    bail!("unmatched `)`");
+                }
+                let variation = lines.pop().expect("checked above");
+                let parent = lines.last_mut().expect("checked above");
+                let last = parent.last_mut().expect("variations follow a move");
+                last.variations.push(variation);
+            }
+            '$' => {
+                chars.next();
+                let token = take_token(text, start + 1, &mut chars);
+                let nag = token
+                    .parse()
+                    .map_err(|_| eyre!("invalid annotation ${token}"))?;
+                if let Some(last) = lines.last_mut().and_then(|line| line.last_mut()) {
+                    last.nags.push(nag);
+                }
+            }
+            _ => {
+                let token = take_token(text, start, &mut chars);
+                if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+                    game.result = token.to_string();
+                    continue;
+                }
+                // Move numbers: `12.`, `12...`, or glued to the move as in `12.e4`
+                let after_number = token.trim_start_matches(|c: char| c.is_ascii_digit());
+                let token = if after_number.starts_with('.') {
+                    after_number.trim_start_matches('.')
+                } else if after_number.is_empty() {
+                    ""
+                } else {
+                    token
+                };
+                if token.is_empty() {
+                    continue;
+                }
+                let san = token.trim_end_matches(['!', '?']);
+                let mut pgn_move = PgnMove::new(san);
+                if let Some(nag) = suffix_nag(&token[san.len()..]) {
+                    pgn_move.nags.push(nag);
+                }
+                pgn_move.comments.append(&mut leading);
+                lines
+                    .last_mut()
+                    .expect("main line is never closed")
+                    .push(pgn_move);
+            }
+        }
+    }
+    if lines.len() > 1 {
+        bail!("unterminated variation");
+    }
+    game.moves = lines.pop().expect("main line is never closed");
+    Ok(())
+}
+
+/// Consumes the characters of a token starting at byte `start`, returning it.
+fn take_token<'a>(
+    text: &'a str,
+    start: usize,
+    chars: &mut std::iter::Peekable<std::str::CharIndices<'a>>,
+) -> &'a str {
+    let mut end = start;
+    while let Some(&(index, c)) = chars.peek() {
+        if c.is_whitespace() || matches!(c, '{' | '}' | '(' | ')' | ';' | '$') {
+            break;
+        }
+        end = index + c.len_utf8();
+        chars.next();
+    }
+    &text[start..end.max(start)]
+}
+
+/// NAG number of a `!`/`?` move suffix.
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    Some(match suffix {
+        "!" => 1,
+        "?" => 2,
+        "!!" => 3,
+        "??" => 4,
+        "!?" => 5,
+        "?!" => 6,
+        _ => return None,
+    })
+}
+
+/// The `!`/`?` suffix for NAGs 1 to 6.
+pub fn nag_symbol(nag: u8) -> Option<&'static str> {
+    Some(match nag {
+        1 => "!",
+        2 => "?",
+        3 => "!!",
+        4 => "??",
+        5 => "!?",
+        6 => "?!",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANNOTATED: &str = r#"[Event "Casual"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+{Opening} 1. e4 e5 2. Nf3 $1 Nc6 (2... d6 {Philidor} 3. d4) 3. Bb5?! a6 ; Morphy
+4. Ba4 1-0
+
+[Event "Second"]
+
+1. d4 d5 *
+"#;
+
+    #[test]
+    fn reads_tags_comments_nags_and_variations() {
+        let games = parse_all(ANNOTATED).unwrap();
+        assert_eq!(games.len(), 2);
+        let game = &games[0];
+        assert_eq!(game.tag("White"), Some("A"));
+        assert_eq!(game.comments, ["Opening"]);
+        assert_eq!(game.result, "1-0");
+        let sans: Vec<&str> = game.moves.iter().map(|m| m.san.as_str()).collect();
+        assert_eq!(sans, ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4"]);
+        assert_eq!(game.moves[2].nags, [1]);
+        assert_eq!(game.moves[4].nags, [6]);
+        assert_eq!(game.moves[5].comments, ["Morphy"]);
+        let variation = &game.moves[3].variations[0];
+        assert_eq!(variation[0].san, "d6");
+        assert_eq!(variation[0].comments, ["Philidor"]);
+        assert_eq!(variation[1].san, "d4");
+        assert_eq!(games[1].tag("Event"), Some("Second"));
+        assert_eq!(games[1].mainline().unwrap().get_move_history().len(), 2);
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        let game = PgnGame::parse(ANNOTATED).unwrap();
+        let text = game.to_string();
+        assert!(
+            text.contains("2. Nf3 $1 Nc6 (2... d6 {Philidor} 3. d4) 3. Bb5 $6"),
+            "{text}"
+        );
+        assert_eq!(PgnGame::parse(&text).unwrap(), game);
+        assert!(text.lines().all(|line| line.len() <= 80));
+    }
+
+    #[test]
+    fn exports_played_games() {
+        let mut game = Game::from_fen(
+            "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2",
+            VariantKind::Standard,
+        )
+        .unwrap();
+        let mate = notation::parse_move(&game, "Qh4").unwrap();
+        game.make_move(mate);
+        let pgn = PgnGame::from_game(&game);
+        assert_eq!(pgn.result, "0-1");
+        assert_eq!(pgn.tag("SetUp"), Some("1"));
+        assert!(pgn.to_string().contains("2... Qh4#"), "{pgn}");
+        assert_eq!(pgn.mainline().unwrap().to_fen(), game.to_fen());
+        assert_eq!(pgn.tag("ECO"), None);
+
+        let berlin = PgnGame::parse("1. e4 e5 2. Nf3 Nc6 3. Bb5 Nf6 *")
+            .unwrap()
+            .mainline()
+            .unwrap();
+        let pgn = PgnGame::from_game(&berlin);
+        assert_eq!(pgn.tag("ECO"), Some("C65"));
+        assert_eq!(pgn.tag("Opening"), Some("Ruy Lopez: Berlin Defense"));
+    }
+}
@@ -1,8 +1,42 @@
 extern crate serde_json;
+use std::sync::OnceLock;
+
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
-#[derive(Debug, Clone, Copy, serde::Serialize, PartialEq)]
+use crate::game::Board;
+
+/// Knight jumps as (row, col) offsets.
+pub const KNIGHT_OFFSETS: [(isize, isize); 8] = [
+    (2, 1),
+    (2, -1),
+    (-2, 1),
+    (-2, -1),
+    (1, 2),
+    (1, -2),
+    (-1, 2),
+    (-1, -2),
+];
+
+/// Orthogonal sliding directions.
+pub const ROOK_DIRECTIONS: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// Diagonal sliding directions.
+pub const BISHOP_DIRECTIONS: [(isize, isize); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+/// One-square king steps, which are also the eight queen directions.
+pub const KING_OFFSETS: [(isize, isize); 8] = [
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, 1),
+    (0, -1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash)]
 pub enum Color {
     White,
     Black,
@@ -22,9 +56,19 @@ impl Color {
             Color::Black => ratatui::style::Color::Black,
         }
     }
+
+    /// Row direction a pawn of this colour advances in.
+    pub fn pawn_direction(&self) -> isize {
+        match self {
+            Color::White => -1,
+            Color::Black => 1,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, serde::Serialize, EnumIter)]
+#[derive(
+    Debug, Clone, Copy, serde::Serialize, serde::Deserialize, EnumIter, PartialEq, Eq, Hash,
+)]
 pub enum PieceType {
     Pawn = 1,
     Knight = 2,
@@ -32,9 +76,112 @@ pub enum PieceType {
     Rook = 4,
     Queen = 5,
     King = 6,
+    /// Bishop + knight compound, used in Capablanca and Grand chess.
+    Archbishop = 7,
+    /// Rook + knight compound, used in Capablanca and Grand chess.
+    Chancellor = 8,
+    /// Queen + knight compound.
+    Amazon = 9,
+}
+
+/// How one piece type moves and what it is worth, keyed by its lowercase FEN letter.
+#[derive(Debug, Clone, Copy)]
+pub struct PieceDef {
+    pub letter: char,
+    pub piece_type: PieceType,
+    /// (row, col) vectors the piece jumps along once.
+    pub leaps: &'static [(isize, isize)],
+    /// (row, col) vectors the piece slides along until blocked.
+    pub rides: &'static [(isize, isize)],
+    pub value: usize,
+}
+
+/// Every piece type in discriminant order. Pawns are special-cased by the move generator and
+/// have no vectors; a new fairy piece only needs a row here and a variant listing it.
+pub const PIECE_TABLE: [PieceDef; 9] = [
+    piece('p', PieceType::Pawn, &[], &[], 1),
+    piece('n', PieceType::Knight, &KNIGHT_OFFSETS, &[], 3),
+    piece('b', PieceType::Bishop, &[], &BISHOP_DIRECTIONS, 3),
+    piece('r', PieceType::Rook, &[], &ROOK_DIRECTIONS, 5),
+    piece('q', PieceType::Queen, &[], &KING_OFFSETS, 9),
+    piece('k', PieceType::King, &KING_OFFSETS, &[], usize::MAX),
+    piece(
+        'a',
+        PieceType::Archbishop,
+        &KNIGHT_OFFSETS,
+        &BISHOP_DIRECTIONS,
+        7,
+    ),
+    piece(
+        'c',
+        PieceType::Chancellor,
+        &KNIGHT_OFFSETS,
+        &ROOK_DIRECTIONS,
+        8,
+    ),
+    piece('z', PieceType::Amazon, &KNIGHT_OFFSETS, &KING_OFFSETS, 12),
+];
+
+const fn piece(
+    letter: char,
+    piece_type: PieceType,
+    leaps: &'static [(isize, isize)],
+    rides: &'static [(isize, isize)],
+    value: usize,
+) -> PieceDef {
+    PieceDef {
+        letter,
+        piece_type,
+        leaps,
+        rides,
+        value,
+    }
+}
+
+/// Every distinct leap in `PIECE_TABLE`, for finding which pieces attack a square.
+pub fn all_leaps() -> &'static [(isize, isize)] {
+    static LEAPS: OnceLock<Vec<(isize, isize)>> = OnceLock::new();
+    LEAPS.get_or_init(|| distinct(PIECE_TABLE.iter().flat_map(|def| def.leaps)))
+}
+
+/// Every distinct ride in `PIECE_TABLE`.
+pub fn all_rides() -> &'static [(isize, isize)] {
+    static RIDES: OnceLock<Vec<(isize, isize)>> = OnceLock::new();
+    RIDES.get_or_init(|| distinct(PIECE_TABLE.iter().flat_map(|def| def.rides)))
+}
+
+fn distinct<'a>(vectors: impl Iterator<Item = &'a (isize, isize)>) -> Vec<(isize, isize)> {
+    let mut distinct = vec![];
+    for &vector in vectors {
+        if !distinct.contains(&vector) {
+            distinct.push(vector);
+        }
+    }
+    distinct
 }
 
-#[derive(Debug, Clone, Copy, serde::Serialize)]
+impl PieceType {
+    /// This piece type's row of `PIECE_TABLE`.
+    pub fn def(&self) -> &'static PieceDef {
+        &PIECE_TABLE[*self as usize - 1]
+    }
+
+    /// Lowercase FEN letter for this piece type.
+    pub fn to_char(&self) -> char {
+        self.def().letter
+    }
+
+    /// Parses a FEN letter in either case.
+    pub fn from_char(c: char) -> Option<Self> {
+        let letter = c.to_ascii_lowercase();
+        PIECE_TABLE
+            .iter()
+            .find(|def| def.letter == letter)
+            .map(|def| def.piece_type)
+    }
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash)]
 pub struct Piece {
     pub color: Color,
     pub piece_type: PieceType,
@@ -43,14 +190,7 @@ pub struct Piece {
 #[allow(dead_code)]
 impl Piece {
     pub(crate) fn get_value(&self) -> usize {
-        match self.piece_type {
-            PieceType::Pawn => 1,
-            PieceType::Knight => 3,
-            PieceType::Bishop => 3,
-            PieceType::Rook => 5,
-            PieceType::Queen => 9,
-            PieceType::King => usize::MAX,
-        }
+        self.piece_type.def().value
     }
 
     pub fn symbol(&self) -> &'static str {
@@ -106,6 +246,33 @@ impl Piece {
     ▞▀▄▀▚\n\
     ▙▄█▄▟\n\
     ▐███▌\n\
+   ▗█████▖\n\
+    "
+            }
+            PieceType::Archbishop => {
+                "\
+    \n\
+    ⭘▟█▙\n\
+   █✝███\n\
+    ▀▟██▌\n\
+   ▗█████▖\n\
+    "
+            }
+            PieceType::Chancellor => {
+                "\
+    \n\
+   █▟█▙█▙\n\
+   ▜████▛\n\
+    ▐▟██▌\n\
+   ▗█████▖\n\
+    "
+            }
+            PieceType::Amazon => {
+                "\
+    \n\
+   ◀█▟█▙█▶\n\
+   ◥▟▛██▙◤\n\
+     ▟███▌\n\
    ▗█████▖\n\
     "
             }
@@ -124,6 +291,27 @@ impl Piece {
         }
     }
 
+    /// Filled or outlined Unicode chess glyph depending on the colour.
+    pub fn unicode_symbol(&self) -> char {
+        // ♔♕♖♗♘♙  ♚♛♜♝♞♟
+        match (self.color, self.piece_type) {
+            (Color::White, PieceType::King) => '♔',
+            (Color::White, PieceType::Queen) => '♕',
+            (Color::White, PieceType::Rook) => '♖',
+            (Color::White, PieceType::Bishop) => '♗',
+            (Color::White, PieceType::Knight) => '♘',
+            (Color::White, PieceType::Pawn) => '♙',
+            (Color::Black, PieceType::King) => '♚',
+            (Color::Black, PieceType::Queen) => '♛',
+            (Color::Black, PieceType::Rook) => '♜',
+            (Color::Black, PieceType::Bishop) => '♝',
+            (Color::Black, PieceType::Knight) => '♞',
+            (Color::Black, PieceType::Pawn) => '♟',
+            // Unicode has no widely supported fairy glyphs, fall back to the FEN letter
+            _ => self.fen_char(),
+        }
+    }
+
     pub(crate) fn get_piece_type_as_string(&self) -> String {
         match self.piece_type {
             PieceType::Pawn => "♙".to_string(),
@@ -132,126 +320,130 @@ impl Piece {
             PieceType::Rook => "♖".to_string(),
             PieceType::Queen => "♕".to_string(),
             PieceType::King => "♔".to_string(),
+            _ => self.piece_type.to_char().to_ascii_uppercase().to_string(),
         }
     }
 
-    pub(crate) fn get_pawn_moves(&self, position: (usize, usize)) -> Vec<(usize, usize)> {
-        let mut moves = vec![];
-        let direction = if self.color == Color::White { -1 } else { 1 };
-        let new_row = position.0 as isize + direction;
-
-        // Check if the new position is within bounds before casting back to usize
-        if new_row >= 0 {
-            moves.push((new_row as usize, position.1));
+    /// FEN letter for this piece, uppercase for white.
+    pub fn fen_char(&self) -> char {
+        let c = self.piece_type.to_char();
+        match self.color {
+            Color::White => c.to_ascii_uppercase(),
+            Color::Black => c,
         }
+    }
 
-        moves
+    /// Parses a FEN letter, using its case to pick the colour.
+    pub fn from_fen_char(c: char) -> Option<Self> {
+        let piece_type = PieceType::from_char(c)?;
+        let color = if c.is_ascii_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        Some(Piece::new(color, piece_type))
     }
 
-    pub(crate) fn get_knight_moves(&self, position: (usize, usize)) -> Vec<(usize, usize)> {
+    pub(crate) fn get_pawn_moves(
+        &self,
+        board: &Board,
+        position: (usize, usize),
+    ) -> Vec<(usize, usize)> {
         let mut moves = vec![];
-        let (row, col) = position;
-
-        // All possible L-shaped moves
-        let potential_moves = [
-            (row as isize + 2, col as isize + 1),
-            (row as isize + 2, col as isize - 1),
-            (row as isize - 2, col as isize + 1),
-            (row as isize - 2, col as isize - 1),
-            (row as isize + 1, col as isize + 2),
-            (row as isize + 1, col as isize - 2),
-            (row as isize - 1, col as isize + 2),
-            (row as isize - 1, col as isize - 2),
-        ];
-
-        for (r, c) in potential_moves {
-            if (0..8).contains(&r) && (0..8).contains(&c) {
-                moves.push((r as usize, c as usize));
+        let direction = self.color.pawn_direction();
+
+        // `offset` keeps every target within the board's bounds
+        let Some(forward) = board.offset(position, direction, 0) else {
+            return moves;
+        };
+
+        if board.get_piece_at(forward).is_none() {
+            moves.push(forward);
+
+            // Pawns start one rank in front of their own back rank
+            let start_row = board.back_rank(self.color) as isize + direction;
+            if position.0 as isize == start_row
+                && let Some(double) = board.offset(forward, direction, 0)
+                && board.get_piece_at(double).is_none()
+            {
+                moves.push(double);
             }
         }
 
-        moves
-    }
-
-    pub(crate) fn get_bishop_moves(&self, position: (usize, usize)) -> Vec<(usize, usize)> {
-        let mut moves = vec![];
-        let (row, col) = position;
-
-        let directions = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
-
-        for (dr, dc) in directions {
-            let mut r = row as isize + dr;
-            let mut c = col as isize + dc;
-
-            while (0..8).contains(&r) && (0..8).contains(&c) {
-                moves.push((r as usize, c as usize));
-                // Add blocking piece logic here
-                r += dr;
-                c += dc;
+        for dc in [-1, 1] {
+            if let Some(target) = board.offset(position, direction, dc)
+                && board
+                    .get_piece_at(target)
+                    .is_some_and(|other| other.color != self.color)
+            {
+                moves.push(target);
             }
         }
 
         moves
     }
 
-    pub(crate) fn get_rook_moves(&self, position: (usize, usize)) -> Vec<(usize, usize)> {
+    /// Single-step targets for leaping pieces; own pieces block, enemy pieces can be captured.
+    fn get_step_moves(
+        &self,
+        board: &Board,
+        position: (usize, usize),
+        offsets: &[(isize, isize)],
+    ) -> Vec<(usize, usize)> {
         let mut moves = vec![];
         let (row, col) = position;
 
-        let directions = [(1, 0), (-1, 0), (0, 1), (0, -1)];
-
-        for (dr, dc) in directions {
-            let mut r = row as isize + dr;
-            let mut c = col as isize + dc;
-
-            while (0..8).contains(&r) && (0..8).contains(&c) {
-                moves.push((r as usize, c as usize));
-                // Add blocking piece logic here
-                r += dr;
-                c += dc;
+        for &(dr, dc) in offsets {
+            if let Some(target) = board.offset((row, col), dr, dc) {
+                match board.get_piece_at(target) {
+                    Some(other) if other.color == self.color => {}
+                    _ => moves.push(target),
+                }
             }
         }
 
         moves
     }
 
-    pub(crate) fn get_queen_moves(&self, position: (usize, usize)) -> Vec<(usize, usize)> {
-        let mut moves = self.get_rook_moves(position);
-        moves.extend(self.get_bishop_moves(position));
-        moves
-    }
-
-    pub(crate) fn get_king_moves(&self, position: (usize, usize)) -> Vec<(usize, usize)> {
+    /// Ray targets for sliding pieces, stopping at the first piece in each direction.
+    fn get_sliding_moves(
+        &self,
+        board: &Board,
+        position: (usize, usize),
+        directions: &[(isize, isize)],
+    ) -> Vec<(usize, usize)> {
         let mut moves = vec![];
         let (row, col) = position;
 
-        for dr in -1..=1 {
-            for dc in -1..=1 {
-                if dr == 0 && dc == 0 {
-                    continue;
-                }
-
-                let r = row as isize + dr;
-                let c = col as isize + dc;
-
-                if (0..8).contains(&r) && (0..8).contains(&c) {
-                    moves.push((r as usize, c as usize));
+        for &(dr, dc) in directions {
+            let mut current = board.offset((row, col), dr, dc);
+
+            while let Some(target) = current {
+                match board.get_piece_at(target) {
+                    None => moves.push(target),
+                    Some(other) => {
+                        if other.color != self.color {
+                            moves.push(target);
+                        }
+                        break;
+                    }
                 }
+                current = board.offset(target, dr, dc);
             }
         }
 
         moves
     }
 
-    pub fn get_legal_moves(&self, position: (usize, usize)) -> Vec<(usize, usize)> {
-        match self.piece_type {
-            PieceType::Pawn => self.get_pawn_moves(position),
-            PieceType::Knight => self.get_knight_moves(position),
-            PieceType::Bishop => self.get_bishop_moves(position),
-            PieceType::Rook => self.get_rook_moves(position),
-            PieceType::Queen => self.get_queen_moves(position),
-            PieceType::King => self.get_king_moves(position),
+    /// Pseudo-legal target squares for this piece, ignoring check, castling and en passant.
+    pub fn get_legal_moves(&self, board: &Board, position: (usize, usize)) -> Vec<(usize, usize)> {
+        if self.piece_type == PieceType::Pawn {
+            return self.get_pawn_moves(board, position);
         }
+        let def = self.piece_type.def();
+        let mut moves = self.get_step_moves(board, position, def.leaps);
+        moves.extend(self.get_sliding_moves(board, position, def.rides));
+        moves
     }
 
     pub(crate) fn get_color(&self) -> Color {
@@ -0,0 +1,340 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::OptionExt;
+
+use crate::clock::TimeControl;
+use crate::config;
+use crate::eco;
+use crate::game::Game;
+use crate::pgn::PgnGame;
+use crate::pieces::Color;
+use crate::puzzle::Rating;
+
+/// Opening shown for games the ECO table does not classify.
+pub const UNCLASSIFIED: &str = "Unclassified";
+
+/// Someone who plays games on this machine, the engine included.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub rating: Rating,
+    #[serde(default)]
+    pub games: u32,
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            rating: Rating::default(),
+            games: 0,
+        }
+    }
+}
+
+/// Who has the white and who has the black pieces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pairing {
+    pub white: String,
+    pub black: String,
+}
+
+impl Pairing {
+    pub fn name(&self, color: Color) -> &str {
+        match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
+}
+
+/// One finished game as it was recorded.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct GameEntry {
+    pub white: String,
+    pub black: String,
+    /// `1-0`, `0-1` or `1/2-1/2`.
+    pub result: String,
+    /// How the game ended, such as `checkmate` or `timeout`.
+    pub reason: String,
+    pub time_control: Option<TimeControl>,
+    pub opening: Option<String>,
+    pub pgn: String,
+}
+
+impl GameEntry {
+    /// The colour `name` played and what they scored, 1 for a win and ½ for a draw, or `None`
+    /// if they did not play.
+    pub fn score(&self, name: &str) -> Option<(Color, f64)> {
+        let white = match self.result.as_str() {
+            "1-0" => 1.0,
+            "0-1" => 0.0,
+            _ => 0.5,
+        };
+        if self.white == name {
+            Some((Color::White, white))
+        } else if self.black == name {
+            Some((Color::Black, 1.0 - white))
+        } else {
+            None
+        }
+    }
+
+    /// The other player of a game `name` played.
+    pub fn opponent(&self, name: &str) -> &str {
+        if self.white == name {
+            &self.black
+        } else {
+            &self.white
+        }
+    }
+}
+
+/// Wins, losses and draws.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl Tally {
+    fn add(&mut self, score: f64) {
+        if score == 1.0 {
+            self.wins += 1;
+        } else if score == 0.0 {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+}
+
+impl fmt::Display for Tally {
+    /// Like `+3 -1 =2`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "+{} -{} ={}", self.wins, self.losses, self.draws)
+    }
+}
+
+/// A player's results, overall and split by colour, opening and opponent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Breakdown {
+    pub overall: Tally,
+    pub white: Tally,
+    pub black: Tally,
+    pub openings: BTreeMap<String, Tally>,
+    pub opponents: BTreeMap<String, Tally>,
+}
+
+/// The players and every game recorded between them, kept as TOML in
+/// `$XDG_DATA_HOME/chess-tui/profiles.toml` on Linux.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Profiles {
+    pub players: Vec<Profile>,
+    pub games: Vec<GameEntry>,
+}
+
+impl Profiles {
+    /// Where profiles are kept, if the platform has a data directory.
+    pub fn path() -> Option<PathBuf> {
+        config::data_dir().map(|dir| dir.join("profiles.toml"))
+    }
+
+    pub fn load_from(path: &Path) -> color_eyre::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(toml::from_str(&text)?),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save_to(&self, path: &Path) -> color_eyre::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.players.iter().find(|profile| profile.name == name)
+    }
+
+    /// The profile called `name`, created with the starting rating if it is new.
+    fn profile_mut(&mut self, name: &str) -> &mut Profile {
+        let index = match self.players.iter().position(|profile| profile.name == name) {
+            Some(index) => index,
+            None => {
+                self.players.push(Profile::new(name));
+                self.players.len() - 1
+            }
+        };
+        &mut self.players[index]
+    }
+
+    /// Adds the finished `game` between `pairing` and rates both players on it. A player facing
+    /// themselves has the game counted but keeps their rating.
+    pub fn record(
+        &mut self,
+        pairing: &Pairing,
+        game: &Game,
+        time_control: Option<TimeControl>,
+    ) -> color_eyre::Result<&GameEntry> {
+        let outcome = game.outcome().ok_or_eyre("the game is not over")?;
+        let mut pgn = PgnGame::from_game(game);
+        pgn.set_tag("White", pairing.white.as_str());
+        pgn.set_tag("Black", pairing.black.as_str());
+        if let Some(control) = time_control {
+            let tag = format!("{}+{}", control.base.as_secs(), control.increment.as_secs());
+            pgn.set_tag("TimeControl", tag);
+        }
+        let entry = GameEntry {
+            white: pairing.white.clone(),
+            black: pairing.black.clone(),
+            result: outcome.result().to_string(),
+            reason: outcome.reason.clone(),
+            time_control,
+            opening: eco::classify(game).map(|opening| opening.name.to_string()),
+            pgn: pgn.to_string(),
+        };
+
+        let score = match outcome.winner {
+            Some(Color::White) => 1.0,
+            Some(Color::Black) => 0.0,
+            None => 0.5,
+        };
+        if pairing.white == pairing.black {
+            self.profile_mut(&pairing.white).games += 1;
+        } else {
+            let white = self.profile_mut(&pairing.white).rating;
+            let black = self.profile_mut(&pairing.black).rating;
+            for (name, opponent, score) in [
+                (&pairing.white, black, score),
+                (&pairing.black, white, 1.0 - score),
+            ] {
+                let profile = self.profile_mut(name);
+                profile.games += 1;
+                profile.rating.update(opponent, score);
+            }
+        }
+        self.games.push(entry);
+        Ok(self.games.last().expect("just pushed"))
+    }
+
+    /// The results of every game `name` played.
+    pub fn breakdown(&self, name: &str) -> Breakdown {
+        let mut breakdown = Breakdown::default();
+        for entry in &self.games {
+            let Some((color, score)) = entry.score(name) else {
+                continue;
+            };
+            breakdown.overall.add(score);
+            match color {
+                Color::White => breakdown.white.add(score),
+                Color::Black => breakdown.black.add(score),
+            }
+            let opening = entry.opening.as_deref().unwrap_or(UNCLASSIFIED);
+            breakdown
+                .openings
+                .entry(opening.to_string())
+                .or_default()
+                .add(score);
+            breakdown
+                .opponents
+                .entry(entry.opponent(name).to_string())
+                .or_default()
+                .add(score);
+        }
+        breakdown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+    use crate::variant::VariantKind;
+
+    fn pairing() -> Pairing {
+        Pairing {
+            white: "Ada".to_string(),
+            black: "Bob".to_string(),
+        }
+    }
+
+    /// Fool's mate, won by Black.
+    fn fools_mate() -> Game {
+        let mut game = Game::default();
+        for san in ["f3", "e5", "g4", "Qh4#"] {
+            let mv = notation::parse_move(&game, san).unwrap();
+            game.make_move(mv);
+        }
+        game
+    }
+
+    #[test]
+    fn recording_a_game_rates_both_players() {
+        let mut profiles = Profiles::default();
+        let control = TimeControl::new(5, 3);
+        let entry = profiles
+            .record(&pairing(), &fools_mate(), Some(control))
+            .unwrap()
+            .clone();
+        assert_eq!(entry.result, "0-1");
+        assert_eq!(entry.reason, "checkmate");
+        assert!(entry.pgn.contains("[White \"Ada\"]"));
+        assert!(entry.pgn.contains("[TimeControl \"300+3\"]"));
+
+        let ada = profiles.profile("Ada").unwrap();
+        let bob = profiles.profile("Bob").unwrap();
+        assert_eq!((ada.games, bob.games), (1, 1));
+        assert!(ada.rating.rating < 1500.0 && bob.rating.rating > 1500.0);
+
+        let unfinished = Game::with_variant(VariantKind::Standard);
+        assert!(profiles.record(&pairing(), &unfinished, None).is_err());
+        assert_eq!(profiles.games.len(), 1);
+    }
+
+    #[test]
+    fn breakdowns_split_results_by_colour_opening_and_opponent() {
+        let mut profiles = Profiles::default();
+        profiles.record(&pairing(), &fools_mate(), None).unwrap();
+        let swapped = Pairing {
+            white: "Bob".to_string(),
+            black: "Ada".to_string(),
+        };
+        profiles.record(&swapped, &fools_mate(), None).unwrap();
+
+        let ada = profiles.breakdown("Ada");
+        assert_eq!(ada.overall.to_string(), "+1 -1 =0");
+        assert_eq!(ada.white.losses, 1);
+        assert_eq!(ada.black.wins, 1);
+        assert_eq!(ada.opponents["Bob"].games(), 2);
+        assert_eq!(ada.openings.values().map(Tally::games).sum::<u32>(), 2);
+        assert_eq!(profiles.breakdown("Carol"), Breakdown::default());
+    }
+
+    #[test]
+    fn profiles_round_trip_through_toml() {
+        let mut profiles = Profiles::default();
+        profiles
+            .record(&pairing(), &fools_mate(), Some(TimeControl::new(3, 2)))
+            .unwrap();
+        let dir = std::env::temp_dir().join(format!("chess-tui-profiles-{}", std::process::id()));
+        let path = dir.join("profiles.toml");
+        profiles.save_to(&path).unwrap();
+        assert_eq!(Profiles::load_from(&path).unwrap(), profiles);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(Profiles::load_from(&path).unwrap(), Profiles::default());
+    }
+}
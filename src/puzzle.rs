@@ -0,0 +1,494 @@
+use std::collections::BTreeSet;
+use std::f64::consts::{LN_10, PI};
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{OptionExt, bail};
+
+use crate::config;
+use crate::game::{Game, Move};
+use crate::notation;
+use crate::pieces::Color;
+use crate::variant::VariantKind;
+
+/// Lowest rating deviation a player's puzzle rating settles to.
+const MIN_DEVIATION: f64 = 45.0;
+
+/// One row of the Lichess puzzle database: the position before the opponent's last move, then
+/// that move and the solution, all in UCI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Puzzle {
+    pub id: String,
+    pub fen: String,
+    pub moves: Vec<String>,
+    pub rating: u32,
+    pub deviation: u32,
+    pub themes: Vec<String>,
+}
+
+impl Puzzle {
+    /// Reads a line such as
+    /// `00sHx,q3k1nr/...  b k - 0 17,e8d7 a2e6 d7d8 f7f8,1760,80,83,72,mate mateIn2 short,...`,
+    /// the columns after the themes being ignored.
+    pub fn parse(line: &str) -> color_eyre::Result<Self> {
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() < 8 {
+            bail!("expected at least 8 columns, found {}", fields.len());
+        }
+        let moves: Vec<String> = fields[2].split_whitespace().map(str::to_string).collect();
+        if moves.len() < 2 {
+            bail!("puzzle {} has no solution moves", fields[0]);
+        }
+        Ok(Self {
+            id: fields[0].to_string(),
+            fen: fields[1].to_string(),
+            moves,
+            rating: fields[3].parse()?,
+            deviation: fields[4].parse()?,
+            themes: fields[7].split_whitespace().map(str::to_string).collect(),
+        })
+    }
+
+    /// The puzzles of a CSV file that `filter` keeps, skipping the header row if there is one.
+    pub fn load(path: &Path, filter: &Filter) -> color_eyre::Result<Vec<Self>> {
+        let file = std::fs::File::open(path)?;
+        let mut puzzles = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() || line.starts_with("PuzzleId") {
+                continue;
+            }
+            let puzzle =
+                Self::parse(&line).map_err(|err| err.wrap_err(format!("line {}", number + 1)))?;
+            if filter.matches(&puzzle) {
+                puzzles.push(puzzle);
+            }
+        }
+        Ok(puzzles)
+    }
+}
+
+/// Which puzzles to train on, parsed from terms such as `fork mateIn2 rating:1200-1800`. Bare
+/// words are themes, all of which a puzzle must have; either end of the rating range may be
+/// left off.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    pub themes: Vec<String>,
+    pub min_rating: Option<u32>,
+    pub max_rating: Option<u32>,
+}
+
+impl Filter {
+    pub fn parse(query: &str) -> color_eyre::Result<Self> {
+        let mut filter = Self::default();
+        for term in query.split_whitespace() {
+            match term.split_once(':') {
+                Some(("rating" | "r", range)) => {
+                    let (min, max) = range.split_once('-').unwrap_or((range, range));
+                    let bound = |value: &str| {
+                        (!value.is_empty())
+                            .then(|| value.parse::<u32>())
+                            .transpose()
+                    };
+                    filter.min_rating = bound(min)?;
+                    filter.max_rating = bound(max)?;
+                }
+                Some(("theme" | "t", theme)) => filter.themes.push(theme.to_lowercase()),
+                Some((field, _)) => bail!("unknown filter `{field}`: use theme or rating"),
+                None => filter.themes.push(term.to_lowercase()),
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn matches(&self, puzzle: &Puzzle) -> bool {
+        self.themes.iter().all(|theme| {
+            puzzle
+                .themes
+                .iter()
+                .any(|own| own.eq_ignore_ascii_case(theme))
+        }) && self.min_rating.is_none_or(|min| puzzle.rating >= min)
+            && self.max_rating.is_none_or(|max| puzzle.rating <= max)
+    }
+}
+
+/// A Glicko rating: the estimate and how uncertain it is.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            rating: 1500.0,
+            deviation: 350.0,
+        }
+    }
+}
+
+impl Rating {
+    /// Glicko-1 update after one game against `opponent`, scoring 1 for a win and 0 for a loss.
+    pub fn update(&mut self, opponent: Rating, score: f64) {
+        let q = LN_10 / 400.0;
+        let g = 1.0 / (1.0 + 3.0 * q * q * opponent.deviation.powi(2) / (PI * PI)).sqrt();
+        let expected = 1.0 / (1.0 + 10f64.powf(-g * (self.rating - opponent.rating) / 400.0));
+        let d2 = 1.0 / (q * q * g * g * expected * (1.0 - expected));
+        let precision = 1.0 / self.deviation.powi(2) + 1.0 / d2;
+        self.rating += q / precision * g * (score - expected);
+        self.deviation = (1.0 / precision).sqrt().max(MIN_DEVIATION);
+    }
+}
+
+impl std::fmt::Display for Rating {
+    /// Like `1523 ± 80`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0} ± {:.0}", self.rating, self.deviation)
+    }
+}
+
+/// The player's puzzle rating and the puzzles already played, kept as TOML in
+/// `$XDG_DATA_HOME/chess-tui/puzzles.toml` on Linux.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Progress {
+    pub rating: Rating,
+    pub solved: BTreeSet<String>,
+    pub failed: BTreeSet<String>,
+}
+
+impl Progress {
+    /// Where progress is kept, if the platform has a data directory.
+    pub fn path() -> Option<PathBuf> {
+        config::data_dir().map(|dir| dir.join("puzzles.toml"))
+    }
+
+    pub fn load_from(path: &Path) -> color_eyre::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(toml::from_str(&text)?),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save_to(&self, path: &Path) -> color_eyre::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn played(&self, id: &str) -> bool {
+        self.solved.contains(id) || self.failed.contains(id)
+    }
+}
+
+/// Where an attempt at a puzzle stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Solving,
+    Solved,
+    /// A wrong move was played, or the solution was shown.
+    Failed,
+}
+
+/// One puzzle being solved: the board after the opponent's opening move, and how far into the
+/// solution the player is.
+#[derive(Debug, Clone)]
+pub struct Attempt {
+    pub puzzle: Puzzle,
+    game: Game,
+    /// Index into the puzzle's moves of the player's next move.
+    next: usize,
+    pub status: Status,
+    /// 0 for no hint, 1 once the piece to move is shown, 2 once the move itself is.
+    pub hint: u8,
+}
+
+impl Attempt {
+    /// Sets up `puzzle` and plays the opponent's move that starts it.
+    pub fn new(puzzle: Puzzle) -> color_eyre::Result<Self> {
+        let mut game = Game::from_fen(&puzzle.fen, VariantKind::Standard)?;
+        let opening = notation::parse_move(&game, &puzzle.moves[0])?;
+        game.make_move(opening);
+        Ok(Self {
+            puzzle,
+            game,
+            next: 1,
+            status: Status::Solving,
+            hint: 0,
+        })
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// The side the player finds moves for.
+    pub fn player(&self) -> Color {
+        self.game.get_current_turn()
+    }
+
+    /// The solution move expected next, while the puzzle is being solved.
+    pub fn expected(&self) -> Option<Move> {
+        let uci = self.puzzle.moves.get(self.next)?;
+        notation::parse_move(&self.game, uci).ok()
+    }
+
+    /// Plays the player's move if it is the solution's, or any move that mates, then the
+    /// opponent's reply. Any other move fails the puzzle without being played.
+    pub fn play(&mut self, mv: Move) -> Status {
+        if self.status != Status::Solving {
+            return self.status;
+        }
+        let Some(expected) = self.expected() else {
+            return self.status;
+        };
+        let mut after = self.game.clone();
+        after.make_move(mv);
+        let mates = after
+            .outcome()
+            .is_some_and(|outcome| outcome.winner == Some(self.player()));
+        if mv != expected && !mates {
+            self.status = Status::Failed;
+            return self.status;
+        }
+        self.game = after;
+        self.next += 1;
+        self.hint = 0;
+        if mates || self.next >= self.puzzle.moves.len() {
+            self.status = Status::Solved;
+        } else if let Some(reply) = self.expected() {
+            self.game.make_move(reply);
+            self.next += 1;
+            if self.next >= self.puzzle.moves.len() {
+                self.status = Status::Solved;
+            }
+        }
+        self.status
+    }
+
+    /// Shows more of the next move: first the piece, then the move, which fails the puzzle.
+    pub fn hint(&mut self) {
+        if self.status == Status::Solving && self.hint < 2 {
+            self.hint += 1;
+            if self.hint == 2 {
+                self.status = Status::Failed;
+            }
+        }
+    }
+}
+
+/// Puzzles from a CSV file, the one being solved and the player's progress.
+#[derive(Debug)]
+pub struct Trainer {
+    pub file: PathBuf,
+    pub filter: Filter,
+    /// The filter as typed.
+    pub query: String,
+    pub puzzles: Vec<Puzzle>,
+    pub progress: Progress,
+    /// Where progress is saved after each puzzle, if anywhere.
+    progress_path: Option<PathBuf>,
+    pub attempt: Option<Attempt>,
+    /// Filter being edited, while the filter bar is open.
+    pub filter_input: Option<String>,
+    /// Why the last filter was rejected or progress could not be saved.
+    pub message: Option<String>,
+}
+
+impl Trainer {
+    /// Loads the puzzles of `file` that `query` keeps and sets up the first one.
+    pub fn open(
+        file: &Path,
+        query: &str,
+        progress_path: Option<PathBuf>,
+    ) -> color_eyre::Result<Self> {
+        let filter = Filter::parse(query)?;
+        let progress = match &progress_path {
+            Some(path) => Progress::load_from(path)?,
+            None => Progress::default(),
+        };
+        let mut trainer = Self {
+            file: file.to_path_buf(),
+            puzzles: Puzzle::load(file, &filter)?,
+            filter,
+            query: query.trim().to_string(),
+            progress,
+            progress_path,
+            attempt: None,
+            filter_input: None,
+            message: None,
+        };
+        trainer.next_puzzle()?;
+        Ok(trainer)
+    }
+
+    /// Reloads the puzzles with a typed filter, keeping the old one if it does not parse.
+    pub fn set_filter(&mut self, query: &str) -> color_eyre::Result<()> {
+        let filter = Filter::parse(query)?;
+        self.puzzles = Puzzle::load(&self.file, &filter)?;
+        self.filter = filter;
+        self.query = query.trim().to_string();
+        self.next_puzzle()
+    }
+
+    /// Sets up the unplayed puzzle rated closest to the player.
+    pub fn next_puzzle(&mut self) -> color_eyre::Result<()> {
+        let rating = self.progress.rating.rating;
+        let current = self.attempt.as_ref().map(|attempt| &attempt.puzzle.id);
+        let puzzle = self
+            .puzzles
+            .iter()
+            .filter(|puzzle| !self.progress.played(&puzzle.id) && Some(&puzzle.id) != current)
+            .min_by(|a, b| {
+                (a.rating as f64 - rating)
+                    .abs()
+                    .total_cmp(&(b.rating as f64 - rating).abs())
+            })
+            .ok_or_eyre("no unplayed puzzles match the filter")?;
+        self.attempt = Some(Attempt::new(puzzle.clone())?);
+        Ok(())
+    }
+
+    /// Plays the player's move and, once the puzzle is over, rates and records it.
+    pub fn play(&mut self, mv: Move) -> Status {
+        let Some(attempt) = &mut self.attempt else {
+            return Status::Failed;
+        };
+        let status = attempt.play(mv);
+        self.record();
+        status
+    }
+
+    pub fn hint(&mut self) {
+        if let Some(attempt) = &mut self.attempt {
+            attempt.hint();
+            self.record();
+        }
+    }
+
+    /// Updates the rating for a finished puzzle played for the first time, and saves progress.
+    fn record(&mut self) {
+        let Some(attempt) = &self.attempt else {
+            return;
+        };
+        let puzzle = &attempt.puzzle;
+        if attempt.status == Status::Solving || self.progress.played(&puzzle.id) {
+            return;
+        }
+        let opponent = Rating {
+            rating: puzzle.rating as f64,
+            deviation: puzzle.deviation as f64,
+        };
+        if attempt.status == Status::Solved {
+            self.progress.rating.update(opponent, 1.0);
+            self.progress.solved.insert(puzzle.id.clone());
+        } else {
+            self.progress.rating.update(opponent, 0.0);
+            self.progress.failed.insert(puzzle.id.clone());
+        }
+        if let Some(path) = &self.progress_path
+            && let Err(err) = self.progress.save_to(path)
+        {
+            self.message = Some(format!("Could not save progress: {err}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "\
+PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+0009B,r2qr1k1/b1p2ppp/pp4n1/P1P1p3/4P1n1/B2P2Pb/3NBP1P/RN1QR1K1 b - - 1 16,b6c5 e2g4 h3g4 d1g4,1112,74,87,569,advantage middlegame short,https://lichess.org/4MWQCxQ6/black#32,
+000aY,r4rk1/pp3ppp/2n1b3/q1pp2B1/8/P1Q2NP1/1PP1PP1P/2KR3R w - - 0 15,g5e7 a5c3 b2c3 c6e7,1430,74,93,2178,advantageEndgame crushing endgame fork short,https://lichess.org/iihZGl6t#29,
+";
+
+    fn csv(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "chess-tui-puzzles-{name}-{}.csv",
+            std::process::id()
+        ));
+        std::fs::write(&path, CSV).unwrap();
+        path
+    }
+
+    #[test]
+    fn loads_and_filters_puzzles() {
+        let path = csv("filters");
+        let all = Puzzle::load(&path, &Filter::default()).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].moves, ["g5e7", "a5c3", "b2c3", "c6e7"]);
+        assert_eq!(all[1].deviation, 74);
+
+        let forks = Filter::parse("fork rating:1200-").unwrap();
+        assert_eq!(forks.min_rating, Some(1200));
+        assert_eq!(forks.max_rating, None);
+        let kept = Puzzle::load(&path, &forks).unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].id, "000aY");
+        assert!(Puzzle::load(&path, &Filter::parse("r:-1200").unwrap()).unwrap()[0].id == "0009B");
+        assert!(Filter::parse("elo:1500").is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn plays_the_solution_with_replies() {
+        let path = csv("solve");
+        let puzzle = Puzzle::load(&path, &Filter::default()).unwrap().remove(1);
+        let mut attempt = Attempt::new(puzzle.clone()).unwrap();
+        assert_eq!(attempt.player(), Color::Black);
+        let mv = |attempt: &Attempt, san: &str| notation::parse_move(attempt.game(), san).unwrap();
+
+        assert_eq!(attempt.play(mv(&attempt, "Qxc3")), Status::Solving);
+        // White's recapture was played for us
+        assert_eq!(attempt.game().get_move_history().len(), 3);
+        attempt.hint();
+        assert_eq!(attempt.hint, 1);
+        assert_eq!(attempt.play(mv(&attempt, "Nxe7")), Status::Solved);
+
+        let mut wrong = Attempt::new(puzzle).unwrap();
+        assert_eq!(wrong.play(mv(&wrong, "Qa4")), Status::Failed);
+        assert_eq!(wrong.game().get_move_history().len(), 1);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rates_and_remembers_puzzles() {
+        let mut rating = Rating::default();
+        rating.update(
+            Rating {
+                rating: 1500.0,
+                deviation: 75.0,
+            },
+            1.0,
+        );
+        assert!(rating.rating > 1600.0 && rating.rating < 1700.0, "{rating}");
+        assert!(rating.deviation < 350.0);
+
+        let path = csv("progress");
+        let progress_path = std::env::temp_dir()
+            .join(format!("chess-tui-progress-{}", std::process::id()))
+            .join("puzzles.toml");
+        let mut trainer = Trainer::open(&path, "", Some(progress_path.clone())).unwrap();
+        // The 1430 puzzle is nearer a new player's 1500
+        let attempt = trainer.attempt.as_ref().unwrap();
+        assert_eq!(attempt.puzzle.id, "000aY");
+        trainer.hint();
+        trainer.hint();
+        assert_eq!(trainer.attempt.as_ref().unwrap().status, Status::Failed);
+        assert!(trainer.progress.rating.rating < 1500.0);
+
+        let saved = Progress::load_from(&progress_path).unwrap();
+        assert_eq!(saved, trainer.progress);
+        let mut reopened = Trainer::open(&path, "", Some(progress_path.clone())).unwrap();
+        assert_eq!(reopened.attempt.as_ref().unwrap().puzzle.id, "0009B");
+        assert!(reopened.next_puzzle().is_err());
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_dir_all(progress_path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+use color_eyre::eyre::{WrapErr, eyre};
+
+use crate::game::Game;
+use crate::pgn::{PgnGame, UNKNOWN_RESULT};
+use crate::variant::VariantKind;
+
+/// A game saved as JSON: the starting position, the moves in coordinate notation and the
+/// position they lead to.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct GameRecord {
+    pub variant: VariantKind,
+    pub start_fen: String,
+    /// Moves in UCI notation, such as `e2e4` or `P@e4`.
+    pub moves: Vec<String>,
+    /// Final position, for readers that do not replay the moves.
+    pub fen: String,
+    pub result: String,
+    /// PGN tag pairs carried over from the game's source.
+    #[serde(default)]
+    pub tags: Vec<(String, String)>,
+}
+
+impl GameRecord {
+    pub fn from_game(game: &Game) -> Self {
+        let mut replay = Game::from_fen(game.start_fen(), game.variant())
+            .expect("a game's starting FEN reloads");
+        let moves = game
+            .get_move_history()
+            .iter()
+            .map(|mv| {
+                let uci = mv.to_uci(replay.get_board());
+                replay.make_move(*mv);
+                uci
+            })
+            .collect();
+        Self {
+            variant: game.variant(),
+            start_fen: game.start_fen().to_string(),
+            moves,
+            fen: game.to_fen(),
+            result: game
+                .outcome()
+                .map_or(UNKNOWN_RESULT, |outcome| outcome.result())
+                .to_string(),
+            tags: Vec::new(),
+        }
+    }
+
+    /// Replays the moves from the starting position.
+    pub fn to_game(&self) -> color_eyre::Result<Game> {
+        let mut game = Game::from_fen(&self.start_fen, self.variant)?;
+        for (index, uci) in self.moves.iter().enumerate() {
+            let mv = game
+                .legal_moves()
+                .into_iter()
+                .find(|mv| mv.to_uci(game.get_board()) == *uci)
+                .ok_or_else(|| eyre!("{uci} is not a legal move"))
+                .wrap_err_with(|| format!("move {} of the record", index + 1))?;
+            game.make_move(mv);
+        }
+        Ok(game)
+    }
+
+    /// The record of a PGN game's main line, keeping its tags and result.
+    pub fn from_pgn(pgn: &PgnGame) -> color_eyre::Result<Self> {
+        Ok(Self {
+            result: pgn.result.clone(),
+            tags: pgn.tags.clone(),
+            ..Self::from_game(&pgn.mainline()?)
+        })
+    }
+
+    /// The record as PGN, with the saved tags or the seven-tag roster.
+    pub fn to_pgn(&self) -> color_eyre::Result<PgnGame> {
+        let mut pgn = PgnGame::from_game(&self.to_game()?);
+        for (name, value) in &self.tags {
+            pgn.set_tag(name, value.clone());
+        }
+        pgn.result = self.result.clone();
+        pgn.set_tag("Result", self.result.clone());
+        Ok(pgn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_json_and_pgn() {
+        let pgn =
+            PgnGame::parse("[White \"A\"]\n\n1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0").unwrap();
+        let record = GameRecord::from_pgn(&pgn).unwrap();
+        assert_eq!(record.moves[..2], ["e2e4", "e7e5"]);
+        assert_eq!(record.result, "1-0");
+
+        let json = serde_json::to_string(&record).unwrap();
+        let read: GameRecord = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, record);
+        assert_eq!(read.to_game().unwrap().to_fen(), record.fen);
+
+        let back = read.to_pgn().unwrap();
+        assert_eq!(back.tag("White"), Some("A"));
+        assert_eq!(back.moves.len(), 7);
+        assert_eq!(back.moves[6].san, "Qxf7#");
+    }
+}
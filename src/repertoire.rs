@@ -0,0 +1,533 @@
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use color_eyre::eyre::{WrapErr, bail};
+
+use crate::config;
+use crate::game::{Game, Move};
+use crate::notation;
+use crate::pgn::{self, PgnGame, PgnMove};
+use crate::pieces::Color;
+
+/// Ease factor of a line never reviewed, and the lowest it can fall to.
+const START_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+
+/// Days since the Unix epoch, the unit lines are scheduled in.
+pub fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86_400)
+}
+
+/// One path through the repertoire, from the start of its chapter to the end of a variation.
+#[derive(Debug, Clone)]
+pub struct Line {
+    /// The chapter's `Event` tag, or its number.
+    pub chapter: String,
+    pub start: Game,
+    pub moves: Vec<Move>,
+    /// The moves in numbered SAN, such as `1. e4 e5 2. Nf3`, which identifies the line's
+    /// statistics.
+    pub key: String,
+}
+
+/// Opening lines imported from PGN, every variation a line of its own, drilled from one side.
+#[derive(Debug, Clone)]
+pub struct Repertoire {
+    pub color: Color,
+    pub lines: Vec<Line>,
+}
+
+impl Repertoire {
+    /// Reads the repertoire from a PGN file.
+    pub fn open(path: &Path, color: Option<Color>) -> color_eyre::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("cannot read {}", path.display()))?;
+        Self::from_pgn(&pgn::parse_all(&text)?, color)
+    }
+
+    /// Splits every game of `games` into lines. Without a `color`, the side trained is the one
+    /// whose moves never branch: a repertoire answers each opponent move one way.
+    pub fn from_pgn(games: &[PgnGame], color: Option<Color>) -> color_eyre::Result<Self> {
+        let mut lines = Vec::new();
+        let mut branching = [false; 2];
+        for (index, pgn) in games.iter().enumerate() {
+            let chapter = pgn
+                .tag("Event")
+                .filter(|event| *event != "?")
+                .map_or_else(|| format!("Chapter {}", index + 1), str::to_string);
+            let start = pgn.starting_position()?;
+            let mut paths = Vec::new();
+            walk(&start, &pgn.moves, Vec::new(), &mut paths, &mut branching)
+                .wrap_err_with(|| format!("in {chapter}"))?;
+            for moves in paths {
+                lines.push(Line {
+                    chapter: chapter.clone(),
+                    start: start.clone(),
+                    key: notation::numbered(&start, &moves),
+                    moves,
+                });
+            }
+        }
+        if lines.is_empty() {
+            bail!("the PGN has no moves to drill");
+        }
+        let color = color.unwrap_or(
+            match (
+                branching[Color::White as usize],
+                branching[Color::Black as usize],
+            ) {
+                (true, false) => Color::Black,
+                _ => Color::White,
+            },
+        );
+        Ok(Self { color, lines })
+    }
+}
+
+/// Collects the move paths of `moves` and their variations into `paths`, noting which side
+/// had alternatives.
+fn walk(
+    start: &Game,
+    moves: &[PgnMove],
+    mut line: Vec<Move>,
+    paths: &mut Vec<Vec<Move>>,
+    branching: &mut [bool; 2],
+) -> color_eyre::Result<()> {
+    let mut game = start.clone();
+    for pgn_move in moves {
+        if !pgn_move.variations.is_empty() {
+            branching[game.get_current_turn() as usize] = true;
+        }
+        for variation in &pgn_move.variations {
+            walk(&game, variation, line.clone(), paths, branching)?;
+        }
+        let mv = notation::parse_move(&game, &pgn_move.san)
+            .wrap_err_with(|| format!("move {}", pgn_move.san))?;
+        game.make_move(mv);
+        line.push(mv);
+    }
+    if !line.is_empty() {
+        paths.push(line);
+    }
+    Ok(())
+}
+
+/// SM-2 schedule and record of one line.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct LineStats {
+    /// Reviews in a row recalled well enough.
+    pub repetitions: u32,
+    pub interval: u64,
+    pub ease: f64,
+    /// Day the line is next due, in days since the Unix epoch.
+    pub due: u64,
+    pub reviews: u32,
+    /// Reviews that had to start the schedule over.
+    pub lapses: u32,
+}
+
+impl Default for LineStats {
+    fn default() -> Self {
+        Self {
+            repetitions: 0,
+            interval: 0,
+            ease: START_EASE,
+            due: 0,
+            reviews: 0,
+            lapses: 0,
+        }
+    }
+}
+
+impl LineStats {
+    /// Reschedules the line after a review on day `today` graded `quality`, from 0 for a
+    /// blackout to 5 for perfect recall.
+    pub fn review(&mut self, quality: u8, today: u64) {
+        let quality = quality.min(5);
+        if quality >= 3 {
+            self.interval = match self.repetitions {
+                0 => 1,
+                1 => 6,
+                _ => (self.interval as f64 * self.ease).round() as u64,
+            };
+            self.repetitions += 1;
+        } else {
+            self.repetitions = 0;
+            self.interval = 1;
+            self.lapses += 1;
+        }
+        let miss = (5 - quality) as f64;
+        self.ease = (self.ease + 0.1 - miss * (0.08 + miss * 0.02)).max(MIN_EASE);
+        self.due = today + self.interval;
+        self.reviews += 1;
+    }
+}
+
+/// Statistics of every line drilled, by side and line, kept as TOML in
+/// `repertoire.toml` in the data directory.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    pub white: BTreeMap<String, LineStats>,
+    pub black: BTreeMap<String, LineStats>,
+}
+
+impl Stats {
+    /// Where statistics are kept, if the platform has a data directory.
+    pub fn path() -> Option<PathBuf> {
+        config::data_dir().map(|dir| dir.join("repertoire.toml"))
+    }
+
+    pub fn load_from(path: &Path) -> color_eyre::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(toml::from_str(&text)?),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save_to(&self, path: &Path) -> color_eyre::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn line(&self, color: Color, key: &str) -> LineStats {
+        let lines = match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        };
+        lines.get(key).copied().unwrap_or_default()
+    }
+
+    fn line_mut(&mut self, color: Color, key: &str) -> &mut LineStats {
+        let lines = match color {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        };
+        lines.entry(key.to_string()).or_default()
+    }
+}
+
+/// How a move played in a drill compared with the repertoire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The repertoire move; the drill goes on.
+    Correct,
+    /// The repertoire move, and the last one of the line.
+    Finished,
+    /// Not the repertoire move; it is taken back.
+    Wrong,
+}
+
+/// One line being quizzed: the opponent's moves are played for the user, whose own moves must
+/// match the line.
+#[derive(Debug, Clone)]
+pub struct Drill {
+    /// Index into the repertoire's lines.
+    pub line: usize,
+    game: Game,
+    moves: Vec<Move>,
+    color: Color,
+    /// Moves of the line played so far.
+    ply: usize,
+    pub mistakes: u32,
+    /// Whether the expected move is shown, after a hint or repeated mistakes.
+    pub revealed: bool,
+    pub finished: bool,
+    /// How the last move played compared with the line.
+    pub verdict: Option<Verdict>,
+}
+
+impl Drill {
+    pub fn new(repertoire: &Repertoire, line: usize) -> Self {
+        let source = &repertoire.lines[line];
+        let mut drill = Self {
+            line,
+            game: source.start.clone(),
+            moves: source.moves.clone(),
+            color: repertoire.color,
+            ply: 0,
+            mistakes: 0,
+            revealed: false,
+            finished: false,
+            verdict: None,
+        };
+        drill.play_replies();
+        drill
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// The moves of the line played so far.
+    pub fn played(&self) -> &[Move] {
+        &self.moves[..self.ply]
+    }
+
+    /// The repertoire move the user is to play next.
+    pub fn expected(&self) -> Option<Move> {
+        self.moves.get(self.ply).copied()
+    }
+
+    /// Plays `mv` if it is the repertoire's, then the opponent's reply. A wrong move counts as
+    /// a mistake, and a second one shows the expected move.
+    pub fn play(&mut self, mv: Move) -> Verdict {
+        if self.finished {
+            return Verdict::Finished;
+        }
+        let verdict = if Some(mv) != self.expected() {
+            self.mistakes += 1;
+            self.revealed |= self.mistakes >= 2;
+            Verdict::Wrong
+        } else {
+            self.game.make_move(mv);
+            self.ply += 1;
+            self.revealed = false;
+            self.play_replies();
+            if self.finished {
+                Verdict::Finished
+            } else {
+                Verdict::Correct
+            }
+        };
+        self.verdict = Some(verdict);
+        verdict
+    }
+
+    /// Shows the expected move, which counts as a mistake.
+    pub fn hint(&mut self) {
+        if !self.finished && !self.revealed {
+            self.revealed = true;
+            self.mistakes += 1;
+        }
+    }
+
+    /// How well the line was recalled, for [`LineStats::review`].
+    pub fn quality(&self) -> u8 {
+        match self.mistakes {
+            0 => 5,
+            1 => 3,
+            _ => 1,
+        }
+    }
+
+    /// Plays the opponent's moves until it is the user's turn, finishing the drill when the
+    /// line has no more of the user's moves.
+    fn play_replies(&mut self) {
+        while let Some(mv) = self.expected()
+            && self.game.get_current_turn() != self.color
+        {
+            self.game.make_move(mv);
+            self.ply += 1;
+        }
+        self.finished = self.expected().is_none();
+    }
+}
+
+/// A repertoire being drilled with its statistics, one line at a time.
+#[derive(Debug)]
+pub struct Session {
+    pub repertoire: Repertoire,
+    pub stats: Stats,
+    /// Where statistics are saved after each line, if anywhere.
+    stats_path: Option<PathBuf>,
+    pub drill: Drill,
+    /// The day lines are scheduled from, in days since the Unix epoch.
+    pub today: u64,
+    /// Why statistics could not be saved.
+    pub message: Option<String>,
+}
+
+impl Session {
+    /// Starts on the line most overdue on day `today`.
+    pub fn new(
+        repertoire: Repertoire,
+        stats_path: Option<PathBuf>,
+        today: u64,
+    ) -> color_eyre::Result<Self> {
+        let stats = match &stats_path {
+            Some(path) => Stats::load_from(path)?,
+            None => Stats::default(),
+        };
+        let line = next_line(&repertoire, &stats, None);
+        Ok(Self {
+            drill: Drill::new(&repertoire, line),
+            repertoire,
+            stats,
+            stats_path,
+            today,
+            message: None,
+        })
+    }
+
+    /// The line being drilled.
+    pub fn line(&self) -> &Line {
+        &self.repertoire.lines[self.drill.line]
+    }
+
+    /// The statistics of the line being drilled.
+    pub fn line_stats(&self) -> LineStats {
+        self.stats.line(self.repertoire.color, &self.line().key)
+    }
+
+    /// How many lines are due for review.
+    pub fn due(&self) -> usize {
+        self.repertoire
+            .lines
+            .iter()
+            .filter(|line| self.stats.line(self.repertoire.color, &line.key).due <= self.today)
+            .count()
+    }
+
+    /// Plays the user's move and, once the line is finished, reschedules it.
+    pub fn play(&mut self, mv: Move) -> Verdict {
+        let finished = self.drill.finished;
+        let verdict = self.drill.play(mv);
+        if verdict == Verdict::Finished && !finished {
+            self.record();
+        }
+        verdict
+    }
+
+    pub fn hint(&mut self) {
+        self.drill.hint();
+    }
+
+    /// Moves on to the line due first, other than the one just drilled.
+    pub fn next_line(&mut self) {
+        let line = next_line(&self.repertoire, &self.stats, Some(self.drill.line));
+        self.drill = Drill::new(&self.repertoire, line);
+    }
+
+    /// Reviews the finished line with the quality it was recalled with, and saves the
+    /// statistics.
+    fn record(&mut self) {
+        let key = self.line().key.clone();
+        self.stats
+            .line_mut(self.repertoire.color, &key)
+            .review(self.drill.quality(), self.today);
+        if let Some(path) = &self.stats_path
+            && let Err(err) = self.stats.save_to(path)
+        {
+            self.message = Some(format!("Could not save statistics: {err}"));
+        }
+    }
+}
+
+/// The line due first, other than `skip` when there is another, preferring lines reviewed
+/// less often.
+fn next_line(repertoire: &Repertoire, stats: &Stats, skip: Option<usize>) -> usize {
+    (0..repertoire.lines.len())
+        .filter(|&index| Some(index) != skip || repertoire.lines.len() == 1)
+        .min_by_key(|&index| {
+            let line = stats.line(repertoire.color, &repertoire.lines[index].key);
+            (line.due, line.reviews, index)
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PGN: &str = "\
+[Event \"Italian\"]
+
+1. e4 e5 (1... c5 2. Nf3) 2. Nf3 Nc6 (2... d6 3. d4) 3. Bc4 *
+
+[Event \"?\"]
+
+1. d4 d5 2. c4 *
+";
+
+    fn repertoire() -> Repertoire {
+        Repertoire::from_pgn(&pgn::parse_all(PGN).unwrap(), None).unwrap()
+    }
+
+    #[test]
+    fn splits_variations_into_lines() {
+        let repertoire = repertoire();
+        // Only Black's moves branch, so the repertoire is White's
+        assert_eq!(repertoire.color, Color::White);
+        let keys: Vec<&str> = repertoire
+            .lines
+            .iter()
+            .map(|line| line.key.as_str())
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "1. e4 c5 2. Nf3",
+                "1. e4 e5 2. Nf3 d6 3. d4",
+                "1. e4 e5 2. Nf3 Nc6 3. Bc4",
+                "1. d4 d5 2. c4",
+            ]
+        );
+        assert_eq!(repertoire.lines[0].chapter, "Italian");
+        assert_eq!(repertoire.lines[3].chapter, "Chapter 2");
+        let black = Repertoire::from_pgn(&pgn::parse_all(PGN).unwrap(), Some(Color::Black));
+        assert_eq!(black.unwrap().color, Color::Black);
+    }
+
+    #[test]
+    fn schedules_reviews_sm2_style() {
+        let mut stats = LineStats::default();
+        stats.review(5, 100);
+        assert_eq!((stats.interval, stats.due), (1, 101));
+        stats.review(5, 101);
+        assert_eq!((stats.interval, stats.due), (6, 107));
+        stats.review(4, 107);
+        assert_eq!(stats.interval, 16);
+        assert!((stats.ease - 2.7).abs() < 1e-9);
+        stats.review(1, 123);
+        assert_eq!((stats.repetitions, stats.interval, stats.lapses), (0, 1, 1));
+        assert!(stats.ease < 2.7);
+        for _ in 0..10 {
+            stats.review(0, 124);
+        }
+        assert_eq!(stats.ease, MIN_EASE);
+    }
+
+    #[test]
+    fn drills_lines_and_saves_statistics() {
+        let path = std::env::temp_dir()
+            .join(format!("chess-tui-repertoire-{}", std::process::id()))
+            .join("repertoire.toml");
+        let mut session = Session::new(repertoire(), Some(path.clone()), 200).unwrap();
+        assert_eq!(session.due(), 4);
+        assert_eq!(session.line().key, "1. e4 c5 2. Nf3");
+        let mv =
+            |session: &Session, san: &str| notation::parse_move(session.drill.game(), san).unwrap();
+
+        assert_eq!(session.play(mv(&session, "d4")), Verdict::Wrong);
+        assert_eq!(session.drill.game().get_move_history().len(), 0);
+        assert_eq!(session.play(mv(&session, "e4")), Verdict::Correct);
+        // Black's reply was played for us
+        assert_eq!(session.drill.game().get_move_history().len(), 2);
+        assert_eq!(session.play(mv(&session, "Nf3")), Verdict::Finished);
+        let stats = session.line_stats();
+        assert_eq!((stats.reviews, stats.interval, stats.due), (1, 1, 201));
+        assert_eq!(session.due(), 3);
+
+        session.next_line();
+        assert_eq!(session.line().key, "1. e4 e5 2. Nf3 d6 3. d4");
+        session.hint();
+        assert!(session.drill.revealed);
+        assert_eq!(session.drill.quality(), 3);
+
+        let saved = Stats::load_from(&path).unwrap();
+        assert_eq!(saved, session.stats);
+        let reopened = Session::new(repertoire(), Some(path.clone()), 200).unwrap();
+        assert_eq!(reopened.line().key, "1. e4 e5 2. Nf3 d6 3. d4");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,330 @@
+use std::time::{Duration, Instant};
+
+use crate::game::Game;
+use crate::notation;
+use crate::pgn::{PgnGame, PgnMove, nag_symbol};
+use crate::pieces::Color;
+
+/// A stretch of the move tree being followed: the main line, or a variation branching off the
+/// previous segment where that segment stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    /// Which alternative to the branch move this is; unused for the main line.
+    variation: usize,
+    /// Moves of this line played so far.
+    len: usize,
+}
+
+/// Steps through a recorded game and its variations.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pgn: PgnGame,
+    start: Game,
+    /// The main line segment first, then any variations entered from it.
+    path: Vec<Segment>,
+    /// Position after the moves on `path`.
+    game: Game,
+    /// Why the position could not be reached, if a recorded move is illegal.
+    error: Option<String>,
+    /// When autoplay makes the next move, while it is running.
+    next_step: Option<Instant>,
+}
+
+impl Replay {
+    /// Opens `pgn` at its starting position.
+    pub fn new(pgn: PgnGame) -> color_eyre::Result<Self> {
+        let start = pgn.starting_position()?;
+        Ok(Self {
+            game: start.clone(),
+            pgn,
+            start,
+            path: vec![Segment {
+                variation: 0,
+                len: 0,
+            }],
+            error: None,
+            next_step: None,
+        })
+    }
+
+    pub fn pgn(&self) -> &PgnGame {
+        &self.pgn
+    }
+
+    /// The position being shown.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// How many variations deep the shown position is; 0 on the main line.
+    pub fn depth(&self) -> usize {
+        self.path.len() - 1
+    }
+
+    /// The moves of the line a segment follows.
+    fn line(&self, depth: usize) -> &[PgnMove] {
+        if depth == 0 {
+            return &self.pgn.moves;
+        }
+        let parent = self.line(depth - 1);
+        let branch = &parent[self.path[depth - 1].len];
+        &branch.variations[self.path[depth].variation]
+    }
+
+    fn segment(&mut self) -> &mut Segment {
+        self.path
+            .last_mut()
+            .expect("the main line segment is never removed")
+    }
+
+    /// The moves that lead to the shown position.
+    pub fn moves_played(&self) -> Vec<&PgnMove> {
+        (0..self.path.len())
+            .flat_map(|depth| &self.line(depth)[..self.path[depth].len])
+            .collect()
+    }
+
+    /// The move that led to the shown position.
+    pub fn current_move(&self) -> Option<&PgnMove> {
+        self.moves_played().pop()
+    }
+
+    /// The last move in numbered SAN with its annotation symbols, such as `12... Nf6!?`.
+    pub fn current_move_label(&self) -> Option<String> {
+        let pgn_move = self.current_move()?;
+        let fullmove: usize = self.game.to_fen().rsplit(' ').next()?.parse().ok()?;
+        let number = match self.game.get_current_turn() {
+            Color::White => format!("{}...", fullmove.saturating_sub(1)),
+            Color::Black => format!("{fullmove}."),
+        };
+        let nags: Vec<String> = pgn_move
+            .nags
+            .iter()
+            .map(|&nag| nag_symbol(nag).map_or(format!(" ${nag}"), str::to_string))
+            .collect();
+        Some(format!("{number} {}{}", pgn_move.san, nags.concat()))
+    }
+
+    /// The move the current line continues with.
+    pub fn next_move(&self) -> Option<&PgnMove> {
+        self.line(self.depth()).get(self.path[self.depth()].len)
+    }
+
+    /// Number of moves played to reach the shown position.
+    pub fn ply(&self) -> usize {
+        self.path.iter().map(|segment| segment.len).sum()
+    }
+
+    /// Plays the next move of the current line. Returns `false` at the end of the line.
+    pub fn forward(&mut self) -> bool {
+        if self.next_move().is_none() || self.error.is_some() {
+            return false;
+        }
+        self.segment().len += 1;
+        self.rebuild();
+        true
+    }
+
+    /// Takes back the last move, leaving a variation once its first move is taken back.
+    pub fn back(&mut self) -> bool {
+        if self.segment().len == 0 {
+            return false;
+        }
+        self.segment().len -= 1;
+        if self.segment().len == 0 && self.path.len() > 1 {
+            self.path.pop();
+        }
+        self.rebuild();
+        true
+    }
+
+    pub fn to_start(&mut self) {
+        self.path.truncate(1);
+        self.segment().len = 0;
+        self.rebuild();
+    }
+
+    /// Shows the position after `ply` moves of the main line, or its last one.
+    pub fn go_to(&mut self, ply: usize) {
+        self.to_start();
+        while self.ply() < ply && self.forward() {}
+    }
+
+    /// Every position of the main line from the start, up to its first illegal move.
+    pub fn main_line(&self) -> Vec<Game> {
+        let mut games = vec![self.start.clone()];
+        for pgn_move in &self.pgn.moves {
+            let mut game = games[games.len() - 1].clone();
+            match notation::parse_move(&game, &pgn_move.san) {
+                Ok(mv) => game.make_move(mv),
+                Err(_) => break,
+            }
+            games.push(game);
+        }
+        games
+    }
+
+    /// Plays the current line to its end.
+    pub fn to_end(&mut self) {
+        while self.forward() {}
+    }
+
+    /// Plays the first alternative to the next move. Right after entering a variation this
+    /// switches to the next alternative instead.
+    pub fn enter_variation(&mut self) -> bool {
+        let depth = self.depth();
+        let segment = self.path[depth];
+        if depth > 0 && segment.len == 1 {
+            let siblings = self.line(depth - 1)[self.path[depth - 1].len]
+                .variations
+                .len();
+            if segment.variation + 1 < siblings {
+                self.segment().variation += 1;
+                self.rebuild();
+                return true;
+            }
+            return false;
+        }
+        if self
+            .next_move()
+            .is_none_or(|next| next.variations.is_empty())
+        {
+            return false;
+        }
+        self.path.push(Segment {
+            variation: 0,
+            len: 1,
+        });
+        self.rebuild();
+        true
+    }
+
+    /// Returns to where the current variation branched off.
+    pub fn exit_variation(&mut self) -> bool {
+        if self.path.len() == 1 {
+            return false;
+        }
+        self.path.pop();
+        self.rebuild();
+        true
+    }
+
+    /// Replays the moves on the path from the start, stopping at the first illegal one.
+    fn rebuild(&mut self) {
+        let mut game = self.start.clone();
+        let mut error = None;
+        for (ply, pgn_move) in self.moves_played().into_iter().enumerate() {
+            match notation::parse_move(&game, &pgn_move.san) {
+                Ok(mv) => game.make_move(mv),
+                Err(err) => {
+                    error = Some(format!("Move {}: {err}", ply + 1));
+                    break;
+                }
+            }
+        }
+        self.game = game;
+        self.error = error;
+    }
+
+    pub fn is_autoplaying(&self) -> bool {
+        self.next_step.is_some()
+    }
+
+    /// Starts or stops stepping forward on its own.
+    pub fn toggle_autoplay(&mut self, delay: Duration) {
+        self.next_step = match self.next_step {
+            Some(_) => None,
+            None => Some(Instant::now() + delay),
+        };
+    }
+
+    /// Advances autoplay if its next move is due, stopping at the end of the line.
+    pub fn tick(&mut self, delay: Duration) {
+        let Some(due) = self.next_step else {
+            return;
+        };
+        if Instant::now() < due {
+            return;
+        }
+        self.next_step = self.forward().then(|| Instant::now() + delay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay() -> Replay {
+        let pgn = PgnGame::parse(
+            "1. e4 e5 2. Nf3 Nc6 (2... d6 3. d4) (2... Nf6 {Petrov}) 3. Bb5 {Ruy Lopez} *",
+        )
+        .unwrap();
+        Replay::new(pgn).unwrap()
+    }
+
+    fn sans(replay: &Replay) -> Vec<&str> {
+        replay
+            .moves_played()
+            .iter()
+            .map(|m| m.san.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn steps_through_the_main_line() {
+        let mut replay = replay();
+        assert!(!replay.back());
+        assert!(replay.forward());
+        assert_eq!(sans(&replay), ["e4"]);
+        replay.to_end();
+        assert_eq!(replay.ply(), 5);
+        assert_eq!(replay.main_line().len(), 6);
+        assert_eq!(replay.main_line()[5].to_fen(), replay.game().to_fen());
+        assert_eq!(replay.current_move().unwrap().comments, ["Ruy Lopez"]);
+        assert_eq!(replay.current_move_label().unwrap(), "3. Bb5");
+        assert!(!replay.forward());
+        assert!(replay.back());
+        assert_eq!(replay.game().get_move_history().len(), 4);
+        replay.to_start();
+        assert_eq!(replay.game().to_fen(), Game::new().to_fen());
+    }
+
+    #[test]
+    fn follows_variations() {
+        let mut replay = replay();
+        for _ in 0..3 {
+            replay.forward();
+        }
+        assert!(replay.enter_variation());
+        assert_eq!(sans(&replay), ["e4", "e5", "Nf3", "d6"]);
+        assert_eq!(replay.current_move_label().unwrap(), "2... d6");
+        assert!(replay.enter_variation());
+        assert_eq!(replay.current_move().unwrap().comments, ["Petrov"]);
+        assert!(!replay.enter_variation());
+        assert!(replay.exit_variation());
+        assert_eq!(sans(&replay), ["e4", "e5", "Nf3"]);
+
+        replay.enter_variation();
+        replay.to_end();
+        assert_eq!(sans(&replay), ["e4", "e5", "Nf3", "d6", "d4"]);
+        replay.back();
+        replay.back();
+        assert_eq!(replay.depth(), 0);
+        assert_eq!(replay.next_move().unwrap().san, "Nc6");
+    }
+
+    #[test]
+    fn autoplay_stops_at_the_end() {
+        let mut replay = replay();
+        replay.toggle_autoplay(Duration::ZERO);
+        for _ in 0..10 {
+            replay.tick(Duration::ZERO);
+        }
+        assert_eq!(replay.ply(), 5);
+        assert!(!replay.is_autoplaying());
+    }
+}
@@ -0,0 +1,283 @@
+use std::fmt;
+
+use crate::engine::{self, Score, SearchLimits};
+use crate::game::Game;
+use crate::notation;
+use crate::pgn::PgnGame;
+use crate::pieces::Color;
+
+/// Centipawns a mate counts for when measuring how much a move lost.
+const MATE_CP: i32 = 1000;
+
+/// How a move compares with the engine's choice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Judgement {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    /// Judges a move that lost `loss` centipawns against the engine's best.
+    fn from_loss(loss: i32, best: bool) -> Self {
+        match loss {
+            _ if best => Judgement::Best,
+            ..50 => Judgement::Good,
+            50..100 => Judgement::Inaccuracy,
+            100..300 => Judgement::Mistake,
+            _ => Judgement::Blunder,
+        }
+    }
+
+    /// The PGN annotation glyph: `?!`, `?` or `??`.
+    pub fn nag(self) -> Option<u8> {
+        match self {
+            Judgement::Best | Judgement::Good => None,
+            Judgement::Inaccuracy => Some(6),
+            Judgement::Mistake => Some(2),
+            Judgement::Blunder => Some(4),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Judgement::Best => "Best",
+            Judgement::Good => "Good",
+            Judgement::Inaccuracy => "Inaccuracy",
+            Judgement::Mistake => "Mistake",
+            Judgement::Blunder => "Blunder",
+        }
+    }
+}
+
+/// What the engine made of one move.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveReport {
+    /// The move in numbered SAN, such as `12... Qxb2`.
+    pub label: String,
+    pub mover: Color,
+    /// Evaluation after the move, from White's point of view.
+    pub eval: Score,
+    /// The engine's choice in SAN, when the move was not it.
+    pub best: Option<String>,
+    /// Centipawns lost against the engine's choice, from the mover's point of view.
+    pub loss: i32,
+    /// From 0 to 100, by how much the move lowered the mover's winning chances.
+    pub accuracy: f64,
+    pub judgement: Judgement,
+}
+
+/// Averages over one player's moves.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerSummary {
+    pub moves: usize,
+    pub accuracy: f64,
+    pub average_loss: f64,
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+}
+
+impl fmt::Display for PlayerSummary {
+    /// Like `91.5% accuracy, 23 ACPL, 1 inaccuracy, 0 mistakes, 2 blunders`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |count: usize, one: &str, many: &str| {
+            format!("{count} {}", if count == 1 { one } else { many })
+        };
+        write!(
+            f,
+            "{:.1}% accuracy, {:.0} ACPL, {}, {}, {}",
+            self.accuracy,
+            self.average_loss,
+            count(self.inaccuracies, "inaccuracy", "inaccuracies"),
+            count(self.mistakes, "mistake", "mistakes"),
+            count(self.blunders, "blunder", "blunders"),
+        )
+    }
+}
+
+/// Every move of a game judged by the engine, with each player's accuracy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub moves: Vec<MoveReport>,
+    pub white: PlayerSummary,
+    pub black: PlayerSummary,
+}
+
+impl Report {
+    /// Searches every position of `game` from its start with `limits`, calling `on_progress`
+    /// with the positions done and the total after each.
+    pub fn new(
+        game: &Game,
+        limits: &SearchLimits,
+        mut on_progress: impl FnMut(usize, usize),
+    ) -> color_eyre::Result<Self> {
+        let mut position = Game::from_fen(game.start_fen(), game.variant())?;
+        let history: Vec<_> = game.get_move_history().to_vec();
+        let total = history.len() + 1;
+        let mut before = engine::search(&position, limits);
+        on_progress(1, total);
+        let mut moves = Vec::new();
+        for (i, mv) in history.into_iter().enumerate() {
+            let mover = position.get_current_turn();
+            let label = notation::numbered(&position, &[mv]);
+            let best = before
+                .best_move
+                .filter(|&best| best != mv)
+                .map(|best| notation::san(&position, &best));
+            position.make_move(mv);
+            let after = engine::search(&position, limits);
+            on_progress(i + 2, total);
+
+            // Both from the mover's point of view; a mate on the board scores as one
+            let expected = clamped_cp(before.score);
+            let reached = match position.outcome() {
+                Some(outcome) => match outcome.winner {
+                    Some(winner) if winner == mover => MATE_CP,
+                    Some(_) => -MATE_CP,
+                    None => 0,
+                },
+                None => clamped_cp(after.score.flip()),
+            };
+            let loss = if best.is_none() {
+                0
+            } else {
+                (expected - reached).max(0)
+            };
+            let accuracy = (103.1668
+                * (-0.04354 * (win_percent(expected) - win_percent(reached)).max(0.0)).exp()
+                - 3.1669)
+                .clamp(0.0, 100.0);
+            moves.push(MoveReport {
+                label,
+                mover,
+                eval: after.score.white_view(position.get_current_turn()),
+                judgement: Judgement::from_loss(loss, best.is_none()),
+                best,
+                loss,
+                accuracy,
+            });
+            before = after;
+        }
+        Ok(Self {
+            white: summarise(&moves, Color::White),
+            black: summarise(&moves, Color::Black),
+            moves,
+        })
+    }
+
+    pub fn summary(&self, color: Color) -> &PlayerSummary {
+        match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
+
+    /// Adds judgement glyphs and `[%eval]` comments to the main line of `pgn`, which must be
+    /// the analysed game, naming the engine's choice after inaccuracies and worse.
+    pub fn annotate(&self, pgn: &mut PgnGame) {
+        for (pgn_move, report) in pgn.moves.iter_mut().zip(&self.moves) {
+            if let Some(nag) = report.judgement.nag()
+                && !pgn_move.nags.contains(&nag)
+            {
+                pgn_move.nags.push(nag);
+            }
+            let mut comment = format!("[%eval {}]", eval_tag(report.eval));
+            if report.judgement >= Judgement::Inaccuracy
+                && let Some(best) = &report.best
+            {
+                comment.push_str(&format!(" {}. {best} was best.", report.judgement.name()));
+            }
+            pgn_move.comments.insert(0, comment);
+        }
+        pgn.set_tag("Annotator", "chess-tui");
+    }
+}
+
+/// A score in centipawns with mates counted as [`MATE_CP`].
+fn clamped_cp(score: Score) -> i32 {
+    match score {
+        Score::Centipawns(cp) => cp.clamp(-MATE_CP, MATE_CP),
+        Score::Mate(moves) if moves > 0 => MATE_CP,
+        Score::Mate(_) => -MATE_CP,
+    }
+}
+
+/// The chance of winning, from 0 to 100, for a centipawn advantage.
+fn win_percent(cp: i32) -> f64 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * cp as f64).exp()) - 1.0)
+}
+
+/// A score as the `[%eval]` command writes it: pawns (`-1.25`) or a mate (`#-3`).
+fn eval_tag(score: Score) -> String {
+    match score {
+        Score::Centipawns(cp) => format!("{:.2}", cp as f64 / 100.0),
+        Score::Mate(moves) => format!("#{moves}"),
+    }
+}
+
+fn summarise(moves: &[MoveReport], color: Color) -> PlayerSummary {
+    let own: Vec<&MoveReport> = moves.iter().filter(|m| m.mover == color).collect();
+    if own.is_empty() {
+        return PlayerSummary::default();
+    }
+    let count = |judgement| own.iter().filter(|m| m.judgement == judgement).count();
+    PlayerSummary {
+        moves: own.len(),
+        accuracy: own.iter().map(|m| m.accuracy).sum::<f64>() / own.len() as f64,
+        average_loss: own.iter().map(|m| m.loss as f64).sum::<f64>() / own.len() as f64,
+        inaccuracies: count(Judgement::Inaccuracy),
+        mistakes: count(Judgement::Mistake),
+        blunders: count(Judgement::Blunder),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn played(sans: &[&str]) -> Game {
+        let mut game = Game::new();
+        for san in sans {
+            let mv = notation::parse_move(&game, san).unwrap();
+            game.make_move(mv);
+        }
+        game
+    }
+
+    #[test]
+    fn judges_moves_by_what_they_lose() {
+        assert_eq!(Judgement::from_loss(0, true), Judgement::Best);
+        assert_eq!(Judgement::from_loss(20, false), Judgement::Good);
+        assert_eq!(Judgement::from_loss(60, false), Judgement::Inaccuracy);
+        assert_eq!(Judgement::from_loss(150, false), Judgement::Mistake);
+        assert_eq!(Judgement::from_loss(900, false), Judgement::Blunder);
+        assert_eq!(clamped_cp(Score::Mate(-2)), -MATE_CP);
+        assert_eq!(win_percent(0), 50.0);
+    }
+
+    #[test]
+    fn finds_the_blunder_and_annotates_it() {
+        // Fool's mate: 2. g4 lets Black mate at once
+        let game = played(&["f3", "e5", "g4", "Qh4#"]);
+        let report = Report::new(&game, &SearchLimits::depth(2), |_, _| {}).unwrap();
+        assert_eq!(report.moves.len(), 4);
+        let g4 = &report.moves[2];
+        assert_eq!(g4.label, "2. g4");
+        assert_eq!(g4.judgement, Judgement::Blunder);
+        assert_eq!(report.moves[3].judgement, Judgement::Best);
+        assert_eq!(report.white.blunders, 1);
+        assert_eq!(report.black.blunders, 0);
+        assert!(report.black.accuracy > report.white.accuracy);
+        assert!(report.white.to_string().ends_with("0 mistakes, 1 blunder"));
+
+        let mut pgn = PgnGame::from_game(&game);
+        report.annotate(&mut pgn);
+        let text = pgn.to_string();
+        assert!(text.contains("2. g4 $4"), "{text}");
+        assert!(text.contains("{[%eval #-1] Blunder."), "{text}");
+        assert!(text.contains("[Annotator \"chess-tui\"]"), "{text}");
+    }
+}
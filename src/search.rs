@@ -0,0 +1,293 @@
+use std::fmt;
+use std::str::FromStr;
+
+use color_eyre::eyre::{bail, eyre};
+
+use crate::database::Database;
+use crate::game::Game;
+use crate::notation;
+use crate::pgn::PgnGame;
+use crate::pieces::{Color, PieceType};
+use crate::variant::VariantKind;
+use crate::zobrist;
+
+/// What to look for in the positions of a game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// An exact position, compared by [`position_key`] so move counters do not matter.
+    Position(String),
+    /// A position given by its Polyglot key.
+    Zobrist(u64),
+    /// The pieces left on the board.
+    Material(Signature),
+}
+
+impl FromStr for Query {
+    type Err = color_eyre::Report;
+
+    /// Reads a FEN, a Polyglot key in hex such as `0x463b96181691fc9c`, or a material signature
+    /// such as `KRvKB` or `R vs B`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.contains('/') {
+            let game = Game::from_fen(s, VariantKind::Standard)
+                .or_else(|_| Game::from_fen(s, VariantKind::Crazyhouse))?;
+            return Ok(Query::Position(position_key(&game)));
+        }
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        if hex.len() == 16 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(Query::Zobrist(u64::from_str_radix(hex, 16)?));
+        }
+        Ok(Query::Material(s.parse()?))
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Query::Position(key) => f.write_str(key),
+            Query::Zobrist(key) => write!(f, "0x{key:016x}"),
+            Query::Material(signature) => write!(f, "{signature}"),
+        }
+    }
+}
+
+impl Query {
+    pub fn matches(&self, game: &Game) -> bool {
+        match self {
+            Query::Position(key) => position_key(game) == *key,
+            Query::Zobrist(key) => zobrist::polyglot(game) == Some(*key),
+            Query::Material(signature) => signature.matches(game),
+        }
+    }
+}
+
+/// The parts of a FEN that make two positions the same: placement, side to move, castling
+/// rights and an en passant capture that can really be played.
+pub fn position_key(game: &Game) -> String {
+    let fen = game.to_fen();
+    let fields: Vec<&str> = fen.split(' ').take(3).collect();
+    let en_passant = game.capturable_en_passant().map_or_else(
+        || "-".to_string(),
+        |square| game.get_board().square_name(square),
+    );
+    format!("{} {en_passant}", fields.join(" "))
+}
+
+/// The pieces of each side, kings included, written like `KRPvKR`. Either side may be White.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    sides: [String; 2],
+}
+
+/// Order of the pieces in a signature, strongest first.
+const SIGNATURE_ORDER: &str = "KZQCARBNP";
+
+impl Signature {
+    fn side(game: &Game, color: Color) -> String {
+        let letters: String = game
+            .get_board()
+            .pieces(color)
+            .map(|(_, piece)| piece.piece_type.to_char().to_ascii_uppercase())
+            .collect();
+        sorted(&letters)
+    }
+
+    pub fn matches(&self, game: &Game) -> bool {
+        let white = Self::side(game, Color::White);
+        let black = Self::side(game, Color::Black);
+        let [a, b] = &self.sides;
+        (*a == white && *b == black) || (*a == black && *b == white)
+    }
+}
+
+fn sorted(letters: &str) -> String {
+    let mut letters: Vec<char> = letters.chars().collect();
+    letters.sort_by_key(|&c| SIGNATURE_ORDER.find(c));
+    letters.into_iter().collect()
+}
+
+impl FromStr for Signature {
+    type Err = color_eyre::Report;
+
+    /// Kings may be left out, so `R vs B` is the same as `KRvKB`. Pawns must be listed.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let compact: String = s.split_whitespace().collect::<String>().to_uppercase();
+        let compact = compact.replace("VS", "V");
+        let Some((first, second)) = compact.split_once('V') else {
+            bail!("expected a FEN, a Polyglot key or material such as KRvKB, got `{s}`");
+        };
+        let mut sides = [String::new(), String::new()];
+        for (side, letters) in sides.iter_mut().zip([first, second]) {
+            if let Some(c) = letters.chars().find(|&c| PieceType::from_char(c).is_none()) {
+                bail!("unknown piece `{c}` in `{s}`");
+            }
+            let kings = letters.matches('K').count();
+            if kings > 1 {
+                return Err(eyre!("a side can have only one king in `{s}`"));
+            }
+            *side = sorted(&format!("{}{letters}", if kings == 0 { "K" } else { "" }));
+        }
+        Ok(Self { sides })
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.sides[0], self.sides[1])
+    }
+}
+
+/// Where a game first reaches a position a query matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// Moves played to reach the position; 0 for the starting position.
+    pub ply: usize,
+    /// The move that reached it in numbered SAN, such as `12... Nf6`.
+    pub label: Option<String>,
+}
+
+/// Replays the main line of `pgn` until `query` matches, failing at an illegal move.
+pub fn find(pgn: &PgnGame, query: &Query) -> color_eyre::Result<Option<Match>> {
+    let mut game = pgn.starting_position()?;
+    if query.matches(&game) {
+        return Ok(Some(Match {
+            ply: 0,
+            label: None,
+        }));
+    }
+    for (ply, pgn_move) in pgn.moves.iter().enumerate() {
+        let mv = notation::parse_move(&game, &pgn_move.san)?;
+        let fullmove = game.to_fen().rsplit(' ').next().unwrap_or("1").to_string();
+        let dots = match game.get_current_turn() {
+            Color::White => ".",
+            Color::Black => "...",
+        };
+        let label = format!("{fullmove}{dots} {}", notation::san(&game, &mv));
+        game.make_move(mv);
+        if query.matches(&game) {
+            return Ok(Some(Match {
+                ply: ply + 1,
+                label: Some(label),
+            }));
+        }
+    }
+    Ok(None)
+}
+
+/// A search of a database, as shown in the search screen.
+#[derive(Debug, Clone, Default)]
+pub struct PositionSearch {
+    /// The query being typed, while the input is open.
+    pub input: Option<String>,
+    /// The last query that was run.
+    pub query: Option<Query>,
+    /// Indices into the database's games of the games that matched, in file order.
+    pub hits: Vec<(usize, Match)>,
+    pub selected: usize,
+    /// Why the typed query could not be run.
+    pub error: Option<String>,
+    /// Rows that fit on screen when the results were last drawn.
+    pub page: usize,
+}
+
+impl PositionSearch {
+    /// Opens the query input, starting from the last query.
+    pub fn edit(&mut self) {
+        self.input = Some(
+            self.query
+                .as_ref()
+                .map(Query::to_string)
+                .unwrap_or_default(),
+        );
+        self.error = None;
+    }
+
+    /// Runs the typed query over `database`, closing the input if it succeeds.
+    pub fn run(&mut self, database: &Database) {
+        let Some(input) = &self.input else {
+            return;
+        };
+        match input
+            .parse::<Query>()
+            .and_then(|query| Ok((database.search(&query)?, query)))
+        {
+            Ok((hits, query)) => {
+                self.hits = hits;
+                self.query = Some(query);
+                self.selected = 0;
+                self.input = None;
+                self.error = None;
+            }
+            Err(err) => self.error = Some(err.to_string()),
+        }
+    }
+
+    pub fn selected_hit(&self) -> Option<&(usize, Match)> {
+        self.hits.get(self.selected)
+    }
+
+    /// Moves the highlight by `delta` rows, stopping at either end.
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.hits.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last) as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_queries() {
+        assert_eq!(
+            "0x463b96181691fc9c".parse::<Query>().unwrap(),
+            Query::Zobrist(0x463b96181691fc9c)
+        );
+        let material = "R vs B".parse::<Query>().unwrap();
+        assert_eq!(material.to_string(), "KRvKB");
+        assert_eq!("kbrvkp".parse::<Query>().unwrap().to_string(), "KRBvKP");
+        assert!("KKRvK".parse::<Query>().is_err());
+        assert!("KXvK".parse::<Query>().is_err());
+        assert!("rook ending".parse::<Query>().is_err());
+
+        // The en passant square does not count when no pawn can take
+        let after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        assert_eq!(
+            after_e4.parse::<Query>().unwrap(),
+            after_e4.replace("e3", "-").parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn finds_the_first_matching_ply() {
+        let pgn = PgnGame::parse("1. e4 d5 2. exd5 Qxd5 3. Nc3 *").unwrap();
+        let query: Query = "rnb1kbnr/ppp1pppp/8/3q4/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3"
+            .parse()
+            .unwrap();
+        let found = find(&pgn, &query).unwrap().unwrap();
+        assert_eq!(found.ply, 4);
+        assert_eq!(found.label.as_deref(), Some("2... Qxd5"));
+        assert_eq!(
+            find(&pgn, &Query::Zobrist(0x463b96181691fc9c)).unwrap(),
+            Some(Match {
+                ply: 0,
+                label: None
+            })
+        );
+
+        let ending = PgnGame::parse(
+            "[SetUp \"1\"]\n[FEN \"4k3/8/2b5/8/8/8/8/R3K3 w - - 0 1\"]\n\n1. Ra8+ Bxa8 *",
+        )
+        .unwrap();
+        let found = find(&ending, &"KRvKB".parse().unwrap()).unwrap().unwrap();
+        assert_eq!(found.ply, 0);
+        assert_eq!(
+            find(&ending, &"B vs".parse().unwrap())
+                .unwrap()
+                .unwrap()
+                .ply,
+            2
+        );
+    }
+}
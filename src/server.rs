@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Mutex, broadcast};
+
+use crate::game::Game;
+use crate::notation;
+use crate::variant::VariantKind;
+
+/// Hosts one shared game on `127.0.0.1:port`. Clients send one command per line:
+///
+/// - `move <move>` plays a move in SAN or coordinate notation
+/// - `fen` and `moves` print the position and the legal moves
+/// - `new [variant]` starts over
+/// - `quit` closes the connection
+///
+/// Every client is told `moved <san> <fen>` after each move and `new <fen>` after a restart.
+pub async fn run(port: u16, game: Game) -> color_eyre::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    eprintln!(
+        "serving {} on {}",
+        game.variant().name(),
+        listener.local_addr()?
+    );
+    let game = Arc::new(Mutex::new(game));
+    let (updates, _) = broadcast::channel(64);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let game = game.clone();
+        let updates = updates.clone();
+        tokio::spawn(async move {
+            // A client hanging up mid-write only ends its own connection
+            let _ = serve_client(stream, game, updates).await;
+        });
+    }
+}
+
+async fn serve_client(
+    stream: TcpStream,
+    game: Arc<Mutex<Game>>,
+    updates: broadcast::Sender<String>,
+) -> color_eyre::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut receiver = updates.subscribe();
+    let greeting = {
+        let game = game.lock().await;
+        format!("chess-tui {} {}\n", game.variant().name(), game.to_fen())
+    };
+    writer.write_all(greeting.as_bytes()).await?;
+
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    return Ok(());
+                };
+                if line.trim() == "quit" {
+                    return Ok(());
+                }
+                let reply = handle_command(&mut *game.lock().await, &line);
+                if let Some(update) = reply.update {
+                    let _ = updates.send(update);
+                }
+                writer.write_all(format!("{}\n", reply.text).as_bytes()).await?;
+            }
+            update = receiver.recv() => {
+                if let Ok(update) = update {
+                    writer.write_all(format!("{update}\n").as_bytes()).await?;
+                }
+            }
+        }
+    }
+}
+
+/// The answer to a client command and the news, if any, for every client.
+#[derive(Debug, PartialEq, Eq)]
+struct Reply {
+    text: String,
+    update: Option<String>,
+}
+
+impl Reply {
+    fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            update: None,
+        }
+    }
+}
+
+fn handle_command(game: &mut Game, line: &str) -> Reply {
+    let (command, args) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+    match command {
+        "move" if game.is_game_over() => Reply::text("error the game is over"),
+        "move" => match notation::parse_move(game, args) {
+            Ok(mv) => {
+                let san = notation::san(game, &mv);
+                game.make_move(mv);
+                let mut reply = Reply::text(format!("ok {san}"));
+                reply.update = Some(format!("moved {san} {}", game.to_fen()));
+                reply
+            }
+            Err(err) => Reply::text(format!("error {err}")),
+        },
+        "fen" => Reply::text(format!("fen {}", game.to_fen())),
+        "moves" => {
+            let mut sans: Vec<String> = game
+                .legal_moves()
+                .iter()
+                .map(|mv| notation::san(game, mv))
+                .collect();
+            sans.sort();
+            Reply::text(format!("moves {}", sans.join(" ")))
+        }
+        "new" => {
+            let variant = match args.trim() {
+                "" => Ok(game.variant()),
+                name => name.parse::<VariantKind>(),
+            };
+            match variant {
+                Ok(variant) => {
+                    *game = Game::with_variant(variant);
+                    let mut reply = Reply::text("ok");
+                    reply.update = Some(format!("new {}", game.to_fen()));
+                    reply
+                }
+                Err(err) => Reply::text(format!("error {err}")),
+            }
+        }
+        _ => Reply::text(format!("error unknown command `{command}`")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_commands() {
+        let mut game = Game::new();
+        let reply = handle_command(&mut game, "move e4");
+        assert_eq!(reply.text, "ok e4");
+        assert_eq!(
+            reply.update.as_deref(),
+            Some("moved e4 rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1")
+        );
+        assert!(
+            handle_command(&mut game, "move e4")
+                .text
+                .starts_with("error")
+        );
+        assert!(handle_command(&mut game, "moves").text.contains(" Nf6 "));
+        assert_eq!(handle_command(&mut game, "new atomic").text, "ok");
+        assert_eq!(game.variant(), VariantKind::Atomic);
+        assert!(handle_command(&mut game, "dance").text.starts_with("error"));
+    }
+}
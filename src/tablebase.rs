@@ -0,0 +1,1452 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Neg;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use color_eyre::eyre::{WrapErr, bail, eyre};
+
+use crate::game::{Game, Move};
+use crate::pieces::{Color, PieceType};
+use crate::variant::VariantKind;
+
+/// Most pieces, kings included, that a Syzygy table holds.
+const MAX_PIECES: usize = 7;
+
+// Flags of each value table in a file
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+/// A game result under best play, from the point of view of the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    /// Lost, but the 50-move rule draws the game first.
+    BlessedLoss,
+    Draw,
+    /// Won, but the 50-move rule draws the game first.
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Self {
+        match value {
+            ..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    fn value(self) -> i32 {
+        self as i32 - 2
+    }
+
+    /// The distance to zeroing of a move that captures or moves a pawn into a position
+    /// with this result for the side that moved.
+    fn dtz_before_zeroing(self) -> i32 {
+        match self {
+            Wdl::Win => 1,
+            Wdl::CursedWin => 101,
+            Wdl::Draw => 0,
+            Wdl::BlessedLoss => -101,
+            Wdl::Loss => -1,
+        }
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::from_value(-self.value())
+    }
+}
+
+/// What the tables say about a position, from the point of view of the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Probe {
+    pub wdl: Wdl,
+    /// Plies to the next capture or pawn move under best play, negative when losing and 0
+    /// for draws. Like the tables themselves, it may be one ply too long.
+    pub dtz: i32,
+}
+
+impl fmt::Display for Probe {
+    /// `Tablebase win, DTZ 15`. DTZ counts plies to the next capture or pawn move, so it is
+    /// not a distance to mate.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.wdl {
+            Wdl::Win => write!(f, "Tablebase win, DTZ {}", self.dtz),
+            Wdl::CursedWin => f.write_str("Tablebase win, drawn by the 50-move rule"),
+            Wdl::Draw => f.write_str("Tablebase draw"),
+            Wdl::BlessedLoss => f.write_str("Tablebase loss, saved by the 50-move rule"),
+            Wdl::Loss => write!(f, "Tablebase loss, DTZ {}", self.dtz),
+        }
+    }
+}
+
+/// A directory of Syzygy tables: `.rtbw` files with the result of each position and `.rtbz`
+/// files with the distance to the next capture or pawn move. Tables are read on first use.
+pub struct Tablebase {
+    dir: PathBuf,
+    /// Materials with a WDL table, named strongest side first like `KRPvKR`.
+    names: HashSet<String>,
+    max_pieces: usize,
+    /// Tables read so far.
+    tables: Mutex<Tables>,
+}
+
+/// Tables by material and kind, `None` for those that failed to load.
+type Tables = HashMap<(String, Kind), Option<Arc<Table>>>;
+
+impl fmt::Debug for Tablebase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tablebase")
+            .field("dir", &self.dir)
+            .field("tables", &self.names.len())
+            .field("max_pieces", &self.max_pieces)
+            .finish()
+    }
+}
+
+impl Tablebase {
+    /// Finds the tables in `dir`, without reading them yet.
+    pub fn open(dir: &Path) -> color_eyre::Result<Self> {
+        let entries =
+            std::fs::read_dir(dir).wrap_err_with(|| format!("cannot read {}", dir.display()))?;
+        let mut names = HashSet::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "rtbw")
+                && let Some(name) = path.file_stem().and_then(|stem| stem.to_str())
+                && Material::from_name(name).is_some()
+            {
+                names.insert(name.to_string());
+            }
+        }
+        let Some(max_pieces) = names.iter().map(|name| name.len() - 1).max() else {
+            bail!("no Syzygy tables (.rtbw files) in {}", dir.display());
+        };
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            names,
+            max_pieces,
+            tables: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Pieces on the board, kings included, at or below which positions can be probed.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Whether `game` is a standard chess position with few enough pieces and no castling
+    /// rights, the only kind the tables hold.
+    fn covers(&self, game: &Game) -> bool {
+        let board = game.get_board();
+        let castling = game.castling_rights();
+        let mut count = 0;
+        for color in [Color::White, Color::Black] {
+            for ((row, _), piece) in board.pieces(color) {
+                count += 1;
+                if piece.piece_type as u8 > PieceType::King as u8
+                    || (piece.piece_type == PieceType::Pawn && (row == 0 || row == 7))
+                {
+                    return false;
+                }
+            }
+        }
+        game.variant() == VariantKind::Standard
+            && board.dimensions() == (8, 8)
+            && count <= self.max_pieces
+            && !castling.get(Color::White, true)
+            && !castling.get(Color::White, false)
+            && !castling.get(Color::Black, true)
+            && !castling.get(Color::Black, false)
+    }
+
+    /// The result of `game` with best play, or `None` if a table it needs is missing.
+    pub fn probe_wdl(&self, game: &Game) -> Option<Wdl> {
+        if !self.covers(game) {
+            return None;
+        }
+        self.search(game, false).map(|(wdl, _)| wdl)
+    }
+
+    /// The result and distance to zeroing of `game`, or `None` if a table is missing.
+    pub fn probe(&self, game: &Game) -> Option<Probe> {
+        if !self.covers(game) {
+            return None;
+        }
+        let wdl = self.search(game, false)?.0;
+        let dtz = self.dtz(game)?;
+        Some(Probe { wdl, dtz })
+    }
+
+    /// The moves that keep the best result for the side to move: the quickest to zero among
+    /// winning moves, any drawing move, or the slowest among losing ones. Also gives that
+    /// result.
+    pub fn best_moves(&self, game: &Game) -> Option<(Wdl, Vec<Move>)> {
+        if !self.covers(game) {
+            return None;
+        }
+        let mut ranked = Vec::new();
+        for mv in game.legal_moves() {
+            let mut child = game.clone();
+            child.make_move(mv);
+            let mut dtz = if mv.is_capture() || mv.piece.piece_type == PieceType::Pawn {
+                (-self.search(&child, false)?.0).dtz_before_zeroing()
+            } else {
+                let dtz = -self.dtz(&child)?;
+                dtz + dtz.signum()
+            };
+            if dtz == 2 && child.is_in_check() && child.legal_moves().is_empty() {
+                dtz = 1;
+            }
+            ranked.push((mv, dtz));
+        }
+        let rank = |dtz: i32| match dtz {
+            1.. => (2, -dtz),
+            0 => (1, 0),
+            _ => (0, -dtz),
+        };
+        let best = ranked.iter().map(|&(_, dtz)| rank(dtz)).max()?;
+        let dtz = ranked
+            .iter()
+            .map(|&(_, dtz)| dtz)
+            .find(|&dtz| rank(dtz) == best)?;
+        let wdl = match dtz {
+            101.. => Wdl::CursedWin,
+            1.. => Wdl::Win,
+            0 => Wdl::Draw,
+            -100..0 => Wdl::Loss,
+            _ => Wdl::BlessedLoss,
+        };
+        let moves = ranked
+            .into_iter()
+            .filter(|&(_, dtz)| rank(dtz) == best)
+            .map(|(mv, _)| mv)
+            .collect();
+        Some((wdl, moves))
+    }
+
+    /// Tries captures, and with `zeroing` pawn moves too, as the tables leave out positions
+    /// where one of them is best. Also tells whether such a move is best.
+    fn search(&self, game: &Game, zeroing: bool) -> Option<(Wdl, bool)> {
+        let moves = game.legal_moves();
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for mv in &moves {
+            let pawn_move = zeroing && mv.piece.piece_type == PieceType::Pawn;
+            if !mv.is_capture() && !pawn_move {
+                continue;
+            }
+            searched += 1;
+            let mut child = game.clone();
+            child.make_move(*mv);
+            let value = -self.search(&child, false)?.0;
+            if value > best {
+                best = value;
+                if value == Wdl::Win {
+                    return Some((value, true));
+                }
+            }
+        }
+        // With every move searched the stored value may be wrong, e.g. after a double step
+        let all_searched = searched > 0 && searched == moves.len();
+        let value = if all_searched {
+            best
+        } else {
+            match self.probe_table(game, Kind::Wdl, Wdl::Draw)? {
+                Lookup::Value(value) => Wdl::from_value(value),
+                Lookup::OtherSide => return None,
+            }
+        };
+        if best >= value {
+            Some((best, best > Wdl::Draw || all_searched))
+        } else {
+            Some((value, false))
+        }
+    }
+
+    /// Distance to zeroing in plies, signed like the result.
+    fn dtz(&self, game: &Game) -> Option<i32> {
+        let (wdl, zeroing_best) = self.search(game, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        if zeroing_best {
+            return Some(wdl.dtz_before_zeroing());
+        }
+        match self.probe_table(game, Kind::Dtz, wdl)? {
+            Lookup::Value(dtz) => {
+                let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+                Some((dtz + if cursed { 100 } else { 0 }) * wdl.value().signum())
+            }
+            // The table only has the other side to move, so look one move ahead
+            Lookup::OtherSide => {
+                let mut best: Option<i32> = None;
+                for mv in game.legal_moves() {
+                    let zeroing = mv.is_capture() || mv.piece.piece_type == PieceType::Pawn;
+                    let mut child = game.clone();
+                    child.make_move(mv);
+                    let mut dtz = if zeroing {
+                        -self.search(&child, false)?.0.dtz_before_zeroing()
+                    } else {
+                        -self.dtz(&child)?
+                    };
+                    if dtz == 1 && child.is_in_check() && child.legal_moves().is_empty() {
+                        best = Some(1);
+                    }
+                    if !zeroing {
+                        dtz += dtz.signum();
+                    }
+                    if dtz.signum() == wdl.value().signum() && best.is_none_or(|best| dtz < best) {
+                        best = Some(dtz);
+                    }
+                }
+                // Without legal moves the side to move is mated
+                Some(best.unwrap_or(-1))
+            }
+        }
+    }
+
+    /// Looks `game` up in the table for its material.
+    fn probe_table(&self, game: &Game, kind: Kind, wdl: Wdl) -> Option<Lookup> {
+        let board = game.get_board();
+        let mut pieces: Vec<(usize, u8)> = [Color::White, Color::Black]
+            .into_iter()
+            .flat_map(|color| board.pieces(color))
+            .map(|((row, col), piece)| {
+                let code = piece.piece_type as u8 + if piece.color == Color::Black { 8 } else { 0 };
+                ((7 - row) * 8 + col, code)
+            })
+            .collect();
+        if pieces.len() == 2 {
+            return Some(Lookup::Value(0));
+        }
+        pieces.sort_unstable();
+
+        let side = |color: Color| {
+            let mut letters: Vec<char> = board
+                .pieces(color)
+                .map(|(_, piece)| piece.piece_type.to_char().to_ascii_uppercase())
+                .collect();
+            letters.sort_by_key(|&c| "KQRBNP".find(c));
+            letters.into_iter().collect::<String>()
+        };
+        let (white, black) = (side(Color::White), side(Color::Black));
+        let (name, black_stronger) = if self.names.contains(&format!("{white}v{black}")) {
+            (format!("{white}v{black}"), false)
+        } else if self.names.contains(&format!("{black}v{white}")) {
+            (format!("{black}v{white}"), true)
+        } else {
+            return None;
+        };
+        let table = self.table(name, kind)?;
+        let white_to_move = game.get_current_turn() == Color::White;
+        table.lookup(&pieces, white_to_move, black_stronger, wdl)
+    }
+
+    fn table(&self, name: String, kind: Kind) -> Option<Arc<Table>> {
+        let mut tables = self.tables.lock().unwrap_or_else(|err| err.into_inner());
+        tables
+            .entry((name, kind))
+            .or_insert_with_key(|(name, kind)| {
+                let path = self.dir.join(format!("{name}.{}", kind.extension()));
+                let material = Material::from_name(name)?;
+                let bytes = std::fs::read(path).ok()?;
+                Table::parse(bytes, material, *kind).ok().map(Arc::new)
+            })
+            .clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    Wdl,
+    Dtz,
+}
+
+impl Kind {
+    fn extension(self) -> &'static str {
+        match self {
+            Kind::Wdl => "rtbw",
+            Kind::Dtz => "rtbz",
+        }
+    }
+
+    fn magic(self) -> [u8; 4] {
+        match self {
+            Kind::Wdl => [0x71, 0xe8, 0x23, 0x5d],
+            Kind::Dtz => [0xd7, 0x66, 0x0c, 0xa5],
+        }
+    }
+}
+
+/// What a table's name, such as `KRPvKR`, says about how its positions are indexed. The
+/// first side is White in the table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Material {
+    piece_count: usize,
+    has_pawns: bool,
+    /// Whether some side has exactly one of a piece other than the king.
+    has_unique_pieces: bool,
+    /// Pawns of the side whose pawns lead the index, then of the other side.
+    pawn_count: [usize; 2],
+    /// Both sides have the same pieces, so only White to move is stored.
+    symmetric: bool,
+}
+
+impl Material {
+    fn from_name(name: &str) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        let valid = |side: &str| {
+            side.starts_with('K')
+                && side.matches('K').count() == 1
+                && side.chars().all(|c| "KQRBNP".contains(c))
+        };
+        if !valid(white) || !valid(black) || white.len() + black.len() > MAX_PIECES {
+            return None;
+        }
+        let unique = |side: &str| {
+            "QRBNP"
+                .chars()
+                .any(|piece| side.matches(piece).count() == 1)
+        };
+        let white_pawns = white.matches('P').count();
+        let black_pawns = black.matches('P').count();
+        // The side with fewer pawns leads, as that compresses better
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        Some(Self {
+            piece_count: white.len() + black.len(),
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: unique(white) || unique(black),
+            pawn_count: if white_leads {
+                [white_pawns, black_pawns]
+            } else {
+                [black_pawns, white_pawns]
+            },
+            symmetric: white == black,
+        })
+    }
+}
+
+/// A value from a table, or word that it only stores the other side to move.
+enum Lookup {
+    Value(i32),
+    OtherSide,
+}
+
+/// A table file read into memory, with where its parts start.
+struct Table {
+    material: Material,
+    kind: Kind,
+    bytes: Vec<u8>,
+    /// Value tables by side to move, only White's in DTZ and symmetric tables, then by the
+    /// file of the leading pawn, only the first without pawns.
+    pairs: Vec<Vec<PairsData>>,
+    /// Where the maps from stored DTZ values to real ones start.
+    map: usize,
+}
+
+/// One table of values, compressed by recursive pairing of symbols and Huffman coding into
+/// blocks. Offsets point into the file's bytes.
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    /// Shortest code length in bits, or the value itself for single-value tables.
+    min_sym_len: u8,
+    block_size: usize,
+    /// Values between entries of the sparse index.
+    span: u64,
+    num_blocks: usize,
+    block_length_size: usize,
+    sparse_index_size: usize,
+    lowest_sym: usize,
+    btree: usize,
+    block_lengths: usize,
+    sparse_index: usize,
+    data: usize,
+    /// The lowest code of each length, left-aligned in 64 bits.
+    base64: Vec<u64>,
+    /// Values a symbol expands to, minus one.
+    symlen: Vec<u8>,
+    /// Pieces in the order they are indexed, coded like [`Table::lookup`]'s pieces.
+    pieces: [u8; MAX_PIECES],
+    /// Pieces per group, ending with 0.
+    group_len: [usize; MAX_PIECES + 1],
+    /// Multiplier of each group's index; the one after the last group is the table size.
+    group_idx: [u64; MAX_PIECES + 1],
+    /// Where the DTZ map of each result starts.
+    map_idx: [u16; 4],
+}
+
+/// Reads `N` bytes at `at`, if the file has them.
+fn read<const N: usize>(bytes: &[u8], at: usize) -> Option<[u8; N]> {
+    bytes.get(at..at.checked_add(N)?)?.try_into().ok()
+}
+
+/// Reads `N` bytes at `at`, with zeros past the end of the file.
+fn read_padded<const N: usize>(bytes: &[u8], at: usize) -> [u8; N] {
+    let mut buf = [0; N];
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = bytes.get(at + i).copied().unwrap_or(0);
+    }
+    buf
+}
+
+impl Table {
+    fn parse(bytes: Vec<u8>, material: Material, kind: Kind) -> color_eyre::Result<Self> {
+        if bytes.get(..4) != Some(&kind.magic()[..]) {
+            bail!("not a Syzygy .{} file", kind.extension());
+        }
+        let truncated = || eyre!("the table is truncated");
+        let byte = |at: usize| bytes.get(at).copied().ok_or_else(truncated);
+        let flags = byte(4)?;
+        if (flags & 2 != 0) != material.has_pawns || (flags & 1 != 0) == material.symmetric {
+            bail!("the table does not hold the material its name says");
+        }
+
+        let sides = if kind == Kind::Wdl && !material.symmetric {
+            2
+        } else {
+            1
+        };
+        let files = if material.has_pawns { 4 } else { 1 };
+        let both_have_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        let mut pairs = vec![vec![PairsData::default(); files]; sides];
+        let mut at = 5;
+        for file in 0..files {
+            let orders = byte(at)?;
+            let pawn_orders = if both_have_pawns { byte(at + 1)? } else { 0xff };
+            let order = [
+                [orders & 0xf, pawn_orders & 0xf],
+                [orders >> 4, pawn_orders >> 4],
+            ];
+            at += 1 + usize::from(both_have_pawns);
+            for k in 0..material.piece_count {
+                let pieces = byte(at)?;
+                for (side, pairs) in pairs.iter_mut().enumerate() {
+                    pairs[file].pieces[k] = if side == 0 { pieces & 0xf } else { pieces >> 4 };
+                }
+                at += 1;
+            }
+            for (side, pairs) in pairs.iter_mut().enumerate() {
+                pairs[file].set_groups(&material, order[side], file);
+            }
+        }
+        at += at & 1;
+
+        for file in 0..files {
+            for pairs in &mut pairs {
+                at = pairs[file].set_sizes(&bytes, at).ok_or_else(truncated)?;
+            }
+        }
+
+        let map = at;
+        if kind == Kind::Dtz {
+            for d in &mut pairs[0] {
+                if d.flags & MAPPED == 0 {
+                    continue;
+                }
+                for i in 0..4 {
+                    if d.flags & WIDE != 0 {
+                        at += at & 1;
+                        d.map_idx[i] = ((at - map) / 2 + 1) as u16;
+                        let len = u16::from_le_bytes(read(&bytes, at).ok_or_else(truncated)?);
+                        at += 2 * usize::from(len) + 2;
+                    } else {
+                        d.map_idx[i] = (at - map + 1) as u16;
+                        at += usize::from(byte(at)?) + 1;
+                    }
+                }
+            }
+            at += at & 1;
+        }
+
+        for file in 0..files {
+            for pairs in &mut pairs {
+                pairs[file].sparse_index = at;
+                at += pairs[file].sparse_index_size * 6;
+            }
+        }
+        for file in 0..files {
+            for pairs in &mut pairs {
+                pairs[file].block_lengths = at;
+                at += pairs[file].block_length_size * 2;
+            }
+        }
+        if at > bytes.len() {
+            return Err(truncated());
+        }
+        for file in 0..files {
+            for pairs in &mut pairs {
+                // Blocks start on 64-byte boundaries
+                at = (at + 0x3f) & !0x3f;
+                pairs[file].data = at;
+                at += pairs[file].num_blocks * pairs[file].block_size;
+            }
+        }
+        Ok(Self {
+            material,
+            kind,
+            bytes,
+            pairs,
+            map,
+        })
+    }
+
+    /// The value stored for a position given as its pieces by square, counted from a1, and
+    /// coded 1 to 6 for White's pawn to king and 9 to 14 for Black's. `black_stronger` says
+    /// that the table's White is Black on the board.
+    fn lookup(
+        &self,
+        pieces: &[(usize, u8)],
+        white_to_move: bool,
+        black_stronger: bool,
+        wdl: Wdl,
+    ) -> Option<Lookup> {
+        let material = &self.material;
+        // Tables are stored with the stronger side as White, and symmetric ones only with
+        // White to move, so mirror the position if needed
+        let flip = black_stronger || (material.symmetric && !white_to_move);
+        let (flip_color, flip_squares) = if flip { (8, 56) } else { (0, 0) };
+        let stm = usize::from(flip) ^ usize::from(!white_to_move);
+
+        let mut squares = [0; MAX_PIECES];
+        let mut codes = [0; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut file = 0;
+        let lead_pawn = self.pairs[0][0].pieces[0] ^ flip_color;
+        if material.has_pawns {
+            for &(square, code) in pieces {
+                if code == lead_pawn {
+                    squares[size] = square ^ flip_squares;
+                    size += 1;
+                }
+            }
+            lead_pawns = size;
+            let maps = maps();
+            let leading = (0..lead_pawns).max_by_key(|&i| maps.pawns[squares[i]])?;
+            squares.swap(0, leading);
+            file = (squares[0] & 7).min(7 - (squares[0] & 7));
+        }
+
+        if self.kind == Kind::Dtz {
+            let stored = usize::from(self.pairs[0][file].flags & STM);
+            let both_sides_stored = material.symmetric && !material.has_pawns;
+            if stored != stm && !both_sides_stored {
+                return Some(Lookup::OtherSide);
+            }
+        }
+
+        for &(square, code) in pieces {
+            if material.has_pawns && code == lead_pawn {
+                continue;
+            }
+            squares[size] = square ^ flip_squares;
+            codes[size] = code ^ flip_color;
+            size += 1;
+        }
+        let d = &self.pairs[stm % self.pairs.len()][file];
+        let index = d.index(
+            material,
+            &mut squares[..size],
+            &mut codes[..size],
+            lead_pawns,
+        );
+        let value = i32::from(d.decompress(&self.bytes, index)?);
+        Some(Lookup::Value(match self.kind {
+            Kind::Wdl => value - 2,
+            Kind::Dtz => self.map_dtz(d, value, wdl)?,
+        }))
+    }
+
+    /// Turns a stored DTZ value back into plies. Values are stored by frequency per result
+    /// when the table is mapped, and in full moves unless flagged as plies.
+    fn map_dtz(&self, d: &PairsData, value: i32, wdl: Wdl) -> Option<i32> {
+        let mut value = value as usize;
+        if d.flags & MAPPED != 0 {
+            let slot = match wdl {
+                Wdl::Win => 0,
+                Wdl::Loss => 1,
+                Wdl::CursedWin => 2,
+                Wdl::BlessedLoss => 3,
+                Wdl::Draw => return Some(0),
+            };
+            let start = usize::from(d.map_idx[slot]) + value;
+            value = if d.flags & WIDE != 0 {
+                usize::from(u16::from_le_bytes(read(&self.bytes, self.map + 2 * start)?))
+            } else {
+                usize::from(*self.bytes.get(self.map + start)?)
+            };
+        }
+        let plies = match wdl {
+            Wdl::Win => d.flags & WIN_PLIES != 0,
+            Wdl::Loss => d.flags & LOSS_PLIES != 0,
+            _ => false,
+        };
+        let value = value as i32;
+        Some(if plies { value } else { value * 2 } + 1)
+    }
+}
+
+impl PairsData {
+    /// Splits the pieces into the groups indexed together, and works out the multiplier of
+    /// each group from the order the file gives them in.
+    fn set_groups(&mut self, material: &Material, order: [u8; 2], file: usize) {
+        let maps = maps();
+        let mut groups = 0;
+        let mut first_len: i32 = if material.has_pawns {
+            0
+        } else if material.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        self.group_len[0] = 1;
+        for i in 1..material.piece_count {
+            first_len -= 1;
+            if first_len > 0 || self.pieces[i] == self.pieces[i - 1] {
+                self.group_len[groups] += 1;
+            } else {
+                groups += 1;
+                self.group_len[groups] = 1;
+            }
+        }
+        groups += 1;
+        self.group_len[groups] = 0;
+
+        let both_have_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        let mut next = if both_have_pawns { 2 } else { 1 };
+        let mut free_squares = 64
+            - self.group_len[0]
+            - if both_have_pawns {
+                self.group_len[1]
+            } else {
+                0
+            };
+        let mut index = 1;
+        let mut k = 0;
+        while next < groups || k == usize::from(order[0]) || k == usize::from(order[1]) {
+            if k == usize::from(order[0]) {
+                self.group_idx[0] = index;
+                index *= if material.has_pawns {
+                    maps.lead_pawns_size[self.group_len[0]][file]
+                } else if material.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == usize::from(order[1]) {
+                self.group_idx[1] = index;
+                index *= maps.binomial[self.group_len[1]][48 - self.group_len[0]];
+            } else {
+                self.group_idx[next] = index;
+                index *= maps.binomial[self.group_len[next]][free_squares];
+                free_squares -= self.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        self.group_idx[groups] = index;
+    }
+
+    /// Reads the sizes and Huffman code of the table starting at `at`, returning where the
+    /// next one starts.
+    fn set_sizes(&mut self, bytes: &[u8], mut at: usize) -> Option<usize> {
+        let byte = |at: usize| bytes.get(at).copied();
+        self.flags = byte(at)?;
+        at += 1;
+        if self.flags & SINGLE_VALUE != 0 {
+            self.min_sym_len = byte(at)?;
+            return Some(at + 1);
+        }
+
+        let groups = self.group_len.iter().position(|&len| len == 0)?;
+        let size = self.group_idx[groups];
+        self.block_size = 1 << byte(at)?;
+        self.span = 1 << byte(at + 1)?;
+        self.sparse_index_size = size.div_ceil(self.span) as usize;
+        let padding = usize::from(byte(at + 2)?);
+        self.num_blocks = u32::from_le_bytes(read(bytes, at + 3)?) as usize;
+        self.block_length_size = self.num_blocks + padding;
+        let max_sym_len = byte(at + 7)?;
+        self.min_sym_len = byte(at + 8)?;
+        at += 9;
+        if max_sym_len < self.min_sym_len || self.min_sym_len == 0 {
+            return None;
+        }
+
+        // Canonical Huffman codes: longer codes have lower values, and the codes of one
+        // length are consecutive
+        self.lowest_sym = at;
+        let lengths = usize::from(max_sym_len - self.min_sym_len) + 1;
+        let lowest =
+            |i: usize| read(bytes, at + 2 * i).map(|sym| u64::from(u16::from_le_bytes(sym)));
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            self.base64[i] = (self.base64[i + 1] + lowest(i)?).wrapping_sub(lowest(i + 1)?) / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            let shift = 64_usize.checked_sub(i + usize::from(self.min_sym_len))?;
+            *base = base.checked_shl(shift as u32).unwrap_or(0);
+        }
+        at += lengths * 2;
+
+        let symbols = usize::from(u16::from_le_bytes(read(bytes, at)?));
+        at += 2;
+        self.btree = at;
+        if bytes.len() < at + symbols * 3 {
+            return None;
+        }
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                self.symlen[sym] = self.set_symlen(bytes, sym, &mut visited);
+            }
+        }
+        Some(at + symbols * 3 + (symbols & 1))
+    }
+
+    /// The two symbols a symbol pairs, in 12 bits each; a right half of `0xfff` marks a
+    /// value, held in the left half.
+    fn pair(&self, bytes: &[u8], sym: usize) -> (usize, usize) {
+        let [a, b, c] = read_padded(bytes, self.btree + 3 * sym);
+        let left = usize::from(b & 0xf) << 8 | usize::from(a);
+        let right = usize::from(c) << 4 | usize::from(b >> 4);
+        (left, right)
+    }
+
+    fn set_symlen(&mut self, bytes: &[u8], sym: usize, visited: &mut [bool]) -> u8 {
+        visited[sym] = true;
+        let (left, right) = self.pair(bytes, sym);
+        if right == 0xfff || left >= visited.len() || right >= visited.len() {
+            return 0;
+        }
+        for child in [left, right] {
+            if !visited[child] {
+                self.symlen[child] = self.set_symlen(bytes, child, visited);
+            }
+        }
+        self.symlen[left]
+            .wrapping_add(self.symlen[right])
+            .wrapping_add(1)
+    }
+
+    /// The value at `index`: finds its block through the sparse index, decodes symbols up
+    /// to it and expands the last one.
+    fn decompress(&self, bytes: &[u8], index: u64) -> Option<u16> {
+        if self.flags & SINGLE_VALUE != 0 {
+            return Some(u16::from(self.min_sym_len));
+        }
+        let entry = self.sparse_index + 6 * (index / self.span) as usize;
+        let mut block = u32::from_le_bytes(read(bytes, entry)?) as usize;
+        let mut offset = i64::from(u16::from_le_bytes(read(bytes, entry + 4)?));
+        offset += (index % self.span) as i64 - (self.span / 2) as i64;
+        let block_length = |block: usize| {
+            read(bytes, self.block_lengths + 2 * block)
+                .map(|len| i64::from(u16::from_le_bytes(len)))
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        let mut at = self.data + block * self.block_size;
+        let mut buf = u64::from_be_bytes(read_padded(bytes, at));
+        at += 8;
+        let mut buf_size = 64;
+        let min_len = usize::from(self.min_sym_len);
+        let mut sym = loop {
+            let mut len = 0;
+            while buf < *self.base64.get(len)? {
+                len += 1;
+            }
+            let lowest = u16::from_le_bytes(read(bytes, self.lowest_sym + 2 * len)?);
+            let sym =
+                ((buf - self.base64[len]) >> (64 - len - min_len)) as usize + usize::from(lowest);
+            let count = i64::from(*self.symlen.get(sym)?) + 1;
+            if offset < count {
+                break sym;
+            }
+            offset -= count;
+            let bits = len + min_len;
+            buf <<= bits;
+            buf_size -= bits;
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= u64::from(u32::from_be_bytes(read_padded(bytes, at))) << (64 - buf_size);
+                at += 4;
+            }
+        };
+
+        while self.symlen[sym] != 0 {
+            let (left, right) = self.pair(bytes, sym);
+            let count = i64::from(*self.symlen.get(left)?) + 1;
+            if offset < count {
+                sym = left;
+            } else {
+                offset -= count;
+                sym = right;
+            }
+        }
+        u16::try_from(self.pair(bytes, sym).0).ok()
+    }
+
+    /// The index of a position whose squares and pieces are listed with the leading pawns
+    /// first, then the rest in any order. Mirrors the squares so the leading piece is in the
+    /// part of the board the tables keep.
+    fn index(
+        &self,
+        material: &Material,
+        squares: &mut [usize],
+        codes: &mut [u8],
+        lead_pawns: usize,
+    ) -> u64 {
+        let maps = maps();
+        let size = squares.len();
+        // Put the pieces in the order the table lists them
+        for i in lead_pawns..size.saturating_sub(1) {
+            if let Some(j) = (i + 1..size).find(|&j| codes[j] == self.pieces[i]) {
+                codes.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+        if squares[0] & 7 > 3 {
+            squares.iter_mut().for_each(|square| *square ^= 7);
+        }
+
+        let mut index = if material.has_pawns {
+            let mut index = maps.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&square| maps.pawns[square]);
+            for (i, &square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                index += maps.binomial[i][maps.pawns[square]];
+            }
+            index
+        } else {
+            if squares[0] >> 3 > 3 {
+                squares.iter_mut().for_each(|square| *square ^= 56);
+            }
+            // The first leading piece off the a1-h8 diagonal goes below it
+            if let Some(i) = (0..self.group_len[0]).find(|&i| diagonal_offset(squares[i]) != 0)
+                && diagonal_offset(squares[i]) > 0
+            {
+                for square in &mut squares[i..] {
+                    *square = ((*square >> 3) | (*square << 3)) & 63;
+                }
+            }
+            if material.has_unique_pieces {
+                unique_index(maps, [squares[0], squares[1], squares[2]])
+            } else {
+                maps.kk[maps.a1d1d4[squares[0]]][squares[1]] as u64
+            }
+        };
+
+        index *= self.group_idx[0];
+        let mut start = self.group_len[0];
+        let mut remaining_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        let mut next = 1;
+        while self.group_len[next] != 0 {
+            let len = self.group_len[next];
+            squares[start..start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                // Squares taken by earlier groups are skipped
+                let below = squares[..start].iter().filter(|&&s| s < square).count();
+                let offset = if remaining_pawns { 8 } else { 0 };
+                n += maps.binomial[i + 1][square - below - offset];
+            }
+            remaining_pawns = false;
+            index += n * self.group_idx[next];
+            start += len;
+            next += 1;
+        }
+        index
+    }
+}
+
+/// How far a square is above the a1-h8 diagonal, negative below it.
+fn diagonal_offset(square: usize) -> i32 {
+    (square >> 3) as i32 - (square & 7) as i32
+}
+
+/// The index of three different leading pieces, the first in the a1-d1-d4 triangle.
+fn unique_index(maps: &Maps, [s0, s1, s2]: [usize; 3]) -> u64 {
+    let rank = |square: usize| square >> 3;
+    let adjust1 = usize::from(s1 > s0);
+    let adjust2 = usize::from(s2 > s0) + usize::from(s2 > s1);
+    let index = if diagonal_offset(s0) != 0 {
+        (maps.a1d1d4[s0] * 63 + (s1 - adjust1)) * 62 + s2 - adjust2
+    } else if diagonal_offset(s1) != 0 {
+        (6 * 63 + rank(s0) * 28 + maps.b1h1h7[s1]) * 62 + s2 - adjust2
+    } else if diagonal_offset(s2) != 0 {
+        6 * 63 * 62 + 4 * 28 * 62 + rank(s0) * 7 * 28 + (rank(s1) - adjust1) * 28 + maps.b1h1h7[s2]
+    } else {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + rank(s0) * 7 * 6
+            + (rank(s1) - adjust1) * 6
+            + (rank(s2) - adjust2)
+    };
+    index as u64
+}
+
+/// Square numberings used by the index, with squares counted from a1.
+struct Maps {
+    /// Pawn squares a2-h7 numbered from the edge files and lowest ranks down, 47 to 0.
+    pawns: [usize; 64],
+    /// Squares below the a1-h8 diagonal, 0 to 27.
+    b1h1h7: [usize; 64],
+    /// The a1-d1-d4 triangle, 0 to 9 with the diagonal last.
+    a1d1d4: [usize; 64],
+    /// The 462 placements of two kings, the first in the a1-d1-d4 triangle.
+    kk: [[usize; 64]; 10],
+    /// `binomial[k][n]` ways to choose `k` of `n`.
+    binomial: [[u64; 64]; 6],
+    lead_pawn_idx: [[u64; 64]; 6],
+    /// Leading pawn placements per count and file.
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+fn maps() -> &'static Maps {
+    static MAPS: OnceLock<Maps> = OnceLock::new();
+    MAPS.get_or_init(|| {
+        let mut maps = Maps {
+            pawns: [0; 64],
+            b1h1h7: [0; 64],
+            a1d1d4: [0; 64],
+            kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+        let file = |square: usize| square & 7;
+
+        let below = (0..64).filter(|&square| diagonal_offset(square) < 0);
+        for (code, square) in below.enumerate() {
+            maps.b1h1h7[square] = code;
+        }
+
+        let triangle = (0..=27).filter(|&square| file(square) <= 3);
+        let (diagonal, off_diagonal): (Vec<usize>, Vec<usize>) =
+            triangle.partition(|&square| diagonal_offset(square) == 0);
+        let off_diagonal = off_diagonal
+            .into_iter()
+            .filter(|&square| diagonal_offset(square) < 0);
+        for (code, square) in off_diagonal.chain(diagonal).enumerate() {
+            maps.a1d1d4[square] = code;
+        }
+
+        // Placements with both kings on the diagonal come last
+        let mut code = 0;
+        let mut both_on_diagonal = Vec::new();
+        for idx in 0..10 {
+            let first = (0..=27)
+                .filter(|&square| file(square) <= 3 && diagonal_offset(square) <= 0)
+                .find(|&square| maps.a1d1d4[square] == idx)
+                .expect("every code has a square");
+            for second in 0..64 {
+                let distance =
+                    (file(first).abs_diff(file(second))).max((first >> 3).abs_diff(second >> 3));
+                if distance <= 1 {
+                    continue;
+                }
+                match (diagonal_offset(first), diagonal_offset(second)) {
+                    (0, 1..) => {}
+                    (0, 0) => both_on_diagonal.push((idx, second)),
+                    _ => {
+                        maps.kk[idx][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, second) in both_on_diagonal {
+            maps.kk[idx][second] = code;
+            code += 1;
+        }
+
+        maps.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                maps.binomial[k][n] = if k > 0 {
+                    maps.binomial[k - 1][n - 1]
+                } else {
+                    0
+                } + if k < n { maps.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        let mut available = 48;
+        for lead in 1..6 {
+            for f in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + f;
+                    if lead == 1 {
+                        available -= 1;
+                        maps.pawns[square] = available;
+                        available -= 1;
+                        maps.pawns[square ^ 7] = available;
+                    }
+                    maps.lead_pawn_idx[lead][square] = idx;
+                    idx += maps.binomial[lead - 1][maps.pawns[square]];
+                }
+                maps.lead_pawns_size[lead][f] = idx;
+            }
+        }
+        maps
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::engine::{self, SearchLimits};
+    use crate::game::Board;
+    use crate::notation;
+    use crate::pieces::Piece;
+
+    /// KQvK, KRvK, KPvK, KBvK, KNvK and KRPvK tables built by
+    /// `tests/fixtures/syzygy/generate.rs`.
+    fn fixtures() -> Tablebase {
+        Tablebase::open(Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/syzygy"
+        )))
+        .unwrap()
+    }
+
+    fn game(fen: &str) -> Game {
+        Game::from_fen(fen, VariantKind::Standard).unwrap()
+    }
+
+    fn probe(tablebase: &Tablebase, fen: &str) -> Option<(Wdl, i32)> {
+        tablebase
+            .probe(&game(fen))
+            .map(|probe| (probe.wdl, probe.dtz))
+    }
+
+    /// The position with `pieces` on squares counted from a1, as in the tables, if it is
+    /// legal.
+    fn place(pieces: &[(usize, char)], turn: Color) -> Option<Game> {
+        let mut board = Board::empty(8, 8);
+        for (i, &(square, c)) in pieces.iter().enumerate() {
+            if pieces[..i].iter().any(|&(other, _)| other == square) {
+                return None;
+            }
+            board.set_piece_at((7 - square / 8, square % 8), Piece::from_fen_char(c));
+        }
+        if board.is_in_check(turn.opposite()) {
+            return None;
+        }
+        let turn = if turn == Color::White { 'w' } else { 'b' };
+        let fen = format!("{} {turn} - - 0 1", board.to_fen_placement());
+        Game::from_fen(&fen, VariantKind::Standard).ok()
+    }
+
+    #[test]
+    fn probes_results_and_distances() {
+        let tablebase = fixtures();
+        assert_eq!(tablebase.max_pieces(), 4);
+        // KQvK: mate in one, mated, stalemate, a hanging queen and the colours swapped
+        assert_eq!(
+            probe(&tablebase, "7k/8/6K1/8/8/8/8/1Q6 w - - 0 1"),
+            Some((Wdl::Win, 1))
+        );
+        assert_eq!(
+            probe(&tablebase, "Q6k/8/6K1/8/8/8/8/8 b - - 0 1"),
+            Some((Wdl::Loss, -1))
+        );
+        assert_eq!(
+            probe(&tablebase, "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"),
+            Some((Wdl::Draw, 0))
+        );
+        assert_eq!(
+            probe(&tablebase, "4k3/4Q3/8/8/8/8/8/4K3 b - - 0 1"),
+            Some((Wdl::Draw, 0))
+        );
+        assert_eq!(
+            probe(&tablebase, "4k3/8/8/8/8/8/8/3QK3 w - - 0 1"),
+            Some((Wdl::Win, 15))
+        );
+        assert_eq!(
+            probe(&tablebase, "4k3/8/8/8/8/8/8/3QK3 b - - 0 1"),
+            Some((Wdl::Loss, -16))
+        );
+        assert_eq!(
+            probe(&tablebase, "3qk3/8/8/8/8/8/8/4K3 b - - 0 1"),
+            Some((Wdl::Win, 15))
+        );
+        // KRvK: the only move walks into mate
+        assert_eq!(
+            probe(&tablebase, "k7/8/1K6/8/8/8/8/7R b - - 0 1"),
+            Some((Wdl::Loss, -2))
+        );
+        // KPvK: the king on the sixth wins whoever moves, the rook pawn draws
+        assert_eq!(
+            probe(&tablebase, "4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"),
+            Some((Wdl::Win, 3))
+        );
+        assert_eq!(
+            probe(&tablebase, "4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"),
+            Some((Wdl::Loss, -4))
+        );
+        assert_eq!(
+            probe(&tablebase, "7k/8/8/8/8/8/7P/7K w - - 0 1"),
+            Some((Wdl::Draw, 0))
+        );
+        assert_eq!(
+            probe(&tablebase, "8/8/8/8/8/8/4P3/k3K3 w - - 0 1"),
+            Some((Wdl::Win, 1))
+        );
+        // The textbook opposition: White wins only if Black has to give way
+        assert_eq!(
+            probe(&tablebase, "8/8/8/4k3/8/4K3/4P3/8 w - - 0 1"),
+            Some((Wdl::Draw, 0))
+        );
+        assert_eq!(
+            probe(&tablebase, "8/8/8/4k3/8/4K3/4P3/8 b - - 0 1"),
+            Some((Wdl::Loss, -8))
+        );
+        // KRPvK: mate whoever moves, and a rook left hanging
+        assert_eq!(
+            probe(&tablebase, "k7/8/1K6/8/8/8/4P3/7R w - - 0 1"),
+            Some((Wdl::Win, 1))
+        );
+        assert_eq!(
+            probe(&tablebase, "k7/8/1K6/8/8/8/4P3/7R b - - 0 1"),
+            Some((Wdl::Loss, -2))
+        );
+        // Lone minor pieces cannot win
+        assert_eq!(
+            probe(&tablebase, "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1"),
+            Some((Wdl::Draw, 0))
+        );
+        assert_eq!(
+            tablebase
+                .probe(&game("4k3/8/8/8/8/8/8/3QK3 w - - 0 1"))
+                .unwrap()
+                .to_string(),
+            "Tablebase win, DTZ 15"
+        );
+        // Missing tables and castling rights are not probed
+        assert_eq!(
+            tablebase.probe_wdl(&game("4k3/8/8/8/8/8/8/2RQK3 w - - 0 1")),
+            None
+        );
+        assert_eq!(
+            tablebase.probe_wdl(&game("4k3/8/8/8/8/8/8/3QK2R w K - 0 1")),
+            None
+        );
+        assert!(Tablebase::open(Path::new("/nonexistent")).is_err());
+    }
+
+    #[test]
+    fn keeps_the_best_moves_at_the_root_and_in_search() {
+        let tablebase = Arc::new(fixtures());
+        let position = game("k7/8/1K6/8/8/8/8/7R w - - 0 1");
+        let (wdl, moves) = tablebase.best_moves(&position).unwrap();
+        assert_eq!(wdl, Wdl::Win);
+        let sans: Vec<String> = moves
+            .iter()
+            .map(|mv| notation::san(&position, mv))
+            .collect();
+        assert_eq!(sans, ["Rh8#"]);
+        let drawn = game("7k/8/8/8/8/8/7P/7K w - - 0 1");
+        assert_eq!(tablebase.best_moves(&drawn).unwrap().0, Wdl::Draw);
+
+        let limits = SearchLimits {
+            tablebase: Some(tablebase),
+            ..SearchLimits::depth(2)
+        };
+        let result = engine::search(&position, &limits);
+        assert!(moves.contains(&result.best_move.unwrap()));
+        // Taking the pawn reaches a won table position
+        let capture = game("4k3/8/8/8/8/3p4/8/3QK3 w - - 0 1");
+        let result = engine::search(&capture, &limits);
+        assert_eq!(notation::san(&capture, &result.best_move.unwrap()), "Qxd3");
+        assert!(result.score.centipawns() > 10_000, "{:?}", result.score);
+    }
+
+    #[test]
+    fn longest_wins_match_the_published_ones() {
+        // Mate takes at most 10 moves in KQvK and 16 in KRvK: 19 and 31 plies with the
+        // winner to move, one more when the loser moves first. Each table stores one side
+        let tablebase = fixtures();
+        let triangle = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+        for (piece, turn, longest) in [('Q', Color::White, 19), ('R', Color::Black, -32)] {
+            let mut extreme: i32 = 0;
+            for king in triangle {
+                for square in 0..64 {
+                    for other in 0..64 {
+                        let Some(position) =
+                            place(&[(king, 'K'), (square, piece), (other, 'k')], turn)
+                        else {
+                            continue;
+                        };
+                        let dtz = tablebase.probe(&position).unwrap().dtz;
+                        if dtz.abs() > extreme.abs() {
+                            extreme = dtz;
+                        }
+                    }
+                }
+            }
+            assert_eq!(extreme, longest, "K{piece}vK");
+        }
+    }
+
+    #[test]
+    fn four_piece_results_agree_with_the_moves() {
+        // KRPvK positions whose pawn is too far back to promote within two moves, as the
+        // tables promotions lead to are missing
+        let tablebase = fixtures();
+        let mut seed = 1u64;
+        let mut square = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % 64
+        };
+        let mut checked = 0;
+        while checked < 300 {
+            let pawn = 8 + square() % 32;
+            let pieces = [
+                (square(), 'K'),
+                (square(), 'R'),
+                (pawn, 'P'),
+                (square(), 'k'),
+            ];
+            let turn = if checked % 2 == 0 {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let Some(position) = place(&pieces, turn) else {
+                continue;
+            };
+            let moves = position.legal_moves();
+            if moves.is_empty() {
+                continue;
+            }
+            let probe = tablebase.probe(&position).unwrap();
+            let mut best: Option<(Wdl, i32)> = None;
+            for mv in moves {
+                let mut child = position.clone();
+                child.make_move(mv);
+                let reply = tablebase.probe(&child).unwrap();
+                let zeroing = mv.is_capture() || mv.piece.piece_type == PieceType::Pawn;
+                let mated = child.is_in_check() && child.legal_moves().is_empty();
+                let dtz = match -reply.wdl {
+                    Wdl::Draw => 0,
+                    wdl if zeroing || mated => wdl.dtz_before_zeroing(),
+                    _ => -reply.dtz + (-reply.dtz).signum(),
+                };
+                // Win quickly, lose slowly
+                let rank = |(wdl, dtz): (Wdl, i32)| (wdl, -dtz.signum() * dtz.abs());
+                if best.is_none_or(|best| rank((-reply.wdl, dtz)) > rank(best)) {
+                    best = Some((-reply.wdl, dtz));
+                }
+            }
+            let fen = position.to_fen();
+            assert_eq!(Some((probe.wdl, probe.dtz)), best, "{fen}");
+            checked += 1;
+        }
+    }
+
+    /// The eight ways to mirror a square that keep a pawnless position the same.
+    fn symmetries(square: usize) -> [usize; 8] {
+        let transpose = |s: usize| ((s >> 3) | (s << 3)) & 63;
+        let flips = [0, 7, 56, 63];
+        let mut out = [0; 8];
+        for (i, flip) in flips.iter().enumerate() {
+            out[i] = square ^ flip;
+            out[i + 4] = transpose(square) ^ flip;
+        }
+        out
+    }
+
+    #[test]
+    fn indexes_mirrored_positions_alike_and_others_apart() {
+        // KRvK: three unique pieces indexed together
+        let material = Material::from_name("KRvK").unwrap();
+        let mut d = PairsData {
+            pieces: [6, 4, 14, 0, 0, 0, 0],
+            ..PairsData::default()
+        };
+        d.set_groups(&material, [0, 0xf], 0);
+        let size = d.group_idx[1];
+        assert_eq!(size, 31332);
+        let mut seen: HashMap<u64, [usize; 3]> = HashMap::new();
+        for king in 0..64 {
+            for rook in (0..64).filter(|&s| s != king) {
+                for other in (0..64).filter(|&s| s != king && s != rook) {
+                    let index_of = |m: usize| {
+                        let mut squares = [
+                            symmetries(king)[m],
+                            symmetries(rook)[m],
+                            symmetries(other)[m],
+                        ];
+                        d.index(&material, &mut squares, &mut [6, 4, 14], 0)
+                    };
+                    let index = index_of(0);
+                    assert!(index < size);
+                    let canonical = (0..8)
+                        .map(|m| {
+                            [
+                                symmetries(king)[m],
+                                symmetries(rook)[m],
+                                symmetries(other)[m],
+                            ]
+                        })
+                        .min()
+                        .unwrap();
+                    assert_eq!(*seen.entry(index).or_insert(canonical), canonical);
+                    assert!((1..8).all(|m| index_of(m) == index));
+                }
+            }
+        }
+
+        // KPvK: pawns allow only the left-right mirror
+        let material = Material::from_name("KPvK").unwrap();
+        let mut seen: HashMap<(usize, u64), [usize; 3]> = HashMap::new();
+        for pawn in 8..56 {
+            let file = (pawn & 7).min(7 - (pawn & 7));
+            let mut d = PairsData {
+                pieces: [1, 6, 14, 0, 0, 0, 0],
+                ..PairsData::default()
+            };
+            d.set_groups(&material, [0, 0xf], file);
+            for king in (0..64).filter(|&s| s != pawn) {
+                for other in (0..64).filter(|&s| s != king && s != pawn) {
+                    let mut squares = [pawn, king, other];
+                    let index = d.index(&material, &mut squares, &mut [1, 6, 14], 1);
+                    assert!(index < d.group_idx[3]);
+                    let canonical = [pawn, king, other].min([pawn ^ 7, king ^ 7, other ^ 7]);
+                    assert_eq!(*seen.entry((file, index)).or_insert(canonical), canonical);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::bail;
+use ratatui::style::Color;
+use strum_macros::EnumIter;
+
+use crate::pieces::{self, Piece};
+
+/// Colours used to draw the board and its highlights.
+///
+/// Themes can be written as TOML or JSON; colours are names (`red`), indices (`208`) or hex
+/// (`#d2b48c`), and any missing field falls back to the classic theme.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Left empty in files that name the theme after the file instead.
+    #[serde(default)]
+    pub name: String,
+    pub light_square: Color,
+    pub dark_square: Color,
+    /// Square of the piece that has been picked up.
+    pub selected: Color,
+    /// Square under the keyboard cursor.
+    pub cursor: Color,
+    /// From and to squares of the previous move.
+    pub last_move: Color,
+    /// Marker drawn on empty squares the selected piece can move to.
+    pub legal_move: Color,
+    /// Background of squares where the selected piece can capture.
+    pub legal_capture: Color,
+    /// Background of a king in check.
+    pub check: Color,
+    pub white_piece: Color,
+    pub black_piece: Color,
+    /// Shown around the strokes of white pieces in the outlined style.
+    pub white_outline: Color,
+    /// Shown around the strokes of black pieces in the outlined style.
+    pub black_outline: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+impl Theme {
+    /// Wooden browns, the look the board has always had.
+    pub fn classic() -> Self {
+        Self {
+            name: "classic".to_string(),
+            light_square: Color::Rgb(210, 180, 140),
+            dark_square: Color::Rgb(139, 69, 19),
+            selected: Color::Rgb(186, 202, 68),
+            cursor: Color::Rgb(100, 149, 237),
+            last_move: Color::Rgb(205, 210, 106),
+            legal_move: Color::Rgb(60, 60, 60),
+            legal_capture: Color::Rgb(214, 120, 90),
+            check: Color::Rgb(220, 40, 40),
+            white_piece: Color::Rgb(250, 248, 235),
+            black_piece: Color::Rgb(25, 20, 15),
+            white_outline: Color::Rgb(60, 45, 30),
+            black_outline: Color::Rgb(235, 225, 200),
+        }
+    }
+
+    /// Strong light/dark contrast with saturated highlights for low-vision use.
+    pub fn high_contrast() -> Self {
+        Self {
+            name: "high-contrast".to_string(),
+            light_square: Color::Rgb(255, 255, 255),
+            dark_square: Color::Rgb(40, 40, 40),
+            selected: Color::Rgb(0, 200, 0),
+            cursor: Color::Rgb(0, 120, 255),
+            last_move: Color::Rgb(255, 215, 0),
+            legal_move: Color::Rgb(255, 0, 255),
+            legal_capture: Color::Rgb(255, 120, 0),
+            check: Color::Rgb(255, 0, 0),
+            white_piece: Color::Rgb(255, 255, 255),
+            black_piece: Color::Rgb(0, 0, 0),
+            white_outline: Color::Rgb(0, 0, 0),
+            black_outline: Color::Rgb(255, 255, 255),
+        }
+    }
+
+    /// Greys only.
+    pub fn monochrome() -> Self {
+        Self {
+            name: "monochrome".to_string(),
+            light_square: Color::Rgb(200, 200, 200),
+            dark_square: Color::Rgb(110, 110, 110),
+            selected: Color::Rgb(240, 240, 240),
+            cursor: Color::Rgb(160, 160, 160),
+            last_move: Color::Rgb(175, 175, 175),
+            legal_move: Color::Rgb(20, 20, 20),
+            legal_capture: Color::Rgb(70, 70, 70),
+            check: Color::Rgb(0, 0, 0),
+            white_piece: Color::Rgb(255, 255, 255),
+            black_piece: Color::Rgb(0, 0, 0),
+            white_outline: Color::Rgb(60, 60, 60),
+            black_outline: Color::Rgb(230, 230, 230),
+        }
+    }
+
+    /// Named ANSI colours for terminals without true-colour support.
+    pub fn ansi16() -> Self {
+        Self {
+            name: "16-colour".to_string(),
+            light_square: Color::Yellow,
+            dark_square: Color::Red,
+            selected: Color::Green,
+            cursor: Color::Blue,
+            last_move: Color::LightYellow,
+            legal_move: Color::Black,
+            legal_capture: Color::LightRed,
+            check: Color::LightMagenta,
+            white_piece: pieces::Color::White.to_color(),
+            black_piece: pieces::Color::Black.to_color(),
+            white_outline: pieces::Color::Black.to_color(),
+            black_outline: pieces::Color::White.to_color(),
+        }
+    }
+
+    /// Fill colour for pieces of the given side.
+    pub fn piece_color(&self, color: pieces::Color) -> Color {
+        match color {
+            pieces::Color::White => self.white_piece,
+            pieces::Color::Black => self.black_piece,
+        }
+    }
+
+    /// Outline colour for pieces of the given side, contrasting with its fill.
+    pub fn outline_color(&self, color: pieces::Color) -> Color {
+        match color {
+            pieces::Color::White => self.white_outline,
+            pieces::Color::Black => self.black_outline,
+        }
+    }
+
+    pub fn builtin() -> Vec<Theme> {
+        vec![
+            Self::classic(),
+            Self::high_contrast(),
+            Self::monochrome(),
+            Self::ansi16(),
+        ]
+    }
+
+    /// Reads a theme from a `.toml` or `.json` file.
+    pub fn load(path: &Path) -> color_eyre::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let mut theme: Theme = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&text)?,
+            Some("json") => serde_json::from_str(&text)?,
+            _ => bail!("unsupported theme format: {}", path.display()),
+        };
+        // Unnamed themes are listed under their file name
+        if theme.name.is_empty()
+            && let Some(stem) = path.file_stem()
+        {
+            theme.name = stem.to_string_lossy().into_owned();
+        }
+        Ok(theme)
+    }
+
+    /// Directory scanned for user themes: `$XDG_CONFIG_HOME/chess-tui/themes` on Linux.
+    pub fn user_dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("chess-tui").join("themes"))
+    }
+
+    /// Built-in themes followed by every theme that loads from `dir`, usually
+    /// [`Theme::user_dir`]. Themes are picked by name, so a file replaces any earlier theme of
+    /// the same name, built-in or not. Unreadable files are skipped so one bad theme cannot stop
+    /// the game from starting.
+    pub fn available(dir: Option<&Path>) -> Vec<Theme> {
+        let mut themes = Self::builtin();
+        if let Some(entries) = dir.and_then(|dir| std::fs::read_dir(dir).ok()) {
+            let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+            paths.sort();
+            for theme in paths.iter().filter_map(|path| Self::load(path).ok()) {
+                match themes.iter_mut().find(|known| known.name == theme.name) {
+                    Some(known) => *known = theme,
+                    None => themes.push(theme),
+                }
+            }
+        }
+        themes
+    }
+}
+
+/// How pieces are drawn inside their squares.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum GlyphSet {
+    /// Multi-line block-character art that fills large squares.
+    #[default]
+    BlockArt,
+    /// Single Unicode chess symbols.
+    Unicode,
+    /// FEN letters, uppercase for white.
+    Ascii,
+}
+
+impl GlyphSet {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GlyphSet::BlockArt => "Block art",
+            GlyphSet::Unicode => "Unicode",
+            GlyphSet::Ascii => "ASCII letters",
+        }
+    }
+
+    pub fn symbol(&self, piece: &Piece) -> String {
+        match self {
+            GlyphSet::BlockArt => piece.symbol().to_string(),
+            GlyphSet::Unicode => piece.unicode_symbol().to_string(),
+            GlyphSet::Ascii => piece.fen_char().to_string(),
+        }
+    }
+}
+
+/// Whether pieces are drawn as plain coloured glyphs or with an outline behind their strokes, which
+/// keeps them readable on squares close to their own colour.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum PieceStyle {
+    #[default]
+    Filled,
+    Outlined,
+}
+
+impl PieceStyle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PieceStyle::Filled => "Filled",
+            PieceStyle::Outlined => "Outlined",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory named after the test, so tests can run in parallel.
+    fn theme_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("chess-tui-themes-{test}-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn loads_toml_and_json_colours() {
+        let dir = theme_dir("load");
+        let toml = dir.join("wood.toml");
+        std::fs::write(
+            &toml,
+            "light_square = \"#d2b48c\"\ndark_square = \"208\"\ncheck = \"light-red\"\n",
+        )
+        .unwrap();
+        let theme = Theme::load(&toml).unwrap();
+        assert_eq!(theme.name, "wood");
+        assert_eq!(theme.light_square, Color::Rgb(0xd2, 0xb4, 0x8c));
+        assert_eq!(theme.dark_square, Color::Indexed(208));
+        assert_eq!(theme.check, Color::LightRed);
+        // Fields left out fall back to the classic theme
+        let classic = Theme::classic();
+        assert_eq!(theme.selected, classic.selected);
+        assert_eq!(theme.black_outline, classic.black_outline);
+
+        let json = dir.join("sea.json");
+        std::fs::write(
+            &json,
+            r##"{"name": "classic", "dark_square": "blue", "cursor": "#000080"}"##,
+        )
+        .unwrap();
+        let theme = Theme::load(&json).unwrap();
+        assert_eq!(theme.name, "classic");
+        assert_eq!(theme.dark_square, Color::Blue);
+        assert_eq!(theme.cursor, Color::Rgb(0, 0, 0x80));
+        assert_eq!(theme.light_square, classic.light_square);
+
+        let yaml = dir.join("plain.yaml");
+        std::fs::write(&yaml, "dark_square: blue\n").unwrap();
+        assert!(Theme::load(&yaml).is_err());
+        std::fs::write(&toml, "dark_square = \"not a colour\"\n").unwrap();
+        assert!(Theme::load(&toml).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn user_themes_replace_themes_of_the_same_name() {
+        let dir = theme_dir("available");
+        std::fs::write(
+            dir.join("a.toml"),
+            "name = \"monochrome\"\ncheck = \"red\"\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("b.toml"), "name = \"dusk\"\n").unwrap();
+        std::fs::write(dir.join("c.json"), r#"{"name": "dusk", "check": "blue"}"#).unwrap();
+        std::fs::write(dir.join("broken.toml"), "check = [").unwrap();
+
+        let themes = Theme::available(Some(&dir));
+        let names: Vec<&str> = themes.iter().map(|theme| theme.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "classic",
+                "high-contrast",
+                "monochrome",
+                "16-colour",
+                "dusk"
+            ]
+        );
+        assert_eq!(themes[2].check, Color::Red);
+        assert_eq!(themes[4].check, Color::Blue);
+        assert_eq!(Theme::available(None), Theme::builtin());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,325 @@
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use color_eyre::eyre::{bail, eyre};
+use strum::IntoEnumIterator;
+
+use crate::book::Book;
+use crate::engine::{self, Score, SearchLimits, SearchResult};
+use crate::game::{Game, Move};
+use crate::pieces::Color;
+use crate::tablebase::Tablebase;
+use crate::variant::VariantKind;
+
+/// Most lines the `MultiPV` option asks for.
+const MAX_MULTIPV: usize = 5;
+
+/// Serves the built-in engine over the UCI protocol on standard input and output until `quit`
+/// or the end of input.
+pub fn run() -> color_eyre::Result<()> {
+    let mut session = Session::default();
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        if !session.handle(line.trim()) {
+            break;
+        }
+    }
+    session.stop();
+    Ok(())
+}
+
+/// State between UCI commands: the position and any running search.
+#[derive(Default)]
+struct Session {
+    variant: VariantKind,
+    game: Game,
+    search: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+    /// Opening book to answer `go` from while it has moves.
+    book: Option<Book>,
+    /// Syzygy tables the search probes.
+    tablebase: Option<Arc<Tablebase>>,
+    /// Lines to report, best first; 0 until set, meaning one.
+    multipv: usize,
+}
+
+impl Session {
+    /// Handles one command, returning `false` on `quit`.
+    fn handle(&mut self, line: &str) -> bool {
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "uci" => {
+                println!("id name chess-tui {}", env!("CARGO_PKG_VERSION"));
+                println!("id author chess-tui contributors");
+                let variants: Vec<String> = VariantKind::iter()
+                    .map(|variant| variant.name().to_ascii_lowercase().replace([' ', '-'], ""))
+                    .collect();
+                println!(
+                    "option name UCI_Variant type combo default standard var {}",
+                    variants.join(" var ")
+                );
+                println!("option name BookFile type string default <empty>");
+                println!("option name SyzygyPath type string default <empty>");
+                println!("option name MultiPV type spin default 1 min 1 max {MAX_MULTIPV}");
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                self.stop();
+                self.game = Game::with_variant(self.variant);
+            }
+            "setoption" => {
+                if let Some(value) = args.strip_prefix("name UCI_Variant value ")
+                    && let Some(variant) = VariantKind::from_name(value)
+                {
+                    self.variant = variant;
+                    self.game = Game::with_variant(variant);
+                }
+                if let Some(value) = args.strip_prefix("name BookFile value ") {
+                    self.book = None;
+                    match value.trim() {
+                        "" | "<empty>" => {}
+                        path => match Book::open(Path::new(path)) {
+                            Ok(book) => self.book = Some(book),
+                            Err(err) => println!("info string {err}"),
+                        },
+                    }
+                }
+                if let Some(value) = args.strip_prefix("name MultiPV value ")
+                    && let Ok(lines) = value.trim().parse::<usize>()
+                {
+                    self.multipv = lines.clamp(1, MAX_MULTIPV);
+                }
+                if let Some(value) = args.strip_prefix("name SyzygyPath value ") {
+                    self.tablebase = None;
+                    match value.trim() {
+                        "" | "<empty>" => {}
+                        dir => match Tablebase::open(Path::new(dir)) {
+                            Ok(tablebase) => self.tablebase = Some(Arc::new(tablebase)),
+                            Err(err) => println!("info string {err}"),
+                        },
+                    }
+                }
+            }
+            "position" => match position(args, self.variant) {
+                Ok(game) => {
+                    self.stop();
+                    self.game = game;
+                }
+                Err(err) => println!("info string {err}"),
+            },
+            "go" => {
+                self.stop();
+                self.go(go_limits(args, self.game.get_current_turn()));
+            }
+            "stop" => self.stop(),
+            "quit" => return false,
+            _ => {}
+        }
+        true
+    }
+
+    /// Starts searching the current position on another thread, which prints `info` lines per
+    /// depth and the `bestmove` at the end.
+    fn go(&mut self, mut limits: SearchLimits) {
+        if let Some(mv) = self
+            .book
+            .as_ref()
+            .and_then(|book| book.choose(&self.game, &mut rand::rng()))
+        {
+            println!("info string book move");
+            println!("bestmove {}", mv.to_uci(self.game.get_board()));
+            return;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        limits.stop = Some(stop.clone());
+        limits.tablebase = self.tablebase.clone();
+        let game = self.game.clone();
+        let lines = self.multipv.max(1);
+        let handle = std::thread::spawn(move || {
+            let results = engine::search_lines(&game, &limits, lines, |results| {
+                for (i, result) in results.iter().enumerate() {
+                    println!("{}", info_line(&game, result, i + 1));
+                }
+            });
+            match results[0].best_move {
+                Some(mv) => println!("bestmove {}", mv.to_uci(game.get_board())),
+                None => println!("bestmove 0000"),
+            }
+        });
+        self.search = Some((stop, handle));
+    }
+
+    /// Ends any running search, waiting for its `bestmove`.
+    fn stop(&mut self) {
+        if let Some((stop, handle)) = self.search.take() {
+            stop.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Reads `startpos` or `fen <fen>`, optionally followed by `moves <uci>...`.
+fn position(args: &str, variant: VariantKind) -> color_eyre::Result<Game> {
+    let (setup, moves) = match args.split_once(" moves ") {
+        Some((setup, moves)) => (setup, moves),
+        None => (args.strip_suffix(" moves").unwrap_or(args), ""),
+    };
+    let mut game = match setup.trim() {
+        "startpos" => Game::with_variant(variant),
+        setup => match setup.strip_prefix("fen ") {
+            Some(fen) => Game::from_fen(fen, variant)?,
+            None => bail!("expected startpos or fen in `position {args}`"),
+        },
+    };
+    for uci in moves.split_whitespace() {
+        let mv = find_move(&game, uci).ok_or_else(|| eyre!("illegal move {uci}"))?;
+        game.make_move(mv);
+    }
+    Ok(game)
+}
+
+/// The legal move of `game` written `uci` in coordinate notation.
+fn find_move(game: &Game, uci: &str) -> Option<Move> {
+    game.legal_moves()
+        .into_iter()
+        .find(|mv| mv.to_uci(game.get_board()) == uci)
+}
+
+/// Search limits from the arguments of `go`. With only clock times the engine spends about a
+/// thirtieth of its remaining time plus half its increment.
+fn go_limits(args: &str, turn: Color) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let mut words = args.split_whitespace();
+    let (mut time_left, mut increment) = (None, 0);
+    while let Some(word) = words.next() {
+        let mut value = || words.next().and_then(|value| value.parse::<u64>().ok());
+        match (word, turn) {
+            ("depth", _) => limits.depth = value().map(|depth| depth as u32),
+            ("nodes", _) => limits.nodes = value(),
+            ("movetime", _) => limits.time = value().map(Duration::from_millis),
+            ("wtime", Color::White) | ("btime", Color::Black) => time_left = value(),
+            ("winc", Color::White) | ("binc", Color::Black) => increment = value().unwrap_or(0),
+            _ => {}
+        }
+    }
+    if limits.time.is_none()
+        && let Some(left) = time_left
+    {
+        limits.time = Some(Duration::from_millis(left / 30 + increment / 2));
+    }
+    limits
+}
+
+fn info_line(game: &Game, result: &SearchResult, multipv: usize) -> String {
+    let score = match result.score {
+        Score::Centipawns(cp) => format!("cp {cp}"),
+        Score::Mate(moves) => format!("mate {moves}"),
+    };
+    let mut replay = game.clone();
+    let pv: Vec<String> = result
+        .pv
+        .iter()
+        .map(|mv| {
+            let uci = mv.to_uci(replay.get_board());
+            replay.make_move(*mv);
+            uci
+        })
+        .collect();
+    format!(
+        "info depth {} multipv {multipv} score {score} nodes {} pv {}",
+        result.depth,
+        result.nodes,
+        pv.join(" ")
+    )
+}
+
+/// Reads an `info` line another engine printed while searching `game`, giving the line's
+/// MultiPV number, from 1, and what it found. Lines without a score and a legal PV are skipped.
+pub fn parse_info(game: &Game, line: &str) -> Option<(usize, SearchResult)> {
+    let mut words = line.split_whitespace();
+    if words.next() != Some("info") {
+        return None;
+    }
+    let (mut multipv, mut depth, mut nodes, mut score) = (1, 0, 0, None);
+    let mut pv = Vec::new();
+    while let Some(word) = words.next() {
+        match word {
+            "multipv" => multipv = words.next()?.parse().ok()?,
+            "depth" => depth = words.next()?.parse().ok()?,
+            "nodes" => nodes = words.next()?.parse().ok()?,
+            "score" => {
+                score = match (words.next()?, words.next()?.parse().ok()?) {
+                    ("cp", cp) => Some(Score::Centipawns(cp)),
+                    ("mate", moves) => Some(Score::Mate(moves)),
+                    _ => return None,
+                }
+            }
+            "pv" => {
+                let mut replay = game.clone();
+                for uci in words.by_ref() {
+                    let mv = find_move(&replay, uci)?;
+                    replay.make_move(mv);
+                    pv.push(mv);
+                }
+            }
+            _ => {}
+        }
+    }
+    let result = SearchResult {
+        best_move: pv.first().copied(),
+        score: score?,
+        depth,
+        pv,
+        nodes,
+    };
+    result.best_move.is_some().then_some((multipv, result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_positions_and_limits() {
+        let game = position("startpos moves e2e4 e7e5 g1f3", VariantKind::Standard).unwrap();
+        assert_eq!(
+            game.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+        let game = position(&format!("fen {fen} moves e1g1"), VariantKind::Standard).unwrap();
+        assert_eq!(game.get_current_turn(), Color::Black);
+        assert!(position("startpos moves e2e5", VariantKind::Standard).is_err());
+
+        let limits = go_limits("wtime 60000 btime 30000 winc 1000 binc 0", Color::Black);
+        assert_eq!(limits.time, Some(Duration::from_millis(1000)));
+        assert_eq!(go_limits("depth 3", Color::White).depth, Some(3));
+    }
+
+    #[test]
+    fn reads_other_engines_info_lines() {
+        let game = Game::new();
+        let (multipv, result) = parse_info(
+            &game,
+            "info depth 18 seldepth 24 multipv 2 score cp -15 upperbound nodes 901 pv d2d4 d7d5",
+        )
+        .unwrap();
+        assert_eq!(multipv, 2);
+        assert_eq!(result.depth, 18);
+        assert_eq!(result.score, Score::Centipawns(-15));
+        assert_eq!(result.pv.len(), 2);
+        assert_eq!(
+            info_line(&game, &result, 2).split(" pv ").nth(1),
+            Some("d2d4 d7d5")
+        );
+
+        assert!(parse_info(&game, "info depth 3 currmove e2e4").is_none());
+        assert!(parse_info(&game, "info depth 3 score mate 2 pv e2e5").is_none());
+        let (_, mate) = parse_info(&game, "info score mate -3 pv e2e4").unwrap();
+        assert_eq!(mate.score, Score::Mate(-3));
+    }
+}
@@ -1,13 +1,116 @@
 use ratatui::{
     buffer::Buffer,
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style, Stylize},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
+    style::{Color, Style, Stylize},
     text::{Line, Span, Text},
-    widgets::{Block, BorderType, Borders, Paragraph, Widget},
+    widgets::{Block, BorderType, Borders, Paragraph, Row, Table, Widget, Wrap},
 };
+use strum::IntoEnumIterator;
 
-use crate::app::{App, AppState};
+use crate::analysis::Analysis;
+use crate::app::{App, AppState, MenuSelector, SettingsRow};
+use crate::clock::format_duration;
+use crate::config::EngineConfig;
+use crate::database::{Browser, Column};
+use crate::editor::Editor;
+use crate::endgame::{Drill, Goal, HOLD_MOVES, Verdict as DrillVerdict};
+use crate::engine::Score;
 use crate::game::Game;
+use crate::keymap::{self, Action, Scope};
+use crate::notation;
+use crate::pgn::nag_symbol;
+use crate::pieces;
+use crate::puzzle::{Status, Trainer};
+use crate::repertoire::{Session, Verdict};
+use crate::replay::Replay;
+use crate::report::{Judgement, MoveReport};
+use crate::theme::{GlyphSet, PieceStyle};
+use crate::variant::VariantKind;
+use crate::widgets::{EvalBar, EvalGraph};
+
+/// Most mistakes and blunders listed under the post-game report.
+const REPORT_WORST_MOVES: usize = 5;
+/// Columns taken by the eval bar left of the board, including a gap.
+const EVAL_BAR_WIDTH: u16 = 5;
+/// Rows of the evaluation graph under the replay board, including its border.
+const EVAL_GRAPH_HEIGHT: u16 = 9;
+
+/// Splits the eval bar off the left of the board's area, level with the squares inside the
+/// board's border.
+fn split_eval_bar(area: Rect) -> (Rect, Rect) {
+    let width = EVAL_BAR_WIDTH.min(area.width);
+    let bar =
+        Rect::new(area.x, area.y, width.saturating_sub(1), area.height).inner(Margin::new(0, 1));
+    let board = Rect::new(area.x + width, area.y, area.width - width, area.height);
+    (bar, board)
+}
+
+/// Where the board was last drawn, so terminal cells can be mapped back to squares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardLayout {
+    /// The board including its border.
+    pub area: Rect,
+    /// The cells inside the border that hold the squares.
+    pub squares: Rect,
+    pub cell_width: u16,
+    pub cell_height: u16,
+    pub rows: usize,
+    pub cols: usize,
+    /// Black at the bottom instead of white.
+    pub flipped: bool,
+}
+
+impl BoardLayout {
+    /// Splits `area` inside a one-cell border into equally sized cells for a `rows` x `cols`
+    /// board.
+    pub fn new(area: Rect, (rows, cols): (usize, usize), flipped: bool) -> Self {
+        let squares = area.inner(Margin::new(1, 1));
+        Self {
+            area,
+            squares,
+            cell_width: squares.width / cols as u16,
+            cell_height: squares.height / rows as u16,
+            rows,
+            cols,
+            flipped,
+        }
+    }
+
+    /// Screen position (column, row in cells) of a board square after flipping.
+    fn display_position(&self, (row, col): (usize, usize)) -> (usize, usize) {
+        if self.flipped {
+            (self.rows - 1 - row, self.cols - 1 - col)
+        } else {
+            (row, col)
+        }
+    }
+
+    /// The terminal cells covering a board square.
+    pub fn cell_rect(&self, square: (usize, usize)) -> Rect {
+        let (row, col) = self.display_position(square);
+        Rect::new(
+            self.squares.x + col as u16 * self.cell_width,
+            self.squares.y + row as u16 * self.cell_height,
+            self.cell_width,
+            self.cell_height,
+        )
+    }
+
+    /// The board square under a terminal cell, if any.
+    pub fn square_at(&self, x: u16, y: u16) -> Option<(usize, usize)> {
+        let squares = self.squares;
+        if self.cell_width == 0 || self.cell_height == 0 || x < squares.x || y < squares.y {
+            return None;
+        }
+        let col = ((x - squares.x) / self.cell_width) as usize;
+        let row = ((y - squares.y) / self.cell_height) as usize;
+        if row >= self.rows || col >= self.cols {
+            return None;
+        }
+        // Flipping is its own inverse
+        Some(self.display_position((row, col)))
+    }
+}
 
 fn create_title() -> Text<'static> {
     Text::from(vec![
@@ -23,12 +126,41 @@ fn create_title() -> Text<'static> {
     ])
 }
 
+/// Menu entries with the selected one highlighted.
+fn menu_lines(selector: &MenuSelector) -> Vec<Line<'static>> {
+    let mut menu_lines = Vec::new();
+    for (i, item) in selector.items.iter().enumerate() {
+        if i == selector.selected_index {
+            menu_lines.push(Line::from(vec![
+                Span::styled("→ ", Style::default().fg(Color::Yellow)),
+                Span::styled(item.clone(), Style::default().fg(Color::Yellow)),
+            ]));
+        } else {
+            menu_lines.push(Line::from(vec![Span::raw("  "), Span::raw(item.clone())]));
+        }
+    }
+    menu_lines
+}
+
 impl Widget for &mut App {
     fn render(self, area: Rect, buf: &mut Buffer) {
+        self.refresh_notes();
+        self.refresh_completions();
         if let Some(current_state) = self.state.last() {
             match current_state {
                 AppState::MainMenu => self.render_main_menu(area, buf),
+                AppState::NewGame => self.render_new_game_menu(area, buf),
                 AppState::Game => self.render_game_menu(area, buf),
+                AppState::Analysis => self.render_analysis(area, buf),
+                AppState::Editor => self.render_editor(area, buf),
+                AppState::Puzzle => self.render_puzzles(area, buf),
+                AppState::Repertoire => self.render_repertoire(area, buf),
+                AppState::Endgame => self.render_endgame(area, buf),
+                AppState::Stats => self.render_stats(area, buf),
+                AppState::Replay => self.render_replay(area, buf),
+                AppState::Database => self.render_database(area, buf),
+                AppState::Search => self.render_search(area, buf),
+                AppState::Settings => self.render_settings(area, buf),
                 AppState::Help => self.render_help(area, buf),
                 AppState::Credits => self.render_credits(area, buf),
             }
@@ -43,22 +175,9 @@ impl App {
             .title_alignment(Alignment::Center)
             .border_type(BorderType::Rounded);
 
-        // Create menu items with proper styling
-        let mut menu_lines = Vec::new();
-        for (i, item) in self.menu_selector.items.iter().enumerate() {
-            if i == self.menu_selector.selected_index {
-                menu_lines.push(Line::from(vec![
-                    Span::styled("→ ", Style::default().fg(Color::Yellow)),
-                    Span::styled(item.clone(), Style::default().fg(Color::Yellow)),
-                ]));
-            } else {
-                menu_lines.push(Line::from(vec![Span::raw("  "), Span::raw(item.clone())]));
-            }
-        }
-
         // Combine all text elements
         let mut text = create_title().lines;
-        text.extend(menu_lines);
+        text.extend(menu_lines(&self.menu_selector));
         text.push(Line::from(""));
         text.push(Line::from(format!("Counter: {}", self.counter)));
 
@@ -71,79 +190,1688 @@ impl App {
         paragraph.render(area, buf);
     }
 
+    fn render_new_game_menu(&self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .title("New game")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded);
+
+        let mut text = vec![Line::from("Choose a variant:"), Line::from("")];
+        text.extend(menu_lines(&self.variant_selector));
+
+        let paragraph = Paragraph::new(text)
+            .block(block)
+            .fg(Color::White)
+            .bg(Color::Black)
+            .alignment(Alignment::Center);
+
+        paragraph.render(area, buf);
+    }
+
     fn render_game_menu(&mut self, area: Rect, buf: &mut Buffer) {
-        // Split the area into board (left 2/3) and move list (right 1/3)
+        // Keep the bottom rows for the command bar
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(4)])
+            .split(area);
+
+        // Split the rest into board (left 2/3) and move list (right 1/3)
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Ratio(2, 3), Constraint::Ratio(1, 3)])
+            .split(rows[0]);
+
+        let (bar_area, board_area) = split_eval_bar(chunks[0]);
+
+        // Remember the geometry for mouse hit-testing
+        self.board_layout = self.game.as_ref().map(|game| {
+            BoardLayout::new(
+                board_area,
+                game.get_board().dimensions(),
+                self.board_flipped,
+            )
+        });
+
+        if let (Some(game), Some(layout)) = (&self.game, &self.board_layout) {
+            self.render_eval_bar(bar_area, buf, game);
+            self.render_chess_board(layout, buf, game);
+            if game.variant() == VariantKind::Crazyhouse {
+                let side = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(0), Constraint::Length(4)])
+                    .split(chunks[1]);
+                self.render_game_info(side[0], buf, game);
+                self.render_pockets(side[1], buf, game);
+            } else {
+                self.render_game_info(chunks[1], buf, game);
+            }
+            self.render_command_bar(rows[1], buf, game, Scope::Game);
+        }
+    }
+
+    /// Progress of the post-game report, then each player's accuracy and the moves that lost
+    /// the most.
+    fn report_lines(&self) -> Vec<Line<'static>> {
+        let mut text = Vec::new();
+        if let Some((done, total)) = self.report_progress {
+            text.push(Line::from(format!("Analysing the game… {done}/{total}")));
+        }
+        if let Some(report) = &self.report {
+            for color in [pieces::Color::White, pieces::Color::Black] {
+                text.push(Line::from(format!("{color:?}: {}", report.summary(color))));
+            }
+            let mut worst: Vec<&MoveReport> = report
+                .moves
+                .iter()
+                .filter(|m| m.judgement >= Judgement::Mistake)
+                .collect();
+            worst.sort_by_key(|m| std::cmp::Reverse(m.loss));
+            for report in worst.into_iter().take(REPORT_WORST_MOVES) {
+                let glyph = report.judgement.nag().and_then(nag_symbol).unwrap_or("");
+                let best = report.best.as_deref().unwrap_or("?");
+                text.push(Line::from(Span::styled(
+                    format!("{}{glyph} (best {best})", report.label),
+                    Style::default().fg(Color::Red),
+                )));
+            }
+        }
+        if let Some(message) = &self.report_message {
+            text.push(Line::from(message.clone()));
+        }
+        text
+    }
+
+    /// The eval bar for `game`, empty until its evaluation comes back.
+    fn render_eval_bar(&self, area: Rect, buf: &mut Buffer, game: &Game) {
+        EvalBar {
+            score: self.evaluations.get(&game.to_fen()).copied(),
+            flipped: self.board_flipped,
+        }
+        .render(area, buf);
+    }
+
+    /// Typed move entry: the input with a cursor, then an error or the legal moves it could
+    /// still become.
+    fn render_command_bar(&self, area: Rect, buf: &mut Buffer, game: &Game, scope: Scope) {
+        let block = Block::bordered()
+            .title("Move")
+            .border_type(BorderType::Rounded);
+
+        let text = match &self.command_input {
+            Some(input) => {
+                let hint = match &self.command_error {
+                    Some(error) => {
+                        Line::from(Span::styled(error.clone(), Style::default().fg(Color::Red)))
+                    }
+                    None if scope == Scope::Editor => Line::from(""),
+                    None => Line::from(Span::styled(
+                        self.completions
+                            .as_ref()
+                            .map(|completions| completions.matching(input).join(" "))
+                            .unwrap_or_default(),
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                };
+                vec![Line::from(format!("> {input}█")), hint]
+            }
+            None if self.promotion.is_some() => vec![Line::from(format!(
+                "Promote to the highlighted piece with {} or a click, or press {} to cancel.",
+                self.keymap.describe(scope, Action::Select),
+                self.keymap.describe(Scope::Global, Action::Back)
+            ))],
+            None if scope == Scope::Game && game.is_game_over() => vec![Line::from(format!(
+                "Press {} to save the annotated game as PGN, e.g. game.pgn.",
+                self.keymap.describe(scope, Action::Command)
+            ))],
+            None if scope == Scope::Puzzle => vec![Line::from(format!(
+                "Press {} to type a move, or {} to filter puzzles, e.g. fork rating:1200-1800.",
+                self.keymap.describe(scope, Action::Command),
+                self.keymap.describe(scope, Action::Filter)
+            ))],
+            None if scope == Scope::Endgame => vec![Line::from(format!(
+                "Press {} to type a move, or an ending such as KRvK, Lucena or Philidor.",
+                self.keymap.describe(scope, Action::Command)
+            ))],
+            None if scope == Scope::Editor => vec![Line::from(format!(
+                "Press {} to type a FEN to set up.",
+                self.keymap.describe(scope, Action::Command)
+            ))],
+            None if scope == Scope::Analysis => vec![Line::from(format!(
+                "Press {} to type a move, or a FEN to set up a position.",
+                self.keymap.describe(scope, Action::Command)
+            ))],
+            None => vec![Line::from(format!(
+                "Press {} to type a move, e.g. Nf3, exd5 or g1f3.",
+                self.keymap.describe(scope, Action::Command)
+            ))],
+        };
+
+        let paragraph = Paragraph::new(text)
+            .block(block)
+            .fg(Color::White)
+            .bg(Color::Black);
+
+        paragraph.render(area, buf);
+    }
+
+    fn render_game_info(&self, area: Rect, buf: &mut Buffer, game: &Game) {
+        let block = Block::bordered()
+            .title(game.variant().name())
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded);
+
+        let status = match game.outcome() {
+            Some(outcome) => format!("{} ({})", outcome.result(), outcome.reason),
+            None if game.is_in_check() => {
+                format!("{:?} to move, in check", game.get_current_turn())
+            }
+            None => format!("{:?} to move", game.get_current_turn()),
+        };
+        let mut text = vec![Line::from(status)];
+        if let Some(color) = self.engine_color {
+            let thinking = if self.engine_thinking {
+                ", thinking…"
+            } else {
+                ""
+            };
+            text.push(Line::from(format!("Engine plays {color:?}{thinking}")));
+        }
+        if let Some(opening) = self.notes.as_ref().and_then(|notes| notes.opening) {
+            text.push(Line::from(Span::styled(
+                opening.to_string(),
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+        if let Some(book) = self.notes.as_ref().and_then(|notes| notes.book.clone()) {
+            text.push(Line::from(book));
+        }
+        if let Some(probe) = self.notes.as_ref().and_then(|notes| notes.probe) {
+            text.push(Line::from(Span::styled(
+                probe.to_string(),
+                Style::default().fg(Color::Green),
+            )));
+        }
+        text.push(Line::from(""));
+        if let Some(clock) = &self.clock {
+            for color in [pieces::Color::White, pieces::Color::Black] {
+                let time = format_duration(clock.remaining(color));
+                let line = format!("{color:?}: {time}");
+                if color == game.get_current_turn() && !game.is_game_over() {
+                    text.push(Line::from(Span::styled(
+                        line,
+                        Style::default().fg(Color::Yellow),
+                    )));
+                } else {
+                    text.push(Line::from(line));
+                }
+            }
+            text.push(Line::from(""));
+        }
+        if game.variant() == VariantKind::ThreeCheck {
+            text.push(Line::from(format!(
+                "Checks left: White {} / Black {}",
+                game.remaining_checks(pieces::Color::White),
+                game.remaining_checks(pieces::Color::Black)
+            )));
+        }
+        text.extend(self.report_lines());
+
+        let paragraph = Paragraph::new(text)
+            .block(block)
+            .fg(Color::White)
+            .bg(Color::Black)
+            .wrap(Wrap { trim: true });
+
+        paragraph.render(area, buf);
+    }
+
+    fn render_replay(&mut self, area: Rect, buf: &mut Buffer) {
+        let Some(replay) = &self.replay else {
+            return;
+        };
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),
+                Constraint::Length(EVAL_GRAPH_HEIGHT),
+                Constraint::Length(4),
+            ])
             .split(area);
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(2, 3), Constraint::Ratio(1, 3)])
+            .split(rows[0]);
 
-        self.game = Some(Game::new());
+        let game = replay.game();
+        let (bar_area, board_area) = split_eval_bar(chunks[0]);
+        let layout = BoardLayout::new(
+            board_area,
+            game.get_board().dimensions(),
+            self.board_flipped,
+        );
+        self.render_eval_bar(bar_area, buf, game);
+        self.render_chess_board(&layout, buf, game);
+        self.render_replay_info(chunks[1], buf, replay);
 
-        self.render_chess_board(chunks[0], buf, self.game.as_ref().unwrap());
+        let block = Block::bordered()
+            .title("Evaluation")
+            .border_type(BorderType::Rounded);
+        let graph_area = block.inner(rows[1]);
+        block.render(rows[1], buf);
+        let scores: Vec<Option<Score>> = self
+            .replay_positions
+            .iter()
+            .map(|fen| self.evaluations.get(fen).copied())
+            .collect();
+        EvalGraph {
+            scores: &scores,
+            current: (replay.depth() == 0).then(|| replay.ply()),
+        }
+        .render(graph_area, buf);
+        self.graph_area = Some(graph_area);
+
+        let keys = |action| self.keymap.describe(Scope::Replay, action);
+        let help = vec![
+            Line::from(format!(
+                "{}/{} step, {}/{} start/end, {}/{} enter/leave variation",
+                keys(Action::Left),
+                keys(Action::Right),
+                keys(Action::First),
+                keys(Action::Last),
+                keys(Action::Down),
+                keys(Action::Up),
+            )),
+            Line::from(format!(
+                "{} autoplay, {}/{} faster/slower, {} flip, click the graph to jump",
+                keys(Action::Autoplay),
+                keys(Action::Increment),
+                keys(Action::Decrement),
+                keys(Action::Flip),
+            )),
+        ];
+        Paragraph::new(help)
+            .block(
+                Block::bordered()
+                    .title("Keys")
+                    .border_type(BorderType::Rounded),
+            )
+            .fg(Color::White)
+            .bg(Color::Black)
+            .render(rows[2], buf);
     }
 
-    fn render_chess_board(&self, area: Rect, buf: &mut Buffer, game: &Game) {
+    /// Players, the current move with its annotations, and where the game can go next.
+    fn render_replay_info(&self, area: Rect, buf: &mut Buffer, replay: &Replay) {
+        let pgn = replay.pgn();
+        let block = Block::bordered()
+            .title(replay.game().variant().name())
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded);
+
+        let tag = |name| pgn.tag(name).unwrap_or("?");
+        let mut text = vec![
+            Line::from(format!("{} - {}", tag("White"), tag("Black"))),
+            Line::from(format!("{} {}", tag("Event"), tag("Date"))),
+            Line::from(format!("Result: {}", pgn.result)),
+            Line::from(""),
+        ];
+        let heading = replay
+            .current_move_label()
+            .unwrap_or_else(|| "Starting position".to_string());
+        text.push(Line::from(Span::styled(
+            heading,
+            Style::default().fg(Color::Yellow),
+        )));
+        let comments = match replay.current_move() {
+            Some(pgn_move) => &pgn_move.comments,
+            None => &pgn.comments,
+        };
+        text.extend(comments.iter().map(|comment| {
+            Line::from(Span::styled(
+                comment.clone(),
+                Style::default().fg(Color::Cyan),
+            ))
+        }));
+        text.push(Line::from(""));
+
+        match replay.next_move() {
+            Some(next) => {
+                text.push(Line::from(format!("Next: {}", next.san)));
+                if !next.variations.is_empty() {
+                    let alternatives: Vec<&str> = next
+                        .variations
+                        .iter()
+                        .filter_map(|line| line.first().map(|m| m.san.as_str()))
+                        .collect();
+                    text.push(Line::from(format!("Instead: {}", alternatives.join(", "))));
+                }
+            }
+            None => text.push(Line::from("End of line")),
+        }
+        if replay.depth() > 0 {
+            text.push(Line::from(format!(
+                "In a variation, {} deep",
+                replay.depth()
+            )));
+        }
+        if let Some(probe) = self.notes.as_ref().and_then(|notes| notes.probe) {
+            text.push(Line::from(Span::styled(
+                probe.to_string(),
+                Style::default().fg(Color::Green),
+            )));
+        }
+        if let Some(error) = replay.error() {
+            text.push(Line::from(Span::styled(
+                error.to_string(),
+                Style::default().fg(Color::Red),
+            )));
+        }
+        text.push(Line::from(""));
+        let delay = self.config.autoplay_delay_ms as f64 / 1000.0;
+        let autoplay = if replay.is_autoplaying() { "on" } else { "off" };
+        text.push(Line::from(format!(
+            "Autoplay {autoplay}, {delay:.1}s per move"
+        )));
+
+        Paragraph::new(text)
+            .block(block)
+            .fg(Color::White)
+            .bg(Color::Black)
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+    }
+
+    fn render_analysis(&mut self, area: Rect, buf: &mut Buffer) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),
+                Constraint::Length(4),
+                Constraint::Length(4),
+            ])
+            .split(area);
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(2, 3), Constraint::Ratio(1, 3)])
+            .split(rows[0]);
+
+        let Some(analysis) = &self.analysis else {
+            return;
+        };
+        let game = analysis.game();
+        let layout = BoardLayout::new(chunks[0], game.get_board().dimensions(), self.board_flipped);
+        self.render_chess_board(&layout, buf, game);
+        self.render_analysis_info(chunks[1], buf, analysis);
+        self.render_command_bar(rows[1], buf, game, Scope::Analysis);
+
+        let keys = |action| self.keymap.describe(Scope::Analysis, action);
+        let help = vec![
+            Line::from(format!(
+                "{}/{} back/forward, {}/{} start/end, {} next variation, {} flip",
+                keys(Action::Previous),
+                keys(Action::Next),
+                keys(Action::First),
+                keys(Action::Last),
+                keys(Action::Variation),
+                keys(Action::Flip),
+            )),
+            Line::from(format!(
+                "{} engine on/off, {}/{} more/fewer lines",
+                keys(Action::Engine),
+                keys(Action::Increment),
+                keys(Action::Decrement),
+            )),
+        ];
+        Paragraph::new(help)
+            .block(
+                Block::bordered()
+                    .title("Keys")
+                    .border_type(BorderType::Rounded),
+            )
+            .fg(Color::White)
+            .bg(Color::Black)
+            .render(rows[2], buf);
+        self.board_layout = Some(layout);
+    }
+
+    /// The moves played, the ones tried from here, and the engine's lines with scores from
+    /// White's side.
+    fn render_analysis_info(&self, area: Rect, buf: &mut Buffer, analysis: &Analysis) {
+        let game = analysis.game();
+        let block = Block::bordered()
+            .title("Analysis")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded);
+
+        let status = match game.outcome() {
+            Some(outcome) => format!("{} ({})", outcome.result(), outcome.reason),
+            None if game.is_in_check() => {
+                format!("{:?} to move, in check", game.get_current_turn())
+            }
+            None => format!("{:?} to move", game.get_current_turn()),
+        };
+        let mut text = vec![Line::from(status)];
+        if let Some(opening) = self.notes.as_ref().and_then(|notes| notes.opening) {
+            text.push(Line::from(Span::styled(
+                opening.to_string(),
+                Style::default().fg(Color::Cyan),
+            )));
+        }
+        if let Some(probe) = self.notes.as_ref().and_then(|notes| notes.probe) {
+            text.push(Line::from(Span::styled(
+                probe.to_string(),
+                Style::default().fg(Color::Green),
+            )));
+        }
+        text.push(Line::from(""));
+
+        let played = notation::numbered(analysis.start(), &analysis.moves_played());
+        text.push(Line::from(if played.is_empty() {
+            "Starting position".to_string()
+        } else {
+            played
+        }));
+        let next: Vec<String> = analysis
+            .next_moves()
+            .iter()
+            .map(|mv| notation::san(game, mv))
+            .collect();
+        if let Some((first, others)) = next.split_first() {
+            text.push(Line::from(format!("Next: {first}")));
+            if !others.is_empty() {
+                text.push(Line::from(format!("Instead: {}", others.join(", "))));
+            }
+        }
+        text.push(Line::from(""));
+
+        let engine = self
+            .analyser
+            .as_ref()
+            .map_or("Engine", |analyser| analyser.name());
+        if !analysis.engine_on {
+            text.push(Line::from(format!("{engine}: off")));
+        } else if let Some(depth) = analysis.lines.first().map(|line| line.depth) {
+            text.push(Line::from(format!("{engine}: depth {depth}")));
+        } else if !game.is_game_over() {
+            text.push(Line::from(format!("{engine}: thinking…")));
+        }
+        for line in &analysis.lines {
+            let score = line.score.white_view(game.get_current_turn());
+            text.push(Line::from(vec![
+                Span::styled(format!("{score} "), Style::default().fg(Color::Yellow)),
+                Span::raw(notation::numbered(game, &line.pv)),
+            ]));
+        }
+        if let Some(message) = &analysis.message {
+            text.push(Line::from(Span::styled(
+                message.clone(),
+                Style::default().fg(Color::Red),
+            )));
+        }
+
+        Paragraph::new(text)
+            .block(block)
+            .fg(Color::White)
+            .bg(Color::Black)
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+    }
+
+    fn render_editor(&mut self, area: Rect, buf: &mut Buffer) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),
+                Constraint::Length(4),
+                Constraint::Length(5),
+            ])
+            .split(area);
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(2, 3), Constraint::Ratio(1, 3)])
+            .split(rows[0]);
+
+        let Some(editor) = &self.editor else {
+            return;
+        };
+        let game = editor.game();
+        let layout = BoardLayout::new(chunks[0], game.get_board().dimensions(), self.board_flipped);
+        self.render_chess_board(&layout, buf, game);
+        self.render_editor_info(chunks[1], buf, editor);
+        self.render_command_bar(rows[1], buf, game, Scope::Editor);
+
+        let keys = |action| self.keymap.describe(Scope::Editor, action);
+        let help = vec![
+            Line::from(format!(
+                "{} place, {} remove, {}/{} choose piece, {} clear board, {} flip",
+                keys(Action::Select),
+                keys(Action::Remove),
+                keys(Action::Previous),
+                keys(Action::Next),
+                keys(Action::Clear),
+                keys(Action::Flip),
+            )),
+            Line::from(format!(
+                "{} side to move, {} castling of the king or rook, {} en passant square",
+                keys(Action::SideToMove),
+                keys(Action::Castling),
+                keys(Action::EnPassant),
+            )),
+            Line::from(format!(
+                "{}/{} variant, {} play, {} analyse",
+                keys(Action::Increment),
+                keys(Action::Decrement),
+                keys(Action::Play),
+                keys(Action::Analyse),
+            )),
+        ];
+        Paragraph::new(help)
+            .block(
+                Block::bordered()
+                    .title("Keys")
+                    .border_type(BorderType::Rounded),
+            )
+            .fg(Color::White)
+            .bg(Color::Black)
+            .render(rows[2], buf);
+        self.board_layout = Some(layout);
+    }
+
+    /// The palette with the selected piece highlighted, the side to move, castling rights and
+    /// en passant square, and whether the position can be played.
+    fn render_editor_info(&self, area: Rect, buf: &mut Buffer, editor: &Editor) {
+        let block = Block::bordered()
+            .title("Board editor")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded);
+
+        let board = editor.board();
+        let mut text = vec![Line::from(editor.variant().name()), Line::from("")];
+        for color in [pieces::Color::White, pieces::Color::Black] {
+            let mut spans = Vec::new();
+            for (index, piece) in editor.palette().iter().enumerate() {
+                if piece.color != color {
+                    continue;
+                }
+                let style = if index == editor.selected {
+                    Style::default().fg(Color::Black).bg(Color::Yellow)
+                } else {
+                    Style::default()
+                };
+                spans.push(Span::styled(piece.unicode_symbol().to_string(), style));
+                spans.push(Span::raw(" "));
+            }
+            text.push(Line::from(spans));
+        }
+        let piece = editor.piece();
+        text.push(Line::from(format!(
+            "Placing: {:?} {:?}",
+            piece.color, piece.piece_type
+        )));
+        text.push(Line::from(""));
+
+        text.push(Line::from(format!("{:?} to move", editor.turn)));
+        text.push(Line::from(format!(
+            "Castling: {}",
+            editor.castling.to_fen()
+        )));
+        text.push(Line::from(format!(
+            "En passant: {}",
+            editor
+                .en_passant
+                .map_or("-".to_string(), |square| board.square_name(square))
+        )));
+        text.push(Line::from(""));
+
+        let status = match (&self.editor_message, editor.validate()) {
+            (Some(message), _) => Span::styled(message.clone(), Style::default().fg(Color::Red)),
+            (None, Ok(_)) => Span::styled(
+                format!(
+                    "Ready: {} to play, {} to analyse",
+                    self.keymap.describe(Scope::Editor, Action::Play),
+                    self.keymap.describe(Scope::Editor, Action::Analyse),
+                ),
+                Style::default().fg(Color::Green),
+            ),
+            (None, Err(err)) => Span::styled(err.to_string(), Style::default().fg(Color::Red)),
+        };
+        text.push(Line::from(status));
+        text.push(Line::from(Span::styled(
+            editor.fen(),
+            Style::default().fg(Color::DarkGray),
+        )));
+
+        Paragraph::new(text)
+            .block(block)
+            .fg(Color::White)
+            .bg(Color::Black)
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+    }
+
+    fn render_puzzles(&mut self, area: Rect, buf: &mut Buffer) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),
+                Constraint::Length(4),
+                Constraint::Length(3),
+            ])
+            .split(area);
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(2, 3), Constraint::Ratio(1, 3)])
+            .split(rows[0]);
+
+        let Some(trainer) = &self.trainer else {
+            let message = self.puzzle_message.clone().unwrap_or_default();
+            Paragraph::new(Line::from(Span::styled(
+                message,
+                Style::default().fg(Color::Red),
+            )))
+            .block(
+                Block::bordered()
+                    .title("Puzzles")
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            )
+            .fg(Color::White)
+            .bg(Color::Black)
+            .alignment(Alignment::Center)
+            .render(area, buf);
+            return;
+        };
+        self.render_puzzle_info(chunks[1], buf, trainer);
+        let keys = |action| self.keymap.describe(Scope::Puzzle, action);
+        match (&trainer.filter_input, &trainer.attempt) {
+            (Some(input), _) => Paragraph::new(vec![
+                Line::from(format!("> {input}█")),
+                Line::from(Span::styled(
+                    "Themes such as fork or mateIn2, and rating:1200-1800",
+                    Style::default().fg(Color::DarkGray),
+                )),
+            ])
+            .block(
+                Block::bordered()
+                    .title("Filter")
+                    .border_type(BorderType::Rounded),
+            )
+            .fg(Color::White)
+            .bg(Color::Black)
+            .render(rows[1], buf),
+            (None, Some(attempt)) => {
+                self.render_command_bar(rows[1], buf, attempt.game(), Scope::Puzzle)
+            }
+            (None, None) => {}
+        }
+        Paragraph::new(Line::from(format!(
+            "{} hint, {} next puzzle, {} flip",
+            keys(Action::Hint),
+            keys(Action::Next),
+            keys(Action::Flip),
+        )))
+        .block(
+            Block::bordered()
+                .title("Keys")
+                .border_type(BorderType::Rounded),
+        )
+        .fg(Color::White)
+        .bg(Color::Black)
+        .render(rows[2], buf);
+
+        let Some(attempt) = &trainer.attempt else {
+            return;
+        };
+        let game = attempt.game();
+        let layout = BoardLayout::new(chunks[0], game.get_board().dimensions(), self.board_flipped);
+        self.render_chess_board(&layout, buf, game);
+        self.board_layout = Some(layout);
+    }
+
+    /// The puzzle's rating and themes, how the attempt is going, and the player's rating.
+    fn render_puzzle_info(&self, area: Rect, buf: &mut Buffer, trainer: &Trainer) {
+        let block = Block::bordered()
+            .title("Puzzle")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded);
+
+        let mut text = Vec::new();
+        if let Some(attempt) = &trainer.attempt {
+            let puzzle = &attempt.puzzle;
+            let game = attempt.game();
+            let solution = attempt
+                .expected()
+                .map(|mv| notation::san(game, &mv))
+                .unwrap_or_default();
+            text.push(Line::from(format!(
+                "Puzzle {}, rated {}",
+                puzzle.id, puzzle.rating
+            )));
+            text.push(Line::from(Span::styled(
+                puzzle.themes.join(", "),
+                Style::default().fg(Color::Cyan),
+            )));
+            text.push(Line::from(""));
+            text.push(match attempt.status {
+                Status::Solving if attempt.hint == 1 => {
+                    Line::from("Hint: the highlighted piece moves")
+                }
+                Status::Solving => {
+                    Line::from(format!("Find the best move for {:?}", attempt.player()))
+                }
+                Status::Solved => {
+                    Line::from(Span::styled("Solved!", Style::default().fg(Color::Green)))
+                }
+                Status::Failed => Line::from(Span::styled(
+                    format!("Not this time: the move was {solution}"),
+                    Style::default().fg(Color::Red),
+                )),
+            });
+        }
+        text.push(Line::from(""));
+        let progress = &trainer.progress;
+        text.push(Line::from(format!("Your rating: {}", progress.rating)));
+        text.push(Line::from(format!(
+            "Solved {}, failed {}",
+            progress.solved.len(),
+            progress.failed.len()
+        )));
+        let query = if trainer.query.is_empty() {
+            "all puzzles"
+        } else {
+            &trainer.query
+        };
+        text.push(Line::from(format!(
+            "Filter: {query} ({} puzzles)",
+            trainer.puzzles.len()
+        )));
+        if let Some(message) = &trainer.message {
+            text.push(Line::from(Span::styled(
+                message.clone(),
+                Style::default().fg(Color::Red),
+            )));
+        }
+
+        Paragraph::new(text)
+            .block(block)
+            .fg(Color::White)
+            .bg(Color::Black)
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+    }
+
+    fn render_repertoire(&mut self, area: Rect, buf: &mut Buffer) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),
+                Constraint::Length(4),
+                Constraint::Length(3),
+            ])
+            .split(area);
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(2, 3), Constraint::Ratio(1, 3)])
+            .split(rows[0]);
+
+        let Some(session) = &self.session else {
+            let message = self.repertoire_message.clone().unwrap_or_default();
+            Paragraph::new(Line::from(Span::styled(
+                message,
+                Style::default().fg(Color::Red),
+            )))
+            .block(
+                Block::bordered()
+                    .title("Repertoire")
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            )
+            .fg(Color::White)
+            .bg(Color::Black)
+            .alignment(Alignment::Center)
+            .render(area, buf);
+            return;
+        };
+        self.render_repertoire_info(chunks[1], buf, session);
+        let game = session.drill.game();
+        self.render_command_bar(rows[1], buf, game, Scope::Repertoire);
+        let keys = |action| self.keymap.describe(Scope::Repertoire, action);
+        Paragraph::new(Line::from(format!(
+            "{} hint, {} next line, {} flip",
+            keys(Action::Hint),
+            keys(Action::Next),
+            keys(Action::Flip),
+        )))
+        .block(
+            Block::bordered()
+                .title("Keys")
+                .border_type(BorderType::Rounded),
+        )
+        .fg(Color::White)
+        .bg(Color::Black)
+        .render(rows[2], buf);
+
+        let layout = BoardLayout::new(chunks[0], game.get_board().dimensions(), self.board_flipped);
+        self.render_chess_board(&layout, buf, game);
+        self.board_layout = Some(layout);
+    }
+
+    /// The line being drilled, how the user is doing on it, and when it is due again.
+    fn render_repertoire_info(&self, area: Rect, buf: &mut Buffer, session: &Session) {
+        let block = Block::bordered()
+            .title("Repertoire")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded);
+
+        let drill = &session.drill;
+        let line = session.line();
+        let color = session.repertoire.color;
+        let total = session.repertoire.lines.len();
+        let played = drill.played();
+        let expected = drill
+            .expected()
+            .map(|mv| notation::san(drill.game(), &mv))
+            .unwrap_or_default();
+        let mut text = vec![
+            Line::from(format!(
+                "{}, line {} of {total}",
+                line.chapter,
+                drill.line + 1
+            )),
+            Line::from(format!(
+                "Playing {color:?}, {} of {total} lines due",
+                session.due()
+            )),
+            Line::from(""),
+            Line::from(Span::styled(
+                if played.is_empty() {
+                    "Starting position".to_string()
+                } else {
+                    notation::numbered(&line.start, played)
+                },
+                Style::default().fg(Color::Cyan),
+            )),
+            Line::from(""),
+        ];
+        text.push(match drill.verdict {
+            _ if drill.finished => Line::from(Span::styled(
+                "Line complete",
+                Style::default().fg(Color::Green),
+            )),
+            Some(Verdict::Wrong) if drill.revealed => Line::from(Span::styled(
+                format!("Not your repertoire move: play {expected}"),
+                Style::default().fg(Color::Red),
+            )),
+            Some(Verdict::Wrong) => Line::from(Span::styled(
+                "Not your repertoire move, try again",
+                Style::default().fg(Color::Red),
+            )),
+            _ if drill.revealed => Line::from(format!("Hint: the repertoire move is {expected}")),
+            _ => Line::from(format!("Your move as {color:?}")),
+        });
+        text.push(Line::from(""));
+        let stats = session.line_stats();
+        if stats.reviews == 0 {
+            text.push(Line::from("A new line"));
+        } else {
+            text.push(Line::from(format!(
+                "Reviews: {}, lapses: {}",
+                stats.reviews, stats.lapses
+            )));
+            text.push(Line::from(match stats.due.saturating_sub(session.today) {
+                0 => "Due again today".to_string(),
+                1 => "Due again tomorrow".to_string(),
+                days => format!("Due again in {days} days"),
+            }));
+        }
+        if let Some(message) = &session.message {
+            text.push(Line::from(Span::styled(
+                message.clone(),
+                Style::default().fg(Color::Red),
+            )));
+        }
+
+        Paragraph::new(text)
+            .block(block)
+            .fg(Color::White)
+            .bg(Color::Black)
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+    }
+
+    fn render_endgame(&mut self, area: Rect, buf: &mut Buffer) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),
+                Constraint::Length(4),
+                Constraint::Length(3),
+            ])
+            .split(area);
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(2, 3), Constraint::Ratio(1, 3)])
+            .split(rows[0]);
+
+        let (Some(drill), Some(game)) = (&self.endgame, &self.game) else {
+            let message = self.endgame_message.clone().unwrap_or_default();
+            Paragraph::new(Line::from(Span::styled(
+                message,
+                Style::default().fg(Color::Red),
+            )))
+            .block(
+                Block::bordered()
+                    .title("Endgames")
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            )
+            .fg(Color::White)
+            .bg(Color::Black)
+            .alignment(Alignment::Center)
+            .render(area, buf);
+            return;
+        };
+        self.render_endgame_info(chunks[1], buf, drill, game);
+        self.render_command_bar(rows[1], buf, game, Scope::Endgame);
+        let keys = |action| self.keymap.describe(Scope::Endgame, action);
+        Paragraph::new(Line::from(format!(
+            "{} new position, {} retry, {}/{} change ending, {} flip",
+            keys(Action::Next),
+            keys(Action::Retry),
+            keys(Action::Decrement),
+            keys(Action::Increment),
+            keys(Action::Flip),
+        )))
+        .block(
+            Block::bordered()
+                .title("Keys")
+                .border_type(BorderType::Rounded),
+        )
+        .fg(Color::White)
+        .bg(Color::Black)
+        .render(rows[2], buf);
+
+        let layout = BoardLayout::new(chunks[0], game.get_board().dimensions(), self.board_flipped);
+        self.render_chess_board(&layout, buf, game);
+        self.board_layout = Some(layout);
+    }
+
+    /// The ending and its goal, the tablebase's verdict on the position, and how the drill
+    /// went once it is settled.
+    fn render_endgame_info(&self, area: Rect, buf: &mut Buffer, drill: &Drill, game: &Game) {
+        let block = Block::bordered()
+            .title("Endgame")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded);
+
+        let goal = match drill.endgame.goal() {
+            Goal::Win => "Win: give checkmate".to_string(),
+            Goal::Hold => format!("Hold the draw for {HOLD_MOVES} moves"),
+        };
+        let turn = if self.engine_thinking {
+            "Engine thinking…".to_string()
+        } else {
+            format!("{:?} to move", game.get_current_turn())
+        };
+        let mut text = vec![
+            Line::from(format!("{} as {:?}", drill.endgame, drill.player)),
+            Line::from(goal),
+            Line::from(""),
+            Line::from(turn),
+            Line::from(format!("Your moves: {}", drill.moves(game))),
+        ];
+        if let Some(probe) = self.notes.as_ref().and_then(|notes| notes.probe) {
+            text.push(Line::from(Span::styled(
+                probe.to_string(),
+                Style::default().fg(Color::Green),
+            )));
+        }
+        text.push(Line::from(""));
+        match &drill.verdict {
+            Some(DrillVerdict::Achieved(reason)) => {
+                text.push(Line::from(Span::styled(
+                    reason.clone(),
+                    Style::default().fg(Color::Green),
+                )));
+                if let Some(comparison) = drill.comparison() {
+                    text.push(Line::from(comparison));
+                }
+            }
+            Some(DrillVerdict::Missed(reason)) => text.push(Line::from(Span::styled(
+                reason.clone(),
+                Style::default().fg(Color::Red),
+            ))),
+            None => {}
+        }
+        if let Some(message) = &self.endgame_message {
+            text.push(Line::from(Span::styled(
+                message.clone(),
+                Style::default().fg(Color::Red),
+            )));
+        }
+
+        Paragraph::new(text)
+            .block(block)
+            .fg(Color::White)
+            .bg(Color::Black)
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+    }
+
+    fn render_database(&mut self, area: Rect, buf: &mut Buffer) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(4)])
+            .split(area);
+        // Borders and the header row take three lines
+        let page = rows[0].height.saturating_sub(3).max(1) as usize;
+        if let Some(browser) = &mut self.database {
+            browser.page = page;
+        }
+        let Some(browser) = &self.database else {
+            return;
+        };
+        self.render_game_table(rows[0], buf, browser);
+
+        let keys = |action| self.keymap.describe(Scope::Database, action);
+        let mut text = match &browser.filter_input {
+            Some(input) => vec![Line::from(format!("> {input}█"))],
+            None if browser.query.is_empty() => vec![Line::from(format!(
+                "Press {} to filter, e.g. player:carlsen result:1-0 eco:B9",
+                keys(Action::Command)
+            ))],
+            None => vec![Line::from(format!("Filter: {}", browser.query))],
+        };
+        text.push(match &browser.error {
+            Some(error) => Line::from(Span::styled(error.clone(), Style::default().fg(Color::Red))),
+            None => Line::from(Span::styled(
+                format!(
+                    "{} open, {}/{} sort column, {} reverse, {} position search",
+                    keys(Action::Select),
+                    keys(Action::Left),
+                    keys(Action::Right),
+                    keys(Action::Reverse),
+                    keys(Action::Search),
+                ),
+                Style::default().fg(Color::DarkGray),
+            )),
+        });
+        Paragraph::new(text)
+            .block(
+                Block::bordered()
+                    .title("Filter")
+                    .border_type(BorderType::Rounded),
+            )
+            .fg(Color::White)
+            .bg(Color::Black)
+            .render(rows[1], buf);
+    }
+
+    /// The page of games holding the highlighted one, with the sort column marked.
+    fn render_game_table(&self, area: Rect, buf: &mut Buffer, browser: &Browser) {
+        let database = &browser.database;
+        let name = database.path().file_name().map_or_else(
+            || database.path().display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let mut title = format!(
+            "{name}: {} of {} games",
+            browser.len(),
+            database.games().len()
+        );
+        if !database.errors().is_empty() {
+            title.push_str(&format!(", {} unreadable", database.errors().len()));
+        }
+
+        let header = Row::new(Column::iter().map(|column| {
+            if column == browser.column {
+                let arrow = if browser.descending { "▼" } else { "▲" };
+                Span::styled(
+                    format!("{}{arrow}", column.name()),
+                    Style::default().fg(Color::Yellow),
+                )
+            } else {
+                Span::raw(column.name())
+            }
+        }))
+        .bold();
+        let page = browser.page.max(1);
+        let first = browser.selected / page * page;
+        let rows = browser
+            .rows()
+            .enumerate()
+            .skip(first)
+            .take(page)
+            .map(|(index, game)| {
+                let row = Row::new(Column::iter().map(|column| game.field(column)));
+                if index == browser.selected {
+                    row.style(Style::default().fg(Color::Black).bg(Color::Yellow))
+                } else {
+                    row
+                }
+            });
+        let widths = [
+            Constraint::Fill(3),
+            Constraint::Fill(3),
+            Constraint::Fill(3),
+            Constraint::Length(11),
+            Constraint::Length(8),
+            Constraint::Length(5),
+            Constraint::Length(6),
+        ];
+        Widget::render(
+            Table::new(rows, widths)
+                .header(header)
+                .block(
+                    Block::bordered()
+                        .title(title)
+                        .border_type(BorderType::Rounded),
+                )
+                .fg(Color::White)
+                .bg(Color::Black),
+            area,
+            buf,
+        );
+    }
+
+    fn render_search(&mut self, area: Rect, buf: &mut Buffer) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(4), Constraint::Min(0)])
+            .split(area);
+        let page = rows[1].height.saturating_sub(3).max(1) as usize;
+        if let Some(search) = &mut self.position_search {
+            search.page = page;
+        }
+        let (Some(search), Some(browser)) = (&self.position_search, &self.database) else {
+            return;
+        };
+
+        let keys = |action| self.keymap.describe(Scope::Search, action);
+        let mut text = match (&search.input, &search.query) {
+            (Some(input), _) => vec![Line::from(format!("> {input}█"))],
+            (None, Some(query)) => vec![Line::from(format!("Query: {query}"))],
+            (None, None) => vec![Line::from("")],
+        };
+        text.push(match &search.error {
+            Some(error) => Line::from(Span::styled(error.clone(), Style::default().fg(Color::Red))),
+            None if search.input.is_some() => Line::from(Span::styled(
+                "A FEN, a Polyglot key such as 0x463b96181691fc9c, or material such as KRvKB",
+                Style::default().fg(Color::DarkGray),
+            )),
+            None => Line::from(Span::styled(
+                format!(
+                    "{} open at the match, {} new search",
+                    keys(Action::Select),
+                    keys(Action::Command),
+                ),
+                Style::default().fg(Color::DarkGray),
+            )),
+        });
+        Paragraph::new(text)
+            .block(
+                Block::bordered()
+                    .title("Position search")
+                    .border_type(BorderType::Rounded),
+            )
+            .fg(Color::White)
+            .bg(Color::Black)
+            .render(rows[0], buf);
+
+        let games = browser.database.games();
+        let title = match &search.query {
+            Some(_) => format!("{} of {} games match", search.hits.len(), games.len()),
+            None => format!("{} games", games.len()),
+        };
+        let header = Row::new(["#", "White", "Black", "Event", "Date", "Result", "Reached"]).bold();
+        let first = search.selected / page * page;
+        let results =
+            search
+                .hits
+                .iter()
+                .enumerate()
+                .skip(first)
+                .take(page)
+                .map(|(index, (game, found))| {
+                    let game = &games[*game];
+                    let reached = found.label.clone().unwrap_or_else(|| "start".to_string());
+                    let row = Row::new([
+                        game.number.to_string(),
+                        game.white.clone(),
+                        game.black.clone(),
+                        game.event.clone(),
+                        game.date.clone(),
+                        game.result.clone(),
+                        reached,
+                    ]);
+                    if index == search.selected {
+                        row.style(Style::default().fg(Color::Black).bg(Color::Yellow))
+                    } else {
+                        row
+                    }
+                });
+        let widths = [
+            Constraint::Length(6),
+            Constraint::Fill(3),
+            Constraint::Fill(3),
+            Constraint::Fill(2),
+            Constraint::Length(11),
+            Constraint::Length(8),
+            Constraint::Length(14),
+        ];
+        Widget::render(
+            Table::new(results, widths)
+                .header(header)
+                .block(
+                    Block::bordered()
+                        .title(title)
+                        .border_type(BorderType::Rounded),
+                )
+                .fg(Color::White)
+                .bg(Color::Black),
+            rows[1],
+            buf,
+        );
+    }
+
+    fn render_chess_board(&self, layout: &BoardLayout, buf: &mut Buffer, game: &Game) {
         let block = Block::default()
             .borders(Borders::ALL)
-            .style(Style::default().fg(Color::Yellow));
+            .border_style(Style::default().fg(Color::Yellow));
+
+        let area = layout.area;
+        let (rows, cols) = (layout.rows, layout.cols);
+        let (cell_width, cell_height) = (layout.cell_width, layout.cell_height);
 
-        let board_dimensions = game.get_board().dimensions();
-        // 8x8 chess board
-        let cell_width = area.width / board_dimensions.0 as u16;
-        let cell_height = area.height / board_dimensions.1 as u16;
+        let theme = &self.theme;
+        let interactive = matches!(
+            self.state.last(),
+            Some(
+                AppState::Game
+                    | AppState::Analysis
+                    | AppState::Editor
+                    | AppState::Puzzle
+                    | AppState::Repertoire
+                    | AppState::Endgame
+            )
+        );
+        let targets = self
+            .selected_square
+            .map(|square| game.legal_moves_from(square))
+            .unwrap_or_default();
+        let last_move = game.get_move_history().last();
+        let checked_king = if game.is_in_check() {
+            game.get_board().king_square(game.get_current_turn())
+        } else {
+            None
+        };
 
         // Render the board squares
-        for row in 0..board_dimensions.1 {
-            for col in 0..board_dimensions.0 {
+        for row in 0..rows {
+            for col in 0..cols {
+                let square = (row, col);
+                let target = targets.iter().find(|mv| mv.to == square);
                 let is_light = (row + col) % 2 == 0;
-                let color = if is_light {
-                    Color::Rgb(210, 180, 140) // Light square
+                // Pieces on offer for a promotion cover the squares they are shown on
+                let choice = self
+                    .promotion
+                    .as_ref()
+                    .and_then(|choice| Some((choice, choice.index_at(square)?)));
+                let color = if let Some((choice, index)) = choice {
+                    if index == choice.index {
+                        theme.selected
+                    } else {
+                        theme.cursor
+                    }
+                } else if self.selected_square == Some(square) {
+                    theme.selected
+                } else if interactive && self.cursor == square {
+                    theme.cursor
+                } else if checked_king == Some(square) {
+                    theme.check
+                } else if target.is_some_and(|mv| mv.is_capture()) {
+                    theme.legal_capture
+                } else if last_move.is_some_and(|mv| mv.from == square || mv.to == square) {
+                    theme.last_move
+                } else if is_light {
+                    theme.light_square
                 } else {
-                    Color::Rgb(139, 69, 19) // Dark square
+                    theme.dark_square
                 };
 
-                let x = area.x + col as u16 * cell_width;
-                let y = area.y + row as u16 * cell_height;
-                let cell_area = Rect::new(x, y, cell_width, cell_height);
+                let cell_area = layout.cell_rect(square);
 
                 // Draw the square background
                 buf.set_style(cell_area, Style::default().bg(color));
 
-                // Draw pieces (replace with your game state)
-                if let Some(piece) = game.get_board().get_piece_at((row, col)) {
-                    let symbol = piece.symbol();
+                // Mark quiet destinations of the selected piece with a dot
+                if choice.is_none() && target.is_some_and(|mv| !mv.is_capture()) {
+                    buf.set_string(
+                        cell_area.x + cell_area.width / 2,
+                        cell_area.y + cell_area.height / 2,
+                        "●",
+                        Style::default().fg(theme.legal_move).bg(color),
+                    );
+                }
+
+                let piece = match choice {
+                    Some((choice, index)) => Some(choice.piece(index)),
+                    None => game.get_board().get_piece_at(square).copied(),
+                };
+                if let Some(piece) = &piece {
+                    let mut symbol = self.config.glyph_set.symbol(piece);
+                    if self.config.glyph_set != GlyphSet::BlockArt {
+                        // Single-character glyphs sit on the middle line of the square
+                        symbol.insert_str(0, &"\n".repeat(cell_height as usize / 2));
+                    }
                     let paragraph = Paragraph::new(symbol)
                         .block(Block::default())
                         .alignment(Alignment::Center)
-                        .style(Style::default().fg(Color::White).bg(color));
+                        .style(
+                            Style::default()
+                                .fg(theme.piece_color(piece.color))
+                                .bg(color),
+                        );
                     paragraph.render(cell_area, buf);
+
+                    if self.config.piece_style == PieceStyle::Outlined {
+                        // Colour the background of every stroke cell, so the uncovered half of
+                        // half-block characters forms an outline around the piece
+                        let outline = theme.outline_color(piece.color);
+                        for y in cell_area.top()..cell_area.bottom() {
+                            for x in cell_area.left()..cell_area.right() {
+                                if let Some(cell) = buf.cell_mut((x, y))
+                                    && cell.symbol() != " "
+                                {
+                                    cell.set_bg(outline);
+                                }
+                            }
+                        }
+                    }
                 }
             }
         }
 
-        // Draw coordinates if there's space
-        if cell_width >= 3 && cell_height >= 2 {
-            for i in 0..8 {
-                // Files (a-h at bottom)
+        block.render(area, buf);
+
+        // Label files along the bottom edge and ranks along the left one if there's space
+        if self.config.show_coordinates && cell_width >= 3 && cell_height >= 2 {
+            for col in 0..cols {
+                // Files (a-h at bottom on an 8x8 board)
+                let cell = layout.cell_rect((rows - 1, col));
                 buf.set_string(
-                    area.x + (i as u16 * cell_width) + cell_width / 2,
+                    cell.x + cell_width / 2,
                     area.y + area.height - 1,
-                    format!("{}", (b'a' + i) as char),
+                    format!("{}", (b'a' + col as u8) as char),
                     Style::default(),
                 );
-
-                // Ranks (1-8 at left)
+            }
+            for row in 0..rows {
+                // Ranks (1-8 at left on an 8x8 board)
+                let cell = layout.cell_rect((row, 0));
                 buf.set_string(
                     area.x,
-                    area.y + (i as u16 * cell_height) + cell_height / 2,
-                    format!("{}", 8 - i),
+                    cell.y + cell_height / 2,
+                    format!("{}", rows - row),
                     Style::default(),
                 );
             }
         }
+    }
 
-        block.render(area, buf);
+    /// Crazyhouse pockets, one line per side, e.g. `Black: ♛ ♞×2`.
+    fn render_pockets(&self, area: Rect, buf: &mut Buffer, game: &Game) {
+        let block = Block::bordered()
+            .title("Pockets")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded);
+
+        let text: Vec<Line> = [pieces::Color::Black, pieces::Color::White]
+            .into_iter()
+            .map(|color| {
+                let mut spans = vec![Span::raw(format!("{color:?}: "))];
+                for (piece_type, count) in game.pocket(color).iter() {
+                    let symbol = pieces::Piece::new(color, piece_type).unicode_symbol();
+                    let entry = if count > 1 {
+                        format!("{symbol}×{count} ")
+                    } else {
+                        format!("{symbol} ")
+                    };
+                    spans.push(Span::raw(entry));
+                }
+                Line::from(spans)
+            })
+            .collect();
+
+        let paragraph = Paragraph::new(text)
+            .block(block)
+            .fg(Color::White)
+            .bg(Color::Black);
+
+        paragraph.render(area, buf);
+    }
+
+    fn render_settings(&self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .title("Settings")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded);
+
+        let config = &self.config;
+        let on_off = |on: bool| if on { "On" } else { "Off" }.to_string();
+        let selected = SettingsRow::nth(self.settings_selector.selected_index);
+        // A path or name being typed replaces the value of its row
+        let typed = |row: SettingsRow| {
+            self.path_input
+                .as_ref()
+                .filter(|_| Some(row) == selected)
+                .map(|input| format!("{input}█"))
+        };
+        let path = |row: SettingsRow, path: &Option<std::path::PathBuf>, unset: &str| {
+            typed(row).unwrap_or_else(|| match path {
+                Some(path) => path.display().to_string(),
+                None => unset.to_string(),
+            })
+        };
+        let name = |row: SettingsRow, name: &str| typed(row).unwrap_or_else(|| name.to_string());
+        let help = match selected {
+            Some(row) if self.path_input.is_some() && row.is_name() => {
+                "Type the profile name, Enter to keep it, Esc to cancel."
+            }
+            _ if self.path_input.is_some() => {
+                "Type the path, Enter to keep it, Esc to cancel. Leave empty for none."
+            }
+            _ if self.rebinding => {
+                "Press the new key, Delete for the preset's keys, Esc to cancel."
+            }
+            _ => {
+                "Up/Down choose a setting, Left/Right change it, Enter edits a path or name or rebinds a key."
+            }
+        };
+        let mut text = vec![Line::from(help), Line::from("")];
+        for row in SettingsRow::iter() {
+            let value = match row {
+                SettingsRow::Theme => self.theme.name.clone(),
+                SettingsRow::Glyphs => config.glyph_set.name().to_string(),
+                SettingsRow::Pieces => config.piece_style.name().to_string(),
+                SettingsRow::Orientation => if config.flip_board {
+                    "Black at bottom"
+                } else {
+                    "White at bottom"
+                }
+                .to_string(),
+                SettingsRow::Coordinates => on_off(config.show_coordinates),
+                SettingsRow::Bell => on_off(config.bell),
+                SettingsRow::TimeControl => config
+                    .time_control
+                    .map_or("Untimed".to_string(), |control| control.to_string()),
+                SettingsRow::Engine => path(row, &config.engine.path, "Built-in"),
+                SettingsRow::EngineStrength => {
+                    format!("{}/{}", config.engine.strength, EngineConfig::MAX_STRENGTH)
+                }
+                SettingsRow::OpeningBook => path(row, &config.engine.book, "None"),
+                SettingsRow::Tablebases => path(row, &config.engine.tablebases, "None"),
+                SettingsRow::KeyBindings => match config
+                    .key_bindings
+                    .values()
+                    .map(|actions| actions.len())
+                    .sum()
+                {
+                    0 => config.keymap.name().to_string(),
+                    n => format!("{} + {n} custom", config.keymap.name()),
+                },
+                SettingsRow::Rebind => match keymap::bindable().get(self.rebind_index) {
+                    Some(&(scope, action)) => {
+                        let keys = if self.rebinding {
+                            "…".to_string()
+                        } else {
+                            self.keymap.describe(scope, action)
+                        };
+                        format!("{scope:?} {}: {keys}", action.name())
+                    }
+                    None => String::new(),
+                },
+                SettingsRow::Puzzles => path(row, &config.puzzles, "None"),
+                SettingsRow::Repertoire => path(row, &config.repertoire, "None"),
+                SettingsRow::Player => name(row, &config.player),
+                SettingsRow::Opponent => name(row, &config.opponent),
+            };
+            let entry = format!("{}: ◀ {value} ▶", row.label());
+            if Some(row) == selected {
+                text.push(Line::from(Span::styled(
+                    entry,
+                    Style::default().fg(Color::Yellow),
+                )));
+            } else {
+                text.push(Line::from(entry));
+            }
+        }
+        text.push(Line::from(""));
+        match (&self.settings_message, &self.config_path) {
+            (Some(message), _) => text.push(Line::from(Span::styled(
+                message.clone(),
+                Style::default().fg(Color::Red),
+            ))),
+            (None, Some(path)) => text.push(Line::from(format!("Saved to {}", path.display()))),
+            (None, None) => {}
+        }
+
+        let paragraph = Paragraph::new(text)
+            .block(block)
+            .fg(Color::White)
+            .bg(Color::Black)
+            .alignment(Alignment::Center);
+
+        paragraph.render(area, buf);
+    }
+
+    fn render_stats(&self, area: Rect, buf: &mut Buffer) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(3)])
+            .split(area);
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(1, 3), Constraint::Ratio(2, 3)])
+            .split(rows[0]);
+
+        let keys = |action| self.keymap.describe(Scope::Stats, action);
+        Paragraph::new(Line::from(format!(
+            "{}/{} choose a player, {} back",
+            keys(Action::Up),
+            keys(Action::Down),
+            self.keymap.describe(Scope::Global, Action::Back),
+        )))
+        .block(
+            Block::bordered()
+                .title("Keys")
+                .border_type(BorderType::Rounded),
+        )
+        .fg(Color::White)
+        .bg(Color::Black)
+        .render(rows[1], buf);
+
+        let profiles = self
+            .profiles
+            .as_ref()
+            .filter(|profiles| !profiles.players.is_empty());
+        let mut players = Vec::new();
+        match profiles {
+            Some(profiles) => {
+                for (i, profile) in profiles.players.iter().enumerate() {
+                    let entry = format!(
+                        "{}: {}, {} {}",
+                        profile.name,
+                        profile.rating,
+                        profile.games,
+                        if profile.games == 1 { "game" } else { "games" },
+                    );
+                    if i == self.stats_player {
+                        players.push(Line::from(Span::styled(
+                            entry,
+                            Style::default().fg(Color::Yellow),
+                        )));
+                    } else {
+                        players.push(Line::from(entry));
+                    }
+                }
+            }
+            None => players.push(Line::from("No games recorded yet.")),
+        }
+        if let Some(message) = &self.stats_message {
+            players.push(Line::from(""));
+            players.push(Line::from(Span::styled(
+                message.clone(),
+                Style::default().fg(Color::Red),
+            )));
+        }
+        Paragraph::new(players)
+            .block(
+                Block::bordered()
+                    .title("Players")
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            )
+            .fg(Color::White)
+            .bg(Color::Black)
+            .wrap(Wrap { trim: true })
+            .render(chunks[0], buf);
+
+        let selected = profiles.and_then(|profiles| profiles.players.get(self.stats_player));
+        let mut text = Vec::new();
+        if let (Some(profiles), Some(profile)) = (profiles, selected) {
+            let breakdown = profiles.breakdown(&profile.name);
+            text.push(Line::from(format!("Overall: {}", breakdown.overall)));
+            text.push(Line::from(format!("As White: {}", breakdown.white)));
+            text.push(Line::from(format!("As Black: {}", breakdown.black)));
+            for (title, tallies) in [
+                ("Openings", &breakdown.openings),
+                ("Opponents", &breakdown.opponents),
+            ] {
+                text.push(Line::from(""));
+                text.push(Line::from(title).bold());
+                for (name, tally) in tallies {
+                    text.push(Line::from(format!("{name}: {tally}")));
+                }
+            }
+        } else {
+            text.push(Line::from(
+                "Finished games are recorded here with their players.",
+            ));
+        }
+        Paragraph::new(text)
+            .block(
+                Block::bordered()
+                    .title(selected.map_or("Results".to_string(), |profile| {
+                        format!("Results of {}", profile.name)
+                    }))
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            )
+            .fg(Color::White)
+            .bg(Color::Black)
+            .wrap(Wrap { trim: true })
+            .render(chunks[1], buf);
     }
 
     fn render_help(&self, area: Rect, buf: &mut Buffer) {
@@ -152,9 +1880,31 @@ impl App {
             .title_alignment(Alignment::Center)
             .border_type(BorderType::Rounded);
 
+        let keys = |action| self.keymap.describe(Scope::Game, action);
         let text = Text::from(vec![
             Line::from("Help section..."),
             Line::from("\n"),
+            Line::from(format!(
+                "In a game: {} / {} / {} / {} move the cursor, {} picks up and drops.",
+                keys(Action::Up),
+                keys(Action::Down),
+                keys(Action::Left),
+                keys(Action::Right),
+                keys(Action::Select),
+            )),
+            Line::from(format!(
+                "Click a piece then its destination, or drag it there. {} flips the board.",
+                keys(Action::Flip),
+            )),
+            Line::from(format!(
+                "{} opens the move bar: type SAN or coordinates, Tab completes, Enter plays.",
+                keys(Action::Command),
+            )),
+            Line::from(format!(
+                "{} goes back. Keys can be changed under [key_bindings] in the config file.",
+                self.keymap.describe(Scope::Global, Action::Back),
+            )),
+            Line::from("\n"),
             Line::from("If you are seeing a bug, please report it."),
             Line::from("If you want to make a feature request, make it yourself."),
             Line::from("https://github.com/Jacob1010-h/chess-project"),
@@ -192,3 +1942,995 @@ impl App {
         paragraph.render(area, buf);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ratatui::crossterm::event::{
+        KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    };
+    use ratatui::{Terminal, backend::TestBackend, buffer::Cell};
+
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::book::Book;
+    use crate::engine::SearchLimits;
+    use crate::profile::Profiles;
+    use crate::report::Report;
+    use crate::tablebase::Tablebase;
+    use crate::theme::Theme;
+
+    /// An app showing `fen` with ASCII glyphs, the cursor parked off the pieces.
+    fn game_app(fen: &str) -> App {
+        let mut app = App {
+            game: Some(Game::from_fen(fen, VariantKind::Standard).unwrap()),
+            cursor: (4, 0),
+            ..App::default()
+        };
+        app.state.push(AppState::Game);
+        app.config.glyph_set = GlyphSet::Ascii;
+        app
+    }
+
+    fn draw(app: &mut App, width: u16, height: u16) -> Terminal<TestBackend> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|frame| frame.render_widget(&mut *app, frame.area()))
+            .unwrap();
+        terminal
+    }
+
+    /// The characters on screen, one string per row, ignoring styles.
+    fn screen_lines(buffer: &Buffer) -> Vec<String> {
+        buffer
+            .content()
+            .chunks(buffer.area.width as usize)
+            .map(|row| row.iter().map(Cell::symbol).collect())
+            .collect()
+    }
+
+    fn find_cell<'a>(buffer: &'a Buffer, symbol: &str) -> &'a Cell {
+        buffer
+            .content()
+            .iter()
+            .find(|cell| cell.symbol() == symbol)
+            .unwrap_or_else(|| panic!("{symbol} is not on screen"))
+    }
+
+    #[tokio::test]
+    async fn pieces_use_their_side_colour() {
+        for theme in Theme::builtin() {
+            let mut app = game_app("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+            app.theme = theme.clone();
+            let terminal = draw(&mut app, 60, 20);
+            let buffer = terminal.backend().buffer();
+            assert_eq!(
+                find_cell(buffer, "K").fg,
+                theme.white_piece,
+                "{}",
+                theme.name
+            );
+            assert_eq!(
+                find_cell(buffer, "k").fg,
+                theme.black_piece,
+                "{}",
+                theme.name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn outlined_pieces_sit_on_their_outline_colour() {
+        let mut app = game_app("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        app.config.piece_style = PieceStyle::Outlined;
+        let terminal = draw(&mut app, 60, 20);
+        let buffer = terminal.backend().buffer();
+        let theme = Theme::classic();
+        assert_eq!(find_cell(buffer, "K").bg, theme.white_outline);
+        assert_eq!(find_cell(buffer, "k").bg, theme.black_outline);
+    }
+
+    #[tokio::test]
+    async fn filled_pieces_keep_the_square_colour() {
+        let mut app = game_app("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        let terminal = draw(&mut app, 60, 20);
+        let buffer = terminal.backend().buffer();
+        let theme = Theme::classic();
+        // e1 is a dark square and e8 a light one
+        assert_eq!(find_cell(buffer, "K").bg, theme.dark_square);
+        assert_eq!(find_cell(buffer, "k").bg, theme.light_square);
+    }
+
+    #[tokio::test]
+    async fn starting_position_snapshot() {
+        let mut app = game_app("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let terminal = draw(&mut app, 60, 30);
+        let expected = [
+            "     ┌─────────────────────────────────┐╭─────Standard─────╮",
+            "     │                                 ││White to move     │",
+            "     8  r   n   b   q   k   b   n   r  ││                  │",
+            "     │                                 ││                  │",
+            "     │                                 ││                  │",
+            "     7  p   p   p   p   p   p   p   p  ││                  │",
+            "     │                                 ││                  │",
+            "     │                                 ││                  │",
+            "     6                                 ││                  │",
+            "     │                                 ││                  │",
+            "     │                                 ││                  │",
+            "     5                                 ││                  │",
+            "     │                                 ││                  │",
+            "     │                                 ││                  │",
+            "     4                                 ││                  │",
+            "     │                                 ││                  │",
+            "     │                                 ││                  │",
+            "     3                                 ││                  │",
+            "     │                                 ││                  │",
+            "     │                                 ││                  │",
+            "     2  P   P   P   P   P   P   P   P  ││                  │",
+            "     │                                 ││                  │",
+            "     │                                 ││                  │",
+            "     1  R   N   B   Q   K   B   N   R  ││                  │",
+            "…    │                                 ││                  │",
+            "     └──a───b───c───d───e───f───g───h──┘╰──────────────────╯",
+            "╭Move──────────────────────────────────────────────────────╮",
+            "│Press / or : to type a move, e.g. Nf3, exd5 or g1f3.      │",
+            "│                                                          │",
+            "╰──────────────────────────────────────────────────────────╯",
+        ];
+        assert_eq!(screen_lines(terminal.backend().buffer()), expected);
+    }
+
+    #[tokio::test]
+    async fn command_bar_completes_and_plays_moves() {
+        let mut app = game_app("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let type_keys = |app: &mut App, keys: &str| {
+            for c in keys.chars() {
+                app.handle_key_events(KeyEvent::from(KeyCode::Char(c)))
+                    .unwrap();
+            }
+        };
+        type_keys(&mut app, ":N");
+        let terminal = draw(&mut app, 60, 30);
+        let lines = screen_lines(terminal.backend().buffer());
+        assert!(lines[27].contains("> N█"), "{}", lines[27]);
+        assert!(lines[28].contains("Na3 Nc3 Nf3 Nh3"), "{}", lines[28]);
+
+        type_keys(&mut app, "f6");
+        app.handle_key_events(KeyEvent::from(KeyCode::Enter))
+            .unwrap();
+        let terminal = draw(&mut app, 60, 30);
+        let lines = screen_lines(terminal.backend().buffer());
+        assert!(
+            lines[28].contains("Nf6 is not a legal move"),
+            "{}",
+            lines[28]
+        );
+
+        app.handle_key_events(KeyEvent::from(KeyCode::Backspace))
+            .unwrap();
+        type_keys(&mut app, "3");
+        app.handle_key_events(KeyEvent::from(KeyCode::Enter))
+            .unwrap();
+        assert_eq!(app.command_input, None);
+        let game = app.game.as_ref().unwrap();
+        assert_eq!(game.get_move_history().len(), 1);
+        assert_eq!(game.get_current_turn(), pieces::Color::Black);
+    }
+
+    #[test]
+    fn board_layout_maps_cells_to_squares() {
+        for (rows, cols) in [(8, 8), (8, 10), (10, 10)] {
+            for (cell_width, cell_height) in [(1, 1), (3, 2), (7, 4)] {
+                // One spare column and row past the squares, as a board that does not divide
+                // evenly has
+                let area = Rect::new(5, 3, cols * cell_width + 3, rows * cell_height + 3);
+                for flipped in [false, true] {
+                    let layout = BoardLayout::new(area, (rows as usize, cols as usize), flipped);
+                    assert_eq!(
+                        (layout.cell_width, layout.cell_height),
+                        (cell_width, cell_height)
+                    );
+                    for row in 0..rows as usize {
+                        for col in 0..cols as usize {
+                            let cell = layout.cell_rect((row, col));
+                            for (x, y) in [(cell.x, cell.y), (cell.right() - 1, cell.bottom() - 1)]
+                            {
+                                assert_eq!(layout.square_at(x, y), Some((row, col)));
+                            }
+                        }
+                    }
+                    let top_left = layout.square_at(area.x + 1, area.y + 1);
+                    let expected = if flipped {
+                        (rows as usize - 1, cols as usize - 1)
+                    } else {
+                        (0, 0)
+                    };
+                    assert_eq!(top_left, Some(expected));
+
+                    // The border, the spare cells and anything beyond are off the board
+                    let squares = layout.squares;
+                    for (x, y) in [
+                        (area.x, area.y + 1),
+                        (area.x + 1, area.y),
+                        (squares.x + cols * cell_width, squares.y),
+                        (squares.x, squares.y + rows * cell_height),
+                        (0, 0),
+                        (area.right() + 5, area.bottom() + 5),
+                    ] {
+                        assert_eq!(layout.square_at(x, y), None, "({x}, {y}) on {layout:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends a left-button mouse event over the middle of `square` as last drawn.
+    fn mouse(app: &mut App, kind: MouseEventKind, square: (usize, usize)) {
+        let cell = app.board_layout.unwrap().cell_rect(square);
+        app.handle_mouse_events(MouseEvent {
+            kind,
+            column: cell.x + cell.width / 2,
+            row: cell.y + cell.height / 2,
+            modifiers: KeyModifiers::NONE,
+        });
+    }
+
+    fn click(app: &mut App, square: (usize, usize)) {
+        mouse(app, MouseEventKind::Down(MouseButton::Left), square);
+        mouse(app, MouseEventKind::Up(MouseButton::Left), square);
+    }
+
+    #[tokio::test]
+    async fn mouse_clicks_and_drags_move_pieces_on_a_flipped_board() {
+        let mut app = game_app("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        app.board_flipped = true;
+        draw(&mut app, 90, 40);
+        let layout = app.board_layout.unwrap();
+        assert!(layout.flipped);
+        // White sits at the top of a flipped board
+        assert!(layout.cell_rect((6, 4)).y < layout.cell_rect((1, 4)).y);
+
+        click(&mut app, (6, 4));
+        assert_eq!(app.selected_square, Some((6, 4)));
+        app.handle_mouse_events(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: layout.area.right() + 2,
+            row: layout.area.y,
+            modifiers: KeyModifiers::NONE,
+        });
+        assert_eq!(app.selected_square, None);
+
+        click(&mut app, (6, 4));
+        click(&mut app, (4, 4));
+        let game = app.game.as_ref().unwrap();
+        assert_eq!(
+            game.get_board().get_piece_at((4, 4)),
+            Some(&pieces::Piece::w_pawn())
+        );
+        assert_eq!(app.selected_square, None);
+
+        // Black drags a pawn instead
+        draw(&mut app, 90, 40);
+        mouse(&mut app, MouseEventKind::Down(MouseButton::Left), (1, 3));
+        assert!(app.dragging);
+        mouse(&mut app, MouseEventKind::Up(MouseButton::Left), (3, 3));
+        let game = app.game.as_ref().unwrap();
+        assert_eq!(
+            game.get_board().get_piece_at((3, 3)),
+            Some(&pieces::Piece::b_pawn())
+        );
+        assert_eq!(game.get_move_history().len(), 2);
+
+        // A click on an illegal destination drops the piece without moving it
+        click(&mut app, (7, 6));
+        click(&mut app, (4, 6));
+        assert_eq!(app.selected_square, None);
+        assert_eq!(app.game.as_ref().unwrap().get_move_history().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn promotions_wait_for_the_piece_to_be_picked() {
+        let fen = "4k3/1P6/8/8/8/8/8/4K3 w - - 0 1";
+        let mut app = game_app(fen);
+        let press = |app: &mut App, code| app.handle_key_events(KeyEvent::from(code)).unwrap();
+        draw(&mut app, 90, 40);
+        click(&mut app, (1, 1));
+        click(&mut app, (0, 1));
+        let choice = app.promotion.clone().unwrap();
+        assert_eq!(choice.moves.len(), 4);
+        assert!(app.game.as_ref().unwrap().get_move_history().is_empty());
+
+        // The pieces on offer cover the promotion square and the ones below it
+        let terminal = draw(&mut app, 90, 40);
+        let screen = screen_lines(terminal.backend().buffer());
+        for (index, letter) in ["Q", "R", "B", "N"].into_iter().enumerate() {
+            let cell = app.board_layout.unwrap().cell_rect(choice.square(index));
+            let line = &screen[(cell.y + cell.height / 2) as usize];
+            let text: String = line
+                .chars()
+                .skip(cell.x as usize)
+                .take(cell.width as usize)
+                .collect();
+            assert_eq!(text.trim(), letter, "{screen:?}");
+        }
+        assert!(
+            screen
+                .iter()
+                .any(|line| line.contains("Promote to the highlighted piece"))
+        );
+
+        click(&mut app, choice.square(3));
+        let game = app.game.as_ref().unwrap();
+        assert_eq!(
+            game.get_board().get_piece_at((0, 1)),
+            Some(&pieces::Piece::w_knight())
+        );
+        assert_eq!(app.promotion, None);
+
+        // From the keyboard, Back puts the pawn down again and Select plays the highlight
+        let mut app = game_app(fen);
+        draw(&mut app, 90, 40);
+        app.cursor = (1, 1);
+        press(&mut app, KeyCode::Enter);
+        app.cursor = (0, 1);
+        press(&mut app, KeyCode::Enter);
+        assert!(app.promotion.is_some());
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.promotion, None);
+        assert_eq!(app.state.last(), Some(&AppState::Game));
+
+        app.cursor = (1, 1);
+        press(&mut app, KeyCode::Enter);
+        app.cursor = (0, 1);
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Enter);
+        let game = app.game.as_ref().unwrap();
+        assert_eq!(
+            game.get_board().get_piece_at((0, 1)),
+            Some(&pieces::Piece::w_rook())
+        );
+    }
+
+    #[tokio::test]
+    async fn board_highlights_selection_targets_last_move_and_check() {
+        for theme in Theme::builtin() {
+            let mut app = game_app("4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1");
+            app.theme = theme.clone();
+            let press = |app: &mut App, code| app.handle_key_events(KeyEvent::from(code)).unwrap();
+            // The corner of a square stays clear of its glyph and of the quiet-move dot
+            let background = |terminal: &Terminal<TestBackend>, layout: BoardLayout, square| {
+                let cell = layout.cell_rect(square);
+                terminal.backend().buffer()[(cell.x, cell.y)].bg
+            };
+
+            app.cursor = (7, 3);
+            press(&mut app, KeyCode::Enter);
+            app.cursor = (5, 0);
+            let terminal = draw(&mut app, 90, 40);
+            let layout = app.board_layout.unwrap();
+            assert_eq!(background(&terminal, layout, (7, 3)), theme.selected);
+            assert_eq!(background(&terminal, layout, (5, 0)), theme.cursor);
+            assert_eq!(background(&terminal, layout, (3, 3)), theme.legal_capture);
+            // d2 is a quiet destination, a2 is out of reach
+            let d2 = layout.cell_rect((6, 3));
+            let dot = &terminal.backend().buffer()[(d2.x + d2.width / 2, d2.y + d2.height / 2)];
+            assert_eq!((dot.symbol(), dot.fg), ("●", theme.legal_move));
+            assert_eq!(background(&terminal, layout, (6, 3)), theme.dark_square);
+            assert_eq!(background(&terminal, layout, (6, 0)), theme.light_square);
+            let a2 = layout.cell_rect((6, 0));
+            let empty = &terminal.backend().buffer()[(a2.x + a2.width / 2, a2.y + a2.height / 2)];
+            assert_eq!(empty.symbol(), " ");
+
+            // Qh5+ leaves its squares marked and the black king in check
+            app.cursor = (3, 7);
+            press(&mut app, KeyCode::Enter);
+            app.cursor = (5, 0);
+            let terminal = draw(&mut app, 90, 40);
+            assert!(app.game.as_ref().unwrap().is_in_check());
+            assert_eq!(app.selected_square, None);
+            assert_eq!(background(&terminal, layout, (7, 3)), theme.last_move);
+            assert_eq!(background(&terminal, layout, (3, 7)), theme.last_move);
+            assert_eq!(background(&terminal, layout, (0, 4)), theme.check);
+            assert_eq!(background(&terminal, layout, (3, 3)), theme.light_square);
+        }
+    }
+
+    #[tokio::test]
+    async fn game_panel_names_the_opening_and_book() {
+        let mut app = game_app("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        app.book = Some(Book::default());
+        for san in ["e4", "e5", "Nf3", "Nc6", "Bb5"] {
+            let game = app.game.as_mut().unwrap();
+            game.make_move(notation::parse_move(game, san).unwrap());
+        }
+        let terminal = draw(&mut app, 90, 30);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("C60 Ruy Lopez"), "{screen}");
+        assert!(screen.contains("Out of book"), "{screen}");
+    }
+
+    #[tokio::test]
+    async fn game_panel_shows_tablebase_results() {
+        let mut app = game_app("4k3/8/8/8/8/8/8/3QK3 w - - 0 1");
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy");
+        app.tablebase = Some(Arc::new(Tablebase::open(dir.as_ref()).unwrap()));
+        let terminal = draw(&mut app, 90, 30);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("Tablebase win, DTZ 15"), "{screen}");
+
+        // Moving probes the new position
+        let game = app.game.as_mut().unwrap();
+        game.make_move(notation::parse_move(game, "Qd5").unwrap());
+        let terminal = draw(&mut app, 90, 30);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("Tablebase loss, DTZ -14"), "{screen}");
+    }
+
+    #[tokio::test]
+    async fn analysis_board_keeps_variations_and_sets_up_positions() {
+        let mut app = App::default();
+        app.config.glyph_set = GlyphSet::Ascii;
+        let press = |app: &mut App, code| app.handle_key_events(KeyEvent::from(code)).unwrap();
+        let command = |app: &mut App, text: &str| {
+            press(app, KeyCode::Char(':'));
+            for c in text.chars() {
+                press(app, KeyCode::Char(c));
+            }
+            press(app, KeyCode::Enter);
+        };
+        app.menu_selector.select(1);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.state.last(), Some(&AppState::Analysis));
+
+        command(&mut app, "e4");
+        press(&mut app, KeyCode::Backspace);
+        command(&mut app, "d4");
+        press(&mut app, KeyCode::Backspace);
+        let terminal = draw(&mut app, 100, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("Next: e4"), "{screen}");
+        assert!(screen.contains("Instead: d4"), "{screen}");
+        assert!(screen.contains("Built-in engine: thinking"), "{screen}");
+
+        press(&mut app, KeyCode::Char(']'));
+        press(&mut app, KeyCode::Char('e'));
+        let terminal = draw(&mut app, 100, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("1. e4"), "{screen}");
+        assert!(screen.contains("Built-in engine: off"), "{screen}");
+
+        command(&mut app, "4k3/8/8/8/8/8/8/3QK3 b - - 0 1");
+        let analysis = app.analysis.as_ref().unwrap();
+        assert!(analysis.moves_played().is_empty());
+        assert!(!analysis.engine_on);
+        press(&mut app, KeyCode::Char('q'));
+        assert_eq!(app.state.last(), Some(&AppState::MainMenu));
+        assert!(app.analyser.is_none());
+    }
+
+    #[tokio::test]
+    async fn board_editor_validates_before_play() {
+        let mut app = App::default();
+        let press = |app: &mut App, code| app.handle_key_events(KeyEvent::from(code)).unwrap();
+        let presses = |app: &mut App, code, times| {
+            for _ in 0..times {
+                press(app, code);
+            }
+        };
+        app.menu_selector.select(2);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.state.last(), Some(&AppState::Editor));
+
+        press(&mut app, KeyCode::Char('X'));
+        press(&mut app, KeyCode::Char('p'));
+        assert_eq!(app.state.last(), Some(&AppState::Editor));
+        let terminal = draw(&mut app, 100, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("White needs exactly one king"), "{screen}");
+
+        // White king on e1, black king on e8, with Black to move
+        presses(&mut app, KeyCode::Char(']'), 5);
+        press(&mut app, KeyCode::Enter);
+        presses(&mut app, KeyCode::Up, 7);
+        presses(&mut app, KeyCode::Char(']'), 6);
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Char('c'));
+        assert_eq!(app.editor.as_ref().unwrap().castling.to_fen(), "kq");
+        press(&mut app, KeyCode::Char('c'));
+        assert!(app.editor_message.is_none());
+        presses(&mut app, KeyCode::Down, 3);
+        press(&mut app, KeyCode::Char('c'));
+        assert!(app.editor_message.is_some());
+        press(&mut app, KeyCode::Char('t'));
+        let terminal = draw(&mut app, 100, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("Black to move"), "{screen}");
+        assert!(screen.contains("Castling: -"), "{screen}");
+        assert!(screen.contains("Ready: p to play"), "{screen}");
+
+        press(&mut app, KeyCode::Char('p'));
+        assert_eq!(app.state.last(), Some(&AppState::Game));
+        assert_eq!(
+            app.game.as_ref().unwrap().to_fen(),
+            "4k3/8/8/8/8/8/8/4K3 b - - 0 1"
+        );
+        press(&mut app, KeyCode::Char('q'));
+        assert_eq!(app.state.last(), Some(&AppState::MainMenu));
+    }
+
+    #[tokio::test]
+    async fn puzzles_give_hints_and_rate_the_player() {
+        let path =
+            std::env::temp_dir().join(format!("chess-tui-ui-puzzles-{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "000aY,r4rk1/pp3ppp/2n1b3/q1pp2B1/8/P1Q2NP1/1PP1PP1P/2KR3R w - - 0 15,\
+             g5e7 a5c3 b2c3 c6e7,1430,74,93,2178,advantage endgame fork short,,\n",
+        )
+        .unwrap();
+        let mut app = App::default();
+        app.show_puzzles(Trainer::open(&path, "", None).unwrap());
+        std::fs::remove_file(path).unwrap();
+        let press = |app: &mut App, code| app.handle_key_events(KeyEvent::from(code)).unwrap();
+        let type_text = |app: &mut App, text: &str| {
+            for c in text.chars() {
+                press(app, KeyCode::Char(c));
+            }
+            press(app, KeyCode::Enter);
+        };
+        assert!(app.board_flipped);
+        let terminal = draw(&mut app, 100, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("Puzzle 000aY, rated 1430"), "{screen}");
+        assert!(screen.contains("Find the best move for Black"), "{screen}");
+
+        // The hint picks up the queen on a5
+        press(&mut app, KeyCode::Char('?'));
+        assert_eq!(app.selected_square, Some((3, 0)));
+        press(&mut app, KeyCode::Char(':'));
+        type_text(&mut app, "Qxc3");
+        press(&mut app, KeyCode::Char(':'));
+        type_text(&mut app, "Nxe7");
+        let terminal = draw(&mut app, 100, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("Solved!"), "{screen}");
+        assert!(screen.contains("Solved 1, failed 0"), "{screen}");
+        assert!(app.trainer.as_ref().unwrap().progress.rating.rating > 1500.0);
+
+        press(&mut app, KeyCode::Char('/'));
+        type_text(&mut app, "elo:1500");
+        let trainer = app.trainer.as_ref().unwrap();
+        assert!(trainer.filter_input.is_some());
+        assert!(trainer.message.as_ref().unwrap().contains("unknown filter"));
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Char('n'));
+        let terminal = draw(&mut app, 100, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("no unplayed puzzles"), "{screen}");
+    }
+
+    #[tokio::test]
+    async fn repertoire_drills_play_the_opponent_and_check_replies() {
+        let path = std::env::temp_dir().join(format!(
+            "chess-tui-ui-repertoire-{}.pgn",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            "[Event \"Italian\"]\n\n1. e4 e5 (1... c5 2. Nf3) 2. Nf3 Nc6 3. Bc4 *\n",
+        )
+        .unwrap();
+        let repertoire = crate::repertoire::Repertoire::open(&path, None).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let mut app = App::default();
+        app.show_repertoire(Session::new(repertoire, None, 100).unwrap());
+        let press = |app: &mut App, code| app.handle_key_events(KeyEvent::from(code)).unwrap();
+        let type_text = |app: &mut App, text: &str| {
+            press(app, KeyCode::Char(':'));
+            for c in text.chars() {
+                press(app, KeyCode::Char(c));
+            }
+            press(app, KeyCode::Enter);
+        };
+        assert!(!app.board_flipped);
+        let terminal = draw(&mut app, 120, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("Italian, line 1 of 2"), "{screen}");
+        assert!(
+            screen.contains("Playing White, 2 of 2 lines due"),
+            "{screen}"
+        );
+        assert!(screen.contains("A new line"), "{screen}");
+
+        type_text(&mut app, "d4");
+        let terminal = draw(&mut app, 120, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(
+            screen.contains("Not your repertoire move, try again"),
+            "{screen}"
+        );
+
+        // Black's reply comes at once, and the hint picks up the knight
+        type_text(&mut app, "e4");
+        press(&mut app, KeyCode::Char('?'));
+        assert_eq!(app.selected_square, Some((7, 6)));
+        let terminal = draw(&mut app, 120, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("1. e4 c5"), "{screen}");
+        assert!(
+            screen.contains("Hint: the repertoire move is Nf3"),
+            "{screen}"
+        );
+
+        type_text(&mut app, "Nf3");
+        let terminal = draw(&mut app, 120, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("Line complete"), "{screen}");
+        assert!(screen.contains("Reviews: 1, lapses: 1"), "{screen}");
+        assert!(screen.contains("Due again tomorrow"), "{screen}");
+
+        press(&mut app, KeyCode::Char('n'));
+        let terminal = draw(&mut app, 120, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("line 2 of 2"), "{screen}");
+        assert!(
+            screen.contains("Playing White, 1 of 2 lines due"),
+            "{screen}"
+        );
+    }
+
+    #[tokio::test]
+    async fn endgame_drills_are_judged_and_changed() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy");
+        let tablebase = Arc::new(Tablebase::open(dir.as_ref()).unwrap());
+        let start = Game::from_fen("6k1/8/6K1/8/8/8/8/Q7 w - - 0 1", VariantKind::Standard);
+        let drill = Drill::from_position("KQvK".parse().unwrap(), start.unwrap(), Some(&tablebase));
+        let mut app = App {
+            tablebase: Some(tablebase),
+            ..App::default()
+        };
+        app.show_endgame(drill);
+        let press = |app: &mut App, code| app.handle_key_events(KeyEvent::from(code)).unwrap();
+        let type_text = |app: &mut App, text: &str| {
+            press(app, KeyCode::Char(':'));
+            for c in text.chars() {
+                press(app, KeyCode::Char(c));
+            }
+            press(app, KeyCode::Enter);
+        };
+        assert_eq!(app.engine_color, Some(pieces::Color::Black));
+        let terminal = draw(&mut app, 120, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("KQvK as White"), "{screen}");
+        assert!(screen.contains("Win: give checkmate"), "{screen}");
+        assert!(screen.contains("Tablebase win, DTZ 1"), "{screen}");
+
+        type_text(&mut app, "Qa8#");
+        let terminal = draw(&mut app, 120, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("Checkmate in 1 move"), "{screen}");
+        assert!(screen.contains("against 1 with best play"), "{screen}");
+
+        press(&mut app, KeyCode::Char('r'));
+        let drill = app.endgame.as_ref().unwrap();
+        assert_eq!(drill.verdict, None);
+        assert_eq!(app.game.as_ref().unwrap().get_move_history().len(), 0);
+        press(&mut app, KeyCode::Char('+'));
+        assert_eq!(app.endgame.as_ref().unwrap().endgame.to_string(), "KRvK");
+        type_text(&mut app, "philidor");
+        let terminal = draw(&mut app, 120, 34);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("Hold the draw for 25 moves"), "{screen}");
+
+        press(&mut app, KeyCode::Char('q'));
+        assert_eq!(app.state.last(), Some(&AppState::MainMenu));
+        assert_eq!(app.engine_color, None);
+    }
+
+    #[tokio::test]
+    async fn finished_games_are_recorded_and_shown_in_stats() {
+        let dir = std::env::temp_dir().join(format!("chess-tui-stats-{}", std::process::id()));
+        let mut app = App {
+            profiles_path: Some(dir.join("profiles.toml")),
+            ..App::default()
+        };
+        app.start_game(Game::new());
+        assert_eq!(app.pairing.as_ref().unwrap().black, "Guest");
+        let press = |app: &mut App, code| app.handle_key_events(KeyEvent::from(code)).unwrap();
+        for san in ["f3", "e5", "g4", "Qh4#"] {
+            press(&mut app, KeyCode::Char(':'));
+            for c in san.chars() {
+                press(&mut app, KeyCode::Char(c));
+            }
+            press(&mut app, KeyCode::Enter);
+        }
+        assert_eq!(app.pairing, None);
+        assert_eq!(app.stats_message, None);
+
+        app.show_stats();
+        let terminal = draw(&mut app, 120, 30);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("Results of Player"), "{screen}");
+        assert!(screen.contains("Player: 1"), "{screen}");
+        assert!(screen.contains("1 game"), "{screen}");
+        assert!(screen.contains("As White: +0 -1 =0"), "{screen}");
+        assert!(screen.contains("Guest: +0 -1 =0"), "{screen}");
+
+        press(&mut app, KeyCode::Down);
+        let terminal = draw(&mut app, 120, 30);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("As Black: +1 -0 =0"), "{screen}");
+
+        // Engine games are against the built-in engine, even with an external one configured
+        app.config.engine.path = Some("/usr/bin/stockfish".into());
+        app.start_game(Game::new());
+        app.play_against_engine(pieces::Color::Black);
+        let pairing = app.pairing.as_ref().unwrap();
+        let level = app.config.engine.strength;
+        assert_eq!(pairing.black, format!("Built-in engine level {level}"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn abandoned_games_do_not_flag_or_get_recorded() {
+        let dir = std::env::temp_dir().join(format!("chess-tui-abandon-{}", std::process::id()));
+        let path = dir.join("profiles.toml");
+        let mut app = App {
+            profiles_path: Some(path.clone()),
+            ..App::default()
+        };
+        app.config.time_control = Some("0.001+0".parse().unwrap());
+        app.state.push(AppState::NewGame);
+        app.start_game(Game::new());
+        assert!(app.clock.is_some() && app.pairing.is_some());
+
+        app.handle_key_events(KeyEvent::from(KeyCode::Char('q')))
+            .unwrap();
+        assert_eq!(app.state.last(), Some(&AppState::MainMenu));
+        std::thread::sleep(Duration::from_millis(100));
+        app.tick();
+        assert!(!app.game.as_ref().unwrap().is_game_over());
+        assert_eq!(Profiles::load_from(&path).unwrap(), Profiles::default());
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn games_opened_over_the_main_menu_return_to_it() {
+        let mut app = App::default();
+        app.start_game(Game::new());
+        assert_eq!(app.state, [AppState::MainMenu, AppState::Game]);
+        app.handle_key_events(KeyEvent::from(KeyCode::Char('q')))
+            .unwrap();
+        assert_eq!(app.state, [AppState::MainMenu]);
+        assert!(app.running);
+    }
+
+    #[tokio::test]
+    async fn new_games_are_played_by_hand_unless_the_engine_is_asked_for() {
+        let mut app = App::default();
+        app.state.push(AppState::NewGame);
+        app.start_game(Game::new());
+        app.play_against_engine(pieces::Color::Black);
+        assert_eq!(app.engine_color, Some(pieces::Color::Black));
+        app.handle_key_events(KeyEvent::from(KeyCode::Char('q')))
+            .unwrap();
+        assert_eq!(app.engine_color, None);
+
+        // Even a game started straight from another one leaves the engine out
+        app.state.push(AppState::NewGame);
+        app.start_game(Game::new());
+        app.play_against_engine(pieces::Color::White);
+        app.start_game(Game::new());
+        assert_eq!((app.engine_color, app.engine_thinking), (None, false));
+        assert_eq!(app.pairing.as_ref().unwrap().black, "Guest");
+    }
+
+    #[tokio::test]
+    async fn settings_rebind_one_action_at_a_time() {
+        let dir = std::env::temp_dir().join(format!("chess-tui-settings-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        let mut app = App {
+            config_path: Some(path.clone()),
+            ..App::default()
+        };
+        app.state.push(AppState::Settings);
+        let press = |app: &mut App, code| app.handle_key_events(KeyEvent::from(code)).unwrap();
+        let row = SettingsRow::iter().position(|row| row == SettingsRow::Rebind);
+        for _ in 0..row.unwrap() {
+            press(&mut app, KeyCode::Down);
+        }
+        let flip = (Scope::Game, Action::Flip);
+        app.rebind_index = keymap::bindable().iter().position(|&b| b == flip).unwrap();
+        let terminal = draw(&mut app, 120, 30);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("Rebind: ◀ Game flip: f ▶"), "{screen}");
+
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Char('F'));
+        assert_eq!(app.keymap.describe(Scope::Game, Action::Flip), "F");
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            saved.contains("[key_bindings.game]\nflip = \"F\""),
+            "{saved}"
+        );
+        let terminal = draw(&mut app, 120, 30);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(
+            screen.contains("Key bindings: ◀ Default + 1 custom ▶"),
+            "{screen}"
+        );
+
+        // A key taken by another action is refused, and Delete restores the default
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Char(':'));
+        assert!(
+            app.settings_message
+                .as_ref()
+                .unwrap()
+                .contains("Key not bound")
+        );
+        assert_eq!(app.keymap.describe(Scope::Game, Action::Flip), "F");
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Delete);
+        assert_eq!(app.keymap.describe(Scope::Game, Action::Flip), "f");
+        assert!(app.config.key_bindings.is_empty());
+
+        press(&mut app, KeyCode::Right);
+        assert_eq!(
+            keymap::bindable()[app.rebind_index],
+            (Scope::Game, Action::Command)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn eval_bar_and_graph_show_evaluations() {
+        let mut app = game_app("4k3/8/8/8/8/8/8/3QK3 w - - 0 1");
+        let fen = app.game.as_ref().unwrap().to_fen();
+        app.evaluations.insert(fen, Score::Mate(6));
+        let terminal = draw(&mut app, 60, 30);
+        let screen = screen_lines(terminal.backend().buffer());
+        assert!(screen[24].starts_with("#6"), "{screen:?}");
+
+        let pgn =
+            crate::pgn::PgnGame::parse("1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0").unwrap();
+        let mut app = App::default();
+        app.start_replay(pgn).unwrap();
+        assert_eq!(app.replay_positions.len(), 8);
+        let last = app.replay_positions[7].clone();
+        app.evaluations.insert(last, Score::Mate(1));
+        draw(&mut app, 90, 40);
+        let area = app.graph_area.unwrap();
+        app.handle_mouse_events(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column: area.right() - 1,
+            row: area.y,
+            modifiers: KeyModifiers::NONE,
+        });
+        assert_eq!(app.replay.as_ref().unwrap().ply(), 7);
+        let terminal = draw(&mut app, 90, 40);
+        let cell = &terminal.backend().buffer()[(area.right() - 1, area.y + area.height / 2)];
+        assert_eq!(cell.fg, Color::Yellow);
+    }
+
+    #[tokio::test]
+    async fn finished_game_shows_and_saves_its_report() {
+        let mut app = game_app("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let press = |app: &mut App, code| app.handle_key_events(KeyEvent::from(code)).unwrap();
+        let command = |app: &mut App, text: &str| {
+            press(app, KeyCode::Char(':'));
+            for c in text.chars() {
+                press(app, KeyCode::Char(c));
+            }
+            press(app, KeyCode::Enter);
+        };
+        for san in ["f3", "e5", "g4", "Qh4#"] {
+            command(&mut app, san);
+        }
+        assert!(app.report_stop.is_some());
+        let game = app.game.clone().unwrap();
+        let report = Report::new(&game, &SearchLimits::depth(2), |_, _| {}).unwrap();
+        app.report_stop = None;
+        app.report_progress = None;
+        app.report = Some(report);
+        let terminal = draw(&mut app, 100, 30);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("2. g4?? (best"), "{screen}");
+        assert!(screen.contains("1 blunder"), "{screen}");
+        assert!(screen.contains("save the annotated game"), "{screen}");
+
+        let path =
+            std::env::temp_dir().join(format!("chess-tui-report-{}.pgn", std::process::id()));
+        command(&mut app, path.to_str().unwrap());
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(saved.contains("2. g4 $4"), "{saved}");
+        assert!(
+            app.report_message
+                .as_deref()
+                .unwrap()
+                .starts_with("Saved to")
+        );
+    }
+
+    #[tokio::test]
+    async fn replay_shows_annotations_and_variations() {
+        let pgn = crate::pgn::PgnGame::parse(
+            "[White \"Anderssen\"]\n\n1. e4 e5 2. f4 $1 {King's Gambit} (2. Nf3 Nc6) exf4 *",
+        )
+        .unwrap();
+        let mut app = App::default();
+        app.start_replay(pgn).unwrap();
+        let press = |app: &mut App, code| app.handle_key_events(KeyEvent::from(code)).unwrap();
+        press(&mut app, KeyCode::End);
+        press(&mut app, KeyCode::Left);
+        let terminal = draw(&mut app, 90, 30);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("Anderssen - ?"), "{screen}");
+        assert!(screen.contains("2. f4!"), "{screen}");
+        assert!(screen.contains("King's Gambit"), "{screen}");
+
+        press(&mut app, KeyCode::Left);
+        press(&mut app, KeyCode::Down);
+        let terminal = draw(&mut app, 90, 30);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("2. Nf3"), "{screen}");
+        assert!(screen.contains("In a variation"), "{screen}");
+
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.state.last(), Some(&AppState::MainMenu));
+    }
+
+    #[tokio::test]
+    async fn database_lists_filters_searches_and_opens_games() {
+        let path =
+            std::env::temp_dir().join(format!("chess-tui-browse-{}.pgn", std::process::id()));
+        std::fs::write(
+            &path,
+            "[White \"Tal\"]\n[Black \"Botvinnik\"]\n[Date \"1960.03.15\"]\n\n1. e4 1-0\n\n\
+             [White \"Fischer\"]\n[Black \"Spassky\"]\n[Date \"1972.07.11\"]\n\n1. d4 d5 1/2-1/2\n",
+        )
+        .unwrap();
+        let mut app = App::default();
+        app.open_database(crate::database::Database::open(&path).unwrap());
+        let press = |app: &mut App, code| app.handle_key_events(KeyEvent::from(code)).unwrap();
+
+        let terminal = draw(&mut app, 90, 20);
+        let lines = screen_lines(terminal.backend().buffer());
+        assert!(lines[0].contains("2 of 2 games"), "{}", lines[0]);
+        assert!(lines[1].contains("Date▲"), "{}", lines[1]);
+        assert!(lines[2].contains("Tal"), "{}", lines[2]);
+
+        press(&mut app, KeyCode::Char('r'));
+        for c in "/p:fisch".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        press(&mut app, KeyCode::Enter);
+        let terminal = draw(&mut app, 90, 20);
+        let lines = screen_lines(terminal.backend().buffer());
+        assert!(lines[0].contains("1 of 2 games"), "{}", lines[0]);
+        assert!(lines[1].contains("Date▼"), "{}", lines[1]);
+        assert!(lines[2].contains("Spassky"), "{}", lines[2]);
+
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.state.last(), Some(&AppState::Replay));
+        assert_eq!(app.replay.as_ref().unwrap().pgn().moves.len(), 2);
+        press(&mut app, KeyCode::Esc);
+        assert_eq!(app.state.last(), Some(&AppState::Database));
+
+        press(&mut app, KeyCode::Char('s'));
+        for c in "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        press(&mut app, KeyCode::Enter);
+        let terminal = draw(&mut app, 90, 20);
+        let screen = screen_lines(terminal.backend().buffer()).join("\n");
+        assert!(screen.contains("1 of 2 games match"), "{screen}");
+        assert!(screen.contains("1. d4"), "{screen}");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.replay.as_ref().unwrap().ply(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,397 @@
+use std::fmt::Debug;
+
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use crate::game::{Board, Game, Move, Outcome, STANDARD_FEN};
+use crate::pieces::{Color, PieceType};
+
+mod antichess;
+mod atomic;
+mod capablanca;
+mod crazyhouse;
+mod grand;
+mod horde;
+mod king_of_the_hill;
+mod three_check;
+
+pub use antichess::Antichess;
+pub use atomic::Atomic;
+pub use capablanca::Capablanca;
+pub use crazyhouse::Crazyhouse;
+pub use grand::Grand;
+pub use horde::Horde;
+pub use king_of_the_hill::KingOfTheHill;
+pub use three_check::ThreeCheck;
+
+const STANDARD_PIECES: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+const STANDARD_PROMOTIONS: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+/// A rule set layered on top of the standard move generator in [`Game`].
+///
+/// Every hook has a default matching orthodox chess, so a variant only overrides what it changes.
+pub trait Variant: Debug + Sync {
+    fn name(&self) -> &'static str;
+
+    /// FEN of the position a new game starts from.
+    fn starting_fen(&self) -> &'static str {
+        STANDARD_FEN
+    }
+
+    /// Piece types that may stand on this variant's board, in `PIECE_TABLE` order.
+    fn piece_types(&self) -> &'static [PieceType] {
+        &STANDARD_PIECES
+    }
+
+    /// Piece types a pawn may promote to.
+    fn promotion_types(&self) -> &'static [PieceType] {
+        &STANDARD_PROMOTIONS
+    }
+
+    /// Moves available before king safety is taken into account.
+    fn pseudo_legal_moves(&self, game: &Game) -> Vec<Move> {
+        game.generate_pseudo_legal_moves()
+    }
+
+    /// Moves that do not leave the mover's king in check.
+    fn legal_moves(&self, game: &Game) -> Vec<Move> {
+        let color = game.get_current_turn();
+        self.pseudo_legal_moves(game)
+            .into_iter()
+            .filter(|mv| {
+                let mut board = game.get_board().clone();
+                board.apply_move(mv);
+                !self.is_in_check(&board, color)
+            })
+            .collect()
+    }
+
+    /// Whether a king of `color` standing on `square` would be in check.
+    fn is_king_square_attacked(&self, board: &Board, square: (usize, usize), color: Color) -> bool {
+        board.is_square_attacked(square, color.opposite())
+    }
+
+    fn is_in_check(&self, board: &Board, color: Color) -> bool {
+        board
+            .king_square(color)
+            .is_some_and(|square| self.is_king_square_attacked(board, square, color))
+    }
+
+    /// Extra effects of a move once the board has been updated, before the turn passes.
+    fn after_move(&self, _game: &mut Game, _mv: &Move) {}
+
+    /// A win or draw that ends the game regardless of the moves available.
+    fn variant_outcome(&self, _game: &Game) -> Option<Outcome> {
+        None
+    }
+
+    /// The result when the side to move has no legal moves.
+    fn no_moves_outcome(&self, game: &Game) -> Outcome {
+        if game.is_in_check() {
+            Outcome::win(game.get_current_turn().opposite(), "checkmate")
+        } else {
+            Outcome::draw("stalemate")
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Standard;
+
+impl Variant for Standard {
+    fn name(&self) -> &'static str {
+        "Standard"
+    }
+}
+
+/// The rule sets offered when starting a new game.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, EnumIter, serde::Serialize, serde::Deserialize,
+)]
+pub enum VariantKind {
+    #[default]
+    Standard,
+    KingOfTheHill,
+    ThreeCheck,
+    Antichess,
+    Atomic,
+    Horde,
+    Crazyhouse,
+    Capablanca,
+    Grand,
+}
+
+impl VariantKind {
+    pub fn rules(&self) -> &'static dyn Variant {
+        match self {
+            VariantKind::Standard => &Standard,
+            VariantKind::KingOfTheHill => &KingOfTheHill,
+            VariantKind::ThreeCheck => &ThreeCheck,
+            VariantKind::Antichess => &Antichess,
+            VariantKind::Atomic => &Atomic,
+            VariantKind::Horde => &Horde,
+            VariantKind::Crazyhouse => &Crazyhouse,
+            VariantKind::Capablanca => &Capablanca,
+            VariantKind::Grand => &Grand,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.rules().name()
+    }
+
+    /// Looks a variant up by name, ignoring case, spaces and dashes, so `three-check`,
+    /// `ThreeCheck` and the PGN `Variant` tag value `Three-check` all match. `chess` and `koth`
+    /// are accepted too.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let normalize = |name: &str| -> String {
+            name.chars()
+                .filter(char::is_ascii_alphanumeric)
+                .map(|c| c.to_ascii_lowercase())
+                .collect()
+        };
+        let wanted = normalize(name);
+        match wanted.as_str() {
+            "chess" | "standard" | "fromposition" => return Some(VariantKind::Standard),
+            "koth" => return Some(VariantKind::KingOfTheHill),
+            "3check" => return Some(VariantKind::ThreeCheck),
+            "grand" => return Some(VariantKind::Grand),
+            _ => {}
+        }
+        VariantKind::iter().find(|variant| normalize(variant.name()) == wanted)
+    }
+}
+
+impl std::str::FromStr for VariantKind {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_name(s).ok_or_else(|| color_eyre::eyre::eyre!("unknown variant `{s}`"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+
+    use super::{Capablanca, Grand, Variant, VariantKind};
+    use crate::game::Game;
+    use crate::notation;
+    use crate::pieces::{Color, PIECE_TABLE, PieceType};
+
+    fn assert_perft(variant: VariantKind, fen: &str, expected: &[u64]) {
+        let game = Game::from_fen(fen, variant).unwrap();
+        for (depth, &nodes) in expected.iter().enumerate() {
+            assert_eq!(
+                game.perft(depth as u32 + 1),
+                nodes,
+                "{fen} at depth {}",
+                depth + 1
+            );
+        }
+    }
+
+    #[test]
+    fn perft_standard() {
+        let game = Game::new();
+        assert_eq!(game.perft(4), 197_281);
+        assert_perft(
+            VariantKind::Standard,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
+            &[48, 2039],
+        );
+        assert_perft(
+            VariantKind::Standard,
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -",
+            &[14, 191, 2812],
+        );
+        assert_perft(
+            VariantKind::Standard,
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ -",
+            &[44, 1486],
+        );
+    }
+
+    #[test]
+    fn draws_at_fivefold_repetition_and_the_75_move_rule() {
+        let mut game = Game::new();
+        for cycle in 1..=4 {
+            assert!(!game.is_game_over(), "over after {} cycles", cycle - 1);
+            for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+                game.make_move(notation::parse_move(&game, san).unwrap());
+            }
+        }
+        assert_eq!(game.repetitions(), 5);
+        assert_eq!(game.outcome().unwrap().reason, "fivefold repetition");
+
+        // Fifty moves without a capture or pawn move only allow a claim
+        let mut game =
+            Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80", VariantKind::Standard).unwrap();
+        game.make_move(notation::parse_move(&game, "Ra2").unwrap());
+        assert!(!game.is_game_over());
+        let mut game =
+            Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 149 80", VariantKind::Standard).unwrap();
+        game.make_move(notation::parse_move(&game, "Ra2").unwrap());
+        assert_eq!(game.outcome().unwrap().reason, "75-move rule");
+    }
+
+    #[test]
+    fn perft_king_of_the_hill() {
+        assert_eq!(
+            Game::with_variant(VariantKind::KingOfTheHill).perft(4),
+            197_281
+        );
+    }
+
+    #[test]
+    fn perft_three_check() {
+        assert_eq!(
+            Game::with_variant(VariantKind::ThreeCheck).perft(4),
+            197_281
+        );
+        assert_perft(
+            VariantKind::ThreeCheck,
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 1+1",
+            &[26, 562, 13410],
+        );
+    }
+
+    #[test]
+    fn perft_antichess() {
+        assert_eq!(Game::with_variant(VariantKind::Antichess).perft(4), 153_299);
+        assert_perft(
+            VariantKind::Antichess,
+            "8/2p5/8/8/8/8/P7/8 w - -",
+            &[2, 4, 4, 4, 4, 4, 4, 4, 12, 36],
+        );
+    }
+
+    #[test]
+    fn perft_atomic() {
+        assert_eq!(Game::with_variant(VariantKind::Atomic).perft(4), 197_326);
+        assert_perft(
+            VariantKind::Atomic,
+            "rn2kb1r/1pp1p2p/p2q1pp1/3P4/2P3b1/4PN2/PP3PPP/R2QKB1R b KQkq -",
+            &[40, 1238],
+        );
+    }
+
+    #[test]
+    fn perft_horde() {
+        assert_eq!(Game::with_variant(VariantKind::Horde).perft(4), 23_310);
+        assert_perft(
+            VariantKind::Horde,
+            "k7/5p2/4p2P/3p2P1/2p2P2/1p2P2P/p2P2P1/2P2P2 w - -",
+            &[13, 172, 2205],
+        );
+    }
+
+    #[test]
+    fn perft_crazyhouse() {
+        assert_eq!(Game::with_variant(VariantKind::Crazyhouse).perft(3), 8902);
+        assert_perft(
+            VariantKind::Crazyhouse,
+            "2k5/8/8/8/8/8/8/4K3[QRBNPqrbnp] w - -",
+            &[301],
+        );
+        assert_perft(
+            VariantKind::Crazyhouse,
+            "2k5/8/8/8/8/8/8/4K3[Qn] w - -",
+            &[67, 3083],
+        );
+        assert_perft(
+            VariantKind::Crazyhouse,
+            "r1bqk2r/pppp1ppp/2n1p3/4P3/1b1Pn3/2NB1N2/PPP2PPP/R1BQK2R[] b KQkq -",
+            &[42, 1347],
+        );
+        assert_perft(
+            VariantKind::Crazyhouse,
+            "4k3/1Q~6/8/8/4b3/8/Kpp5/8/ b - - 0 1",
+            &[20, 360, 5445],
+        );
+    }
+
+    #[test]
+    fn crazyhouse_fen_round_trip() {
+        let fen = "r1bqk2r/pppp1ppp/2n1p3/4P3/1b1Pn3/2NB1N2/PPP2PPQ~/R1B1K2R[RNPqp] b KQkq - 0 9";
+        let game = Game::from_fen(fen, VariantKind::Crazyhouse).unwrap();
+        assert_eq!(game.to_fen(), fen);
+    }
+
+    #[test]
+    fn perft_capablanca() {
+        assert_perft(
+            VariantKind::Capablanca,
+            Capablanca.starting_fen(),
+            &[28, 784, 25_228],
+        );
+    }
+
+    #[test]
+    fn perft_grand() {
+        assert_perft(
+            VariantKind::Grand,
+            Grand.starting_fen(),
+            &[65, 4225, 259_514],
+        );
+    }
+
+    #[test]
+    fn variants_use_pieces_from_the_table() {
+        for (index, def) in PIECE_TABLE.iter().enumerate() {
+            assert_eq!(def.piece_type as usize, index + 1);
+            assert_eq!(PieceType::from_char(def.letter), Some(def.piece_type));
+        }
+        for variant in VariantKind::iter() {
+            let rules = variant.rules();
+            let start = Game::with_variant(variant);
+            let board = start.get_board();
+            for (_, piece) in board.pieces(Color::White).chain(board.pieces(Color::Black)) {
+                assert!(rules.piece_types().contains(&piece.piece_type));
+            }
+            for promotion in rules.promotion_types() {
+                assert!(rules.piece_types().contains(promotion), "{variant:?}");
+            }
+        }
+
+        let archbishop = "4k3/8/8/8/8/8/8/A3K3 w - -";
+        assert!(Game::from_fen(archbishop, VariantKind::Standard).is_err());
+        let archbishop = "4k5/10/10/10/10/10/10/A3K5 w - -";
+        let game = Game::from_fen(archbishop, VariantKind::Capablanca).unwrap();
+        // Knight jumps and bishop rides from a1, plus the king's five steps
+        assert_eq!(game.legal_moves().len(), 2 + 7 + 5);
+    }
+
+    #[test]
+    fn fen_board_size_is_bounded() {
+        for fen in [
+            "kK w - -",
+            "k/K w - -",
+            "99999999999999999999k/K w - -",
+            "999999k/K w - -",
+            "k26/K26 w - -",
+            &["k", "K"].repeat(50).join("/"),
+        ] {
+            assert!(Game::from_fen(fen, VariantKind::Standard).is_err(), "{fen}");
+        }
+        // Too narrow to castle, so the rights are dropped rather than looked up off the board
+        let game = Game::from_fen("k1/K1 w KQkq - 0 1", VariantKind::Standard).unwrap();
+        assert_eq!(game.to_fen(), "k1/K1 w - - 0 1");
+        assert_eq!(game.perft(1), 1);
+        let widest = Game::from_fen("k25/K25 w - -", VariantKind::Standard).unwrap();
+        assert_eq!(widest.get_board().square_name((0, 25)), "z2");
+    }
+}
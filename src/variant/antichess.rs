@@ -0,0 +1,47 @@
+use super::Variant;
+use crate::game::{Board, Game, Move, Outcome};
+use crate::pieces::{Color, PieceType};
+
+/// Losing chess: captures are compulsory, the king has no royal status and whoever runs out of
+/// pieces or moves wins.
+#[derive(Debug)]
+pub struct Antichess;
+
+const PROMOTIONS: [PieceType; 5] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::King,
+];
+
+impl Variant for Antichess {
+    fn name(&self) -> &'static str {
+        "Antichess"
+    }
+
+    fn starting_fen(&self) -> &'static str {
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1"
+    }
+
+    fn promotion_types(&self) -> &'static [PieceType] {
+        &PROMOTIONS
+    }
+
+    fn legal_moves(&self, game: &Game) -> Vec<Move> {
+        let moves = self.pseudo_legal_moves(game);
+        if moves.iter().any(Move::is_capture) {
+            moves.into_iter().filter(Move::is_capture).collect()
+        } else {
+            moves
+        }
+    }
+
+    fn is_in_check(&self, _board: &Board, _color: Color) -> bool {
+        false
+    }
+
+    fn no_moves_outcome(&self, game: &Game) -> Outcome {
+        Outcome::win(game.get_current_turn(), "no moves left")
+    }
+}
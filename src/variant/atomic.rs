@@ -0,0 +1,78 @@
+use super::Variant;
+use crate::game::{Board, Game, Move, Outcome};
+use crate::pieces::{Color, KING_OFFSETS, PieceType};
+
+/// Every capture sets off an explosion that removes the capturing piece and all non-pawn pieces
+/// around the target square. Exploding the enemy king wins.
+#[derive(Debug)]
+pub struct Atomic;
+
+impl Atomic {
+    /// Removes the pieces caught in the blast of a capture on `mv.to`.
+    fn explode(board: &mut Board, mv: &Move) {
+        board.set_piece_at(mv.to, None);
+        for (dr, dc) in KING_OFFSETS {
+            if let Some(square) = board.offset(mv.to, dr, dc)
+                && board
+                    .get_piece_at(square)
+                    .is_some_and(|piece| piece.piece_type != PieceType::Pawn)
+            {
+                board.set_piece_at(square, None);
+            }
+        }
+    }
+
+    fn kings_touch(board: &Board, square: (usize, usize), color: Color) -> bool {
+        board
+            .king_square(color.opposite())
+            .is_some_and(|enemy| enemy.0.abs_diff(square.0) <= 1 && enemy.1.abs_diff(square.1) <= 1)
+    }
+}
+
+impl Variant for Atomic {
+    fn name(&self) -> &'static str {
+        "Atomic"
+    }
+
+    /// A king next to the enemy king cannot be captured, since the blast would take both.
+    fn is_king_square_attacked(&self, board: &Board, square: (usize, usize), color: Color) -> bool {
+        !Self::kings_touch(board, square, color)
+            && board.is_square_attacked(square, color.opposite())
+    }
+
+    fn legal_moves(&self, game: &Game) -> Vec<Move> {
+        let color = game.get_current_turn();
+        self.pseudo_legal_moves(game)
+            .into_iter()
+            .filter(|mv| {
+                if mv.is_capture() && mv.piece.piece_type == PieceType::King {
+                    return false;
+                }
+                let mut board = game.get_board().clone();
+                board.apply_move(mv);
+                if mv.is_capture() {
+                    Self::explode(&mut board, mv);
+                }
+                if board.king_square(color).is_none() {
+                    return false;
+                }
+                board.king_square(color.opposite()).is_none() || !self.is_in_check(&board, color)
+            })
+            .collect()
+    }
+
+    fn after_move(&self, game: &mut Game, mv: &Move) {
+        if mv.is_capture() {
+            Self::explode(game.board_mut(), mv);
+        }
+    }
+
+    fn variant_outcome(&self, game: &Game) -> Option<Outcome> {
+        [Color::White, Color::Black].into_iter().find_map(|color| {
+            game.get_board()
+                .king_square(color)
+                .is_none()
+                .then(|| Outcome::win(color.opposite(), "king exploded"))
+        })
+    }
+}
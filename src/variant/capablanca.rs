@@ -0,0 +1,44 @@
+use super::Variant;
+use crate::pieces::PieceType;
+
+/// Chess on a 10x8 board with an archbishop and a chancellor added to each army.
+#[derive(Debug)]
+pub struct Capablanca;
+
+const PIECES: [PieceType; 8] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+    PieceType::Archbishop,
+    PieceType::Chancellor,
+];
+
+const PROMOTIONS: [PieceType; 6] = [
+    PieceType::Queen,
+    PieceType::Chancellor,
+    PieceType::Archbishop,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+impl Variant for Capablanca {
+    fn name(&self) -> &'static str {
+        "Capablanca"
+    }
+
+    fn starting_fen(&self) -> &'static str {
+        "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1"
+    }
+
+    fn piece_types(&self) -> &'static [PieceType] {
+        &PIECES
+    }
+
+    fn promotion_types(&self) -> &'static [PieceType] {
+        &PROMOTIONS
+    }
+}
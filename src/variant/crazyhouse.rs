@@ -0,0 +1,62 @@
+use super::Variant;
+use crate::game::{Game, Move};
+use crate::pieces::{Piece, PieceType};
+
+/// Captured pieces change colour and go to the capturer's pocket, from where they can be dropped
+/// onto any empty square instead of making a board move.
+#[derive(Debug)]
+pub struct Crazyhouse;
+
+impl Variant for Crazyhouse {
+    fn name(&self) -> &'static str {
+        "Crazyhouse"
+    }
+
+    fn starting_fen(&self) -> &'static str {
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1"
+    }
+
+    /// Board moves plus a drop of every pocketed piece type onto each empty square. Pawns may
+    /// not be dropped on the first or last rank.
+    fn pseudo_legal_moves(&self, game: &Game) -> Vec<Move> {
+        let mut moves = game.generate_pseudo_legal_moves();
+        let color = game.get_current_turn();
+        let board = game.get_board();
+        let (rows, cols) = board.dimensions();
+        for (piece_type, _) in game.pocket(color).iter() {
+            for row in 0..rows {
+                if piece_type == PieceType::Pawn && (row == 0 || row == rows - 1) {
+                    continue;
+                }
+                for col in 0..cols {
+                    if board.get_piece_at((row, col)).is_none() {
+                        moves.push(Move::drop(Piece::new(color, piece_type), (row, col)));
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    fn after_move(&self, game: &mut Game, mv: &Move) {
+        let mover = mv.piece.color;
+        if mv.is_drop() {
+            game.pocket_mut(mover).remove(mv.piece.piece_type);
+            return;
+        }
+
+        let promoted = game.promoted_mut();
+        let captured_promoted = promoted.remove(&mv.capture_square());
+        if promoted.remove(&mv.from) || mv.promotion.is_some() {
+            promoted.insert(mv.to);
+        }
+        if let Some(captured) = mv.captured {
+            let piece_type = if captured_promoted {
+                PieceType::Pawn
+            } else {
+                captured.piece_type
+            };
+            game.pocket_mut(mover).add(piece_type);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use super::Variant;
+use crate::game::{Game, Move, MoveKind};
+use crate::pieces::{Color, PieceType};
+
+/// Christian Freeling's Grand chess on a 10x10 board. There is no castling, pawns may promote on
+/// the eighth and ninth ranks and must on the tenth, and only to a piece type the player has
+/// already lost.
+#[derive(Debug)]
+pub struct Grand;
+
+const PIECES: [PieceType; 8] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+    PieceType::Archbishop,
+    PieceType::Chancellor,
+];
+
+/// Promotion choices and how many of each a player starts with.
+const PROMOTIONS: [(PieceType, usize); 6] = [
+    (PieceType::Queen, 1),
+    (PieceType::Chancellor, 1),
+    (PieceType::Archbishop, 1),
+    (PieceType::Rook, 2),
+    (PieceType::Bishop, 2),
+    (PieceType::Knight, 2),
+];
+
+const PROMOTION_TYPES: [PieceType; 6] = [
+    PieceType::Queen,
+    PieceType::Chancellor,
+    PieceType::Archbishop,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
+impl Grand {
+    /// Piece types `color` has fewer of on the board than it started with.
+    fn available_promotions(game: &Game, color: Color) -> Vec<PieceType> {
+        PROMOTIONS
+            .iter()
+            .filter(|&&(piece_type, initial)| {
+                game.get_board()
+                    .pieces(color)
+                    .filter(|(_, piece)| piece.piece_type == piece_type)
+                    .count()
+                    < initial
+            })
+            .map(|&(piece_type, _)| piece_type)
+            .collect()
+    }
+}
+
+impl Variant for Grand {
+    fn name(&self) -> &'static str {
+        "Grand chess"
+    }
+
+    fn starting_fen(&self) -> &'static str {
+        "r8r/1nbqkcabn1/pppppppppp/10/10/10/10/PPPPPPPPPP/1NBQKCABN1/R8R w - - 0 1"
+    }
+
+    fn piece_types(&self) -> &'static [PieceType] {
+        &PIECES
+    }
+
+    fn promotion_types(&self) -> &'static [PieceType] {
+        &PROMOTION_TYPES
+    }
+
+    fn pseudo_legal_moves(&self, game: &Game) -> Vec<Move> {
+        let color = game.get_current_turn();
+        let board = game.get_board();
+        let available = Self::available_promotions(game, color);
+        let last_rank = board.back_rank(color.opposite());
+        let mut moves = vec![];
+
+        for mv in game.generate_pseudo_legal_moves() {
+            if mv.piece.piece_type != PieceType::Pawn {
+                moves.push(mv);
+                continue;
+            }
+            match mv.promotion {
+                Some(promotion) => {
+                    if available.contains(&promotion) {
+                        moves.push(mv);
+                    }
+                }
+                None => {
+                    moves.push(mv);
+                    // The eighth and ninth ranks form an optional promotion zone
+                    if mv.to.0.abs_diff(last_rank) <= 2 {
+                        moves.extend(available.iter().map(|&promotion| Move {
+                            promotion: Some(promotion),
+                            ..mv
+                        }));
+                    }
+                }
+            }
+        }
+
+        // Pawns start on the third rank and may advance two squares from there
+        let direction = color.pawn_direction();
+        let start_row = (board.back_rank(color) as isize + 2 * direction) as usize;
+        for (from, piece) in board.pieces(color) {
+            if piece.piece_type != PieceType::Pawn || from.0 != start_row {
+                continue;
+            }
+            if let Some(step) = board.offset(from, direction, 0)
+                && let Some(double) = board.offset(step, direction, 0)
+                && board.get_piece_at(step).is_none()
+                && board.get_piece_at(double).is_none()
+            {
+                moves.push(Move {
+                    kind: MoveKind::DoublePush,
+                    ..Move::new(piece, from, double)
+                });
+            }
+        }
+
+        moves
+    }
+}
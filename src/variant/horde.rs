@@ -0,0 +1,50 @@
+use super::Variant;
+use crate::game::{Game, Move, Outcome};
+use crate::pieces::{Color, PieceType};
+
+/// Thirty-six white pawns against a normal black army. White wins by checkmate, black by
+/// capturing every white piece.
+#[derive(Debug)]
+pub struct Horde;
+
+impl Variant for Horde {
+    fn name(&self) -> &'static str {
+        "Horde"
+    }
+
+    fn starting_fen(&self) -> &'static str {
+        "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1"
+    }
+
+    /// White pawns on the first rank may also advance two squares. Such a push does not open an
+    /// en passant square.
+    fn pseudo_legal_moves(&self, game: &Game) -> Vec<Move> {
+        let mut moves = game.generate_pseudo_legal_moves();
+        let board = game.get_board();
+        let row = board.back_rank(Color::White);
+        if game.get_current_turn() == Color::White {
+            for col in 0..board.dimensions().1 {
+                let from = (row, col);
+                let Some(&piece) = board.get_piece_at(from) else {
+                    continue;
+                };
+                if piece.color == Color::White
+                    && piece.piece_type == PieceType::Pawn
+                    && board.get_piece_at((row - 1, col)).is_none()
+                    && board.get_piece_at((row - 2, col)).is_none()
+                {
+                    moves.push(Move::new(piece, from, (row - 2, col)));
+                }
+            }
+        }
+        moves
+    }
+
+    fn variant_outcome(&self, game: &Game) -> Option<Outcome> {
+        if game.get_board().pieces(Color::White).next().is_none() {
+            Some(Outcome::win(Color::Black, "the horde was destroyed"))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use super::Variant;
+use crate::game::{Game, Outcome};
+use crate::pieces::Color;
+
+/// Standard chess, except that bringing your king to one of the four centre squares wins.
+#[derive(Debug)]
+pub struct KingOfTheHill;
+
+const HILL: [(usize, usize); 4] = [(3, 3), (3, 4), (4, 3), (4, 4)];
+
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "King of the Hill"
+    }
+
+    fn variant_outcome(&self, game: &Game) -> Option<Outcome> {
+        [Color::White, Color::Black].into_iter().find_map(|color| {
+            game.get_board()
+                .king_square(color)
+                .filter(|square| HILL.contains(square))
+                .map(|_| Outcome::win(color, "king reached the hill"))
+        })
+    }
+}
@@ -0,0 +1,28 @@
+use super::Variant;
+use crate::game::{Game, Move, Outcome};
+use crate::pieces::Color;
+
+/// Standard chess, except that the first side to give three checks wins.
+#[derive(Debug)]
+pub struct ThreeCheck;
+
+impl Variant for ThreeCheck {
+    fn name(&self) -> &'static str {
+        "Three-check"
+    }
+
+    fn after_move(&self, game: &mut Game, mv: &Move) {
+        let mover = mv.piece.color;
+        if game.get_board().is_in_check(mover.opposite()) {
+            let remaining = game.remaining_checks_mut(mover);
+            *remaining = remaining.saturating_sub(1);
+        }
+    }
+
+    fn variant_outcome(&self, game: &Game) -> Option<Outcome> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .find(|&color| game.remaining_checks(color) == 0)
+            .map(|color| Outcome::win(color, "third check"))
+    }
+}
@@ -0,0 +1,208 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    widgets::Widget,
+};
+
+use crate::engine::Score;
+
+/// Centipawns at which White's share of the eval bar is about three quarters.
+const SCALE_CP: f64 = 400.0;
+
+/// How much of the bar White's side fills for a score from White's point of view, from 0 to 1.
+pub fn white_share(score: Score) -> f64 {
+    match score {
+        Score::Mate(moves) if moves > 0 => 1.0,
+        Score::Mate(_) => 0.0,
+        Score::Centipawns(cp) => 1.0 / (1.0 + (-(cp as f64) * 3f64.ln() / SCALE_CP).exp()),
+    }
+}
+
+/// A short label for a score: pawns to one decimal (`+0.4`) or the mate distance (`#3`).
+fn label(score: Score) -> String {
+    match score {
+        Score::Centipawns(cp) => format!("{:+.1}", cp as f64 / 100.0),
+        Score::Mate(moves) => format!("#{moves}"),
+    }
+}
+
+/// A vertical bar split between White and Black by the evaluation, White from the bottom
+/// unless the board is flipped, labelled at the leading side's end.
+#[derive(Debug, Clone, Copy)]
+pub struct EvalBar {
+    /// From White's point of view; `None` while the position is being evaluated.
+    pub score: Option<Score>,
+    pub flipped: bool,
+}
+
+impl Widget for EvalBar {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.is_empty() {
+            return;
+        }
+        let share = self.score.map_or(0.5, white_share);
+        let white_rows = (share * area.height as f64).round() as u16;
+        for offset in 0..area.height {
+            // Rows counted from White's end
+            let y = if self.flipped {
+                area.y + offset
+            } else {
+                area.bottom() - 1 - offset
+            };
+            let color = if offset < white_rows {
+                Color::Gray
+            } else {
+                Color::DarkGray
+            };
+            buf.set_style(
+                Rect::new(area.x, y, area.width, 1),
+                Style::default().bg(color),
+            );
+        }
+        let (text, white_leads) = match self.score {
+            Some(score) => (label(score), share >= 0.5),
+            None => ("…".to_string(), true),
+        };
+        let y = if white_leads != self.flipped {
+            area.bottom() - 1
+        } else {
+            area.y
+        };
+        let (fg, bg) = if white_leads {
+            (Color::Black, Color::Gray)
+        } else {
+            (Color::White, Color::DarkGray)
+        };
+        buf.set_stringn(
+            area.x,
+            y,
+            text,
+            area.width as usize,
+            Style::default().fg(fg).bg(bg),
+        );
+    }
+}
+
+/// The evaluation of every position of a game, White's advantage rising above the middle row
+/// and Black's falling below it, with the shown position highlighted.
+#[derive(Debug, Clone, Copy)]
+pub struct EvalGraph<'a> {
+    /// From White's point of view, one per position; `None` where not evaluated yet.
+    pub scores: &'a [Option<Score>],
+    pub current: Option<usize>,
+}
+
+impl EvalGraph<'_> {
+    /// The position drawn in column `x` of `area` for a graph of `points` positions.
+    pub fn point_at(points: usize, area: Rect, x: u16) -> Option<usize> {
+        if points == 0 || x < area.x || x >= area.right() {
+            return None;
+        }
+        Some((x - area.x) as usize * points / area.width as usize)
+    }
+}
+
+impl Widget for EvalGraph<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.is_empty() {
+            return;
+        }
+        let middle = area.y + area.height / 2;
+        let above = middle - area.y;
+        let below = area.bottom() - 1 - middle;
+        for x in area.x..area.right() {
+            let Some(point) = Self::point_at(self.scores.len(), area, x) else {
+                return;
+            };
+            let highlight = |color| {
+                if self.current == Some(point) {
+                    Color::Yellow
+                } else {
+                    color
+                }
+            };
+            buf.set_string(
+                x,
+                middle,
+                "─",
+                Style::default().fg(highlight(Color::DarkGray)),
+            );
+            let Some(score) = self.scores[point] else {
+                continue;
+            };
+            let advantage = white_share(score) * 2.0 - 1.0;
+            if advantage > 0.0 {
+                let rows = (advantage * above as f64).round() as u16;
+                for y in middle - rows..middle {
+                    buf.set_string(x, y, "█", Style::default().fg(highlight(Color::White)));
+                }
+            } else {
+                let rows = (-advantage * below as f64).round() as u16;
+                for y in middle + 1..=middle + rows {
+                    buf.set_string(x, y, "█", Style::default().fg(highlight(Color::DarkGray)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_follow_the_score() {
+        assert_eq!(white_share(Score::Centipawns(0)), 0.5);
+        assert!((white_share(Score::Centipawns(400)) - 0.75).abs() < 1e-9);
+        assert!(white_share(Score::Centipawns(-150)) < 0.5);
+        assert_eq!(white_share(Score::Mate(3)), 1.0);
+        assert_eq!(white_share(Score::Mate(-2)), 0.0);
+    }
+
+    #[test]
+    fn bar_fills_from_whites_end() {
+        let area = Rect::new(0, 0, 4, 10);
+        let mut buf = Buffer::empty(area);
+        let bar = EvalBar {
+            score: Some(Score::Mate(2)),
+            flipped: false,
+        };
+        bar.render(area, &mut buf);
+        assert_eq!(buf[(0, 0)].bg, Color::Gray);
+        assert_eq!(buf[(0, 9)].symbol(), "#");
+
+        let mut buf = Buffer::empty(area);
+        let bar = EvalBar {
+            score: Some(Score::Centipawns(-400)),
+            flipped: true,
+        };
+        bar.render(area, &mut buf);
+        assert_eq!(buf[(0, 0)].bg, Color::Gray);
+        assert_eq!(buf[(0, 9)].bg, Color::DarkGray);
+        assert_eq!(buf[(0, 9)].symbol(), "-");
+    }
+
+    #[test]
+    fn graph_columns_map_to_positions() {
+        let area = Rect::new(10, 0, 20, 5);
+        assert_eq!(EvalGraph::point_at(5, area, 10), Some(0));
+        assert_eq!(EvalGraph::point_at(5, area, 13), Some(0));
+        assert_eq!(EvalGraph::point_at(5, area, 14), Some(1));
+        assert_eq!(EvalGraph::point_at(5, area, 29), Some(4));
+        assert_eq!(EvalGraph::point_at(5, area, 30), None);
+        assert_eq!(EvalGraph::point_at(100, area, 29), Some(95));
+
+        let scores = [Some(Score::Centipawns(900)), None, Some(Score::Mate(-1))];
+        let mut buf = Buffer::empty(area);
+        let graph = EvalGraph {
+            scores: &scores,
+            current: Some(1),
+        };
+        graph.render(area, &mut buf);
+        assert_eq!(buf[(10, 0)].symbol(), "█");
+        assert_eq!(buf[(20, 2)].fg, Color::Yellow);
+        assert_eq!(buf[(25, 4)].symbol(), "█");
+        assert_eq!(buf[(25, 1)].symbol(), " ");
+    }
+}
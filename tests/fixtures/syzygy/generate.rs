@@ -0,0 +1,1083 @@
+//! Writes the Syzygy tables in this directory: KQvK, KRvK, KPvK, KBvK, KNvK and KRPvK, each
+//! as a `.rtbw` WDL and a `.rtbz` DTZ file.
+//!
+//! Every position is solved by retrograde analysis, then the results are compressed the way
+//! the Syzygy format stores them: symbols paired recursively, Huffman coded into blocks, with
+//! a sparse index and, for DTZ, value maps. The distances follow the usual conventions, so a
+//! mated side has DTZ -1 and a mate in one has DTZ 1. KRPvK promotes into KRQvK, KRRvK, KRBvK
+//! and KRNvK, which are solved too but not written. Not part of the build; run with
+//!
+//! ```sh
+//! rustc -O --edition 2024 tests/fixtures/syzygy/generate.rs -o /tmp/generate
+//! /tmp/generate tests/fixtures/syzygy
+//! ```
+
+use std::collections::{BinaryHeap, HashMap};
+use std::cmp::Reverse;
+
+const PAWN: u8 = 1;
+const KNIGHT: u8 = 2;
+const BISHOP: u8 = 3;
+const ROOK: u8 = 4;
+const QUEEN: u8 = 5;
+const KING: u8 = 6;
+/// Added to a piece code for Black's pieces.
+const BLACK: u8 = 8;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const SINGLE_VALUE: u8 = 128;
+
+const BLOCK_SIZE_LOG: u8 = 6;
+const SPAN_LOG: u8 = 7;
+/// Values a block holds at most, so offsets fit the 16-bit fields.
+const MAX_BLOCK_VALUES: usize = 60_000;
+/// Values a paired symbol expands to at most, as its length is stored in a byte.
+const MAX_SYMBOL_VALUES: usize = 256;
+
+const LOSS: i8 = -2;
+const DRAW: i8 = 0;
+const WIN: i8 = 2;
+
+/// Most pieces, kings included, in the tables solved here.
+const MAX_PIECES: usize = 4;
+
+// Positions: White's king, White's other pieces in table order and Black's king, with the
+// side to move
+fn position(black_to_move: bool, squares: &[usize]) -> usize {
+    squares
+        .iter()
+        .fold(usize::from(black_to_move), |pos, &square| pos * 64 + square)
+}
+
+fn unpack(count: usize, pos: usize) -> (bool, [usize; MAX_PIECES]) {
+    let mut squares = [0; MAX_PIECES];
+    for (i, square) in squares.iter_mut().enumerate().take(count) {
+        *square = (pos >> (6 * (count - 1 - i))) & 63;
+    }
+    (pos >> (6 * count) == 1, squares)
+}
+
+fn positions(count: usize) -> usize {
+    2 << (6 * count)
+}
+
+fn file(square: usize) -> i32 {
+    (square & 7) as i32
+}
+
+fn rank(square: usize) -> i32 {
+    (square >> 3) as i32
+}
+
+fn offset(square: usize, df: i32, dr: i32) -> Option<usize> {
+    let (f, r) = (file(square) + df, rank(square) + dr);
+    ((0..8).contains(&f) && (0..8).contains(&r)).then(|| (r * 8 + f) as usize)
+}
+
+fn adjacent(a: usize, b: usize) -> bool {
+    (file(a) - file(b)).abs() <= 1 && (rank(a) - rank(b)).abs() <= 1
+}
+
+const ORTHOGONAL: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const DIAGONAL: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const JUMPS: [(i32, i32); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const STEPS: [(i32, i32); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+fn directions(kind: u8) -> &'static [(i32, i32)] {
+    const LINES: [(i32, i32); 8] = [
+        (1, 0),
+        (-1, 0),
+        (0, 1),
+        (0, -1),
+        (1, 1),
+        (1, -1),
+        (-1, 1),
+        (-1, -1),
+    ];
+    match kind {
+        QUEEN => &LINES,
+        ROOK => &ORTHOGONAL,
+        BISHOP => &DIAGONAL,
+        _ => &[],
+    }
+}
+
+/// Squares White's piece of `kind` on `from` moves to, stopping at any of `occupied`.
+fn piece_moves(kind: u8, from: usize, occupied: &[usize], moves: &mut Vec<usize>) {
+    if kind == KNIGHT {
+        for (df, dr) in JUMPS {
+            if let Some(to) = offset(from, df, dr)
+                && !occupied.contains(&to)
+            {
+                moves.push(to);
+            }
+        }
+    }
+    for &(df, dr) in directions(kind) {
+        let mut square = from;
+        while let Some(to) = offset(square, df, dr) {
+            if occupied.contains(&to) {
+                break;
+            }
+            moves.push(to);
+            square = to;
+        }
+    }
+}
+
+/// Whether White's piece of `kind` on `from` attacks `target`, with the squares `blocked`
+/// accepts in the way.
+fn attacks(kind: u8, from: usize, target: usize, blocked: impl Fn(usize) -> bool) -> bool {
+    match kind {
+        PAWN => [(-1, 1), (1, 1)]
+            .into_iter()
+            .any(|(df, dr)| offset(from, df, dr) == Some(target)),
+        KNIGHT => JUMPS
+            .into_iter()
+            .any(|(df, dr)| offset(from, df, dr) == Some(target)),
+        _ => directions(kind).iter().any(|&(df, dr)| {
+            let mut square = from;
+            while let Some(to) = offset(square, df, dr) {
+                if to == target {
+                    return true;
+                }
+                if blocked(to) {
+                    return false;
+                }
+                square = to;
+            }
+            false
+        }),
+    }
+}
+
+/// Whether White's pieces other than the king attack `target`, leaving out the piece at index
+/// `captured` of `squares`. Black's king never blocks, as it is the piece that would stand
+/// on `target`.
+fn attacked(pieces: &[u8], squares: &[usize], target: usize, captured: Option<usize>) -> bool {
+    let white = &squares[..=pieces.len()];
+    let blocked = |square: usize| {
+        white
+            .iter()
+            .enumerate()
+            .any(|(i, &s)| s == square && Some(i) != captured)
+    };
+    (1..=pieces.len())
+        .filter(|&i| Some(i) != captured)
+        .any(|i| attacks(pieces[i - 1], squares[i], target, blocked))
+}
+
+/// Whether `pos` can arise in the table of White's king and `pieces` against the bare king.
+fn legal(pieces: &[u8], pos: usize) -> bool {
+    let count = pieces.len() + 2;
+    let (black_to_move, squares) = unpack(count, pos);
+    let squares = &squares[..count];
+    let (wk, bk) = (squares[0], squares[count - 1]);
+    (0..count).all(|i| !squares[..i].contains(&squares[i]))
+        && !adjacent(wk, bk)
+        && pieces
+            .iter()
+            .zip(&squares[1..])
+            .all(|(&kind, &square)| kind != PAWN || (1..7).contains(&rank(square)))
+        && (black_to_move || !attacked(pieces, squares, bk, None))
+}
+
+/// Where a move leads: a position of the same table, or one elsewhere whose WDL, for the side
+/// to move there, is known.
+#[derive(Clone, Copy)]
+enum Child {
+    Same(usize),
+    Known(i8),
+}
+
+struct Move {
+    child: Child,
+    /// A capture or pawn move, which resets the 50-move counter.
+    zeroing: bool,
+}
+
+/// Results of the tables a capture or a promotion leads to, for the side to move there.
+struct Exits<'a> {
+    /// By index of the captured piece in `pieces`; `None` leaves the bare kings.
+    captures: Vec<Option<&'a [i8]>>,
+    /// After promoting to a queen, rook, bishop and knight.
+    promotions: Vec<&'a [i8]>,
+}
+
+const PROMOTIONS: [u8; 4] = [QUEEN, ROOK, BISHOP, KNIGHT];
+
+/// The material left after removing the piece at `index`, or after promoting it to `kind`.
+fn replaced(pieces: &[u8], index: usize, kind: Option<u8>) -> Vec<u8> {
+    let mut pieces = pieces.to_vec();
+    match kind {
+        Some(kind) => pieces[index] = kind,
+        None => {
+            pieces.remove(index);
+        }
+    }
+    pieces
+}
+
+fn moves(pieces: &[u8], pos: usize, exits: &Exits, moves: &mut Vec<Move>) {
+    moves.clear();
+    let count = pieces.len() + 2;
+    let (black_to_move, mut squares) = unpack(count, pos);
+    let (wk, bk) = (squares[0], squares[count - 1]);
+    if black_to_move {
+        for (df, dr) in STEPS {
+            let Some(to) = offset(bk, df, dr) else {
+                continue;
+            };
+            if adjacent(to, wk) {
+                continue;
+            }
+            let captured = (1..=pieces.len()).find(|&i| squares[i] == to);
+            if attacked(pieces, &squares[..count], to, captured) {
+                continue;
+            }
+            let child = match captured {
+                Some(i) => {
+                    let mut left: Vec<usize> = squares[..count].to_vec();
+                    left.remove(i);
+                    *left.last_mut().unwrap() = to;
+                    let value =
+                        exits.captures[i - 1].map_or(DRAW, |wdl| wdl[position(false, &left)]);
+                    Child::Known(value)
+                }
+                None => {
+                    squares[count - 1] = to;
+                    let child = position(false, &squares[..count]);
+                    squares[count - 1] = bk;
+                    Child::Same(child)
+                }
+            };
+            moves.push(Move {
+                child,
+                zeroing: captured.is_some(),
+            });
+        }
+        return;
+    }
+
+    let occupied = squares;
+    let white = &occupied[..count - 1];
+    for (df, dr) in STEPS {
+        if let Some(to) = offset(wk, df, dr)
+            && !white.contains(&to)
+            && !adjacent(to, bk)
+        {
+            squares[0] = to;
+            moves.push(Move {
+                child: Child::Same(position(true, &squares[..count])),
+                zeroing: false,
+            });
+            squares[0] = wk;
+        }
+    }
+    let mut targets = Vec::new();
+    for (i, &kind) in pieces.iter().enumerate() {
+        let from = occupied[i + 1];
+        let mut child = |to: usize| {
+            squares[i + 1] = to;
+            let child = position(true, &squares[..count]);
+            squares[i + 1] = from;
+            child
+        };
+        if kind == PAWN {
+            let push = from + 8;
+            if occupied[..count].contains(&push) {
+                continue;
+            }
+            if rank(push) == 7 {
+                let promoted = child(push);
+                for wdl in &exits.promotions {
+                    moves.push(Move {
+                        child: Child::Known(wdl[promoted]),
+                        zeroing: true,
+                    });
+                }
+            } else {
+                moves.push(Move {
+                    child: Child::Same(child(push)),
+                    zeroing: true,
+                });
+                if rank(from) == 1 && !occupied[..count].contains(&(push + 8)) {
+                    moves.push(Move {
+                        child: Child::Same(child(push + 8)),
+                        zeroing: true,
+                    });
+                }
+            }
+        } else {
+            targets.clear();
+            piece_moves(kind, from, &occupied[..count], &mut targets);
+            for &to in &targets {
+                moves.push(Move {
+                    child: Child::Same(child(to)),
+                    zeroing: false,
+                });
+            }
+        }
+    }
+}
+
+/// Results and, if asked for, distances to zeroing of every legal position of White's king
+/// and `pieces` against the bare king, from the side to move's point of view.
+struct Solution {
+    pieces: Vec<u8>,
+    legal: Vec<bool>,
+    wdl: Vec<i8>,
+    dtz: Vec<i16>,
+}
+
+fn solve(pieces: &[u8], solved: &HashMap<Vec<u8>, Solution>, distances: bool) -> Solution {
+    let count = pieces.len() + 2;
+    let size = positions(count);
+    let table = |pieces: Vec<u8>| (!pieces.is_empty()).then(|| &solved[&pieces].wdl[..]);
+    let exits = Exits {
+        captures: (0..pieces.len())
+            .map(|i| table(replaced(pieces, i, None)))
+            .collect(),
+        promotions: match pieces.iter().position(|&kind| kind == PAWN) {
+            Some(pawn) => PROMOTIONS
+                .iter()
+                .map(|&kind| table(replaced(pieces, pawn, Some(kind))).unwrap())
+                .collect(),
+            None => Vec::new(),
+        },
+    };
+    let legal: Vec<bool> = (0..size).map(|pos| legal(pieces, pos)).collect();
+    let mut buffer = Vec::new();
+    let in_check = |pos: usize| {
+        let (black_to_move, squares) = unpack(count, pos);
+        black_to_move && attacked(pieces, &squares[..count], squares[count - 1], None)
+    };
+
+    // Results, growing the sets of won and lost positions until nothing changes
+    const UNKNOWN: i8 = i8::MIN;
+    let mut wdl = vec![UNKNOWN; size];
+    let mut mated = vec![false; size];
+    for pos in (0..size).filter(|&pos| legal[pos]) {
+        moves(pieces, pos, &exits, &mut buffer);
+        if buffer.is_empty() {
+            mated[pos] = in_check(pos);
+            wdl[pos] = if mated[pos] { LOSS } else { DRAW };
+        }
+    }
+    let child_wdl = |wdl: &[i8], child: Child| match child {
+        Child::Same(pos) => wdl[pos],
+        Child::Known(value) => value,
+    };
+    loop {
+        let mut changed = false;
+        for pos in 0..size {
+            if !legal[pos] || wdl[pos] != UNKNOWN {
+                continue;
+            }
+            moves(pieces, pos, &exits, &mut buffer);
+            let children = buffer.iter().map(|mv| child_wdl(&wdl, mv.child));
+            if children.clone().any(|value| value == LOSS) {
+                wdl[pos] = WIN;
+                changed = true;
+            } else if children.clone().all(|value| value == WIN) {
+                wdl[pos] = LOSS;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    for value in &mut wdl {
+        if *value == UNKNOWN {
+            *value = DRAW;
+        }
+    }
+    if !distances {
+        return Solution {
+            pieces: pieces.to_vec(),
+            legal,
+            wdl,
+            dtz: Vec::new(),
+        };
+    }
+
+    // Distances, one ply at a time so each position settles at its own distance
+    let mut dtz = vec![0i16; size];
+    let mut settled: Vec<bool> = (0..size)
+        .map(|pos| !legal[pos] || wdl[pos] == DRAW)
+        .collect();
+    let mut ply = 1;
+    while settled.iter().any(|&done| !done) {
+        let before = settled.clone();
+        for pos in 0..size {
+            if before[pos] {
+                continue;
+            }
+            moves(pieces, pos, &exits, &mut buffer);
+            if wdl[pos] == WIN {
+                let best = buffer
+                    .iter()
+                    .filter(|mv| child_wdl(&wdl, mv.child) == LOSS)
+                    .filter_map(|mv| match mv.child {
+                        _ if mv.zeroing => Some(1),
+                        Child::Same(child) if mated[child] => Some(1),
+                        Child::Same(child) if before[child] => Some(-dtz[child] + 1),
+                        _ => None,
+                    })
+                    .min();
+                if let Some(best) = best
+                    && best <= ply
+                {
+                    dtz[pos] = best;
+                    settled[pos] = true;
+                }
+            } else if buffer.is_empty() {
+                dtz[pos] = -1;
+                settled[pos] = true;
+            } else {
+                let longest = buffer
+                    .iter()
+                    .map(|mv| match mv.child {
+                        _ if mv.zeroing => Some(1),
+                        Child::Same(child) if before[child] => Some(dtz[child] + 1),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<i16>>>();
+                if let Some(longest) = longest {
+                    dtz[pos] = -longest.into_iter().max().unwrap_or(1);
+                    settled[pos] = true;
+                }
+            }
+        }
+        ply += 1;
+        assert!(ply < 200, "distances do not settle");
+    }
+    Solution {
+        pieces: pieces.to_vec(),
+        legal,
+        wdl,
+        dtz,
+    }
+}
+
+/// Square numberings of the index, as in the reader.
+struct Maps {
+    pawns: [usize; 64],
+    b1h1h7: [usize; 64],
+    a1d1d4: [usize; 64],
+    binomial: [[u64; 64]; 6],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+fn diagonal_offset(square: usize) -> i32 {
+    rank(square) - file(square)
+}
+
+fn maps() -> Maps {
+    let mut maps = Maps {
+        pawns: [0; 64],
+        b1h1h7: [0; 64],
+        a1d1d4: [0; 64],
+        binomial: [[0; 64]; 6],
+        lead_pawn_idx: [[0; 64]; 6],
+        lead_pawns_size: [[0; 4]; 6],
+    };
+    for (code, square) in (0..64).filter(|&s| diagonal_offset(s) < 0).enumerate() {
+        maps.b1h1h7[square] = code;
+    }
+    let triangle: Vec<usize> = (0..=27).filter(|&s| file(s) <= 3).collect();
+    let below = triangle.iter().filter(|&&s| diagonal_offset(s) < 0);
+    let on = triangle.iter().filter(|&&s| diagonal_offset(s) == 0);
+    for (code, &square) in below.chain(on).enumerate() {
+        maps.a1d1d4[square] = code;
+    }
+    maps.binomial[0][0] = 1;
+    for n in 1..64 {
+        for k in 0..6.min(n + 1) {
+            maps.binomial[k][n] = if k > 0 { maps.binomial[k - 1][n - 1] } else { 0 }
+                + if k < n { maps.binomial[k][n - 1] } else { 0 };
+        }
+    }
+    let mut available = 48;
+    for lead in 1..6 {
+        for f in 0..4 {
+            let mut idx = 0;
+            for r in 1..7 {
+                let square = r * 8 + f;
+                if lead == 1 {
+                    available -= 1;
+                    maps.pawns[square] = available;
+                    available -= 1;
+                    maps.pawns[square ^ 7] = available;
+                }
+                maps.lead_pawn_idx[lead][square] = idx;
+                idx += maps.binomial[lead - 1][maps.pawns[square]];
+            }
+            maps.lead_pawns_size[lead][f] = idx;
+        }
+    }
+    maps
+}
+
+/// The squares of a position in the order `piece_codes` lists the pieces: with a pawn, the
+/// pawn first, then White's king, White's other pieces and Black's king.
+fn table_order(pieces: &[u8], squares: &[usize]) -> Vec<usize> {
+    let count = pieces.len() + 2;
+    match pieces.iter().position(|&kind| kind == PAWN) {
+        Some(pawn) => std::iter::once(squares[pawn + 1])
+            .chain((0..count).filter(|&i| i != pawn + 1).map(|i| squares[i]))
+            .collect(),
+        None => squares[..count].to_vec(),
+    }
+}
+
+/// The index of a position in a table with one pawn, or without pawns in a three-piece
+/// table, and the file table it is in.
+fn index(maps: &Maps, pieces: &[u8], squares: &[usize]) -> (usize, u64) {
+    let mut squares = table_order(pieces, squares);
+    if pieces.contains(&PAWN) {
+        if file(squares[0]) > 3 {
+            squares.iter_mut().for_each(|s| *s ^= 7);
+        }
+        let table = file(squares[0]) as usize;
+        let lead = maps.lead_pawn_idx[1][squares[0]];
+        let size = maps.lead_pawns_size[1][table];
+        // The other pieces follow as groups of one, skipping the squares taken before them
+        let mut index = 0;
+        let mut factor = 1;
+        for i in 1..squares.len() {
+            let below = squares[..i].iter().filter(|&&s| s < squares[i]).count();
+            index += factor * (squares[i] - below) as u64;
+            factor *= 64 - i as u64;
+        }
+        return (table, lead + size * index);
+    }
+
+    assert_eq!(pieces.len(), 1, "pawnless tables are written for three pieces");
+    if file(squares[0]) > 3 {
+        squares.iter_mut().for_each(|s| *s ^= 7);
+    }
+    if rank(squares[0]) > 3 {
+        squares.iter_mut().for_each(|s| *s ^= 56);
+    }
+    if let Some(i) = (0..3).find(|&i| diagonal_offset(squares[i]) != 0)
+        && diagonal_offset(squares[i]) > 0
+    {
+        for square in &mut squares[i..] {
+            *square = ((*square >> 3) | (*square << 3)) & 63;
+        }
+    }
+    let [s0, s1, s2] = squares[..] else {
+        unreachable!()
+    };
+    let adjust1 = usize::from(s1 > s0);
+    let adjust2 = usize::from(s2 > s0) + usize::from(s2 > s1);
+    let index = if diagonal_offset(s0) != 0 {
+        (maps.a1d1d4[s0] * 63 + (s1 - adjust1)) * 62 + s2 - adjust2
+    } else if diagonal_offset(s1) != 0 {
+        (6 * 63 + rank(s0) as usize * 28 + maps.b1h1h7[s1]) * 62 + s2 - adjust2
+    } else if diagonal_offset(s2) != 0 {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + rank(s0) as usize * 7 * 28
+            + (rank(s1) as usize - adjust1) * 28
+            + maps.b1h1h7[s2]
+    } else {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + rank(s0) as usize * 7 * 6
+            + (rank(s1) as usize - adjust1) * 6
+            + (rank(s2) as usize - adjust2)
+    };
+    (0, index as u64)
+}
+
+fn table_sizes(maps: &Maps, pieces: &[u8]) -> Vec<u64> {
+    if pieces.contains(&PAWN) {
+        let others: u64 = (1..pieces.len() as u64 + 2).map(|i| 64 - i).product();
+        (0..4)
+            .map(|f| maps.lead_pawns_size[1][f] * others)
+            .collect()
+    } else {
+        vec![31332]
+    }
+}
+
+/// Symbols paired recursively over a run of values, then Huffman coded into blocks.
+struct Compressed {
+    /// The table's sizes and code, after its flags byte.
+    sizes: Vec<u8>,
+    sparse_index: Vec<u8>,
+    block_lengths: Vec<u8>,
+    data: Vec<u8>,
+}
+
+enum Symbol {
+    Value(u16),
+    Pair(usize, usize),
+}
+
+fn compress(values: &[u16]) -> Compressed {
+    // Pair the most frequent neighbours while that pays
+    let mut symbols: Vec<Symbol> = Vec::new();
+    let mut lengths: Vec<usize> = Vec::new();
+    let mut leaves = HashMap::new();
+    let mut stream: Vec<usize> = values
+        .iter()
+        .map(|&value| {
+            *leaves.entry(value).or_insert_with(|| {
+                symbols.push(Symbol::Value(value));
+                lengths.push(1);
+                symbols.len() - 1
+            })
+        })
+        .collect();
+    while symbols.len() < 4000 {
+        let mut counts: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut i = 0;
+        while i + 1 < stream.len() {
+            let pair = (stream[i], stream[i + 1]);
+            *counts.entry(pair).or_default() += 1;
+            // Runs of one symbol are counted without overlaps
+            i += if pair.0 == pair.1 { 2 } else { 1 };
+        }
+        let best = counts
+            .into_iter()
+            .filter(|&((a, b), _)| lengths[a] + lengths[b] <= MAX_SYMBOL_VALUES)
+            .max_by_key(|&(pair, count)| (count, Reverse(pair)));
+        let Some(((a, b), count)) = best else {
+            break;
+        };
+        if count < 4 {
+            break;
+        }
+        let mut paired = Vec::with_capacity(stream.len());
+        let mut i = 0;
+        while i < stream.len() {
+            if i + 1 < stream.len() && stream[i] == a && stream[i + 1] == b {
+                paired.push(symbols.len());
+                i += 2;
+            } else {
+                paired.push(stream[i]);
+                i += 1;
+            }
+        }
+        let mut distinct = paired.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        if distinct.len() < 2 {
+            break;
+        }
+        symbols.push(Symbol::Pair(a, b));
+        lengths.push(lengths[a] + lengths[b]);
+        stream = paired;
+    }
+
+    // Huffman code lengths of the symbols left in the stream
+    let mut frequency = vec![0usize; symbols.len()];
+    for &sym in &stream {
+        frequency[sym] += 1;
+    }
+    let mut code_len = vec![0u8; symbols.len()];
+    let mut heap: BinaryHeap<Reverse<(usize, usize, Vec<usize>)>> = frequency
+        .iter()
+        .enumerate()
+        .filter(|&(_, &count)| count > 0)
+        .map(|(sym, &count)| Reverse((count, sym, vec![sym])))
+        .collect();
+    while heap.len() > 1 {
+        let Reverse((c1, s1, mut a)) = heap.pop().unwrap();
+        let Reverse((c2, s2, b)) = heap.pop().unwrap();
+        for &sym in a.iter().chain(&b) {
+            code_len[sym] += 1;
+        }
+        a.extend(b);
+        heap.push(Reverse((c1 + c2, s1.min(s2), a)));
+    }
+    let min_len = *code_len.iter().filter(|&&len| len > 0).min().unwrap();
+    let max_len = *code_len.iter().max().unwrap();
+    assert!(max_len <= 32, "codes too long");
+
+    // Canonical numbering: longer codes first, then the symbols only used inside pairs
+    let mut order: Vec<usize> = (0..symbols.len()).collect();
+    order.sort_by_key(|&sym| (Reverse(code_len[sym]), sym));
+    let coded = order.iter().filter(|&&sym| code_len[sym] > 0).count();
+    let mut number = vec![0usize; symbols.len()];
+    for (n, &sym) in order.iter().enumerate() {
+        number[sym] = n;
+    }
+    let lengths_count = usize::from(max_len - min_len) + 1;
+    let mut lowest = vec![0u16; lengths_count];
+    let mut count = vec![0u64; lengths_count];
+    for i in 0..lengths_count {
+        let len = min_len + i as u8;
+        lowest[i] = code_len.iter().filter(|&&l| l > len).count() as u16;
+        count[i] = code_len.iter().filter(|&&l| l == len).count() as u64;
+    }
+    let mut base = vec![0u64; lengths_count];
+    for i in (0..lengths_count - 1).rev() {
+        base[i] = (base[i + 1] + count[i + 1]) / 2;
+    }
+    let code = |sym: usize| {
+        let i = usize::from(code_len[sym] - min_len);
+        (base[i] + (number[sym] - usize::from(lowest[i])) as u64, code_len[sym])
+    };
+
+    // Blocks of whole symbols, written most significant bit first
+    let block_size = 1usize << BLOCK_SIZE_LOG;
+    let mut data = Vec::new();
+    let mut block_values = Vec::new();
+    let mut bits: Vec<bool> = Vec::new();
+    let mut in_block = 0;
+    let flush = |bits: &mut Vec<bool>, data: &mut Vec<u8>| {
+        let mut block = vec![0u8; block_size];
+        for (i, &bit) in bits.iter().enumerate() {
+            if bit {
+                block[i / 8] |= 0x80 >> (i % 8);
+            }
+        }
+        data.extend(block);
+        bits.clear();
+    };
+    for &sym in &stream {
+        let (value, len) = code(sym);
+        if bits.len() + usize::from(len) > block_size * 8 || in_block + lengths[sym] > MAX_BLOCK_VALUES
+        {
+            flush(&mut bits, &mut data);
+            block_values.push(in_block);
+            in_block = 0;
+        }
+        for bit in (0..len).rev() {
+            bits.push(value >> bit & 1 == 1);
+        }
+        in_block += lengths[sym];
+    }
+    flush(&mut bits, &mut data);
+    block_values.push(in_block);
+
+    let span = 1usize << SPAN_LOG;
+    let mut sparse_index = Vec::new();
+    let mut starts = Vec::new();
+    let mut start = 0;
+    for &len in &block_values {
+        starts.push(start);
+        start += len;
+    }
+    for entry in 0..values.len().div_ceil(span) {
+        let middle = entry * span + span / 2;
+        let block = starts
+            .iter()
+            .rposition(|&start| start <= middle.min(values.len() - 1))
+            .unwrap();
+        sparse_index.extend((block as u32).to_le_bytes());
+        sparse_index.extend(u16::try_from(middle - starts[block]).unwrap().to_le_bytes());
+    }
+    let block_lengths = block_values
+        .iter()
+        .flat_map(|&len| (len as u16 - 1).to_le_bytes())
+        .collect();
+
+    let mut sizes = vec![BLOCK_SIZE_LOG, SPAN_LOG, 0];
+    sizes.extend((block_values.len() as u32).to_le_bytes());
+    sizes.extend([max_len, min_len]);
+    for low in &lowest {
+        sizes.extend(low.to_le_bytes());
+    }
+    sizes.extend((symbols.len() as u16).to_le_bytes());
+    let mut tree = vec![[0u8; 3]; symbols.len()];
+    for (sym, symbol) in symbols.iter().enumerate() {
+        let (left, right) = match *symbol {
+            Symbol::Value(value) => (usize::from(value), 0xfff),
+            Symbol::Pair(a, b) => (number[a], number[b]),
+        };
+        tree[number[sym]] = [
+            left as u8,
+            (left >> 8) as u8 | ((right & 0xf) << 4) as u8,
+            (right >> 4) as u8,
+        ];
+    }
+    sizes.extend(tree.concat());
+    if symbols.len() % 2 == 1 {
+        sizes.push(0);
+    }
+    assert!(coded >= 2);
+    Compressed {
+        sizes,
+        sparse_index,
+        block_lengths,
+        data,
+    }
+}
+
+/// One table of values, either all the same or compressed.
+enum Values {
+    Single(u8),
+    Compressed(Compressed),
+}
+
+impl Values {
+    fn new(values: &[u16]) -> Self {
+        if values.iter().all(|&value| value == values[0]) {
+            Values::Single(values[0] as u8)
+        } else {
+            Values::Compressed(compress(values))
+        }
+    }
+}
+
+/// Fills the indices no legal position reaches, or whose value is never read, with the value
+/// before them so runs stay long.
+fn fill(values: Vec<Option<u16>>) -> Vec<u16> {
+    let first = values.iter().flatten().next().copied().unwrap_or(0);
+    let mut last = first;
+    values
+        .into_iter()
+        .map(|value| {
+            last = value.unwrap_or(last);
+            last
+        })
+        .collect()
+}
+
+/// The pieces in the order [`table_order`] gives their squares.
+fn piece_codes(pieces: &[u8]) -> Vec<u8> {
+    let mut codes = vec![KING];
+    codes.extend(pieces.iter().filter(|&&kind| kind != PAWN));
+    codes.push(KING + BLACK);
+    if pieces.contains(&PAWN) {
+        codes.insert(0, PAWN);
+    }
+    codes
+}
+
+/// Writes a file: the header, the tables' sizes and codes, the DTZ maps, sparse indexes,
+/// block lengths and the 64-byte aligned blocks.
+fn write_file(
+    magic: [u8; 4],
+    pieces: &[u8],
+    tables: &[Vec<(u8, Values)>],
+    maps: Option<&[Vec<[Vec<u8>; 4]>]>,
+) -> Vec<u8> {
+    let mut bytes = magic.to_vec();
+    bytes.push(1 | if pieces.contains(&PAWN) { 2 } else { 0 });
+    let codes = piece_codes(pieces);
+    for _ in tables {
+        bytes.push(0);
+        for &code in &codes {
+            bytes.push(code | code << 4);
+        }
+    }
+    if bytes.len() % 2 == 1 {
+        bytes.push(0);
+    }
+    for file in tables {
+        for (flags, values) in file {
+            bytes.push(*flags);
+            match values {
+                Values::Single(value) => bytes.push(*value),
+                Values::Compressed(compressed) => bytes.extend(&compressed.sizes),
+            }
+        }
+    }
+    if let Some(maps) = maps {
+        for (file, maps) in tables.iter().zip(maps) {
+            for ((flags, _), maps) in file.iter().zip(maps) {
+                if flags & MAPPED == 0 {
+                    continue;
+                }
+                for map in maps {
+                    bytes.push(map.len() as u8);
+                    bytes.extend(map);
+                }
+            }
+        }
+        if bytes.len() % 2 == 1 {
+            bytes.push(0);
+        }
+    }
+    let compressed = || {
+        tables.iter().flatten().filter_map(|(_, values)| match values {
+            Values::Compressed(compressed) => Some(compressed),
+            Values::Single(_) => None,
+        })
+    };
+    for table in compressed() {
+        bytes.extend(&table.sparse_index);
+    }
+    for table in compressed() {
+        bytes.extend(&table.block_lengths);
+    }
+    for table in compressed() {
+        bytes.resize(bytes.len().next_multiple_of(64), 0);
+        bytes.extend(&table.data);
+    }
+    bytes
+}
+
+fn wdl_file(maps: &Maps, solution: &Solution) -> Vec<u8> {
+    let sizes = table_sizes(maps, &solution.pieces);
+    let mut values: Vec<[Vec<Option<u16>>; 2]> = sizes
+        .iter()
+        .map(|&size| [vec![None; size as usize], vec![None; size as usize]])
+        .collect();
+    let count = solution.pieces.len() + 2;
+    for pos in (0..positions(count)).filter(|&pos| solution.legal[pos]) {
+        let (black_to_move, squares) = unpack(count, pos);
+        let (file, index) = index(maps, &solution.pieces, &squares[..count]);
+        values[file][usize::from(black_to_move)][index as usize] =
+            Some((solution.wdl[pos] + 2) as u16);
+    }
+    let tables: Vec<Vec<(u8, Values)>> = values
+        .into_iter()
+        .map(|sides| {
+            sides
+                .into_iter()
+                .map(|side| {
+                    let values = Values::new(&fill(side));
+                    let flags = if matches!(values, Values::Single(_)) {
+                        SINGLE_VALUE
+                    } else {
+                        0
+                    };
+                    (flags, values)
+                })
+                .collect()
+        })
+        .collect();
+    write_file(WDL_MAGIC, &solution.pieces, &tables, None)
+}
+
+/// The DTZ file, storing `black_to_move` positions only, with distances in plies or in full
+/// moves and mapped by frequency per result.
+fn dtz_file(maps: &Maps, solution: &Solution, black_to_move: bool, plies: bool) -> Vec<u8> {
+    let sizes = table_sizes(maps, &solution.pieces);
+    let mut stored: Vec<Vec<Option<(i8, u16)>>> =
+        sizes.iter().map(|&size| vec![None; size as usize]).collect();
+    let count = solution.pieces.len() + 2;
+    for pos in (0..positions(count)).filter(|&pos| solution.legal[pos]) {
+        let (to_move, squares) = unpack(count, pos);
+        let wdl = solution.wdl[pos];
+        if to_move != black_to_move || wdl == DRAW {
+            continue;
+        }
+        let plies_left = solution.dtz[pos].unsigned_abs() - 1;
+        let value = if plies {
+            plies_left
+        } else {
+            assert!(plies_left % 2 == 0, "a distance in moves would round");
+            plies_left / 2
+        };
+        let (file, index) = index(maps, &solution.pieces, &squares[..count]);
+        stored[file][index as usize] = Some((wdl, value));
+    }
+
+    let mut tables = Vec::new();
+    let mut all_maps = Vec::new();
+    for file in stored {
+        // Each result's distances, most frequent first
+        let mut maps: [Vec<u8>; 4] = Default::default();
+        for (slot, wdl) in [WIN, LOSS].into_iter().enumerate() {
+            let mut frequency: HashMap<u16, usize> = HashMap::new();
+            for &(_, value) in file.iter().flatten().filter(|(w, _)| *w == wdl) {
+                *frequency.entry(value).or_default() += 1;
+            }
+            let mut distinct: Vec<(u16, usize)> = frequency.into_iter().collect();
+            distinct.sort_by_key(|&(value, count)| (Reverse(count), value));
+            maps[slot] = distinct.iter().map(|&(value, _)| value as u8).collect();
+        }
+        let symbols = file
+            .iter()
+            .map(|entry| {
+                entry.map(|(wdl, value)| {
+                    let map = &maps[if wdl == WIN { 0 } else { 1 }];
+                    map.iter().position(|&v| u16::from(v) == value).unwrap() as u16
+                })
+            })
+            .collect();
+        let values = Values::new(&fill(symbols));
+        let mut flags = u8::from(black_to_move);
+        match values {
+            Values::Single(_) => flags |= SINGLE_VALUE,
+            Values::Compressed(_) => {
+                flags |= MAPPED;
+                if plies {
+                    flags |= WIN_PLIES | LOSS_PLIES;
+                }
+            }
+        }
+        tables.push(vec![(flags, values)]);
+        all_maps.push(vec![maps]);
+    }
+    write_file(DTZ_MAGIC, &solution.pieces, &tables, Some(&all_maps))
+}
+
+fn main() {
+    let dir = std::env::args().nth(1).unwrap_or_else(|| ".".to_string());
+    let dir = std::path::Path::new(&dir);
+    let maps = maps();
+    // Each table after those its captures and promotions lead to; the tables KRPvK promotes
+    // into only need their results
+    let order: [(&[u8], bool); 10] = [
+        (&[QUEEN], true),
+        (&[ROOK], true),
+        (&[BISHOP], true),
+        (&[KNIGHT], true),
+        (&[PAWN], true),
+        (&[ROOK, QUEEN], false),
+        (&[ROOK, ROOK], false),
+        (&[ROOK, BISHOP], false),
+        (&[ROOK, KNIGHT], false),
+        (&[ROOK, PAWN], true),
+    ];
+    let mut solved = HashMap::new();
+    for (pieces, distances) in order {
+        let solution = solve(pieces, &solved, distances);
+        solved.insert(pieces.to_vec(), solution);
+    }
+
+    // Which side each DTZ table stores and whether in plies, so the reader meets both
+    let tables: [(&str, &[u8], bool, bool); 6] = [
+        ("KQvK", &[QUEEN], false, false),
+        ("KRvK", &[ROOK], true, true),
+        ("KPvK", &[PAWN], false, true),
+        ("KBvK", &[BISHOP], false, false),
+        ("KNvK", &[KNIGHT], false, false),
+        ("KRPvK", &[ROOK, PAWN], true, true),
+    ];
+    for (name, pieces, black_to_move, plies) in tables {
+        let solution = &solved[pieces];
+        let longest = solution.dtz.iter().map(|dtz| dtz.abs()).max().unwrap();
+        println!("{name}: longest DTZ {longest} plies");
+        std::fs::write(dir.join(format!("{name}.rtbw")), wdl_file(&maps, solution)).unwrap();
+        std::fs::write(
+            dir.join(format!("{name}.rtbz")),
+            dtz_file(&maps, solution, black_to_move, plies),
+        )
+        .unwrap();
+    }
+}